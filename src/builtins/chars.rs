@@ -0,0 +1,305 @@
+//! Characters (R5RS section 6.3.4)
+use super::{expect_char, expect_integer, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::lexer::LispNum;
+use crate::value::Value;
+use std::convert::TryFrom;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "char?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a character.",
+        function: is_char,
+    },
+    Builtin {
+        name: "char=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are all equal.",
+        function: char_equal,
+    },
+    Builtin {
+        name: "char<?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are strictly increasing.",
+        function: char_less,
+    },
+    Builtin {
+        name: "char>?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are strictly decreasing.",
+        function: char_greater,
+    },
+    Builtin {
+        name: "char<=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are non-decreasing.",
+        function: char_less_equal,
+    },
+    Builtin {
+        name: "char>=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are non-increasing.",
+        function: char_greater_equal,
+    },
+    Builtin {
+        name: "char-ci=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are all equal, ignoring case.",
+        function: char_ci_equal,
+    },
+    Builtin {
+        name: "char-ci<?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are strictly increasing, ignoring case.",
+        function: char_ci_less,
+    },
+    Builtin {
+        name: "char-ci>?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are strictly decreasing, ignoring case.",
+        function: char_ci_greater,
+    },
+    Builtin {
+        name: "char-ci<=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are non-decreasing, ignoring case.",
+        function: char_ci_less_equal,
+    },
+    Builtin {
+        name: "char-ci>=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "char1 char2 char3 ...",
+        doc: "Returns #t if the characters are non-increasing, ignoring case.",
+        function: char_ci_greater_equal,
+    },
+    Builtin {
+        name: "char-alphabetic?",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns #t if char is a letter.",
+        function: is_alphabetic,
+    },
+    Builtin {
+        name: "char-numeric?",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns #t if char is a digit.",
+        function: is_numeric,
+    },
+    Builtin {
+        name: "char-whitespace?",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns #t if char is whitespace.",
+        function: is_whitespace,
+    },
+    Builtin {
+        name: "char-upper-case?",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "letter",
+        doc: "Returns #t if letter is an upper case letter.",
+        function: is_upper_case,
+    },
+    Builtin {
+        name: "char-lower-case?",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "letter",
+        doc: "Returns #t if letter is a lower case letter.",
+        function: is_lower_case,
+    },
+    Builtin {
+        name: "char->integer",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns the Unicode scalar value of char.",
+        function: char_to_integer,
+    },
+    Builtin {
+        name: "integer->char",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "n",
+        doc: "Returns the character whose Unicode scalar value is n.",
+        function: integer_to_char,
+    },
+    Builtin {
+        name: "char-upcase",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns the upper case counterpart of char, or char itself.",
+        function: char_upcase,
+    },
+    Builtin {
+        name: "char-downcase",
+        library: StandardLibrary::Char,
+        arity: Arity::exactly(1),
+        params: "char",
+        doc: "Returns the lower case counterpart of char, or char itself.",
+        function: char_downcase,
+    },
+];
+
+/// Folds a character to lower case for the `-ci` comparisons
+pub(super) fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn compare_chain(
+    procedure: &str,
+    args: &[Value],
+    fold: fn(char) -> char,
+    accept: fn(char, char) -> bool,
+) -> EvalResult {
+    let chars = args
+        .iter()
+        .map(|arg| expect_char(procedure, arg).map(fold))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::Boolean(
+        chars.windows(2).all(|pair| accept(pair[0], pair[1])),
+    ))
+}
+
+fn is_char(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Character(_))))
+}
+
+fn char_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char=?", &args, |c| c, |a, b| a == b)
+}
+
+fn char_less(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char<?", &args, |c| c, |a, b| a < b)
+}
+
+fn char_greater(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char>?", &args, |c| c, |a, b| a > b)
+}
+
+fn char_less_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char<=?", &args, |c| c, |a, b| a <= b)
+}
+
+fn char_greater_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char>=?", &args, |c| c, |a, b| a >= b)
+}
+
+fn char_ci_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char-ci=?", &args, fold_case, |a, b| a == b)
+}
+
+fn char_ci_less(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char-ci<?", &args, fold_case, |a, b| a < b)
+}
+
+fn char_ci_greater(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char-ci>?", &args, fold_case, |a, b| a > b)
+}
+
+fn char_ci_less_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char-ci<=?", &args, fold_case, |a, b| a <= b)
+}
+
+fn char_ci_greater_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("char-ci>=?", &args, fold_case, |a, b| a >= b)
+}
+
+fn classify(procedure: &str, arg: &Value, test: fn(char) -> bool) -> EvalResult {
+    Ok(Value::Boolean(test(expect_char(procedure, arg)?)))
+}
+
+fn is_alphabetic(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    classify("char-alphabetic?", &args[0], char::is_alphabetic)
+}
+
+fn is_numeric(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    classify("char-numeric?", &args[0], char::is_numeric)
+}
+
+fn is_whitespace(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    classify("char-whitespace?", &args[0], char::is_whitespace)
+}
+
+fn is_upper_case(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    classify("char-upper-case?", &args[0], char::is_uppercase)
+}
+
+fn is_lower_case(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    classify("char-lower-case?", &args[0], char::is_lowercase)
+}
+
+fn char_to_integer(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let c = expect_char("char->integer", &args[0])?;
+    Ok(Value::Number(LispNum::Integer(c as i32)))
+}
+
+fn integer_to_char(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let i = expect_integer("integer->char", &args[0])?;
+    u32::try_from(i)
+        .ok()
+        .and_then(std::char::from_u32)
+        .map(Value::Character)
+        .ok_or_else(|| RuntimeError::out_of_range("integer->char", &args[0]).into())
+}
+
+fn char_upcase(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let c = expect_char("char-upcase", &args[0])?;
+    Ok(Value::Character(c.to_uppercase().next().unwrap_or(c)))
+}
+
+fn char_downcase(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let c = expect_char("char-downcase", &args[0])?;
+    Ok(Value::Character(fold_case(c)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn chars_test() {
+        assert_eq!(eval_to_string("(char? #\\a)"), "#t");
+        assert_eq!(eval_to_string("(char<? #\\a #\\b #\\c)"), "#t");
+        assert_eq!(eval_to_string("(char=? #\\a #\\A)"), "#f");
+        assert_eq!(eval_to_string("(char-ci=? #\\a #\\A)"), "#t");
+        assert_eq!(eval_to_string("(char-alphabetic? #\\a)"), "#t");
+        assert_eq!(eval_to_string("(char-numeric? #\\1)"), "#t");
+        assert_eq!(eval_to_string("(char-whitespace? #\\space)"), "#t");
+        assert_eq!(eval_to_string("(char-upper-case? #\\A)"), "#t");
+        assert_eq!(eval_to_string("(char->integer #\\a)"), "97");
+        assert_eq!(eval_to_string("(integer->char 97)"), "#\\a");
+        assert_eq!(eval_to_string("(char-upcase #\\a)"), "#\\A");
+        assert_eq!(eval_to_string("(char-downcase #\\A)"), "#\\a");
+        assert_eq!(eval_error("(integer->char -1)"), ErrorKind::OutOfRange);
+        assert_eq!(eval_error("(char<? #\\a 1)"), ErrorKind::WrongType);
+    }
+}
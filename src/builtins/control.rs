@@ -0,0 +1,237 @@
+//! Control features (R5RS section 6.4)
+//!
+//! Continuations captured by `call-with-current-continuation` are escape-only: they can be used
+//! to exit early from the extent of the `call/cc` call, but not to re-enter it afterwards.
+use super::{
+    expect_list, expect_procedure, spread_apply_arguments, values_from_vec, Arity, Builtin,
+    StandardLibrary,
+};
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::value::Value;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "procedure?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a procedure.",
+        function: is_procedure,
+    },
+    Builtin {
+        name: "apply",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "proc arg1 ... args",
+        doc: "Calls proc with arg1 ... followed by the elements of the list args.",
+        function: apply,
+    },
+    Builtin {
+        name: "map",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "proc list1 list2 ...",
+        doc: "Returns the list of the results of applying proc to the elements of the lists, taken in parallel.",
+        function: map,
+    },
+    Builtin {
+        name: "for-each",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "proc list1 list2 ...",
+        doc: "Applies proc to the elements of the lists, taken in parallel, in order, for its side effects.",
+        function: for_each,
+    },
+    Builtin {
+        name: "force",
+        library: StandardLibrary::Lazy,
+        arity: Arity::exactly(1),
+        params: "promise",
+        doc: "Returns the value of promise, computing and remembering it the first time.",
+        function: force,
+    },
+    Builtin {
+        name: "call-with-current-continuation",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "proc",
+        doc: "Calls proc with the current continuation, packaged as an escape procedure.",
+        function: call_cc,
+    },
+    Builtin {
+        name: "call/cc",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "proc",
+        doc: "Calls proc with the current continuation; an abbreviation for call-with-current-continuation.",
+        function: call_cc,
+    },
+    Builtin {
+        name: "values",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "obj ...",
+        doc: "Delivers its arguments to the continuation as multiple values.",
+        function: values,
+    },
+    Builtin {
+        name: "call-with-values",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "producer consumer",
+        doc: "Calls producer with no arguments, then consumer with the values it returns.",
+        function: call_with_values,
+    },
+    Builtin {
+        name: "dynamic-wind",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(3),
+        params: "before thunk after",
+        doc: "Calls thunk, calling before whenever control enters it and after whenever control leaves it.",
+        function: dynamic_wind,
+    },
+];
+
+fn is_procedure(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Procedure(_))))
+}
+
+/// The interpreter spreads the arguments of `apply` itself so that the procedure is called in tail
+/// position, which means this is only a fallback
+fn apply(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let (procedure, args) = spread_apply_arguments(args)?;
+    interpreter.apply(&procedure, args)
+}
+
+/// Collects the argument lists of `map` and `for-each` into rows of arguments, stopping at the
+/// shortest list
+fn transpose(procedure: &str, lists: &[Value]) -> Result<Vec<Vec<Value>>, RuntimeError> {
+    let lists = lists
+        .iter()
+        .map(|list| expect_list(procedure, list))
+        .collect::<Result<Vec<_>, _>>()?;
+    let length = lists.iter().map(Vec::len).min().unwrap_or(0);
+    Ok((0..length)
+        .map(|i| lists.iter().map(|list| list[i].clone()).collect())
+        .collect())
+}
+
+fn map(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_procedure("map", &args[0])?;
+    let results = transpose("map", &args[1..])?
+        .into_iter()
+        .map(|row| interpreter.apply(&args[0], row))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::list(results))
+}
+
+fn for_each(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_procedure("for-each", &args[0])?;
+    for row in transpose("for-each", &args[1..])? {
+        interpreter.apply(&args[0], row)?;
+    }
+    Ok(Value::Unspecified)
+}
+
+fn force(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    match &args[0] {
+        Value::Promise(promise) => interpreter.force(promise),
+        other => Ok(other.clone()),
+    }
+}
+
+fn call_cc(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_procedure("call-with-current-continuation", &args[0])?;
+    interpreter.call_with_escape(&args[0])
+}
+
+fn values(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(values_from_vec(args))
+}
+
+fn call_with_values(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let produced = interpreter.apply(&args[0], Vec::new())?;
+    let values = match produced {
+        Value::MultipleValues(values) => values.to_vec(),
+        single => vec![single],
+    };
+    interpreter.apply(&args[1], values)
+}
+
+fn dynamic_wind(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    interpreter.apply(&args[0], Vec::new())?;
+    let result = interpreter.apply(&args[1], Vec::new());
+    interpreter.apply(&args[2], Vec::new())?;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn apply_and_map_test() {
+        assert_eq!(eval_to_string("(procedure? car)"), "#t");
+        assert_eq!(eval_to_string("(procedure? 'car)"), "#f");
+        assert_eq!(eval_to_string("(apply + (list 3 4))"), "7");
+        assert_eq!(eval_to_string("(apply + 1 2 '(3 4))"), "10");
+        assert_eq!(eval_to_string("(map cadr '((a b) (d e) (g h)))"), "(b e h)");
+        assert_eq!(eval_to_string("(map + '(1 2 3) '(10 20 30))"), "(11 22 33)");
+        assert_eq!(
+            eval_to_string("(let ((v (make-vector 5))) (for-each (lambda (i) (vector-set! v i (* i i))) '(0 1 2 3 4)) v)"),
+            "#(0 1 4 9 16)"
+        );
+        assert_eq!(eval_error("(map 1 '(1))"), ErrorKind::WrongType);
+        assert_eq!(eval_error("(apply + 1)"), ErrorKind::WrongType);
+    }
+
+    #[test]
+    fn force_test() {
+        assert_eq!(eval_to_string("(force (delay (+ 1 2)))"), "3");
+        assert_eq!(
+            eval_to_string("(let ((p (delay (+ 1 2)))) (list (force p) (force p)))"),
+            "(3 3)"
+        );
+        assert_eq!(
+            eval_to_string("(define count 0) (define p (delay (begin (set! count (+ count 1)) (if (> count x) count (force p))))) (define x 5) (force p) (set! x 10) (force p)"),
+            "6"
+        );
+        assert_eq!(eval_to_string("(force 3)"), "3");
+    }
+
+    #[test]
+    fn continuations_test() {
+        assert_eq!(
+            eval_to_string("(call-with-current-continuation (lambda (exit) (for-each (lambda (x) (if (negative? x) (exit x))) '(54 0 37 -3 245 19)) #t))"),
+            "-3"
+        );
+        assert_eq!(
+            eval_to_string("(+ 1 (call/cc (lambda (k) (+ 2 (k 3)))))"),
+            "4"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(define k #f) (call/cc (lambda (c) (set! k c))) (call/cc (lambda (c) 1))"
+            ),
+            "1"
+        );
+        assert_eq!(
+            eval_error("(define k #f) (call/cc (lambda (c) (set! k c))) (k 1)"),
+            ErrorKind::Continuation
+        );
+    }
+
+    #[test]
+    fn values_and_dynamic_wind_test() {
+        assert_eq!(
+            eval_to_string("(call-with-values (lambda () (values 4 5)) (lambda (a b) b))"),
+            "5"
+        );
+        assert_eq!(eval_to_string("(call-with-values * -)"), "-1");
+        assert_eq!(
+            eval_to_string("(let ((path '())) (call/cc (lambda (k) (dynamic-wind (lambda () (set! path (cons 'in path))) (lambda () (k 'escaped)) (lambda () (set! path (cons 'out path)))))) (reverse path))"),
+            "(in out)"
+        );
+    }
+}
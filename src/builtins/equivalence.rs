@@ -0,0 +1,92 @@
+//! Equivalence predicates (R5RS section 6.1) and booleans (section 6.3.1)
+use super::{Arity, Builtin, StandardLibrary};
+use crate::interpreter::{EvalResult, Interpreter};
+use crate::value::Value;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "eqv?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj1 obj2",
+        doc: "Returns #t if the objects should be regarded as the same object.",
+        function: eqv,
+    },
+    Builtin {
+        name: "eq?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj1 obj2",
+        doc: "Returns #t if the objects are the same object, which is finer than eqv? on numbers and characters.",
+        function: eq,
+    },
+    Builtin {
+        name: "equal?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj1 obj2",
+        doc: "Returns #t if the objects print the same, comparing pairs, vectors and strings recursively.",
+        function: equal,
+    },
+    Builtin {
+        name: "not",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is false, and #f otherwise.",
+        function: not,
+    },
+    Builtin {
+        name: "boolean?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is #t or #f.",
+        function: is_boolean,
+    },
+];
+
+fn eqv(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(args[0].is_eqv(&args[1])))
+}
+
+fn eq(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(args[0].is_eq(&args[1])))
+}
+
+fn equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(args[0].is_equal(&args[1])))
+}
+
+fn not(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(!args[0].is_true()))
+}
+
+fn is_boolean(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Boolean(_))))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::eval_to_string;
+
+    #[test]
+    fn equivalence_test() {
+        assert_eq!(eval_to_string("(eqv? 'a 'a)"), "#t");
+        assert_eq!(eval_to_string("(eqv? (cons 1 2) (cons 1 2))"), "#f");
+        assert_eq!(
+            eval_to_string("(let ((p (lambda (x) x))) (eqv? p p))"),
+            "#t"
+        );
+        assert_eq!(eval_to_string("(eqv? 2 2.0)"), "#f");
+        assert_eq!(eval_to_string("(eq? '() '())"), "#t");
+        assert_eq!(
+            eval_to_string("(equal? (make-vector 5 'a) (make-vector 5 'a))"),
+            "#t"
+        );
+        assert_eq!(eval_to_string("(equal? \"abc\" \"abc\")"), "#t");
+        assert_eq!(eval_to_string("(not 3)"), "#f");
+        assert_eq!(eval_to_string("(not #f)"), "#t");
+        assert_eq!(eval_to_string("(boolean? '())"), "#f");
+    }
+}
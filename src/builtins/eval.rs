@@ -0,0 +1,184 @@
+//! Eval (R5RS section 6.5) and `load` (R5RS section 6.6.4)
+//!
+//! Only version 5 of the report is supported. Every call to `scheme-report-environment` or
+//! `null-environment` returns a fresh environment, so definitions evaluated in one of them are
+//! not visible in the others, nor in the interaction environment. Neither environment gives
+//! access to files or to the evaluator: `scheme-report-environment` leaves out `load`, `eval`,
+//! the environment procedures and the procedures opening files, so untrusted code can be
+//! evaluated in it.
+use super::{expect_integer, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{Environment, EvalResult, Interpreter, RuntimeError};
+use crate::value::Value;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "eval",
+        library: StandardLibrary::Eval,
+        arity: Arity::between(1, 2),
+        params: "expression [environment]",
+        doc: "Evaluates expression in environment, the interaction environment by default.",
+        function: eval,
+    },
+    Builtin {
+        name: "load",
+        library: StandardLibrary::Load,
+        arity: Arity::between(1, 2),
+        params: "filename [environment]",
+        doc: "Reads and evaluates the forms of the file filename, relative to the current directory, in environment.",
+        function: load,
+    },
+    Builtin {
+        name: "scheme-report-environment",
+        library: StandardLibrary::R5rs,
+        arity: Arity::exactly(1),
+        params: "version",
+        doc: "Returns an environment containing the standard procedures without access to files or eval.",
+        function: scheme_report_environment,
+    },
+    Builtin {
+        name: "null-environment",
+        library: StandardLibrary::R5rs,
+        arity: Arity::exactly(1),
+        params: "version",
+        doc: "Returns an environment containing only the standard syntax.",
+        function: null_environment,
+    },
+    Builtin {
+        name: "interaction-environment",
+        library: StandardLibrary::Repl,
+        arity: Arity::exactly(0),
+        params: "",
+        doc: "Returns the environment programs typed by the user are evaluated in.",
+        function: interaction_environment,
+    },
+];
+
+fn expect_version(procedure: &str, value: &Value) -> Result<(), RuntimeError> {
+    match expect_integer(procedure, value)? {
+        5 => Ok(()),
+        _ => Err(RuntimeError::out_of_range(procedure, value)),
+    }
+}
+
+/// Evaluates a form in the given environment, or in the interaction environment if there is none
+fn eval(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let env = match args.get(1) {
+        None => interpreter.global_environment().clone(),
+        Some(Value::Environment(env)) => env.clone(),
+        Some(other) => return Err(RuntimeError::wrong_type("eval", "environment", other).into()),
+    };
+    interpreter.eval_form(&args[0], &env)
+}
+
+/// Evaluates every form of a file in the given environment, or in the interaction environment if
+/// there is none
+fn load(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let filename = match &args[0] {
+        Value::String(filename) => PathBuf::from(&*filename.borrow()),
+        other => return Err(RuntimeError::wrong_type("load", "string", other).into()),
+    };
+    let env = match args.get(1) {
+        None => interpreter.global_environment().clone(),
+        Some(Value::Environment(env)) => env.clone(),
+        Some(other) => return Err(RuntimeError::wrong_type("load", "environment", other).into()),
+    };
+    interpreter.load(&filename, &env)?;
+    Ok(Value::Unspecified)
+}
+
+fn scheme_report_environment(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_version("scheme-report-environment", &args[0])?;
+    Ok(Value::Environment(Environment::report()))
+}
+
+fn null_environment(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_version("null-environment", &args[0])?;
+    Ok(Value::Environment(Environment::null()))
+}
+
+fn interaction_environment(interpreter: &mut Interpreter, _: Vec<Value>) -> EvalResult {
+    Ok(Value::Environment(Rc::clone(
+        interpreter.global_environment(),
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn eval_test() {
+        assert_eq!(eval_to_string("(eval 1)"), "1");
+        assert_eq!(
+            eval_to_string("(eval '(* 7 3) (scheme-report-environment 5))"),
+            "21"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(let ((f (eval '(lambda (f x) (f x x)) (null-environment 5)))) (f + 10))"
+            ),
+            "20"
+        );
+        assert_eq!(
+            eval_to_string("(eval '(define x 1) (interaction-environment)) x"),
+            "1"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(define env (scheme-report-environment 5))
+                 (eval '(define car cdr) env)
+                 (list (car '(1 2)) (eval '(car '(1 2)) env))"
+            ),
+            "(1 (2))"
+        );
+        assert_eq!(
+            eval_to_string("(call/cc (lambda (k) (eval (list k 3)) 4))"),
+            "3"
+        );
+    }
+
+    #[test]
+    fn restricted_environment_test() {
+        assert_eq!(
+            eval_error("(eval '(open-input-file \"secret\") (null-environment 5))"),
+            ErrorKind::UnboundVariable
+        );
+        assert_eq!(
+            eval_error("(eval '(+ 1 2) (null-environment 5))"),
+            ErrorKind::UnboundVariable
+        );
+        for name in [
+            "open-output-file",
+            "with-output-to-file",
+            "call-with-input-file",
+            "load",
+            "eval",
+            "interaction-environment",
+        ] {
+            assert_eq!(
+                eval_error(&format!("(eval '{} (scheme-report-environment 5))", name)),
+                ErrorKind::UnboundVariable,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            eval_to_string(
+                "(eval '(let ((port (open-output-string)))
+                          (display (cadr '(1 2)) port)
+                          (get-output-string port))
+                       (scheme-report-environment 5))"
+            ),
+            "\"2\""
+        );
+        assert_eq!(eval_error("(null-environment 4)"), ErrorKind::OutOfRange);
+        assert_eq!(
+            eval_error("(scheme-report-environment 'r5rs)"),
+            ErrorKind::WrongType
+        );
+        assert_eq!(eval_error("(eval 1 2)"), ErrorKind::WrongType);
+    }
+}
@@ -0,0 +1,277 @@
+//! Exceptions (R7RS section 6.11, SRFI 34)
+//!
+//! Errors signalled by builtins, such as type and arity errors or references to unbound
+//! variables, are raised as condition objects just like those created by `error`, so handlers
+//! installed by `with-exception-handler` and `guard` see them too.
+use super::{expect_procedure, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{ErrorKind, EvalResult, Interpreter, RuntimeError};
+use crate::value::Value;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "error",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(1),
+        params: "message obj ...",
+        doc: "Raises an error object with message and the objs as irritants.",
+        function: error,
+    },
+    Builtin {
+        name: "raise",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Raises obj as an exception, calling the current exception handler; the handler may not return.",
+        function: raise,
+    },
+    Builtin {
+        name: "raise-continuable",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Raises obj as an exception, returning what the current exception handler returns.",
+        function: raise_continuable,
+    },
+    Builtin {
+        name: "with-exception-handler",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "handler thunk",
+        doc: "Calls thunk with handler installed as the current exception handler.",
+        function: with_exception_handler,
+    },
+    Builtin {
+        name: "error-object?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an object created by error or by the runtime.",
+        function: is_error_object,
+    },
+    Builtin {
+        name: "error-object-message",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "error-object",
+        doc: "Returns the message of error-object.",
+        function: error_object_message,
+    },
+    Builtin {
+        name: "error-object-irritants",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "error-object",
+        doc: "Returns the list of the irritants of error-object.",
+        function: error_object_irritants,
+    },
+    Builtin {
+        name: "file-error?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an error raised because a file could not be opened.",
+        function: is_file_error,
+    },
+    Builtin {
+        name: "read-error?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an error raised by read.",
+        function: is_read_error,
+    },
+];
+
+fn expect_condition(procedure: &str, value: &Value) -> Result<RuntimeError, RuntimeError> {
+    match value {
+        Value::Condition(condition) => Ok((**condition).clone()),
+        other => Err(RuntimeError::wrong_type(procedure, "error object", other)),
+    }
+}
+
+/// The message may be any object, though it is usually a string
+fn error(_: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    let irritants = args.split_off(1);
+    let message = match &args[0] {
+        Value::String(s) => s.borrow().clone(),
+        other => other.written().to_string(),
+    };
+    Err(RuntimeError::new(ErrorKind::User, message, irritants).into())
+}
+
+fn raise(interpreter: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    interpreter.raise(args.pop().unwrap(), false)
+}
+
+fn raise_continuable(interpreter: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    interpreter.raise(args.pop().unwrap(), true)
+}
+
+fn with_exception_handler(interpreter: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    let thunk = args.pop().unwrap();
+    let handler = args.pop().unwrap();
+    expect_procedure("with-exception-handler", &handler)?;
+    expect_procedure("with-exception-handler", &thunk)?;
+    interpreter.with_exception_handler(handler, &thunk)
+}
+
+fn is_error_object(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Condition(_))))
+}
+
+fn error_object_message(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let condition = expect_condition("error-object-message", &args[0])?;
+    Ok(Value::string(condition.message))
+}
+
+fn error_object_irritants(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let condition = expect_condition("error-object-irritants", &args[0])?;
+    Ok(Value::list(condition.irritants))
+}
+
+fn is_file_error(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(
+        matches!(&args[0], Value::Condition(condition) if condition.kind == ErrorKind::Io),
+    ))
+}
+
+fn is_read_error(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(
+        matches!(&args[0], Value::Condition(condition) if condition.kind == ErrorKind::Read),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_str, eval_to_string};
+    use crate::interpreter::ErrorKind;
+    use crate::parser::Position;
+    use crate::value::Value;
+
+    #[test]
+    fn error_test() {
+        let error = eval_str("(error \"something bad:\" 42 'foo)").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::User);
+        assert_eq!(error.to_string(), "something bad: 42 foo");
+        assert_eq!(
+            error.position,
+            Some(Position {
+                line: 1,
+                column: 0,
+                file: None
+            })
+        );
+        assert_eq!(eval_error("(raise 'oops)"), ErrorKind::Raise);
+        assert_eq!(
+            eval_str("(raise 'oops)").unwrap_err().to_string(),
+            "uncaught exception oops"
+        );
+    }
+
+    #[test]
+    fn with_exception_handler_test() {
+        assert_eq!(
+            eval_to_string(
+                "(with-exception-handler
+                   (lambda (con) 42)
+                   (lambda () (+ (raise-continuable 'oops) 23)))"
+            ),
+            "65"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(call/cc (lambda (k)
+                   (with-exception-handler
+                     (lambda (e) (k (list 'caught (error-object-message e)
+                                          (error-object-irritants e))))
+                     (lambda () (+ 1 (error \"bad\" 1 2))))))"
+            ),
+            "(caught \"bad\" (1 2))"
+        );
+        assert_eq!(
+            eval_error("(with-exception-handler (lambda (e) 0) (lambda () (raise 'oops)))"),
+            ErrorKind::Raise
+        );
+        // The handler runs with the outer handler installed
+        assert_eq!(
+            eval_to_string(
+                "(with-exception-handler
+                   (lambda (e) (list 'outer e))
+                   (lambda ()
+                     (with-exception-handler
+                       (lambda (e) (raise-continuable (list 'inner e)))
+                       (lambda () (raise-continuable 'x)))))"
+            ),
+            "(outer (inner x))"
+        );
+    }
+
+    #[test]
+    fn guard_test() {
+        assert_eq!(
+            eval_to_string(
+                "(guard (condition
+                         ((assq 'a condition) => cdr)
+                         ((assq 'b condition)))
+                   (raise (list (cons 'a 42))))"
+            ),
+            "42"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(guard (condition
+                         ((assq 'a condition) => cdr)
+                         ((assq 'b condition)))
+                   (raise (list (cons 'b 23))))"
+            ),
+            "(b . 23)"
+        );
+        assert_eq!(eval_to_string("(guard (e (#f 'no)) 'fine)"), "fine");
+        assert_eq!(
+            eval_to_string("(guard (e ((symbol? e) (list 'outer e))) (guard (e ((string? e) e)) (raise 'sym)))"),
+            "(outer sym)"
+        );
+        assert_eq!(
+            eval_error("(guard (e ((string? e) e)) (raise 'sym))"),
+            ErrorKind::Raise
+        );
+    }
+
+    #[test]
+    fn runtime_errors_are_conditions_test() {
+        assert_eq!(
+            eval_to_string(
+                "(guard (e ((error-object? e) (error-object-message e))) (vector-ref (vector) 0))"
+            ),
+            "\"vector-ref: argument out of range\""
+        );
+        assert_eq!(
+            eval_to_string("(guard (e (#t (error-object-irritants e))) undefined-variable)"),
+            "(undefined-variable)"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(guard (e ((file-error? e) 'file)) (open-input-file \"no/such/file\"))"
+            ),
+            "file"
+        );
+        assert_eq!(
+            eval_to_string("(guard (e ((read-error? e) 'read)) (read (open-input-string \"(\")))"),
+            "read"
+        );
+        match eval_str("(define (f x) (car x))\n(guard (e (#t e))\n  (f 1))").unwrap() {
+            Value::Condition(condition) => {
+                assert_eq!(condition.kind, ErrorKind::WrongType);
+                assert_eq!(
+                    condition.position,
+                    Some(Position {
+                        line: 1,
+                        column: 14,
+                        file: None
+                    })
+                );
+            }
+            other => panic!("expected a condition, got {}", other),
+        }
+    }
+}
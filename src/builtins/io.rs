@@ -0,0 +1,526 @@
+//! Input and output (R5RS section 6.6), plus the string ports of SRFI 6
+//!
+//! Procedures taking an optional port argument default to the interpreter's current input or
+//! output port, which `with-input-from-file` and `with-output-to-file` rebind for the duration
+//! of a thunk.
+use super::{expect_char, expect_string, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{ErrorKind, EvalResult, Interpreter, RuntimeError};
+use crate::port::{InputPort, OutputPort, Port};
+use crate::value::Value;
+use crate::CompilerError;
+use std::{cell::RefCell, io, rc::Rc};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "input-port?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an input port.",
+        function: is_input_port,
+    },
+    Builtin {
+        name: "output-port?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an output port.",
+        function: is_output_port,
+    },
+    Builtin {
+        name: "current-input-port",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(0),
+        params: "",
+        doc: "Returns the current default input port.",
+        function: current_input_port,
+    },
+    Builtin {
+        name: "current-output-port",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(0),
+        params: "",
+        doc: "Returns the current default output port.",
+        function: current_output_port,
+    },
+    Builtin {
+        name: "call-with-input-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(2),
+        params: "filename proc",
+        doc: "Calls proc with an input port reading the file filename, and closes the port when proc returns.",
+        function: call_with_input_file,
+    },
+    Builtin {
+        name: "call-with-output-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(2),
+        params: "filename proc",
+        doc: "Calls proc with an output port writing the file filename, and closes the port when proc returns.",
+        function: call_with_output_file,
+    },
+    Builtin {
+        name: "with-input-from-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(2),
+        params: "filename thunk",
+        doc: "Calls thunk with the current input port reading the file filename.",
+        function: with_input_from_file,
+    },
+    Builtin {
+        name: "with-output-to-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(2),
+        params: "filename thunk",
+        doc: "Calls thunk with the current output port writing the file filename.",
+        function: with_output_to_file,
+    },
+    Builtin {
+        name: "open-input-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(1),
+        params: "filename",
+        doc: "Returns an input port reading the file filename.",
+        function: open_input_file,
+    },
+    Builtin {
+        name: "open-output-file",
+        library: StandardLibrary::File,
+        arity: Arity::exactly(1),
+        params: "filename",
+        doc: "Returns an output port writing the file filename.",
+        function: open_output_file,
+    },
+    Builtin {
+        name: "close-input-port",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "port",
+        doc: "Closes the input port.",
+        function: close_input_port,
+    },
+    Builtin {
+        name: "close-output-port",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "port",
+        doc: "Closes the output port, flushing what was written to it.",
+        function: close_output_port,
+    },
+    Builtin {
+        name: "open-input-string",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "string",
+        doc: "Returns an input port reading the characters of string.",
+        function: open_input_string,
+    },
+    Builtin {
+        name: "open-output-string",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(0),
+        params: "",
+        doc: "Returns an output port accumulating what is written to it, for get-output-string.",
+        function: open_output_string,
+    },
+    Builtin {
+        name: "get-output-string",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "port",
+        doc: "Returns the characters written to the string port so far.",
+        function: get_output_string,
+    },
+    Builtin {
+        name: "read",
+        library: StandardLibrary::Read,
+        arity: Arity::between(0, 1),
+        params: "[port]",
+        doc: "Reads the next datum from port, the current input port by default.",
+        function: read,
+    },
+    Builtin {
+        name: "read-char",
+        library: StandardLibrary::Base,
+        arity: Arity::between(0, 1),
+        params: "[port]",
+        doc: "Reads the next character from port, the current input port by default.",
+        function: read_char,
+    },
+    Builtin {
+        name: "peek-char",
+        library: StandardLibrary::Base,
+        arity: Arity::between(0, 1),
+        params: "[port]",
+        doc: "Returns the next character of port without consuming it.",
+        function: peek_char,
+    },
+    Builtin {
+        name: "eof-object?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is the end of file object.",
+        function: is_eof_object,
+    },
+    Builtin {
+        name: "char-ready?",
+        library: StandardLibrary::Base,
+        arity: Arity::between(0, 1),
+        params: "[port]",
+        doc: "Returns #t if a character can be read from port without blocking.",
+        function: is_char_ready,
+    },
+    Builtin {
+        name: "write",
+        library: StandardLibrary::Write,
+        arity: Arity::between(1, 2),
+        params: "obj [port]",
+        doc: "Writes the external representation of obj to port, the current output port by default.",
+        function: write,
+    },
+    Builtin {
+        name: "display",
+        library: StandardLibrary::Write,
+        arity: Arity::between(1, 2),
+        params: "obj [port]",
+        doc: "Writes obj to port, the current output port by default, without quoting strings and characters.",
+        function: display,
+    },
+    Builtin {
+        name: "newline",
+        library: StandardLibrary::Base,
+        arity: Arity::between(0, 1),
+        params: "[port]",
+        doc: "Writes an end of line to port, the current output port by default.",
+        function: newline,
+    },
+    Builtin {
+        name: "write-char",
+        library: StandardLibrary::Base,
+        arity: Arity::between(1, 2),
+        params: "char [port]",
+        doc: "Writes char to port, the current output port by default.",
+        function: write_char,
+    },
+];
+
+fn io_error(procedure: &str, error: io::Error, irritants: Vec<Value>) -> RuntimeError {
+    RuntimeError::new(
+        ErrorKind::Io,
+        format!("{}: {}", procedure, error),
+        irritants,
+    )
+}
+
+/// Returns the input port at `args[index]`, or the current input port if there is no such argument
+fn input_port(
+    interpreter: &Interpreter,
+    procedure: &str,
+    args: &[Value],
+    index: usize,
+) -> Result<Rc<Port>, RuntimeError> {
+    match args.get(index) {
+        None => Ok(interpreter.current_input_port().clone()),
+        Some(Value::Port(port)) if matches!(**port, Port::Input(_)) => Ok(port.clone()),
+        Some(other) => Err(RuntimeError::wrong_type(procedure, "input port", other)),
+    }
+}
+
+/// Returns the output port at `args[index]`, or the current output port if there is no such
+/// argument
+fn output_port(
+    interpreter: &Interpreter,
+    procedure: &str,
+    args: &[Value],
+    index: usize,
+) -> Result<Rc<Port>, RuntimeError> {
+    match args.get(index) {
+        None => Ok(interpreter.current_output_port().clone()),
+        Some(Value::Port(port)) if matches!(**port, Port::Output(_)) => Ok(port.clone()),
+        Some(other) => Err(RuntimeError::wrong_type(procedure, "output port", other)),
+    }
+}
+
+fn with_input<T>(port: &Port, f: impl FnOnce(&mut InputPort) -> T) -> T {
+    match port {
+        Port::Input(input) => f(&mut input.borrow_mut()),
+        Port::Output(_) => unreachable!("input_port only returns input ports"),
+    }
+}
+
+fn with_output<T>(port: &Port, f: impl FnOnce(&mut OutputPort) -> T) -> T {
+    match port {
+        Port::Output(output) => f(&mut output.borrow_mut()),
+        Port::Input(_) => unreachable!("output_port only returns output ports"),
+    }
+}
+
+fn write_to(
+    interpreter: &Interpreter,
+    procedure: &str,
+    args: &[Value],
+    index: usize,
+    text: &str,
+) -> EvalResult {
+    let port = output_port(interpreter, procedure, args, index)?;
+    with_output(&port, |output| output.write_str(text))
+        .map_err(|e| io_error(procedure, e, Vec::new()))?;
+    Ok(Value::Unspecified)
+}
+
+fn open_input(procedure: &str, filename: &Value) -> Result<Rc<Port>, RuntimeError> {
+    let name = expect_string(procedure, filename)?;
+    let port = InputPort::open(&name.borrow())
+        .map_err(|e| io_error(procedure, e, vec![filename.clone()]))?;
+    Ok(Rc::new(Port::Input(RefCell::new(port))))
+}
+
+fn open_output(procedure: &str, filename: &Value) -> Result<Rc<Port>, RuntimeError> {
+    let name = expect_string(procedure, filename)?;
+    let port = OutputPort::create(&name.borrow())
+        .map_err(|e| io_error(procedure, e, vec![filename.clone()]))?;
+    Ok(Rc::new(Port::Output(RefCell::new(port))))
+}
+
+fn close_output(procedure: &str, port: &Port) -> Result<(), RuntimeError> {
+    with_output(port, |output| output.close()).map_err(|e| io_error(procedure, e, Vec::new()))
+}
+
+fn is_input_port(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(
+        matches!(&args[0], Value::Port(port) if matches!(**port, Port::Input(_))),
+    ))
+}
+
+fn is_output_port(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(
+        matches!(&args[0], Value::Port(port) if matches!(**port, Port::Output(_))),
+    ))
+}
+
+fn current_input_port(interpreter: &mut Interpreter, _: Vec<Value>) -> EvalResult {
+    Ok(Value::Port(interpreter.current_input_port().clone()))
+}
+
+fn current_output_port(interpreter: &mut Interpreter, _: Vec<Value>) -> EvalResult {
+    Ok(Value::Port(interpreter.current_output_port().clone()))
+}
+
+fn call_with_input_file(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = open_input("call-with-input-file", &args[0])?;
+    let result = interpreter.apply(&args[1], vec![Value::Port(port.clone())]);
+    with_input(&port, InputPort::close);
+    result
+}
+
+fn call_with_output_file(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = open_output("call-with-output-file", &args[0])?;
+    let result = interpreter.apply(&args[1], vec![Value::Port(port.clone())]);
+    close_output("call-with-output-file", &port)?;
+    result
+}
+
+fn with_input_from_file(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = open_input("with-input-from-file", &args[0])?;
+    let previous = interpreter.set_current_input_port(port.clone());
+    let result = interpreter.apply(&args[1], Vec::new());
+    interpreter.set_current_input_port(previous);
+    with_input(&port, InputPort::close);
+    result
+}
+
+fn with_output_to_file(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = open_output("with-output-to-file", &args[0])?;
+    let previous = interpreter.set_current_output_port(port.clone());
+    let result = interpreter.apply(&args[1], Vec::new());
+    interpreter.set_current_output_port(previous);
+    close_output("with-output-to-file", &port)?;
+    result
+}
+
+fn open_input_file(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Port(open_input("open-input-file", &args[0])?))
+}
+
+fn open_output_file(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Port(open_output("open-output-file", &args[0])?))
+}
+
+fn close_input_port(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = input_port(interpreter, "close-input-port", &args, 0)?;
+    with_input(&port, InputPort::close);
+    Ok(Value::Unspecified)
+}
+
+fn close_output_port(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = output_port(interpreter, "close-output-port", &args, 0)?;
+    close_output("close-output-port", &port)?;
+    Ok(Value::Unspecified)
+}
+
+fn open_input_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let input = expect_string("open-input-string", &args[0])?;
+    let port = InputPort::from_string(&input.borrow());
+    Ok(Value::Port(Rc::new(Port::Input(RefCell::new(port)))))
+}
+
+fn open_output_string(_: &mut Interpreter, _: Vec<Value>) -> EvalResult {
+    Ok(Value::Port(Rc::new(Port::Output(RefCell::new(
+        OutputPort::string(),
+    )))))
+}
+
+fn get_output_string(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = output_port(interpreter, "get-output-string", &args, 0)?;
+    match with_output(&port, |output| output.contents().map(String::from)) {
+        Some(contents) => Ok(Value::string(contents)),
+        None => Err(RuntimeError::wrong_type("get-output-string", "string port", &args[0]).into()),
+    }
+}
+
+fn read(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = input_port(interpreter, "read", &args, 0)?;
+    match with_input(&port, InputPort::read_datum) {
+        Ok(Some(datum)) => Ok(Value::from(datum)),
+        Ok(None) => Ok(Value::EofObject),
+        Err(CompilerError::IOError(e)) => Err(io_error("read", e, Vec::new()).into()),
+        Err(e) => {
+            Err(RuntimeError::new(ErrorKind::Read, format!("read: {}", e), Vec::new()).into())
+        }
+    }
+}
+
+fn char_or_eof(c: Option<char>) -> Value {
+    c.map_or(Value::EofObject, Value::Character)
+}
+
+fn read_char(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = input_port(interpreter, "read-char", &args, 0)?;
+    let c = with_input(&port, InputPort::read_char)
+        .map_err(|e| io_error("read-char", e, Vec::new()))?;
+    Ok(char_or_eof(c))
+}
+
+fn peek_char(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = input_port(interpreter, "peek-char", &args, 0)?;
+    let c = with_input(&port, InputPort::peek_char)
+        .map_err(|e| io_error("peek-char", e, Vec::new()))?;
+    Ok(char_or_eof(c))
+}
+
+fn is_eof_object(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::EofObject)))
+}
+
+fn is_char_ready(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let port = input_port(interpreter, "char-ready?", &args, 0)?;
+    Ok(Value::Boolean(with_input(&port, |input| {
+        input.char_ready()
+    })))
+}
+
+fn write(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let text = args[0].written().to_string();
+    write_to(interpreter, "write", &args, 1, &text)
+}
+
+fn display(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let text = args[0].to_string();
+    write_to(interpreter, "display", &args, 1, &text)
+}
+
+fn newline(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    write_to(interpreter, "newline", &args, 0, "\n")
+}
+
+fn write_char(interpreter: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let c = expect_char("write-char", &args[0])?;
+    write_to(
+        interpreter,
+        "write-char",
+        &args,
+        1,
+        c.encode_utf8(&mut [0; 4]),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn output_test() {
+        assert_eq!(
+            eval_to_string(
+                "(define p (open-output-string))
+                 (write \"a\\nb\" p) (write-char #\\space p) (display \"a\\nb\" p)
+                 (newline p) (write '(1 #\\x) p) (display '(1 #\\x) p)
+                 (get-output-string p)"
+            ),
+            "\"\\\"a\\\\nb\\\" a\\nb\\n(1 #\\\\x)(1 x)\""
+        );
+        assert_eq!(eval_to_string("(output-port? (open-output-string))"), "#t");
+        assert_eq!(eval_to_string("(input-port? (open-output-string))"), "#f");
+        assert_eq!(eval_to_string("(output-port? (current-output-port))"), "#t");
+        assert_eq!(
+            eval_error("(display 1 (open-input-string \"\"))"),
+            ErrorKind::WrongType
+        );
+        assert_eq!(
+            eval_error("(define p (open-output-string)) (close-output-port p) (display 1 p)"),
+            ErrorKind::Io
+        );
+    }
+
+    #[test]
+    fn input_test() {
+        assert_eq!(
+            eval_to_string(
+                "(define p (open-input-string \"(a . b) #(1 \\\"s\\\") x\"))
+                 (let* ((a (read p)) (b (read p)) (c (peek-char p)) (d (read-char p))
+                        (e (read p)) (f (read p)))
+                   (list a b c d e (eof-object? f) (char-ready? p)))"
+            ),
+            "((a . b) #(1 \"s\") #\\space #\\space x #t #t)"
+        );
+        assert_eq!(
+            eval_to_string("(eof-object? (read-char (open-input-string \"\")))"),
+            "#t"
+        );
+        assert_eq!(
+            eval_error("(read (open-input-string \"(a\"))"),
+            ErrorKind::Read
+        );
+        // Bad text after a datum only fails the read reaching it
+        assert_eq!(
+            eval_to_string(
+                "(define p (open-input-string \"1 #<bad\"))
+                 (let* ((a (read p)) (b (guard (e ((read-error? e) 'bad)) (read p))))
+                   (list a b (eof-object? (read p))))"
+            ),
+            "(1 bad #t)"
+        );
+        assert_eq!(
+            eval_error("(open-input-file \"no/such/file.scm\")"),
+            ErrorKind::Io
+        );
+    }
+
+    #[test]
+    fn file_ports_test() {
+        let path = std::env::temp_dir().join("scheme-io-file-ports-test.scm");
+        let path = path.to_str().unwrap().replace('\\', "\\\\");
+        assert_eq!(
+            eval_to_string(&format!(
+                "(with-output-to-file \"{0}\" (lambda () (write '(define x 42)) (newline)))
+                 (call-with-output-file \"{0}\" (lambda (p) (display \"(1 2)\" p)))
+                 (list (call-with-input-file \"{0}\" read)
+                       (with-input-from-file \"{0}\" (lambda () (read-char))))",
+                path
+            )),
+            "((1 2) #\\()"
+        );
+    }
+}
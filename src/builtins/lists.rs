@@ -0,0 +1,653 @@
+//! Pairs and lists (R5RS section 6.3.2)
+use super::{expect_index, expect_list, expect_pair, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::value::Value;
+
+macro_rules! cxr {
+    ($($function:ident => $name:expr,)*) => {
+        $(
+            fn $function(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+                cxr($name, &args[0])
+            }
+        )*
+    };
+}
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "pair?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a pair.",
+        function: is_pair,
+    },
+    Builtin {
+        name: "cons",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj1 obj2",
+        doc: "Returns a newly allocated pair whose car is obj1 and whose cdr is obj2.",
+        function: cons,
+    },
+    Builtin {
+        name: "car",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the contents of the car field of pair.",
+        function: car,
+    },
+    Builtin {
+        name: "cdr",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the contents of the cdr field of pair.",
+        function: cdr,
+    },
+    Builtin {
+        name: "set-car!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "pair obj",
+        doc: "Stores obj in the car field of pair.",
+        function: set_car,
+    },
+    Builtin {
+        name: "set-cdr!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "pair obj",
+        doc: "Stores obj in the cdr field of pair.",
+        function: set_cdr,
+    },
+    Builtin {
+        name: "caar",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of pair.",
+        function: caar,
+    },
+    Builtin {
+        name: "cadr",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of pair.",
+        function: cadr,
+    },
+    Builtin {
+        name: "cdar",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of pair.",
+        function: cdar,
+    },
+    Builtin {
+        name: "cddr",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of pair.",
+        function: cddr,
+    },
+    Builtin {
+        name: "caaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the car of pair.",
+        function: caaar,
+    },
+    Builtin {
+        name: "caadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the cdr of pair.",
+        function: caadr,
+    },
+    Builtin {
+        name: "cadar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the car of pair.",
+        function: cadar,
+    },
+    Builtin {
+        name: "caddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the cdr of pair.",
+        function: caddr,
+    },
+    Builtin {
+        name: "cdaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the car of pair.",
+        function: cdaar,
+    },
+    Builtin {
+        name: "cdadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the cdr of pair.",
+        function: cdadr,
+    },
+    Builtin {
+        name: "cddar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the car of pair.",
+        function: cddar,
+    },
+    Builtin {
+        name: "cdddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the cdr of pair.",
+        function: cdddr,
+    },
+    Builtin {
+        name: "caaaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the car of the car of pair.",
+        function: caaaar,
+    },
+    Builtin {
+        name: "caaadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the car of the cdr of pair.",
+        function: caaadr,
+    },
+    Builtin {
+        name: "caadar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the cdr of the car of pair.",
+        function: caadar,
+    },
+    Builtin {
+        name: "caaddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the car of the cdr of the cdr of pair.",
+        function: caaddr,
+    },
+    Builtin {
+        name: "cadaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the car of the car of pair.",
+        function: cadaar,
+    },
+    Builtin {
+        name: "cadadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the car of the cdr of pair.",
+        function: cadadr,
+    },
+    Builtin {
+        name: "caddar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the cdr of the car of pair.",
+        function: caddar,
+    },
+    Builtin {
+        name: "cadddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the car of the cdr of the cdr of the cdr of pair.",
+        function: cadddr,
+    },
+    Builtin {
+        name: "cdaaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the car of the car of pair.",
+        function: cdaaar,
+    },
+    Builtin {
+        name: "cdaadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the car of the cdr of pair.",
+        function: cdaadr,
+    },
+    Builtin {
+        name: "cdadar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the cdr of the car of pair.",
+        function: cdadar,
+    },
+    Builtin {
+        name: "cdaddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the car of the cdr of the cdr of pair.",
+        function: cdaddr,
+    },
+    Builtin {
+        name: "cddaar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the car of the car of pair.",
+        function: cddaar,
+    },
+    Builtin {
+        name: "cddadr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the car of the cdr of pair.",
+        function: cddadr,
+    },
+    Builtin {
+        name: "cdddar",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the cdr of the car of pair.",
+        function: cdddar,
+    },
+    Builtin {
+        name: "cddddr",
+        library: StandardLibrary::CxR,
+        arity: Arity::exactly(1),
+        params: "pair",
+        doc: "Returns the cdr of the cdr of the cdr of the cdr of pair.",
+        function: cddddr,
+    },
+    Builtin {
+        name: "null?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is the empty list.",
+        function: is_null,
+    },
+    Builtin {
+        name: "list?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a proper list.",
+        function: is_list,
+    },
+    Builtin {
+        name: "list",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "obj ...",
+        doc: "Returns a newly allocated list of its arguments.",
+        function: list,
+    },
+    Builtin {
+        name: "length",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "list",
+        doc: "Returns the number of elements of list.",
+        function: length,
+    },
+    Builtin {
+        name: "append",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "list ...",
+        doc: "Returns a list of the elements of the first list followed by those of the others; the last argument is shared, not copied.",
+        function: append,
+    },
+    Builtin {
+        name: "reverse",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "list",
+        doc: "Returns a newly allocated list of the elements of list in reverse order.",
+        function: reverse,
+    },
+    Builtin {
+        name: "list-tail",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "list k",
+        doc: "Returns the sublist of list obtained by omitting its first k elements.",
+        function: list_tail,
+    },
+    Builtin {
+        name: "list-ref",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "list k",
+        doc: "Returns the kth element of list, counting from 0.",
+        function: list_ref,
+    },
+    Builtin {
+        name: "memq",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj list",
+        doc: "Returns the first sublist of list whose car is obj, compared with eq?, or #f.",
+        function: memq,
+    },
+    Builtin {
+        name: "memv",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj list",
+        doc: "Returns the first sublist of list whose car is obj, compared with eqv?, or #f.",
+        function: memv,
+    },
+    Builtin {
+        name: "member",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj list",
+        doc: "Returns the first sublist of list whose car is obj, compared with equal?, or #f.",
+        function: member,
+    },
+    Builtin {
+        name: "assq",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj alist",
+        doc: "Returns the first pair of the association list whose car is obj, compared with eq?, or #f.",
+        function: assq,
+    },
+    Builtin {
+        name: "assv",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj alist",
+        doc: "Returns the first pair of the association list whose car is obj, compared with eqv?, or #f.",
+        function: assv,
+    },
+    Builtin {
+        name: "assoc",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "obj alist",
+        doc: "Returns the first pair of the association list whose car is obj, compared with equal?, or #f.",
+        function: assoc,
+    },
+];
+
+fn is_pair(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Pair(_))))
+}
+
+fn cons(_: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    let cdr = args.pop().unwrap();
+    let car = args.pop().unwrap();
+    Ok(Value::cons(car, cdr))
+}
+
+fn car(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(expect_pair("car", &args[0])?.car())
+}
+
+fn cdr(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(expect_pair("cdr", &args[0])?.cdr())
+}
+
+fn set_car(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_pair("set-car!", &args[0])?.set_car(args[1].clone());
+    Ok(Value::Unspecified)
+}
+
+fn set_cdr(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    expect_pair("set-cdr!", &args[0])?.set_cdr(args[1].clone());
+    Ok(Value::Unspecified)
+}
+
+/// Follows the `a`s and `d`s in the name of a `c...r` procedure, from right to left
+fn cxr(name: &str, value: &Value) -> EvalResult {
+    let mut current = value.clone();
+    for step in name[1..name.len() - 1].chars().rev() {
+        let pair = expect_pair(name, &current)
+            .map_err(|_| RuntimeError::wrong_type(name, "a sufficiently nested pair", value))?;
+        current = if step == 'a' { pair.car() } else { pair.cdr() };
+    }
+    Ok(current)
+}
+
+cxr! {
+    caar => "caar",
+    cadr => "cadr",
+    cdar => "cdar",
+    cddr => "cddr",
+    caaar => "caaar",
+    caadr => "caadr",
+    cadar => "cadar",
+    caddr => "caddr",
+    cdaar => "cdaar",
+    cdadr => "cdadr",
+    cddar => "cddar",
+    cdddr => "cdddr",
+    caaaar => "caaaar",
+    caaadr => "caaadr",
+    caadar => "caadar",
+    caaddr => "caaddr",
+    cadaar => "cadaar",
+    cadadr => "cadadr",
+    caddar => "caddar",
+    cadddr => "cadddr",
+    cdaaar => "cdaaar",
+    cdaadr => "cdaadr",
+    cdadar => "cdadar",
+    cdaddr => "cdaddr",
+    cddaar => "cddaar",
+    cddadr => "cddadr",
+    cdddar => "cdddar",
+    cddddr => "cddddr",
+}
+
+fn is_null(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(args[0].is_null()))
+}
+
+fn is_list(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(args[0].is_list()))
+}
+
+fn list(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::list(args))
+}
+
+fn length(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let length = expect_list("length", &args[0])?.len();
+    Ok(Value::Number(crate::lexer::LispNum::Integer(length as i32)))
+}
+
+fn append(_: &mut Interpreter, mut args: Vec<Value>) -> EvalResult {
+    let mut result = match args.pop() {
+        Some(last) => last,
+        None => return Ok(Value::Null),
+    };
+    for arg in args.iter().rev() {
+        result = Value::list_with_tail(expect_list("append", arg)?, result);
+    }
+    Ok(result)
+}
+
+fn reverse(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let items = expect_list("reverse", &args[0])?;
+    Ok(items
+        .into_iter()
+        .fold(Value::Null, |acc, item| Value::cons(item, acc)))
+}
+
+fn list_tail(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let k = expect_index("list-tail", &args[1])?;
+    let mut current = args[0].clone();
+    for _ in 0..k {
+        current = match current {
+            Value::Pair(p) => p.cdr(),
+            _ => return Err(RuntimeError::out_of_range("list-tail", &args[1]).into()),
+        };
+    }
+    Ok(current)
+}
+
+fn list_ref(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let k = expect_index("list-ref", &args[1])?;
+    let mut current = args[0].clone();
+    for _ in 0..k {
+        current = match current {
+            Value::Pair(p) => p.cdr(),
+            _ => break,
+        };
+    }
+    match current {
+        Value::Pair(p) => Ok(p.car()),
+        _ => Err(RuntimeError::out_of_range("list-ref", &args[1]).into()),
+    }
+}
+
+/// Shared implementation of `memq`, `memv` and `member`
+fn mem(procedure: &str, args: &[Value], same: fn(&Value, &Value) -> bool) -> EvalResult {
+    let mut current = args[1].clone();
+    loop {
+        current = match current {
+            Value::Pair(p) if same(&args[0], &p.car()) => return Ok(Value::Pair(p)),
+            Value::Pair(p) => p.cdr(),
+            Value::Null => return Ok(Value::Boolean(false)),
+            _ => return Err(RuntimeError::wrong_type(procedure, "proper list", &args[1]).into()),
+        };
+    }
+}
+
+fn memq(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    mem("memq", &args, Value::is_eq)
+}
+
+fn memv(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    mem("memv", &args, Value::is_eqv)
+}
+
+fn member(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    mem("member", &args, Value::is_equal)
+}
+
+/// Shared implementation of `assq`, `assv` and `assoc`
+fn ass(procedure: &str, args: &[Value], same: fn(&Value, &Value) -> bool) -> EvalResult {
+    for entry in expect_list(procedure, &args[1])? {
+        let pair = expect_pair(procedure, &entry)?;
+        if same(&args[0], &pair.car()) {
+            return Ok(entry);
+        }
+    }
+    Ok(Value::Boolean(false))
+}
+
+fn assq(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    ass("assq", &args, Value::is_eq)
+}
+
+fn assv(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    ass("assv", &args, Value::is_eqv)
+}
+
+fn assoc(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    ass("assoc", &args, Value::is_equal)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn pairs_test() {
+        assert_eq!(eval_to_string("(cons 'a '(b c))"), "(a b c)");
+        assert_eq!(eval_to_string("(cons \"a\" 3)"), "(\"a\" . 3)");
+        assert_eq!(eval_to_string("(car '((a) b c d))"), "(a)");
+        assert_eq!(eval_to_string("(cdr '(1 . 2))"), "2");
+        assert_eq!(eval_to_string("(caddr '(1 2 3))"), "3");
+        assert_eq!(eval_to_string("(cdddar '((1 2 3 4)))"), "(4)");
+        assert_eq!(
+            eval_to_string("(define x (list 'a 'b)) (set-car! (cdr x) 'c) x"),
+            "(a c)"
+        );
+        assert_eq!(eval_error("(car '())"), ErrorKind::WrongType);
+        assert_eq!(eval_error("(cadr '(1))"), ErrorKind::WrongType);
+    }
+
+    #[test]
+    fn shared_and_circular_structure_test() {
+        assert_eq!(
+            eval_to_string("(define x (list 1 2)) (define y (cons 0 x)) (set-car! x 'one) y"),
+            "(0 one 2)"
+        );
+        assert_eq!(
+            eval_to_string("(define x (list 1 2)) (set-cdr! (cdr x) x) (list? x)"),
+            "#f"
+        );
+        assert_eq!(
+            eval_to_string("(define x (list 1 2)) (eq? x (cdr (cons 0 x)))"),
+            "#t"
+        );
+    }
+
+    #[test]
+    fn lists_test() {
+        assert_eq!(eval_to_string("(list? '(a b c))"), "#t");
+        assert_eq!(eval_to_string("(list? '(a . b))"), "#f");
+        assert_eq!(eval_to_string("(length '(a (b) (c d e)))"), "3");
+        assert_eq!(eval_to_string("(append '(a) '(b c d))"), "(a b c d)");
+        assert_eq!(eval_to_string("(append '(a b) '(c . d))"), "(a b c . d)");
+        assert_eq!(eval_to_string("(append '() 'a)"), "a");
+        assert_eq!(
+            eval_to_string("(reverse '(a (b c) d (e (f))))"),
+            "((e (f)) d (b c) a)"
+        );
+        assert_eq!(eval_to_string("(list-tail '(a b c d) 2)"), "(c d)");
+        assert_eq!(eval_to_string("(list-ref '(a b c d) 2)"), "c");
+        assert_eq!(eval_error("(list-ref '(a b) 2)"), ErrorKind::OutOfRange);
+        assert_eq!(eval_error("(length '(a . b))"), ErrorKind::WrongType);
+    }
+
+    #[test]
+    fn membership_test() {
+        assert_eq!(eval_to_string("(memq 'a '(a b c))"), "(a b c)");
+        assert_eq!(eval_to_string("(memq 'a '(b c d))"), "#f");
+        assert_eq!(eval_to_string("(member (list 'a) '(b (a) c))"), "((a) c)");
+        assert_eq!(eval_to_string("(memv 101 '(100 101 102))"), "(101 102)");
+        assert_eq!(eval_to_string("(assq 'b '((a 1) (b 2)))"), "(b 2)");
+        assert_eq!(eval_to_string("(assv 5 '((2 3) (5 7) (11 13)))"), "(5 7)");
+        assert_eq!(
+            eval_to_string("(assoc (list 'a) '(((a)) ((b)) ((c))))"),
+            "((a))"
+        );
+        assert_eq!(eval_to_string("(assq 'd '((a 1) (b 2)))"), "#f");
+        assert_eq!(eval_error("(assq 'a '(1))"), ErrorKind::WrongType);
+    }
+}
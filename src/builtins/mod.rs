@@ -0,0 +1,347 @@
+//! The standard procedures of R5RS section 6, implemented natively
+//!
+//! Each submodule exports a slice of `Builtin`s, and `all` iterates over every one of them. The
+//! interpreter binds them in its global environment, and checks the `Arity` of a builtin before
+//! calling its `function`, so the functions themselves can index into their arguments freely
+//! within the declared arity.
+
+mod chars;
+mod control;
+mod equivalence;
+mod eval;
+mod exceptions;
+mod io;
+mod lists;
+mod numbers;
+mod strings;
+mod symbols;
+mod vectors;
+
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::lexer::LispNum;
+use crate::value::{Pair, Value};
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// Signature shared by all builtin procedures
+pub type BuiltinFunction = fn(&mut Interpreter, Vec<Value>) -> EvalResult;
+
+/// A procedure implemented in Rust
+pub struct Builtin {
+    /// The name the procedure is bound to in the global environment
+    pub name: &'static str,
+    /// The standard library exporting the procedure
+    pub library: StandardLibrary,
+    /// The number of arguments the procedure accepts
+    pub arity: Arity,
+    /// The parameters, in the notation of R5RS: optional ones are in brackets, and `...` follows
+    /// a parameter that may occur any number of times, including none
+    pub params: &'static str,
+    /// A one-sentence description, shown by the language server
+    pub doc: &'static str,
+    /// The implementation
+    pub function: BuiltinFunction,
+}
+
+/// The standard libraries of R7RS appendix A that builtins belong to
+///
+/// Procedures of R5RS that R7RS only keeps in `(scheme r5rs)` are in `R5rs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardLibrary {
+    /// `(scheme base)`
+    Base,
+    /// `(scheme char)`
+    Char,
+    /// `(scheme cxr)`
+    CxR,
+    /// `(scheme eval)`
+    Eval,
+    /// `(scheme file)`
+    File,
+    /// `(scheme inexact)`
+    Inexact,
+    /// `(scheme lazy)`
+    Lazy,
+    /// `(scheme load)`
+    Load,
+    /// `(scheme read)`
+    Read,
+    /// `(scheme repl)`
+    Repl,
+    /// `(scheme write)`
+    Write,
+    /// `(scheme r5rs)`, for the procedures no other library exports
+    R5rs,
+}
+
+impl StandardLibrary {
+    /// Finds the library called `(scheme name)`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "base" => StandardLibrary::Base,
+            "char" => StandardLibrary::Char,
+            "cxr" => StandardLibrary::CxR,
+            "eval" => StandardLibrary::Eval,
+            "file" => StandardLibrary::File,
+            "inexact" => StandardLibrary::Inexact,
+            "lazy" => StandardLibrary::Lazy,
+            "load" => StandardLibrary::Load,
+            "read" => StandardLibrary::Read,
+            "repl" => StandardLibrary::Repl,
+            "write" => StandardLibrary::Write,
+            "r5rs" => StandardLibrary::R5rs,
+            _ => return None,
+        })
+    }
+
+    /// Returns `true` if the procedures of the library reach the file system or the
+    /// evaluator, and so are left out of `scheme-report-environment`
+    pub fn is_privileged(self) -> bool {
+        matches!(
+            self,
+            StandardLibrary::Eval
+                | StandardLibrary::File
+                | StandardLibrary::Load
+                | StandardLibrary::Repl
+        )
+    }
+}
+
+/// The number of arguments a procedure accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    /// The minimum number of arguments
+    pub min: usize,
+    /// The maximum number of arguments, or `None` for variadic procedures
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    /// Accepts exactly `n` arguments
+    pub const fn exactly(n: usize) -> Self {
+        Arity {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    /// Accepts `n` or more arguments
+    pub const fn at_least(n: usize) -> Self {
+        Arity { min: n, max: None }
+    }
+
+    /// Accepts between `min` and `max` arguments, both inclusive
+    pub const fn between(min: usize, max: usize) -> Self {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+
+    /// Returns `true` if a call with `n` arguments is allowed
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+const MODULES: &[&[Builtin]] = &[
+    equivalence::BUILTINS,
+    numbers::BUILTINS,
+    lists::BUILTINS,
+    symbols::BUILTINS,
+    chars::BUILTINS,
+    strings::BUILTINS,
+    vectors::BUILTINS,
+    control::BUILTINS,
+    exceptions::BUILTINS,
+    eval::BUILTINS,
+    io::BUILTINS,
+];
+
+/// Iterates over every builtin procedure
+pub fn all() -> impl Iterator<Item = &'static Builtin> {
+    MODULES.iter().flat_map(|module| module.iter())
+}
+
+/// Finds the builtin procedure with the given name
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    all().find(|builtin| builtin.name == name)
+}
+
+/// Packs the arguments of `values` or of a continuation into a single value
+pub(crate) fn values_from_vec(mut values: Vec<Value>) -> Value {
+    if values.len() == 1 {
+        values.pop().unwrap()
+    } else {
+        Value::MultipleValues(Rc::new(values))
+    }
+}
+
+/// Splits the arguments of `apply` into the procedure and its arguments, spreading the last one
+pub(crate) fn spread_apply_arguments(
+    mut args: Vec<Value>,
+) -> Result<(Value, Vec<Value>), RuntimeError> {
+    let last = args.pop().unwrap();
+    let mut spread = expect_list("apply", &last)?;
+    let procedure = args.remove(0);
+    args.append(&mut spread);
+    Ok((procedure, args))
+}
+
+pub(crate) fn expect_number(procedure: &str, value: &Value) -> Result<LispNum, RuntimeError> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(RuntimeError::wrong_type(procedure, "number", other)),
+    }
+}
+
+pub(crate) fn expect_integer(procedure: &str, value: &Value) -> Result<i32, RuntimeError> {
+    match value {
+        Value::Number(LispNum::Integer(i)) => Ok(*i),
+        other => Err(RuntimeError::wrong_type(procedure, "exact integer", other)),
+    }
+}
+
+/// Expects a non-negative exact integer, such as an index or a length
+pub(crate) fn expect_index(procedure: &str, value: &Value) -> Result<usize, RuntimeError> {
+    match value {
+        Value::Number(LispNum::Integer(i)) if *i >= 0 => Ok(*i as usize),
+        other => Err(RuntimeError::wrong_type(
+            procedure,
+            "non-negative exact integer",
+            other,
+        )),
+    }
+}
+
+pub(crate) fn expect_char(procedure: &str, value: &Value) -> Result<char, RuntimeError> {
+    match value {
+        Value::Character(c) => Ok(*c),
+        other => Err(RuntimeError::wrong_type(procedure, "character", other)),
+    }
+}
+
+pub(crate) fn expect_string(
+    procedure: &str,
+    value: &Value,
+) -> Result<Rc<RefCell<String>>, RuntimeError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        other => Err(RuntimeError::wrong_type(procedure, "string", other)),
+    }
+}
+
+pub(crate) fn expect_symbol(procedure: &str, value: &Value) -> Result<Rc<str>, RuntimeError> {
+    match value {
+        Value::Symbol(s) => Ok(s.clone()),
+        other => Err(RuntimeError::wrong_type(procedure, "symbol", other)),
+    }
+}
+
+pub(crate) fn expect_pair(procedure: &str, value: &Value) -> Result<Rc<Pair>, RuntimeError> {
+    match value {
+        Value::Pair(p) => Ok(p.clone()),
+        other => Err(RuntimeError::wrong_type(procedure, "pair", other)),
+    }
+}
+
+pub(crate) fn expect_vector(
+    procedure: &str,
+    value: &Value,
+) -> Result<Rc<RefCell<Vec<Value>>>, RuntimeError> {
+    match value {
+        Value::Vector(v) => Ok(v.clone()),
+        other => Err(RuntimeError::wrong_type(procedure, "vector", other)),
+    }
+}
+
+pub(crate) fn expect_list(procedure: &str, value: &Value) -> Result<Vec<Value>, RuntimeError> {
+    value
+        .list_to_vec()
+        .ok_or_else(|| RuntimeError::wrong_type(procedure, "proper list", value))
+}
+
+pub(crate) fn expect_procedure(procedure: &str, value: &Value) -> Result<(), RuntimeError> {
+    match value {
+        Value::Procedure(_) => Ok(()),
+        other => Err(RuntimeError::wrong_type(procedure, "procedure", other)),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::interpreter::{ErrorKind, Interpreter, RuntimeError};
+    use crate::reader::{DatumIterator, StringLexer};
+    use crate::value::Value;
+
+    /// Evaluates every datum in `input`, returning the value of the last one
+    pub(crate) fn eval_str(input: &str) -> Result<Value, RuntimeError> {
+        eval_str_with(&mut Interpreter::new(), input)
+    }
+
+    /// Evaluates every datum of `input` with `interpreter`, returning the last value
+    pub(crate) fn eval_str_with(
+        interpreter: &mut Interpreter,
+        input: &str,
+    ) -> Result<Value, RuntimeError> {
+        let mut result = Value::Unspecified;
+        for datum in DatumIterator::new(StringLexer::new(input).into_iter()).with_positions() {
+            let (datum, positions) = datum.unwrap();
+            result = interpreter.eval_datum_with_positions(&datum, &positions)?;
+        }
+        Ok(result)
+    }
+
+    /// Evaluates `input` and returns the `write` representation of the result
+    pub(crate) fn eval_to_string(input: &str) -> String {
+        eval_str(input).unwrap().written().to_string()
+    }
+
+    /// Evaluates `input` and returns the kind of error it signals
+    pub(crate) fn eval_error(input: &str) -> ErrorKind {
+        eval_str(input).unwrap_err().kind
+    }
+
+    #[test]
+    fn arity_test() {
+        assert_eq!(eval_error("(car)"), ErrorKind::Arity);
+        assert_eq!(eval_error("(cons 1 2 3)"), ErrorKind::Arity);
+        assert_eq!(eval_error("(-)"), ErrorKind::Arity);
+        assert_eq!(eval_to_string("(+)"), "0");
+        assert_eq!(
+            eval_str("(vector-ref (vector))").unwrap_err().to_string(),
+            "vector-ref: expected 2 argument(s), got 1"
+        );
+    }
+
+    #[test]
+    fn params_test() {
+        for builtin in super::all() {
+            let params: Vec<&str> = builtin.params.split_whitespace().collect();
+            let repeated = params.iter().filter(|&&param| param == "...").count();
+            let optional = params.iter().filter(|param| param.starts_with('[')).count();
+            let required = params.len() - optional - 2 * repeated;
+            let max = if repeated > 0 {
+                None
+            } else {
+                Some(required + optional)
+            };
+            assert_eq!(
+                super::Arity { min: required, max },
+                builtin.arity,
+                "{}",
+                builtin.name
+            );
+            assert!(builtin.doc.ends_with('.'), "{}", builtin.name);
+        }
+    }
+}
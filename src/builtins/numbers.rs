@@ -0,0 +1,1204 @@
+//! Numerical operations (R5RS section 6.2)
+//!
+//! Only exact integers (`LispNum::Integer`) and inexact reals (`LispNum::Float`) are supported.
+//! Exact results that don't fit in an `i32`, exact non-integral rationals and complex results
+//! are reported as violations of an implementation restriction, except for `/`, which falls back
+//! to an inexact result when the quotient is not an integer.
+use super::{expect_integer, expect_number, expect_string, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{ErrorKind, EvalResult, Interpreter, RuntimeError};
+use crate::lexer::{lex_input, LispNum, Token};
+use crate::value::Value;
+use std::{cmp::Ordering, convert::TryFrom};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "number?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a number.",
+        function: is_number,
+    },
+    Builtin {
+        name: "complex?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a complex number, which every number is.",
+        function: is_number,
+    },
+    Builtin {
+        name: "real?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a real number.",
+        function: is_number,
+    },
+    Builtin {
+        name: "rational?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a rational number.",
+        function: is_rational,
+    },
+    Builtin {
+        name: "integer?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is an integer, exact or not.",
+        function: is_integer,
+    },
+    Builtin {
+        name: "exact?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns #t if z is an exact number.",
+        function: is_exact,
+    },
+    Builtin {
+        name: "inexact?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns #t if z is an inexact number.",
+        function: is_inexact,
+    },
+    Builtin {
+        name: "=",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "z1 z2 z3 ...",
+        doc: "Returns #t if the numbers are all equal.",
+        function: equal,
+    },
+    Builtin {
+        name: "<",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "x1 x2 x3 ...",
+        doc: "Returns #t if the numbers are strictly increasing.",
+        function: less,
+    },
+    Builtin {
+        name: ">",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "x1 x2 x3 ...",
+        doc: "Returns #t if the numbers are strictly decreasing.",
+        function: greater,
+    },
+    Builtin {
+        name: "<=",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "x1 x2 x3 ...",
+        doc: "Returns #t if the numbers are non-decreasing.",
+        function: less_equal,
+    },
+    Builtin {
+        name: ">=",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "x1 x2 x3 ...",
+        doc: "Returns #t if the numbers are non-increasing.",
+        function: greater_equal,
+    },
+    Builtin {
+        name: "zero?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns #t if z is zero.",
+        function: is_zero,
+    },
+    Builtin {
+        name: "positive?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns #t if x is greater than zero.",
+        function: is_positive,
+    },
+    Builtin {
+        name: "negative?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns #t if x is less than zero.",
+        function: is_negative,
+    },
+    Builtin {
+        name: "odd?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "n",
+        doc: "Returns #t if the integer n is odd.",
+        function: is_odd,
+    },
+    Builtin {
+        name: "even?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "n",
+        doc: "Returns #t if the integer n is even.",
+        function: is_even,
+    },
+    Builtin {
+        name: "max",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(1),
+        params: "x1 x2 ...",
+        doc: "Returns the largest of the numbers.",
+        function: max,
+    },
+    Builtin {
+        name: "min",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(1),
+        params: "x1 x2 ...",
+        doc: "Returns the smallest of the numbers.",
+        function: min,
+    },
+    Builtin {
+        name: "+",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "z1 ...",
+        doc: "Returns the sum of the numbers, 0 if there are none.",
+        function: add,
+    },
+    Builtin {
+        name: "*",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "z1 ...",
+        doc: "Returns the product of the numbers, 1 if there are none.",
+        function: multiply,
+    },
+    Builtin {
+        name: "-",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(1),
+        params: "z1 z2 ...",
+        doc: "Subtracts the other numbers from z1, or negates z1 if it is the only one.",
+        function: subtract,
+    },
+    Builtin {
+        name: "/",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(1),
+        params: "z1 z2 ...",
+        doc: "Divides z1 by the other numbers, or returns the reciprocal of z1 if it is the only one.",
+        function: divide,
+    },
+    Builtin {
+        name: "abs",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns the absolute value of x.",
+        function: abs,
+    },
+    Builtin {
+        name: "quotient",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "n1 n2",
+        doc: "Returns n1 divided by n2, truncated towards zero.",
+        function: quotient,
+    },
+    Builtin {
+        name: "remainder",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "n1 n2",
+        doc: "Returns the remainder of n1 divided by n2, with the sign of n1.",
+        function: remainder,
+    },
+    Builtin {
+        name: "modulo",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "n1 n2",
+        doc: "Returns n1 modulo n2, with the sign of n2.",
+        function: modulo,
+    },
+    Builtin {
+        name: "gcd",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "n1 ...",
+        doc: "Returns the greatest common divisor of the integers, 0 if there are none.",
+        function: gcd,
+    },
+    Builtin {
+        name: "lcm",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "n1 ...",
+        doc: "Returns the least common multiple of the integers, 1 if there are none.",
+        function: lcm,
+    },
+    Builtin {
+        name: "numerator",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "q",
+        doc: "Returns the numerator of q in lowest terms.",
+        function: numerator,
+    },
+    Builtin {
+        name: "denominator",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "q",
+        doc: "Returns the denominator of q in lowest terms.",
+        function: denominator,
+    },
+    Builtin {
+        name: "floor",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns the largest integer not larger than x.",
+        function: floor,
+    },
+    Builtin {
+        name: "ceiling",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns the smallest integer not smaller than x.",
+        function: ceiling,
+    },
+    Builtin {
+        name: "truncate",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns the integer closest to x whose absolute value is not larger than that of x.",
+        function: truncate,
+    },
+    Builtin {
+        name: "round",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "x",
+        doc: "Returns the integer closest to x, rounding to even when x is halfway between two integers.",
+        function: round,
+    },
+    Builtin {
+        name: "rationalize",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "x y",
+        doc: "Returns the simplest rational number differing from x by no more than y.",
+        function: rationalize,
+    },
+    Builtin {
+        name: "exp",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns e raised to the power z.",
+        function: exp,
+    },
+    Builtin {
+        name: "log",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the natural logarithm of z.",
+        function: log,
+    },
+    Builtin {
+        name: "sin",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the sine of z, in radians.",
+        function: sin,
+    },
+    Builtin {
+        name: "cos",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the cosine of z, in radians.",
+        function: cos,
+    },
+    Builtin {
+        name: "tan",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the tangent of z, in radians.",
+        function: tan,
+    },
+    Builtin {
+        name: "asin",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the arcsine of z, in radians.",
+        function: asin,
+    },
+    Builtin {
+        name: "acos",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the arccosine of z, in radians.",
+        function: acos,
+    },
+    Builtin {
+        name: "atan",
+        library: StandardLibrary::Inexact,
+        arity: Arity::between(1, 2),
+        params: "y [x]",
+        doc: "Returns the arctangent of y, or the angle of the point (x, y) if x is given.",
+        function: atan,
+    },
+    Builtin {
+        name: "sqrt",
+        library: StandardLibrary::Inexact,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the principal square root of z.",
+        function: sqrt,
+    },
+    Builtin {
+        name: "expt",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "z1 z2",
+        doc: "Returns z1 raised to the power z2.",
+        function: expt,
+    },
+    Builtin {
+        name: "exact->inexact",
+        library: StandardLibrary::R5rs,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the inexact number closest to z.",
+        function: exact_to_inexact,
+    },
+    Builtin {
+        name: "inexact->exact",
+        library: StandardLibrary::R5rs,
+        arity: Arity::exactly(1),
+        params: "z",
+        doc: "Returns the exact number closest to z.",
+        function: inexact_to_exact,
+    },
+    Builtin {
+        name: "number->string",
+        library: StandardLibrary::Base,
+        arity: Arity::between(1, 2),
+        params: "z [radix]",
+        doc: "Returns the external representation of z in the given radix, 10 by default.",
+        function: number_to_string,
+    },
+    Builtin {
+        name: "string->number",
+        library: StandardLibrary::Base,
+        arity: Arity::between(1, 2),
+        params: "string [radix]",
+        doc: "Returns the number string represents in the given radix, or #f if it is not a number.",
+        function: string_to_number,
+    },
+];
+
+fn to_f64(n: LispNum) -> f64 {
+    match n {
+        LispNum::Integer(i) => f64::from(i),
+        LispNum::Float(x) => f64::from(x),
+    }
+}
+
+fn inexact(x: f64) -> Value {
+    Value::Number(LispNum::Float(x as f32))
+}
+
+fn exact(i: i32) -> Value {
+    Value::Number(LispNum::Integer(i))
+}
+
+fn overflow(procedure: &str, args: &[Value]) -> RuntimeError {
+    RuntimeError::new(
+        ErrorKind::ImplementationRestriction,
+        format!(
+            "{}: result is not representable as a 32-bit integer",
+            procedure
+        ),
+        args.to_vec(),
+    )
+}
+
+fn no_complex(procedure: &str, arg: &Value) -> RuntimeError {
+    RuntimeError::new(
+        ErrorKind::ImplementationRestriction,
+        format!("{}: complex numbers are not supported", procedure),
+        vec![arg.clone()],
+    )
+}
+
+fn division_by_zero(procedure: &str, args: &[Value]) -> RuntimeError {
+    RuntimeError::new(
+        ErrorKind::DivisionByZero,
+        format!("{}: division by zero", procedure),
+        args.to_vec(),
+    )
+}
+
+fn numbers(procedure: &str, args: &[Value]) -> Result<Vec<LispNum>, RuntimeError> {
+    args.iter()
+        .map(|arg| expect_number(procedure, arg))
+        .collect()
+}
+
+fn is_integral(n: LispNum) -> bool {
+    match n {
+        LispNum::Integer(_) => true,
+        LispNum::Float(x) => x.is_finite() && x.fract() == 0.0,
+    }
+}
+
+/// An integer argument, which may be inexact as long as it has no fractional part
+enum Integral {
+    Exact(i32),
+    Inexact(f64),
+}
+
+fn expect_integral(procedure: &str, value: &Value) -> Result<Integral, RuntimeError> {
+    match value {
+        Value::Number(LispNum::Integer(i)) => Ok(Integral::Exact(*i)),
+        Value::Number(n @ LispNum::Float(x)) if is_integral(*n) => {
+            Ok(Integral::Inexact(f64::from(*x)))
+        }
+        other => Err(RuntimeError::wrong_type(procedure, "integer", other)),
+    }
+}
+
+fn is_number(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Number(_))))
+}
+
+fn is_rational(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(match args[0] {
+        Value::Number(LispNum::Integer(_)) => true,
+        Value::Number(LispNum::Float(x)) => x.is_finite(),
+        _ => false,
+    }))
+}
+
+fn is_integer(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(
+        matches!(args[0], Value::Number(n) if is_integral(n)),
+    ))
+}
+
+fn is_exact(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(
+        expect_number("exact?", &args[0])?,
+        LispNum::Integer(_)
+    )))
+}
+
+fn is_inexact(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(
+        expect_number("inexact?", &args[0])?,
+        LispNum::Float(_)
+    )))
+}
+
+fn compare(a: LispNum, b: LispNum) -> Option<Ordering> {
+    match (a, b) {
+        (LispNum::Integer(x), LispNum::Integer(y)) => Some(x.cmp(&y)),
+        _ => to_f64(a).partial_cmp(&to_f64(b)),
+    }
+}
+
+fn compare_chain(procedure: &str, args: &[Value], accept: fn(Ordering) -> bool) -> EvalResult {
+    let nums = numbers(procedure, args)?;
+    Ok(Value::Boolean(
+        nums.windows(2)
+            .all(|pair| compare(pair[0], pair[1]).is_some_and(accept)),
+    ))
+}
+
+fn equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("=", &args, |o| o == Ordering::Equal)
+}
+
+fn less(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("<", &args, |o| o == Ordering::Less)
+}
+
+fn greater(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain(">", &args, |o| o == Ordering::Greater)
+}
+
+fn less_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("<=", &args, |o| o != Ordering::Greater)
+}
+
+fn greater_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain(">=", &args, |o| o != Ordering::Less)
+}
+
+fn sign_test(procedure: &str, arg: &Value, accept: fn(Ordering) -> bool) -> EvalResult {
+    let n = expect_number(procedure, arg)?;
+    Ok(Value::Boolean(
+        compare(n, LispNum::Integer(0)).is_some_and(accept),
+    ))
+}
+
+fn is_zero(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    sign_test("zero?", &args[0], |o| o == Ordering::Equal)
+}
+
+fn is_positive(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    sign_test("positive?", &args[0], |o| o == Ordering::Greater)
+}
+
+fn is_negative(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    sign_test("negative?", &args[0], |o| o == Ordering::Less)
+}
+
+fn is_odd(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(match expect_integral("odd?", &args[0])? {
+        Integral::Exact(i) => i % 2 != 0,
+        Integral::Inexact(x) => x % 2.0 != 0.0,
+    }))
+}
+
+fn is_even(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(match expect_integral("even?", &args[0])? {
+        Integral::Exact(i) => i % 2 == 0,
+        Integral::Inexact(x) => x % 2.0 == 0.0,
+    }))
+}
+
+/// Shared implementation of `max` and `min`; the result is inexact if any argument is
+fn extremum(procedure: &str, args: &[Value], keep: Ordering) -> EvalResult {
+    let nums = numbers(procedure, args)?;
+    let any_inexact = nums.iter().any(|n| matches!(n, LispNum::Float(_)));
+    let mut best = nums[0];
+    for &n in &nums[1..] {
+        if compare(n, best) == Some(keep) || to_f64(n).is_nan() {
+            best = n;
+        }
+    }
+    Ok(if any_inexact {
+        inexact(to_f64(best))
+    } else {
+        Value::Number(best)
+    })
+}
+
+fn max(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    extremum("max", &args, Ordering::Greater)
+}
+
+fn min(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    extremum("min", &args, Ordering::Less)
+}
+
+/// Folds `nums` into `initial` with an exact operation, switching to the inexact operation as
+/// soon as an inexact number is encountered
+fn fold_arithmetic(
+    procedure: &str,
+    args: &[Value],
+    initial: LispNum,
+    nums: &[LispNum],
+    exact_op: fn(i32, i32) -> Option<i32>,
+    inexact_op: fn(f64, f64) -> f64,
+) -> EvalResult {
+    let mut acc = initial;
+    for &n in nums {
+        acc = match (acc, n) {
+            (LispNum::Integer(x), LispNum::Integer(y)) => {
+                LispNum::Integer(exact_op(x, y).ok_or_else(|| overflow(procedure, args))?)
+            }
+            (x, y) => LispNum::Float(inexact_op(to_f64(x), to_f64(y)) as f32),
+        };
+    }
+    Ok(Value::Number(acc))
+}
+
+fn add(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let nums = numbers("+", &args)?;
+    fold_arithmetic(
+        "+",
+        &args,
+        LispNum::Integer(0),
+        &nums,
+        i32::checked_add,
+        |x, y| x + y,
+    )
+}
+
+fn multiply(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let nums = numbers("*", &args)?;
+    fold_arithmetic(
+        "*",
+        &args,
+        LispNum::Integer(1),
+        &nums,
+        i32::checked_mul,
+        |x, y| x * y,
+    )
+}
+
+fn subtract(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let nums = numbers("-", &args)?;
+    let (initial, rest) = match nums.as_slice() {
+        [only] => (LispNum::Integer(0), std::slice::from_ref(only)),
+        _ => (nums[0], &nums[1..]),
+    };
+    fold_arithmetic("-", &args, initial, rest, i32::checked_sub, |x, y| x - y)
+}
+
+fn divide_two(args: &[Value], x: LispNum, y: LispNum) -> Result<LispNum, RuntimeError> {
+    match (x, y) {
+        (LispNum::Integer(_), LispNum::Integer(0)) => Err(division_by_zero("/", args)),
+        (LispNum::Integer(a), LispNum::Integer(b)) if a % b == 0 => a
+            .checked_div(b)
+            .map(LispNum::Integer)
+            .ok_or_else(|| overflow("/", args)),
+        (a, b) => Ok(LispNum::Float((to_f64(a) / to_f64(b)) as f32)),
+    }
+}
+
+fn divide(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let nums = numbers("/", &args)?;
+    if nums.len() == 1 {
+        return Ok(Value::Number(divide_two(
+            &args,
+            LispNum::Integer(1),
+            nums[0],
+        )?));
+    }
+    let mut acc = nums[0];
+    for &n in &nums[1..] {
+        acc = divide_two(&args, acc, n)?;
+    }
+    Ok(Value::Number(acc))
+}
+
+fn abs(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    match expect_number("abs", &args[0])? {
+        LispNum::Integer(i) => i
+            .checked_abs()
+            .map(exact)
+            .ok_or_else(|| overflow("abs", &args).into()),
+        LispNum::Float(x) => Ok(Value::Number(LispNum::Float(x.abs()))),
+    }
+}
+
+/// Shared implementation of `quotient`, `remainder` and `modulo`
+fn integer_division(
+    procedure: &str,
+    args: &[Value],
+    exact_op: fn(i32, i32) -> Option<i32>,
+    inexact_op: fn(f64, f64) -> f64,
+) -> EvalResult {
+    let x = expect_integral(procedure, &args[0])?;
+    let y = expect_integral(procedure, &args[1])?;
+    match (x, y) {
+        (_, Integral::Exact(0)) => Err(division_by_zero(procedure, args).into()),
+        (_, Integral::Inexact(0.0)) => Err(division_by_zero(procedure, args).into()),
+        (Integral::Exact(a), Integral::Exact(b)) => exact_op(a, b)
+            .map(exact)
+            .ok_or_else(|| overflow(procedure, args).into()),
+        (a, b) => {
+            let as_f64 = |i: Integral| match i {
+                Integral::Exact(i) => f64::from(i),
+                Integral::Inexact(x) => x,
+            };
+            Ok(inexact(inexact_op(as_f64(a), as_f64(b))))
+        }
+    }
+}
+
+fn quotient(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    integer_division("quotient", &args, i32::checked_div, |a, b| (a / b).trunc())
+}
+
+// Dividing `i32::MIN` by -1 overflows, but leaves no remainder, which wrapping arithmetic gets
+// right; the divisor is never 0 here
+fn remainder(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    integer_division(
+        "remainder",
+        &args,
+        |a, b| Some(a.wrapping_rem(b)),
+        |a, b| a % b,
+    )
+}
+
+fn modulo(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    integer_division(
+        "modulo",
+        &args,
+        |a, b| {
+            let r = a.wrapping_rem_euclid(b);
+            Some(if b < 0 && r != 0 { r + b } else { r })
+        },
+        |a, b| {
+            let r = a % b;
+            if r != 0.0 && (r < 0.0) != (b < 0.0) {
+                r + b
+            } else {
+                r
+            }
+        },
+    )
+}
+
+fn gcd_i64(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a.abs()
+}
+
+fn gcd_f64(mut a: f64, mut b: f64) -> f64 {
+    while b != 0.0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a.abs()
+}
+
+/// Shared implementation of `gcd` and `lcm`
+fn fold_integral(
+    procedure: &str,
+    args: &[Value],
+    initial: i64,
+    exact_op: fn(i64, i64) -> i64,
+    inexact_op: fn(f64, f64) -> f64,
+) -> EvalResult {
+    let mut exact_acc = Some(initial);
+    let mut inexact_acc = initial as f64;
+    for arg in args {
+        match expect_integral(procedure, arg)? {
+            Integral::Exact(i) => {
+                exact_acc = exact_acc.map(|acc| exact_op(acc, i64::from(i)));
+                inexact_acc = inexact_op(inexact_acc, f64::from(i));
+            }
+            Integral::Inexact(x) => {
+                exact_acc = None;
+                inexact_acc = inexact_op(inexact_acc, x);
+            }
+        }
+    }
+    match exact_acc {
+        Some(acc) => i32::try_from(acc)
+            .map(exact)
+            .map_err(|_| overflow(procedure, args).into()),
+        None => Ok(inexact(inexact_acc)),
+    }
+}
+
+fn gcd(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    fold_integral("gcd", &args, 0, gcd_i64, gcd_f64)
+}
+
+fn lcm(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    fold_integral(
+        "lcm",
+        &args,
+        1,
+        |a, b| {
+            if a == 0 || b == 0 {
+                0
+            } else {
+                (a / gcd_i64(a, b) * b).abs()
+            }
+        },
+        |a, b| {
+            if a == 0.0 || b == 0.0 {
+                0.0
+            } else {
+                (a / gcd_f64(a, b) * b).abs()
+            }
+        },
+    )
+}
+
+/// Splits a finite float into a numerator and a power of two denominator
+fn float_fraction(x: f64) -> (f64, f64) {
+    let mut numerator = x;
+    let mut denominator = 1.0;
+    while numerator.fract() != 0.0 {
+        numerator *= 2.0;
+        denominator *= 2.0;
+    }
+    (numerator, denominator)
+}
+
+fn numerator(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    match expect_number("numerator", &args[0])? {
+        n @ LispNum::Integer(_) => Ok(Value::Number(n)),
+        LispNum::Float(x) if x.is_finite() => Ok(inexact(float_fraction(f64::from(x)).0)),
+        _ => Err(RuntimeError::wrong_type("numerator", "rational number", &args[0]).into()),
+    }
+}
+
+fn denominator(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    match expect_number("denominator", &args[0])? {
+        LispNum::Integer(_) => Ok(exact(1)),
+        LispNum::Float(x) if x.is_finite() => Ok(inexact(float_fraction(f64::from(x)).1)),
+        _ => Err(RuntimeError::wrong_type("denominator", "rational number", &args[0]).into()),
+    }
+}
+
+fn rounding(procedure: &str, arg: &Value, op: fn(f32) -> f32) -> EvalResult {
+    match expect_number(procedure, arg)? {
+        n @ LispNum::Integer(_) => Ok(Value::Number(n)),
+        LispNum::Float(x) => Ok(Value::Number(LispNum::Float(op(x)))),
+    }
+}
+
+fn floor(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    rounding("floor", &args[0], f32::floor)
+}
+
+fn ceiling(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    rounding("ceiling", &args[0], f32::ceil)
+}
+
+fn truncate(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    rounding("truncate", &args[0], f32::trunc)
+}
+
+/// Rounds to the nearest integer, rounding to even when halfway between two integers
+fn round_to_even(x: f32) -> f32 {
+    let rounded = x.round();
+    if (rounded - x).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        rounded
+    }
+}
+
+fn round(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    rounding("round", &args[0], round_to_even)
+}
+
+/// Finds the simplest rational in the interval `[low, high]`, for `0 < low <= high`
+fn simplest_between(low: f64, high: f64, depth: usize) -> f64 {
+    let floor = low.floor();
+    if floor == low || depth == 0 {
+        floor
+    } else if floor < high.floor() {
+        floor + 1.0
+    } else {
+        floor + 1.0 / simplest_between(1.0 / (high - floor), 1.0 / (low - floor), depth - 1)
+    }
+}
+
+fn rationalize(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let x = expect_number("rationalize", &args[0])?;
+    let y = expect_number("rationalize", &args[1])?;
+    let (x_f, y_f) = (to_f64(x), to_f64(y).abs());
+    let (low, high) = (x_f - y_f, x_f + y_f);
+    let simplest = if low > 0.0 {
+        simplest_between(low, high, 64)
+    } else if high < 0.0 {
+        -simplest_between(-high, -low, 64)
+    } else {
+        0.0
+    };
+    match (x, y) {
+        (LispNum::Integer(_), LispNum::Integer(_)) => Ok(exact(simplest as i32)),
+        _ => Ok(inexact(simplest)),
+    }
+}
+
+fn transcendental(procedure: &str, arg: &Value, op: fn(f64) -> f64) -> EvalResult {
+    let result = op(to_f64(expect_number(procedure, arg)?));
+    if result.is_nan() {
+        return Err(no_complex(procedure, arg).into());
+    }
+    Ok(inexact(result))
+}
+
+fn exp(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("exp", &args[0], f64::exp)
+}
+
+fn log(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("log", &args[0], f64::ln)
+}
+
+fn sin(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("sin", &args[0], f64::sin)
+}
+
+fn cos(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("cos", &args[0], f64::cos)
+}
+
+fn tan(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("tan", &args[0], f64::tan)
+}
+
+fn asin(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("asin", &args[0], f64::asin)
+}
+
+fn acos(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    transcendental("acos", &args[0], f64::acos)
+}
+
+fn atan(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    if args.len() == 1 {
+        return transcendental("atan", &args[0], f64::atan);
+    }
+    let y = to_f64(expect_number("atan", &args[0])?);
+    let x = to_f64(expect_number("atan", &args[1])?);
+    Ok(inexact(y.atan2(x)))
+}
+
+fn sqrt(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    if let LispNum::Integer(i) = expect_number("sqrt", &args[0])? {
+        if i >= 0 {
+            let root = f64::from(i).sqrt().round() as i32;
+            if root.checked_mul(root) == Some(i) {
+                return Ok(exact(root));
+            }
+        }
+    }
+    transcendental("sqrt", &args[0], f64::sqrt)
+}
+
+fn expt(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let base = expect_number("expt", &args[0])?;
+    let power = expect_number("expt", &args[1])?;
+    match (base, power) {
+        (LispNum::Integer(b), LispNum::Integer(p)) if p >= 0 => b
+            .checked_pow(p as u32)
+            .map(exact)
+            .ok_or_else(|| overflow("expt", &args).into()),
+        (LispNum::Integer(0), LispNum::Integer(_)) => Err(division_by_zero("expt", &args).into()),
+        (b, p) => {
+            let result = to_f64(b).powf(to_f64(p));
+            if result.is_nan() {
+                return Err(no_complex("expt", &args[0]).into());
+            }
+            Ok(inexact(result))
+        }
+    }
+}
+
+fn exact_to_inexact(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(inexact(to_f64(expect_number("exact->inexact", &args[0])?)))
+}
+
+fn inexact_to_exact(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    match expect_number("inexact->exact", &args[0])? {
+        n @ LispNum::Integer(_) => Ok(Value::Number(n)),
+        LispNum::Float(x)
+            if is_integral(LispNum::Float(x))
+                && f64::from(x) >= f64::from(i32::MIN)
+                && f64::from(x) <= f64::from(i32::MAX) =>
+        {
+            Ok(exact(x as i32))
+        }
+        _ => Err(RuntimeError::new(
+            ErrorKind::ImplementationRestriction,
+            "inexact->exact: no exact integer representation for",
+            args,
+        )
+        .into()),
+    }
+}
+
+fn expect_radix(procedure: &str, args: &[Value]) -> Result<u32, RuntimeError> {
+    match args.get(1) {
+        None => Ok(10),
+        Some(radix) => match expect_integer(procedure, radix)? {
+            r @ 2 | r @ 8 | r @ 10 | r @ 16 => Ok(r as u32),
+            _ => Err(RuntimeError::out_of_range(procedure, radix)),
+        },
+    }
+}
+
+fn format_radix(i: i32, radix: u32) -> String {
+    let mut digits = Vec::new();
+    let mut n = i64::from(i).abs();
+    loop {
+        digits.push(std::char::from_digit((n % i64::from(radix)) as u32, radix).unwrap());
+        n /= i64::from(radix);
+        if n == 0 {
+            break;
+        }
+    }
+    if i < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+fn number_to_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let n = expect_number("number->string", &args[0])?;
+    let radix = expect_radix("number->string", &args)?;
+    match n {
+        LispNum::Integer(i) => Ok(Value::string(format_radix(i, radix))),
+        LispNum::Float(_) if radix == 10 => Ok(Value::string(args[0].to_string())),
+        LispNum::Float(_) => Err(RuntimeError::out_of_range("number->string", &args[1]).into()),
+    }
+}
+
+/// Parses the external representation of a number, reusing the lexer for decimal numbers
+fn parse_number(mut text: &str, mut radix: u32) -> Option<LispNum> {
+    while text.len() >= 2 && text.starts_with('#') {
+        radix = match &text[1..2] {
+            "b" | "B" => 2,
+            "o" | "O" => 8,
+            "d" | "D" => 10,
+            "x" | "X" => 16,
+            _ => return None,
+        };
+        text = &text[2..];
+    }
+    if radix == 10 {
+        return match lex_input(text) {
+            Ok(("", Token::Number(n))) => Some(n),
+            _ => None,
+        };
+    }
+    i32::from_str_radix(text, radix).ok().map(LispNum::Integer)
+}
+
+fn string_to_number(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let text = expect_string("string->number", &args[0])?;
+    let radix = expect_radix("string->number", &args)?;
+    let parsed = parse_number(&text.borrow(), radix);
+    Ok(parsed.map_or(Value::Boolean(false), Value::Number))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn arithmetic_test() {
+        assert_eq!(eval_to_string("(+ 3 4)"), "7");
+        assert_eq!(eval_to_string("(+ 3 4.5)"), "7.5");
+        assert_eq!(eval_to_string("(* 4)"), "4");
+        assert_eq!(eval_to_string("(- 3 4 5)"), "-6");
+        assert_eq!(eval_to_string("(- 3)"), "-3");
+        assert_eq!(eval_to_string("(/ 3 4 5)"), "0.15");
+        assert_eq!(eval_to_string("(/ 8 4)"), "2");
+        assert_eq!(eval_to_string("(/ 2)"), "0.5");
+        assert_eq!(eval_to_string("(max 3 4)"), "4");
+        assert_eq!(eval_to_string("(max 3.9 4)"), "4.0");
+        assert_eq!(eval_to_string("(abs -7)"), "7");
+        assert_eq!(eval_error("(/ 1 0)"), ErrorKind::DivisionByZero);
+        assert_eq!(
+            eval_error("(+ 2147483647 1)"),
+            ErrorKind::ImplementationRestriction
+        );
+        assert_eq!(eval_error("(+ 1 \"a\")"), ErrorKind::WrongType);
+        assert_eq!(eval_error("(- 1 \"a\")"), ErrorKind::WrongType);
+    }
+
+    #[test]
+    fn integer_division_test() {
+        assert_eq!(eval_to_string("(modulo 13 4)"), "1");
+        assert_eq!(eval_to_string("(remainder 13 4)"), "1");
+        assert_eq!(eval_to_string("(modulo -13 4)"), "3");
+        assert_eq!(eval_to_string("(remainder -13 4)"), "-1");
+        assert_eq!(eval_to_string("(modulo 13 -4)"), "-3");
+        assert_eq!(eval_to_string("(remainder 13 -4)"), "1");
+        assert_eq!(eval_to_string("(remainder -13 -4.0)"), "-1.0");
+        assert_eq!(eval_to_string("(quotient 17 -5)"), "-3");
+        assert_eq!(eval_to_string("(gcd 32 -36)"), "4");
+        assert_eq!(eval_to_string("(gcd)"), "0");
+        assert_eq!(eval_to_string("(lcm 32 -36)"), "288");
+        assert_eq!(eval_to_string("(lcm 32.0 -36)"), "288.0");
+        assert_eq!(eval_to_string("(lcm)"), "1");
+        assert_eq!(eval_error("(quotient 1 0)"), ErrorKind::DivisionByZero);
+        assert_eq!(eval_to_string("(remainder -2147483648 -1)"), "0");
+        assert_eq!(eval_to_string("(modulo -2147483648 -1)"), "0");
+        assert_eq!(
+            eval_error("(quotient -2147483648 -1)"),
+            ErrorKind::ImplementationRestriction
+        );
+    }
+
+    #[test]
+    fn predicates_test() {
+        assert_eq!(eval_to_string("(integer? 3.0)"), "#t");
+        assert_eq!(eval_to_string("(integer? 3.5)"), "#f");
+        assert_eq!(eval_to_string("(rational? 1.5)"), "#t");
+        assert_eq!(eval_to_string("(exact? 3)"), "#t");
+        assert_eq!(eval_to_string("(inexact? 3.0)"), "#t");
+        assert_eq!(eval_to_string("(= 1 1.0 1)"), "#t");
+        assert_eq!(eval_to_string("(< 1 2 3)"), "#t");
+        assert_eq!(eval_to_string("(< 1 3 2)"), "#f");
+        assert_eq!(eval_to_string("(>= 3 3 2)"), "#t");
+        assert_eq!(eval_to_string("(zero? 0.0)"), "#t");
+        assert_eq!(eval_to_string("(negative? -1)"), "#t");
+        assert_eq!(eval_to_string("(odd? 3)"), "#t");
+        assert_eq!(eval_to_string("(even? 0)"), "#t");
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(eval_to_string("(floor -4.3)"), "-5.0");
+        assert_eq!(eval_to_string("(ceiling -4.3)"), "-4.0");
+        assert_eq!(eval_to_string("(truncate -4.3)"), "-4.0");
+        assert_eq!(eval_to_string("(round -4.3)"), "-4.0");
+        assert_eq!(eval_to_string("(round 3.5)"), "4.0");
+        assert_eq!(eval_to_string("(round 2.5)"), "2.0");
+        assert_eq!(eval_to_string("(round 7)"), "7");
+        assert_eq!(eval_to_string("(rationalize 3 1)"), "2");
+        assert_eq!(eval_to_string("(rationalize .3 .1)"), "0.33333334");
+        assert_eq!(eval_to_string("(numerator 6)"), "6");
+        assert_eq!(eval_to_string("(denominator 0.5)"), "2.0");
+    }
+
+    #[test]
+    fn transcendental_test() {
+        assert_eq!(eval_to_string("(sqrt 16)"), "4");
+        assert_eq!(eval_to_string("(sqrt 2.25)"), "1.5");
+        assert_eq!(eval_to_string("(expt 2 10)"), "1024");
+        assert_eq!(eval_to_string("(expt 2 -1)"), "0.5");
+        assert_eq!(eval_to_string("(exp 0)"), "1.0");
+        assert_eq!(eval_to_string("(atan 1 1)"), "0.7853982");
+        assert_eq!(
+            eval_error("(sqrt -4)"),
+            ErrorKind::ImplementationRestriction
+        );
+        assert_eq!(eval_to_string("(exact->inexact 1)"), "1.0");
+        assert_eq!(eval_to_string("(inexact->exact 2.0)"), "2");
+        assert_eq!(
+            eval_error("(inexact->exact 2.5)"),
+            ErrorKind::ImplementationRestriction
+        );
+    }
+
+    #[test]
+    fn number_conversion_test() {
+        assert_eq!(eval_to_string("(number->string 255 16)"), "\"ff\"");
+        assert_eq!(eval_to_string("(number->string -5 2)"), "\"-101\"");
+        assert_eq!(eval_to_string("(number->string 1.5)"), "\"1.5\"");
+        assert_eq!(eval_to_string("(string->number \"100\")"), "100");
+        assert_eq!(eval_to_string("(string->number \"100\" 16)"), "256");
+        assert_eq!(eval_to_string("(string->number \"#xff\")"), "255");
+        assert_eq!(eval_to_string("(string->number \"-1.5\")"), "-1.5");
+        assert_eq!(eval_to_string("(string->number \"abc\")"), "#f");
+    }
+}
@@ -0,0 +1,396 @@
+//! Strings (R5RS section 6.3.5)
+//!
+//! Strings are indexed by `char`, not by byte, so indexing operations convert to a `Vec<char>`
+//! where necessary.
+use super::{
+    chars::fold_case, expect_char, expect_index, expect_list, expect_string, Arity, Builtin,
+    StandardLibrary,
+};
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::lexer::LispNum;
+use crate::value::Value;
+use std::cmp::Ordering;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "string?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a string.",
+        function: is_string,
+    },
+    Builtin {
+        name: "make-string",
+        library: StandardLibrary::Base,
+        arity: Arity::between(1, 2),
+        params: "k [char]",
+        doc: "Returns a newly allocated string of length k, filled with char if it is given.",
+        function: make_string,
+    },
+    Builtin {
+        name: "string",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "char ...",
+        doc: "Returns a newly allocated string of the characters.",
+        function: string,
+    },
+    Builtin {
+        name: "string-length",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "string",
+        doc: "Returns the number of characters of string.",
+        function: string_length,
+    },
+    Builtin {
+        name: "string-ref",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "string k",
+        doc: "Returns the kth character of string, counting from 0.",
+        function: string_ref,
+    },
+    Builtin {
+        name: "string-set!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(3),
+        params: "string k char",
+        doc: "Stores char as the kth character of string.",
+        function: string_set,
+    },
+    Builtin {
+        name: "string=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are all equal.",
+        function: string_equal,
+    },
+    Builtin {
+        name: "string<?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are lexicographically strictly increasing.",
+        function: string_less,
+    },
+    Builtin {
+        name: "string>?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are lexicographically strictly decreasing.",
+        function: string_greater,
+    },
+    Builtin {
+        name: "string<=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are lexicographically non-decreasing.",
+        function: string_less_equal,
+    },
+    Builtin {
+        name: "string>=?",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are lexicographically non-increasing.",
+        function: string_greater_equal,
+    },
+    Builtin {
+        name: "string-ci=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are all equal, ignoring case.",
+        function: string_ci_equal,
+    },
+    Builtin {
+        name: "string-ci<?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are strictly increasing, ignoring case.",
+        function: string_ci_less,
+    },
+    Builtin {
+        name: "string-ci>?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are strictly decreasing, ignoring case.",
+        function: string_ci_greater,
+    },
+    Builtin {
+        name: "string-ci<=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are non-decreasing, ignoring case.",
+        function: string_ci_less_equal,
+    },
+    Builtin {
+        name: "string-ci>=?",
+        library: StandardLibrary::Char,
+        arity: Arity::at_least(2),
+        params: "string1 string2 string3 ...",
+        doc: "Returns #t if the strings are non-increasing, ignoring case.",
+        function: string_ci_greater_equal,
+    },
+    Builtin {
+        name: "substring",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(3),
+        params: "string start end",
+        doc: "Returns a newly allocated string of the characters of string from start, inclusive, to end, exclusive.",
+        function: substring,
+    },
+    Builtin {
+        name: "string-append",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "string ...",
+        doc: "Returns a newly allocated string of the characters of the strings, in order.",
+        function: string_append,
+    },
+    Builtin {
+        name: "string->list",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "string",
+        doc: "Returns a newly allocated list of the characters of string.",
+        function: string_to_list,
+    },
+    Builtin {
+        name: "list->string",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "list",
+        doc: "Returns a newly allocated string of the characters of list.",
+        function: list_to_string,
+    },
+    Builtin {
+        name: "string-copy",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "string",
+        doc: "Returns a newly allocated copy of string.",
+        function: string_copy,
+    },
+    Builtin {
+        name: "string-fill!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "string char",
+        doc: "Stores char in every element of string.",
+        function: string_fill,
+    },
+];
+
+fn chars_of(procedure: &str, value: &Value) -> Result<Vec<char>, RuntimeError> {
+    Ok(expect_string(procedure, value)?.borrow().chars().collect())
+}
+
+fn is_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::String(_))))
+}
+
+fn make_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let length = expect_index("make-string", &args[0])?;
+    let fill = match args.get(1) {
+        Some(fill) => expect_char("make-string", fill)?,
+        None => ' ',
+    };
+    Ok(Value::string(
+        std::iter::repeat_n(fill, length).collect::<String>(),
+    ))
+}
+
+fn string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let chars = args
+        .iter()
+        .map(|arg| expect_char("string", arg))
+        .collect::<Result<String, _>>()?;
+    Ok(Value::string(chars))
+}
+
+fn string_length(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let length = expect_string("string-length", &args[0])?
+        .borrow()
+        .chars()
+        .count();
+    Ok(Value::Number(LispNum::Integer(length as i32)))
+}
+
+fn string_ref(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let s = expect_string("string-ref", &args[0])?;
+    let k = expect_index("string-ref", &args[1])?;
+    let c = s.borrow().chars().nth(k);
+    c.map(Value::Character)
+        .ok_or_else(|| RuntimeError::out_of_range("string-ref", &args[1]).into())
+}
+
+fn string_set(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let s = expect_string("string-set!", &args[0])?;
+    let k = expect_index("string-set!", &args[1])?;
+    let c = expect_char("string-set!", &args[2])?;
+    let mut chars: Vec<char> = s.borrow().chars().collect();
+    match chars.get_mut(k) {
+        Some(slot) => *slot = c,
+        None => return Err(RuntimeError::out_of_range("string-set!", &args[1]).into()),
+    }
+    *s.borrow_mut() = chars.into_iter().collect();
+    Ok(Value::Unspecified)
+}
+
+fn compare_chain(
+    procedure: &str,
+    args: &[Value],
+    fold: fn(char) -> char,
+    accept: fn(Ordering) -> bool,
+) -> EvalResult {
+    let strings = args
+        .iter()
+        .map(|arg| Ok(chars_of(procedure, arg)?.into_iter().map(fold).collect()))
+        .collect::<Result<Vec<Vec<char>>, RuntimeError>>()?;
+    Ok(Value::Boolean(
+        strings.windows(2).all(|pair| accept(pair[0].cmp(&pair[1]))),
+    ))
+}
+
+fn string_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string=?", &args, |c| c, |o| o == Ordering::Equal)
+}
+
+fn string_less(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string<?", &args, |c| c, |o| o == Ordering::Less)
+}
+
+fn string_greater(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string>?", &args, |c| c, |o| o == Ordering::Greater)
+}
+
+fn string_less_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string<=?", &args, |c| c, |o| o != Ordering::Greater)
+}
+
+fn string_greater_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string>=?", &args, |c| c, |o| o != Ordering::Less)
+}
+
+fn string_ci_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string-ci=?", &args, fold_case, |o| o == Ordering::Equal)
+}
+
+fn string_ci_less(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string-ci<?", &args, fold_case, |o| o == Ordering::Less)
+}
+
+fn string_ci_greater(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string-ci>?", &args, fold_case, |o| o == Ordering::Greater)
+}
+
+fn string_ci_less_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string-ci<=?", &args, fold_case, |o| o != Ordering::Greater)
+}
+
+fn string_ci_greater_equal(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    compare_chain("string-ci>=?", &args, fold_case, |o| o != Ordering::Less)
+}
+
+fn substring(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let chars = chars_of("substring", &args[0])?;
+    let start = expect_index("substring", &args[1])?;
+    let end = expect_index("substring", &args[2])?;
+    if end > chars.len() {
+        return Err(RuntimeError::out_of_range("substring", &args[2]).into());
+    }
+    if start > end {
+        return Err(RuntimeError::out_of_range("substring", &args[1]).into());
+    }
+    Ok(Value::string(chars[start..end].iter().collect::<String>()))
+}
+
+fn string_append(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let mut result = String::new();
+    for arg in &args {
+        result.push_str(&expect_string("string-append", arg)?.borrow());
+    }
+    Ok(Value::string(result))
+}
+
+fn string_to_list(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let chars = chars_of("string->list", &args[0])?;
+    Ok(Value::list(
+        chars.into_iter().map(Value::Character).collect::<Vec<_>>(),
+    ))
+}
+
+fn list_to_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let chars = expect_list("list->string", &args[0])?
+        .iter()
+        .map(|c| expect_char("list->string", c))
+        .collect::<Result<String, _>>()?;
+    Ok(Value::string(chars))
+}
+
+fn string_copy(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let s = expect_string("string-copy", &args[0])?;
+    let copy = s.borrow().clone();
+    Ok(Value::string(copy))
+}
+
+fn string_fill(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let s = expect_string("string-fill!", &args[0])?;
+    let c = expect_char("string-fill!", &args[1])?;
+    let length = s.borrow().chars().count();
+    *s.borrow_mut() = std::iter::repeat_n(c, length).collect();
+    Ok(Value::Unspecified)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn strings_test() {
+        assert_eq!(eval_to_string("(string? \"a\")"), "#t");
+        assert_eq!(eval_to_string("(make-string 3 #\\x)"), "\"xxx\"");
+        assert_eq!(eval_to_string("(string #\\a #\\b)"), "\"ab\"");
+        assert_eq!(eval_to_string("(string-length \"héllo\")"), "5");
+        assert_eq!(eval_to_string("(string-ref \"héllo\" 1)"), "#\\é");
+        assert_eq!(
+            eval_to_string("(define s (make-string 3 #\\*)) (string-set! s 0 #\\?) s"),
+            "\"?**\""
+        );
+        assert_eq!(eval_to_string("(substring \"hello\" 1 3)"), "\"el\"");
+        assert_eq!(
+            eval_to_string("(string-append \"foo\" \"bar\")"),
+            "\"foobar\""
+        );
+        assert_eq!(eval_to_string("(string->list \"ab\")"), "(#\\a #\\b)");
+        assert_eq!(eval_to_string("(list->string '(#\\a #\\b))"), "\"ab\"");
+        assert_eq!(
+            eval_to_string(
+                "(define a \"abc\") (define b (string-copy a)) (string-fill! b #\\z) (list a b)"
+            ),
+            "(\"abc\" \"zzz\")"
+        );
+        assert_eq!(eval_error("(string-ref \"abc\" 3)"), ErrorKind::OutOfRange);
+        assert_eq!(eval_error("(substring \"abc\" 2 1)"), ErrorKind::OutOfRange);
+    }
+
+    #[test]
+    fn string_comparison_test() {
+        assert_eq!(eval_to_string("(string=? \"abc\" \"abc\")"), "#t");
+        assert_eq!(eval_to_string("(string<? \"abc\" \"abd\")"), "#t");
+        assert_eq!(eval_to_string("(string<? \"ab\" \"abc\")"), "#t");
+        assert_eq!(eval_to_string("(string>? \"b\" \"abc\")"), "#t");
+        assert_eq!(eval_to_string("(string-ci=? \"ABC\" \"abc\")"), "#t");
+        assert_eq!(eval_to_string("(string-ci<? \"ABC\" \"abd\")"), "#t");
+    }
+}
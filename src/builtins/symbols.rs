@@ -0,0 +1,67 @@
+//! Symbols (R5RS section 6.3.3)
+use super::{expect_string, expect_symbol, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{EvalResult, Interpreter};
+use crate::value::Value;
+use std::rc::Rc;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "symbol?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a symbol.",
+        function: is_symbol,
+    },
+    Builtin {
+        name: "symbol->string",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "symbol",
+        doc: "Returns the name of symbol as a string.",
+        function: symbol_to_string,
+    },
+    Builtin {
+        name: "string->symbol",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "string",
+        doc: "Returns the symbol whose name is string.",
+        function: string_to_symbol,
+    },
+];
+
+fn is_symbol(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Symbol(_))))
+}
+
+fn symbol_to_string(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::string(&*expect_symbol("symbol->string", &args[0])?))
+}
+
+fn string_to_symbol(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let name = expect_string("string->symbol", &args[0])?;
+    let symbol = Rc::from(name.borrow().as_str());
+    Ok(Value::Symbol(symbol))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::eval_to_string;
+
+    #[test]
+    fn symbols_test() {
+        assert_eq!(eval_to_string("(symbol? 'foo)"), "#t");
+        assert_eq!(eval_to_string("(symbol? (car '(a b)))"), "#t");
+        assert_eq!(eval_to_string("(symbol? \"bar\")"), "#f");
+        assert_eq!(
+            eval_to_string("(symbol->string 'flying-fish)"),
+            "\"flying-fish\""
+        );
+        assert_eq!(
+            eval_to_string("(eq? 'mISSISSIppi (string->symbol \"mISSISSIppi\"))"),
+            "#t"
+        );
+        assert_eq!(eval_to_string("(string->symbol (symbol->string 'K))"), "K");
+    }
+}
@@ -0,0 +1,166 @@
+//! Vectors (R5RS section 6.3.6)
+use super::{expect_index, expect_list, expect_vector, Arity, Builtin, StandardLibrary};
+use crate::interpreter::{EvalResult, Interpreter, RuntimeError};
+use crate::lexer::LispNum;
+use crate::value::Value;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "vector?",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "obj",
+        doc: "Returns #t if obj is a vector.",
+        function: is_vector,
+    },
+    Builtin {
+        name: "make-vector",
+        library: StandardLibrary::Base,
+        arity: Arity::between(1, 2),
+        params: "k [fill]",
+        doc: "Returns a newly allocated vector of k elements, each fill if it is given.",
+        function: make_vector,
+    },
+    Builtin {
+        name: "vector",
+        library: StandardLibrary::Base,
+        arity: Arity::at_least(0),
+        params: "obj ...",
+        doc: "Returns a newly allocated vector of its arguments.",
+        function: vector,
+    },
+    Builtin {
+        name: "vector-length",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "vector",
+        doc: "Returns the number of elements of vector.",
+        function: vector_length,
+    },
+    Builtin {
+        name: "vector-ref",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "vector k",
+        doc: "Returns the kth element of vector, counting from 0.",
+        function: vector_ref,
+    },
+    Builtin {
+        name: "vector-set!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(3),
+        params: "vector k obj",
+        doc: "Stores obj as the kth element of vector.",
+        function: vector_set,
+    },
+    Builtin {
+        name: "vector->list",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "vector",
+        doc: "Returns a newly allocated list of the elements of vector.",
+        function: vector_to_list,
+    },
+    Builtin {
+        name: "list->vector",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(1),
+        params: "list",
+        doc: "Returns a newly allocated vector of the elements of list.",
+        function: list_to_vector,
+    },
+    Builtin {
+        name: "vector-fill!",
+        library: StandardLibrary::Base,
+        arity: Arity::exactly(2),
+        params: "vector fill",
+        doc: "Stores fill in every element of vector.",
+        function: vector_fill,
+    },
+];
+
+fn is_vector(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::Boolean(matches!(args[0], Value::Vector(_))))
+}
+
+fn make_vector(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let length = expect_index("make-vector", &args[0])?;
+    let fill = args.get(1).cloned().unwrap_or(Value::Unspecified);
+    Ok(Value::vector(vec![fill; length]))
+}
+
+fn vector(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::vector(args))
+}
+
+fn vector_length(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let length = expect_vector("vector-length", &args[0])?.borrow().len();
+    Ok(Value::Number(LispNum::Integer(length as i32)))
+}
+
+fn vector_ref(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let v = expect_vector("vector-ref", &args[0])?;
+    let k = expect_index("vector-ref", &args[1])?;
+    let item = v.borrow().get(k).cloned();
+    item.ok_or_else(|| RuntimeError::out_of_range("vector-ref", &args[1]).into())
+}
+
+fn vector_set(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let v = expect_vector("vector-set!", &args[0])?;
+    let k = expect_index("vector-set!", &args[1])?;
+    match v.borrow_mut().get_mut(k) {
+        Some(slot) => *slot = args[2].clone(),
+        None => return Err(RuntimeError::out_of_range("vector-set!", &args[1]).into()),
+    }
+    Ok(Value::Unspecified)
+}
+
+fn vector_to_list(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let items = expect_vector("vector->list", &args[0])?.borrow().clone();
+    Ok(Value::list(items))
+}
+
+fn list_to_vector(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    Ok(Value::vector(expect_list("list->vector", &args[0])?))
+}
+
+fn vector_fill(_: &mut Interpreter, args: Vec<Value>) -> EvalResult {
+    let v = expect_vector("vector-fill!", &args[0])?;
+    for slot in v.borrow_mut().iter_mut() {
+        *slot = args[1].clone();
+    }
+    Ok(Value::Unspecified)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::{eval_error, eval_to_string};
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn vectors_test() {
+        assert_eq!(eval_to_string("(vector? #(1 2))"), "#t");
+        assert_eq!(eval_to_string("(make-vector 2 'a)"), "#(a a)");
+        assert_eq!(eval_to_string("(vector 'a 'b 'c)"), "#(a b c)");
+        assert_eq!(eval_to_string("(vector-length #(1 2 3))"), "3");
+        assert_eq!(eval_to_string("(vector-ref '#(1 1 2 3 5 8 13 21) 5)"), "8");
+        assert_eq!(
+            eval_to_string("(let ((vec (vector 0 '(2 2 2 2) \"Anna\"))) (vector-set! vec 1 '(\"Sue\" \"Sue\")) vec)"),
+            "#(0 (\"Sue\" \"Sue\") \"Anna\")"
+        );
+        assert_eq!(
+            eval_to_string("(vector->list '#(dah dah didah))"),
+            "(dah dah didah)"
+        );
+        assert_eq!(
+            eval_to_string("(list->vector '(dididit dah))"),
+            "#(dididit dah)"
+        );
+        assert_eq!(
+            eval_to_string("(define v (vector 1 2)) (vector-fill! v 0) v"),
+            "#(0 0)"
+        );
+        assert_eq!(eval_error("(vector-ref #(1) 1)"), ErrorKind::OutOfRange);
+        assert_eq!(eval_error("(vector-ref '(1) 0)"), ErrorKind::WrongType);
+    }
+}
@@ -0,0 +1,310 @@
+//! Command line arguments of the `oxyscheme` binary
+
+use oxyscheme::diagnostics::ErrorFormat;
+use oxyscheme::lint::RULES;
+
+/// The text printed by `--help`
+pub const USAGE: &str = "\
+Usage: oxyscheme [OPTIONS] COMMAND [FILE...]
+
+Commands:
+  lex        Print the tokens of the files as `line:column kind text`
+  parse      Print the data read from the files
+  check      Read the files and check the syntax of their forms without running them
+  lint       Warn about likely mistakes in the files, such as unused bindings
+  run        Run the files, one after the other
+  repl       Read, evaluate and print expressions typed on standard input
+  fmt        Print the files with their indentation fixed
+  compile    Check the files; no code generator is available yet
+  debug      Run a file under the step debugger, reading commands from standard input
+  profile    Run the files and print a profile of the procedure calls to standard error
+  lsp        Run a language server speaking LSP on standard input and output
+
+A FILE of - reads standard input.
+
+Options:
+  -o, --output FILE          Write the output of lex, parse and fmt, or the output of the
+                             program for run, repl, debug and profile, to FILE
+      --error-format FORMAT  Report errors as human (the default), json or sarif
+      --json                 With lex, print the tokens as JSON objects, one per line
+      --folded FILE          With profile, also write folded stacks to FILE
+      --enable RULE          With lint, enable a rule, such as unused-parameter
+      --disable RULE         With lint, disable a rule; `; lint: allow RULE` comments
+                             disable it for the rest of their line, or for the next datum
+      --fix                  With check, first rewrite the files with their unmatched
+                             parentheses fixed, as the fix-its of the errors suggest
+  -h, --help                 Print this help
+  -V, --version              Print the version
+
+Exit status:
+  0  success
+  1  the program raised an error
+  2  invalid command line
+  3  a file could not be lexed or parsed, or contains a syntax error
+  4  a file could not be read or written
+  5  lint reported warnings";
+
+/// A subcommand of the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Lex,
+    Parse,
+    Check,
+    Lint,
+    Run,
+    Repl,
+    Fmt,
+    Compile,
+    Debug,
+    Profile,
+    Lsp,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "lex" => Command::Lex,
+            "parse" => Command::Parse,
+            "check" => Command::Check,
+            "lint" => Command::Lint,
+            "run" => Command::Run,
+            "repl" => Command::Repl,
+            "fmt" => Command::Fmt,
+            "compile" => Command::Compile,
+            "debug" => Command::Debug,
+            "profile" => Command::Profile,
+            "lsp" => Command::Lsp,
+            _ => return None,
+        })
+    }
+}
+
+/// A file to read, or standard input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(String),
+}
+
+/// The parsed command line of a command to run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub inputs: Vec<Input>,
+    pub output: Option<String>,
+    pub error_format: ErrorFormat,
+    pub folded: Option<String>,
+    pub json: bool,
+    pub enable: Vec<String>,
+    pub disable: Vec<String>,
+    pub fix: bool,
+}
+
+/// What the command line asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Help,
+    Version,
+    Command(Options),
+}
+
+/// Parses the arguments following the name of the binary
+pub fn parse_args<S: AsRef<str>>(args: &[S]) -> Result<Request, String> {
+    let mut command = None;
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut error_format = ErrorFormat::Human;
+    let mut folded = None;
+    let mut json = false;
+    let mut enable = Vec::new();
+    let mut disable = Vec::new();
+    let mut fix = false;
+    let mut args = args.iter().map(AsRef::as_ref);
+    while let Some(arg) = args.next() {
+        // Options take their value either in the same argument after `=`, or in the next one
+        let (name, inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (&arg[..index], Some(&arg[index + 1..])),
+            _ => (arg, None),
+        };
+        let mut value = || match inline_value {
+            Some(value) => Ok(value.to_string()),
+            None => args
+                .next()
+                .map(str::to_string)
+                .ok_or_else(|| format!("{} expects a value", name)),
+        };
+        match name {
+            "-h" | "--help" => return Ok(Request::Help),
+            "-V" | "--version" => return Ok(Request::Version),
+            "-o" | "--output" => output = Some(value()?),
+            "--error-format" => error_format = value()?.parse()?,
+            "--folded" => folded = Some(value()?),
+            "--json" => json = true,
+            "--enable" => enable.push(value()?),
+            "--disable" => disable.push(value()?),
+            "--fix" => fix = true,
+            "-" => inputs.push(Input::Stdin),
+            _ if name.starts_with('-') => return Err(format!("unknown option {}", name)),
+            _ if command.is_none() => match Command::from_name(arg) {
+                Some(parsed) => command = Some(parsed),
+                None => return Err(format!("unknown command {}", arg)),
+            },
+            _ => inputs.push(Input::File(arg.to_string())),
+        }
+    }
+    let command = command.ok_or_else(|| "no command given".to_string())?;
+    match command {
+        Command::Repl if !inputs.is_empty() => {
+            return Err("repl does not take any files".to_string())
+        }
+        Command::Debug if inputs.len() != 1 || inputs[0] == Input::Stdin => {
+            return Err("debug takes exactly one file, other than standard input".to_string())
+        }
+        Command::Lsp if !inputs.is_empty() => return Err("lsp does not take any files".to_string()),
+        Command::Repl | Command::Lsp => {}
+        _ if inputs.is_empty() => return Err("no input files".to_string()),
+        _ => {}
+    }
+    if folded.is_some() && command != Command::Profile {
+        return Err("--folded is only accepted by profile".to_string());
+    }
+    if json && command != Command::Lex {
+        return Err("--json is only accepted by lex".to_string());
+    }
+    if !(enable.is_empty() && disable.is_empty()) && command != Command::Lint {
+        return Err("--enable and --disable are only accepted by lint".to_string());
+    }
+    if fix && command != Command::Check {
+        return Err("--fix is only accepted by check".to_string());
+    }
+    if fix && inputs.contains(&Input::Stdin) {
+        return Err("--fix cannot rewrite standard input".to_string());
+    }
+    if let Some(name) = enable
+        .iter()
+        .chain(&disable)
+        .find(|name| !RULES.iter().any(|rule| rule.name == name.as_str()))
+    {
+        return Err(format!("unknown lint rule {}", name));
+    }
+    Ok(Request::Command(Options {
+        command,
+        inputs,
+        output,
+        error_format,
+        folded,
+        json,
+        enable,
+        disable,
+        fix,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_args, Command, Input, Options, Request};
+    use oxyscheme::diagnostics::ErrorFormat;
+
+    #[test]
+    fn parse_args_test() {
+        assert_eq!(
+            parse_args(&["--error-format=json", "run", "a.scm", "-", "-o", "out.txt"]),
+            Ok(Request::Command(Options {
+                command: Command::Run,
+                inputs: vec![Input::File("a.scm".to_string()), Input::Stdin],
+                output: Some("out.txt".to_string()),
+                error_format: ErrorFormat::Json,
+                folded: None,
+                json: false,
+                enable: Vec::new(),
+                disable: Vec::new(),
+                fix: false,
+            }))
+        );
+        assert_eq!(
+            parse_args(&["profile", "--folded", "f.txt", "a.scm"]),
+            Ok(Request::Command(Options {
+                command: Command::Profile,
+                inputs: vec![Input::File("a.scm".to_string())],
+                output: None,
+                error_format: ErrorFormat::Human,
+                folded: Some("f.txt".to_string()),
+                json: false,
+                enable: Vec::new(),
+                disable: Vec::new(),
+                fix: false,
+            }))
+        );
+        assert_eq!(
+            parse_args(&["lint", "--disable=redefinition", "a.scm"]),
+            Ok(Request::Command(Options {
+                command: Command::Lint,
+                inputs: vec![Input::File("a.scm".to_string())],
+                output: None,
+                error_format: ErrorFormat::Human,
+                folded: None,
+                json: false,
+                enable: Vec::new(),
+                disable: vec!["redefinition".to_string()],
+                fix: false,
+            }))
+        );
+        assert_eq!(
+            parse_args(&["check", "--fix", "a.scm"]),
+            Ok(Request::Command(Options {
+                command: Command::Check,
+                inputs: vec![Input::File("a.scm".to_string())],
+                output: None,
+                error_format: ErrorFormat::Human,
+                folded: None,
+                json: false,
+                enable: Vec::new(),
+                disable: Vec::new(),
+                fix: true,
+            }))
+        );
+        assert_eq!(parse_args(&["run", "--help"]), Ok(Request::Help));
+        assert_eq!(parse_args(&["-V"]), Ok(Request::Version));
+    }
+
+    #[test]
+    fn invalid_args_test() {
+        let error = |args: &[&str]| parse_args(args).unwrap_err();
+        assert_eq!(error(&[]), "no command given");
+        assert_eq!(error(&["a.scm"]), "unknown command a.scm");
+        assert_eq!(error(&["run"]), "no input files");
+        assert_eq!(error(&["run", "-x", "a.scm"]), "unknown option -x");
+        assert_eq!(error(&["run", "a.scm", "-o"]), "-o expects a value");
+        assert_eq!(error(&["repl", "a.scm"]), "repl does not take any files");
+        assert_eq!(error(&["lsp", "-"]), "lsp does not take any files");
+        assert_eq!(
+            error(&["debug", "-"]),
+            "debug takes exactly one file, other than standard input"
+        );
+        assert_eq!(
+            error(&["run", "a.scm", "--folded=x"]),
+            "--folded is only accepted by profile"
+        );
+        assert_eq!(
+            error(&["run", "--json", "a.scm"]),
+            "--json is only accepted by lex"
+        );
+        assert_eq!(
+            error(&["check", "--enable", "redefinition", "a.scm"]),
+            "--enable and --disable are only accepted by lint"
+        );
+        assert_eq!(
+            error(&["lint", "--enable", "typo", "a.scm"]),
+            "unknown lint rule typo"
+        );
+        assert_eq!(
+            error(&["run", "--fix", "a.scm"]),
+            "--fix is only accepted by check"
+        );
+        assert_eq!(
+            error(&["check", "--fix", "-"]),
+            "--fix cannot rewrite standard input"
+        );
+        assert!(error(&["--error-format", "xml", "run", "a.scm"]).contains("xml"));
+    }
+}
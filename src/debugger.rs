@@ -0,0 +1,451 @@
+//! Interactive step debugger for the interpreter
+//!
+//! The `Debugger` is installed as the `DebugHook` of an `Interpreter`, and pauses before calls
+//! read from source code: at breakpoints, which are set by line, and after stepping. While
+//! paused, it reads commands from its input, which can inspect the paused frame, evaluate
+//! expressions in it, or resume execution.
+//!
+//! A breakpoint pauses at the first call on its line, and not again until a call on another
+//! line has been made.
+
+use crate::interpreter::{DebugHook, Environment, ErrorKind, Interpreter, Procedure, RuntimeError};
+use crate::parser::Position;
+use crate::reader::{DatumIterator, StringLexer};
+use crate::value::Value;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+const HELP: &str = "\
+Commands:
+  break [FILE:]LINE   (b)  pause at the first call on LINE
+  delete LINE         (d)  remove the breakpoint on LINE
+  continue            (c)  run until the next breakpoint
+  step                (s)  pause at the next call, stepping into procedures
+  next                (n)  pause at the next call in the current procedure
+  finish              (f)  pause after returning from the current procedure
+  locals              (l)  show the variables of the paused frame
+  env                 (e)  show the chain of environments of the paused frame
+  backtrace           (bt) show the procedure calls in progress
+  print EXPR          (p)  evaluate EXPR in the paused frame
+  quit                (q)  stop the program
+  help                (h)  show this message";
+
+/// When to pause next, apart from at breakpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Only at breakpoints
+    Continue,
+    /// At the next call
+    Step,
+    /// At the next call made with at most the given number of procedure calls in progress
+    Next(usize),
+    /// At the next call made with fewer than the given number of procedure calls in progress
+    Finish(usize),
+}
+
+/// A step debugger for a single source file
+pub struct Debugger {
+    filename: String,
+    source: Vec<String>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    last_line: Option<usize>,
+}
+
+impl Debugger {
+    /// Creates a debugger for the program in `source`, read from `filename`, which pauses before
+    /// the first call of the program
+    pub fn new(
+        filename: &str,
+        source: &str,
+        input: Box<dyn BufRead>,
+        output: Box<dyn Write>,
+    ) -> Self {
+        Debugger {
+            filename: filename.to_string(),
+            source: source.lines().map(str::to_string).collect(),
+            input,
+            output,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            last_line: None,
+        }
+    }
+
+    fn should_pause(&self, line: usize, depth: usize) -> bool {
+        let stepped = match self.mode {
+            Mode::Continue => false,
+            Mode::Step => true,
+            Mode::Next(level) => depth <= level,
+            Mode::Finish(level) => depth < level,
+        };
+        stepped || (self.breakpoints.contains(&line) && self.last_line != Some(line))
+    }
+
+    /// Shows where execution paused, looking up positions in other files in the source map of
+    /// the interpreter
+    fn show_position(&mut self, interpreter: &Interpreter, position: Position) -> io::Result<()> {
+        let source_map = interpreter.source_map();
+        let (name, text) = match position.file {
+            Some(file) if source_map.path(file) != Some(Path::new(&self.filename)) => (
+                source_map.name(file).unwrap_or(&self.filename),
+                source_map.line(file, position.line),
+            ),
+            _ => (
+                self.filename.as_str(),
+                self.source.get(position.line - 1).map(String::as_str),
+            ),
+        };
+        writeln!(self.output, "Paused at {}, {}", name, position)?;
+        if let Some(text) = text {
+            writeln!(self.output, "{:>5} | {}", position.line, text)?;
+        }
+        Ok(())
+    }
+
+    /// Reads and runs commands until one resumes execution
+    fn prompt(
+        &mut self,
+        interpreter: &mut Interpreter,
+        depth: usize,
+        env: &Rc<Environment>,
+    ) -> Result<(), RuntimeError> {
+        loop {
+            write!(self.output, "(debug) ").map_err(io_error)?;
+            self.output.flush().map_err(io_error)?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line).map_err(io_error)? == 0 {
+                return Err(quit());
+            }
+            let line = line.trim();
+            let (command, argument) = match line.find(char::is_whitespace) {
+                Some(index) => (&line[..index], line[index..].trim()),
+                None => (line, ""),
+            };
+            let resume = match command {
+                "" => None,
+                "c" | "continue" => Some(Mode::Continue),
+                "s" | "step" => Some(Mode::Step),
+                "n" | "next" => Some(Mode::Next(depth)),
+                "f" | "finish" => Some(Mode::Finish(depth)),
+                "q" | "quit" => return Err(quit()),
+                _ => {
+                    self.inspect(interpreter, command, argument, env)
+                        .map_err(io_error)?;
+                    None
+                }
+            };
+            if let Some(mode) = resume {
+                self.mode = mode;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs a command which does not resume execution
+    fn inspect(
+        &mut self,
+        interpreter: &mut Interpreter,
+        command: &str,
+        argument: &str,
+        env: &Rc<Environment>,
+    ) -> io::Result<()> {
+        match command {
+            "b" | "break" => match self.parse_line(argument) {
+                Ok(line) => {
+                    self.breakpoints.insert(line);
+                    writeln!(
+                        self.output,
+                        "Breakpoint set at {}, line {}",
+                        self.filename, line
+                    )
+                }
+                Err(message) => writeln!(self.output, "{}", message),
+            },
+            "d" | "delete" => match self.parse_line(argument) {
+                Ok(line) if self.breakpoints.remove(&line) => {
+                    writeln!(self.output, "Breakpoint at line {} deleted", line)
+                }
+                Ok(line) => writeln!(self.output, "No breakpoint at line {}", line),
+                Err(message) => writeln!(self.output, "{}", message),
+            },
+            "l" | "locals" => {
+                if env.parent().is_none() {
+                    return writeln!(self.output, "No local variables");
+                }
+                for (name, value) in env.bindings() {
+                    writeln!(self.output, "{} = {}", name, value.written())?;
+                }
+                Ok(())
+            }
+            "e" | "env" => self.show_environments(env),
+            "bt" | "backtrace" => {
+                for (index, frame) in interpreter.backtrace().iter().enumerate() {
+                    let name = frame.describe(interpreter.source_map());
+                    match frame.call_site {
+                        Some(position) => {
+                            writeln!(self.output, "#{} {}, called at {}", index, name, position)?
+                        }
+                        None => writeln!(self.output, "#{} {}", index, name)?,
+                    }
+                    if frame.elided > 0 {
+                        writeln!(self.output, "   ({} tail call(s) elided)", frame.elided)?;
+                    }
+                }
+                Ok(())
+            }
+            "p" | "print" => match read_expression(argument) {
+                Ok(form) => match interpreter.eval_in(&form, env) {
+                    Ok(value) => writeln!(self.output, "{}", value.written()),
+                    Err(error) => writeln!(self.output, "Error: {}", error),
+                },
+                Err(message) => writeln!(self.output, "{}", message),
+            },
+            "h" | "help" => writeln!(self.output, "{}", HELP),
+            _ => writeln!(
+                self.output,
+                "Unknown command {}, type help for a list of commands",
+                command
+            ),
+        }
+    }
+
+    /// Shows the bindings of each environment, innermost first, summarizing the global one
+    fn show_environments(&mut self, env: &Rc<Environment>) -> io::Result<()> {
+        let mut env = env;
+        let mut index = 0;
+        while let Some(parent) = env.parent() {
+            let bindings: Vec<_> = env
+                .bindings()
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value.written()))
+                .collect();
+            writeln!(self.output, "#{} {}", index, bindings.join(", "))?;
+            env = parent;
+            index += 1;
+        }
+        let global = env.bindings();
+        let defined: Vec<_> = global
+            .iter()
+            .filter(|(name, value)| !is_standard_binding(name, value))
+            .map(|(name, _)| &**name)
+            .collect();
+        writeln!(
+            self.output,
+            "#{} global: {} ({} bindings in total)",
+            index,
+            defined.join(", "),
+            global.len()
+        )
+    }
+
+    /// Parses the argument of `break` or `delete`, checking that it refers to the debugged file
+    fn parse_line(&self, argument: &str) -> Result<usize, String> {
+        let line = match argument.rfind(':') {
+            Some(index) => {
+                let file = &argument[..index];
+                let debugged = Path::new(&self.filename);
+                if Path::new(file) != debugged && Some(file.as_ref()) != debugged.file_name() {
+                    return Err(format!("No source file named {}", file));
+                }
+                &argument[index + 1..]
+            }
+            None => argument,
+        };
+        match line.parse() {
+            Ok(line) if line > 0 => Ok(line),
+            _ => Err(format!("Invalid line number {:?}", line)),
+        }
+    }
+}
+
+impl DebugHook for Debugger {
+    fn before_call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        position: Position,
+        depth: usize,
+        env: &Rc<Environment>,
+    ) -> Result<(), RuntimeError> {
+        let pause = self.should_pause(position.line, depth);
+        self.last_line = Some(position.line);
+        if !pause {
+            return Ok(());
+        }
+        self.show_position(interpreter, position)
+            .map_err(io_error)?;
+        self.prompt(interpreter, depth, env)
+    }
+}
+
+/// Whether a global variable is bound to the standard procedure of the same name
+fn is_standard_binding(name: &str, value: &Value) -> bool {
+    match value {
+        Value::Procedure(procedure) => {
+            matches!(&**procedure, Procedure::Builtin(builtin) if builtin.name == name)
+        }
+        _ => false,
+    }
+}
+
+fn read_expression(text: &str) -> Result<Value, String> {
+    let mut datums = DatumIterator::new(StringLexer::new(text).into_iter());
+    match datums.next() {
+        Some(Ok(datum)) => Ok(Value::from(&datum)),
+        Some(Err(error)) => Err(error.to_string()),
+        None => Err("Expected an expression".to_string()),
+    }
+}
+
+fn quit() -> RuntimeError {
+    RuntimeError::new(ErrorKind::Aborted, "quit from the debugger", Vec::new())
+}
+
+fn io_error(error: io::Error) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Io, error.to_string(), Vec::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Debugger;
+    use crate::interpreter::{ErrorKind, Interpreter, RuntimeError};
+    use crate::reader::{DatumIterator, StringLexer};
+    use crate::value::Value;
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const PROGRAM: &str = "(define (square x)
+  (* x x))
+(define (sum-of-squares a b)
+  (+ (square a)
+     (square b)))
+(display (sum-of-squares 3 4))
+(newline)";
+
+    /// Debugs `PROGRAM` with the given commands, returning the result and the debugger output
+    fn debug(commands: &str) -> (Result<Value, RuntimeError>, String) {
+        debug_program(PROGRAM, commands)
+    }
+
+    /// Debugs `program` as `squares.scm` with the given commands
+    fn debug_program(program: &str, commands: &str) -> (Result<Value, RuntimeError>, String) {
+        let output = SharedBuffer::default();
+        let debugger = Debugger::new(
+            "squares.scm",
+            program,
+            Box::new(io::Cursor::new(commands.to_string())),
+            Box::new(output.clone()),
+        );
+        let mut interpreter = Interpreter::new();
+        interpreter.set_current_output_port(Rc::new(crate::port::Port::Output(RefCell::new(
+            crate::port::OutputPort::string(),
+        ))));
+        interpreter.set_debug_hook(Some(Box::new(debugger)));
+        let mut result = Ok(Value::Unspecified);
+        for datum in DatumIterator::new(StringLexer::new(program).into_iter()).with_positions() {
+            let (datum, positions) = datum.unwrap();
+            result = interpreter.eval_datum_with_positions(&datum, &positions);
+            if result.is_err() {
+                break;
+            }
+        }
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        (result, output)
+    }
+
+    /// The positions the debugger paused at, in order
+    ///
+    /// Commands are not echoed, so each pause is printed right after the prompt.
+    fn pauses(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .map(|line| line.trim_start_matches("(debug) "))
+            .filter_map(|line| line.strip_prefix("Paused at squares.scm, "))
+            .collect()
+    }
+
+    #[test]
+    fn stepping_test() {
+        let (result, output) = debug("s\ns\ns\nn\nn\nf\nc\n");
+        assert!(result.is_ok());
+        assert_eq!(
+            pauses(&output),
+            vec![
+                "line 6, column 0",
+                "line 6, column 9",
+                "line 4, column 2",
+                "line 4, column 5",
+                "line 5, column 5",
+                "line 7, column 0",
+            ]
+        );
+
+        // Stepping over a call to a procedure does not pause inside of it
+        let (_, output) = debug("s\nn\nq\n");
+        assert_eq!(
+            pauses(&output),
+            vec!["line 6, column 0", "line 6, column 9", "line 7, column 0"]
+        );
+    }
+
+    #[test]
+    fn breakpoints_test() {
+        let (result, output) = debug("b squares.scm:2\nc\nl\nd 2\nc\n");
+        assert!(result.is_ok());
+        assert_eq!(
+            pauses(&output),
+            vec!["line 6, column 0", "line 2, column 2"]
+        );
+        assert!(output.contains("    2 |   (* x x))\n"));
+        assert!(output.contains("x = 3\n"));
+        assert!(output.contains("Breakpoint at line 2 deleted"));
+
+        let (_, output) = debug("b other.scm:2\nb 0\nq\n");
+        assert!(output.contains("No source file named other.scm"));
+        assert!(output.contains("Invalid line number \"0\""));
+    }
+
+    #[test]
+    fn inspection_test() {
+        let (result, output) = debug("b 2\nc\np (+ x 1)\np (car x)\ne\nbt\nq\n");
+        assert_eq!(result.unwrap_err().kind, ErrorKind::Aborted);
+        assert!(output.contains("(debug) 4\n"));
+        assert!(output.contains("Error: car: expected pair, got 3\n"));
+        assert!(output.contains("#0 x = 3\n"));
+        assert!(output.contains("#1 global: square, sum-of-squares ("));
+        assert!(output.contains("#0 square, called at line 4, column 5\n"));
+        assert!(output.contains("#1 sum-of-squares, called at line 6, column 9\n"));
+    }
+
+    #[test]
+    fn anonymous_frames_test() {
+        let program = "(define (run f)
+  (+ (f 1) 1))
+(let ((y 2))
+  (+ (run (lambda (x)
+            (* x y)))
+     1))";
+        let (_, output) = debug_program(program, "b 5\nc\nbt\nq\n");
+        assert!(output.contains("#0 #<procedure lambda at 4:10>, called at line 2, column 5\n"));
+        assert!(output.contains("#1 run, called at line 4, column 5\n"));
+        assert!(output.contains("#2 #<procedure let at 3:0>\n"));
+    }
+}
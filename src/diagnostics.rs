@@ -0,0 +1,929 @@
+//! Diagnostics reported to the user, for people and for tools
+//!
+//! A `Diagnostic` describes one problem: a stable code such as `E0001`, which tools can match on
+//! across versions, a severity, a message, the span the problem is at, secondary spans with
+//! labels of their own, free form notes, and fix-its: replacements of parts of the sources that
+//! resolve the problem, which tools can apply without asking, as `check --fix` does. Diagnostics
+//! are built from `CompilerError`s and `RuntimeError`s, and can be written for people, with the
+//! offending source lines, as one JSON object per line, or as a SARIF log, the format understood
+//! by code scanning services.
+//!
+//! Names that are not known, such as unbound variables and character names, are followed by
+//! suggestions of the known names closest to them by edit distance, found by `closest_names`.
+
+use crate::interpreter::RuntimeError;
+use crate::lexer::CHARACTER_NAMES;
+use crate::source_map::{FileId, SourceMap, Span};
+use crate::CompilerError;
+use serde_json::{json, Value as Json};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::{fmt, mem};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The program cannot be read or run
+    Error,
+    /// The program runs, but probably not as intended
+    Warning,
+    /// Additional information, which does not indicate a problem by itself
+    Note,
+}
+
+impl Severity {
+    /// The name of the level in SARIF logs
+    fn sarif_level(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sarif_level())
+    }
+}
+
+/// A span a diagnostic refers to besides its primary span, with a message of its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    /// The part of the source the label is about
+    pub span: Span,
+    /// What the part of the source has to do with the diagnostic
+    pub message: String,
+}
+
+/// A replacement of part of a source resolving a diagnostic, safe to apply without review
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    /// What the replacement does
+    pub message: String,
+    /// The part of the source replaced, empty for an insertion
+    pub span: Span,
+    /// The text replacing it
+    pub replacement: String,
+}
+
+/// A problem found in a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Stable identifier of the kind of problem
+    pub code: &'static str,
+    /// How serious the problem is
+    pub severity: Severity,
+    /// Human readable description of the problem
+    pub message: String,
+    /// Where the problem is, if it can be attributed to a part of a source
+    pub primary: Option<Span>,
+    /// Other parts of the sources involved in the problem
+    pub secondary: Vec<Label>,
+    /// Further explanations
+    pub notes: Vec<String>,
+    /// Replacements resolving the problem
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic with no spans or notes
+    pub fn new<S: Into<String>>(code: &'static str, severity: Severity, message: S) -> Self {
+        Diagnostic {
+            code,
+            severity,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Sets the span the problem is at
+    pub fn with_primary(mut self, span: Option<Span>) -> Self {
+        self.primary = span;
+        self
+    }
+
+    /// Adds a labelled secondary span
+    pub fn with_secondary<S: Into<String>>(mut self, span: Span, message: S) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Adds a note
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Adds a fix-it replacing `span` with `replacement`
+    pub fn with_fix<S: Into<String>, T: Into<String>>(
+        mut self,
+        span: Span,
+        replacement: S,
+        message: T,
+    ) -> Self {
+        self.fixes.push(Fix {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// Adds a note suggesting names that may have been meant instead of an unknown one, as
+    /// returned by `closest_names`, unless there are none
+    pub fn with_suggestions<S: AsRef<str>>(self, names: &[S]) -> Self {
+        match names.is_empty() {
+            true => self,
+            false => self.with_note(did_you_mean(names)),
+        }
+    }
+
+    /// Describes an error of the lexer or the parser, reading from the source `file`
+    ///
+    /// Unknown character names get suggestions. Unmatched parentheses are reported where the
+    /// parser found the indentation suggests one is superfluous or missing, rather than where it
+    /// noticed, with fix-its removing or adding parentheses.
+    pub fn from_compiler_error(
+        error: &CompilerError,
+        file: Option<FileId>,
+        source_map: &SourceMap,
+    ) -> Self {
+        let span = match (error, file) {
+            (CompilerError::LexError(_, span), Some(file))
+            | (CompilerError::UnexpectedToken(span, _), Some(file)) => {
+                source_map.span(file, span.line, span.column, span.len)
+            }
+            (CompilerError::MissingCloseParen(unclosed), Some(file)) => {
+                let open = unclosed.open;
+                source_map.span(file, open.line, open.column, open.len)
+            }
+            _ => None,
+        };
+        let mut diagnostic =
+            Diagnostic::new(error.code(), Severity::Error, error.to_string()).with_primary(span);
+        match error {
+            CompilerError::IOError(error) => diagnostic = diagnostic.with_note(error.to_string()),
+            CompilerError::LexError(leftover, ..) => {
+                diagnostic = diagnostic.with_suggestions(&character_suggestions(leftover));
+            }
+            CompilerError::UnexpectedToken(..) | CompilerError::MissingCloseParen(_) => {
+                if let Some(file) = file {
+                    diagnostic = diagnose_parens(diagnostic, error, file, source_map);
+                }
+            }
+            _ => {}
+        }
+        diagnostic
+    }
+
+    /// Describes an error of a running program, noting its backtrace
+    pub fn from_runtime_error(error: &RuntimeError, source_map: &SourceMap) -> Self {
+        let span = error.position.and_then(|position| {
+            source_map
+                .datum_span(position)
+                .or_else(|| source_map.position_span(position))
+        });
+        let mut diagnostic = Diagnostic::new(error.kind.code(), Severity::Error, error.to_string())
+            .with_primary(span);
+        if let (None, Some(position)) = (span, error.position) {
+            diagnostic = diagnostic.with_note(format!("at {}", position));
+        }
+        if !error.backtrace.is_empty() {
+            let mut backtrace = String::from("backtrace (most recent call first):");
+            let mut previous = String::new();
+            let mut repeated = 0;
+            for frame in &error.backtrace {
+                let name = frame.describe(source_map);
+                let mut line = match frame.call_site {
+                    Some(position) => {
+                        let file = position.file.and_then(|file| source_map.name(file));
+                        match file {
+                            Some(file) => format!("\n  {}, called at {}, {}", name, file, position),
+                            None => format!("\n  {}, called at {}", name, position),
+                        }
+                    }
+                    None => format!("\n  {}", name),
+                };
+                match frame.elided {
+                    0 => {}
+                    1 => line.push_str("\n    (1 tail call elided)"),
+                    n => line.push_str(&format!("\n    ({} tail calls elided)", n)),
+                }
+                // Deep recursion repeats the same frame, which is shown once with a count
+                if line == previous {
+                    repeated += 1;
+                    continue;
+                }
+                push_repetitions(&mut backtrace, repeated);
+                repeated = 0;
+                backtrace.push_str(&line);
+                previous = line;
+            }
+            push_repetitions(&mut backtrace, repeated);
+            diagnostic = diagnostic.with_note(backtrace);
+        }
+        diagnostic
+    }
+
+    /// Formats the diagnostic for people, showing the source lines its spans and fix-its are on
+    ///
+    /// ```text
+    /// main.scm, line 1, column 14: error[E0101]: car: expected pair, got 2
+    ///     1 | (define (f x) (car x))
+    ///       |               ^
+    /// note: backtrace (most recent call first):
+    ///   car, called at main.scm, line 1, column 14
+    /// ```
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        let header = format!("{}[{}]: {}", self.severity, self.code, self.message);
+        let mut rendered = match self.primary {
+            Some(span) => source_map.format_diagnostic(span, &header),
+            None => header,
+        };
+        for label in &self.secondary {
+            rendered.push('\n');
+            let message = format!("note: {}", label.message);
+            rendered.push_str(&source_map.format_diagnostic(label.span, &message));
+        }
+        for note in &self.notes {
+            rendered.push_str("\nnote: ");
+            rendered.push_str(note);
+        }
+        for fix in &self.fixes {
+            rendered.push('\n');
+            let message = format!("help: {}", fix.message);
+            rendered.push_str(&source_map.format_diagnostic(fix.span, &message));
+        }
+        rendered
+    }
+
+    /// Describes the diagnostic as a JSON object
+    ///
+    /// Spans are given by the name of their source, and by the 1-based line and 0-based byte
+    /// column of their start and end, like the positions of the parser. So are the parts of the
+    /// sources fix-its replace.
+    pub fn to_json(&self, source_map: &SourceMap) -> Json {
+        let mut spans: Vec<Json> = Vec::new();
+        if let Some(span) = self.primary {
+            spans.extend(label_json(span, true, None, source_map));
+        }
+        for label in &self.secondary {
+            spans.extend(label_json(
+                label.span,
+                false,
+                Some(&label.message),
+                source_map,
+            ));
+        }
+        let fixes: Vec<Json> = self
+            .fixes
+            .iter()
+            .filter_map(|fix| {
+                let mut json = span_json(fix.span, source_map)?;
+                json["message"] = json!(fix.message);
+                json["replacement"] = json!(fix.replacement);
+                Some(json)
+            })
+            .collect();
+        json!({
+            "code": self.code,
+            "severity": self.severity.to_string(),
+            "message": self.message,
+            "spans": spans,
+            "notes": self.notes,
+            "fixes": fixes,
+        })
+    }
+}
+
+/// The names among `candidates` closest to `name` by edit distance, sorted, for a diagnostic
+/// to suggest in place of an unknown name
+///
+/// Names further than a third of the length of `name` are not suggested, nor more than three.
+pub fn closest_names<'a, I: IntoIterator<Item = &'a str>>(
+    name: &str,
+    candidates: I,
+) -> Vec<&'a str> {
+    let length = name.chars().count();
+    let mut best = length.max(3) / 3;
+    let mut closest = Vec::new();
+    for candidate in candidates {
+        if candidate.chars().count().abs_diff(length) > best {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        if distance == 0 || distance > best {
+            continue;
+        }
+        if distance < best {
+            closest.clear();
+            best = distance;
+        }
+        closest.push(candidate);
+    }
+    closest.sort_unstable();
+    closest.dedup();
+    closest.truncate(3);
+    closest
+}
+
+/// The number of characters to insert, remove or replace to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The note suggesting `names`, such as "did you mean `car` or `cdr`?"
+fn did_you_mean<S: AsRef<str>>(names: &[S]) -> String {
+    let quoted: Vec<String> = names
+        .iter()
+        .map(|name| format!("`{}`", name.as_ref()))
+        .collect();
+    match quoted.split_last() {
+        Some((last, [])) => format!("did you mean {}?", last),
+        Some((last, rest)) => format!("did you mean {} or {}?", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// The character names to suggest for the text a lexer error is at, if it is an unknown one
+fn character_suggestions(leftover: &str) -> Vec<String> {
+    let name: String = match leftover.strip_prefix("#\\") {
+        Some(rest) => rest.chars().take_while(|c| c.is_alphanumeric()).collect(),
+        None => return Vec::new(),
+    };
+    if name.chars().count() < 2 {
+        return Vec::new();
+    }
+    let names = CHARACTER_NAMES.iter().map(|(name, _)| *name);
+    closest_names(&name, names)
+        .into_iter()
+        .map(|name| format!("#\\{}", name))
+        .collect()
+}
+
+/// Notes in a backtrace that the frame before was repeated `count` more times, if it was
+fn push_repetitions(backtrace: &mut String, count: usize) {
+    match count {
+        0 => {}
+        1 => backtrace.push_str("\n    (repeated 1 more time)"),
+        n => backtrace.push_str(&format!("\n    (repeated {} more times)", n)),
+    }
+}
+
+/// Points a diagnostic about an unmatched parenthesis in `file` at where the indentation
+/// suggests a parenthesis is superfluous or missing, and adds the fix-its removing or adding
+/// parentheses there
+fn diagnose_parens(
+    mut diagnostic: Diagnostic,
+    error: &CompilerError,
+    file: FileId,
+    source_map: &SourceMap,
+) -> Diagnostic {
+    let span = |span: Span| source_map.span(file, span.line, span.column, span.len);
+    // The empty span right after the one given, where parens are added
+    let after = |span: Span| {
+        source_map
+            .end(Span {
+                file: Some(file),
+                ..span
+            })
+            .and_then(|end| source_map.position_span(end))
+    };
+    match error {
+        CompilerError::UnexpectedToken(stray, Some(guess)) => {
+            diagnostic.message = format!(
+                "Superfluous close paren at {}, judging by the indentation",
+                guess.close
+            );
+            diagnostic.primary = span(guess.close);
+            if let Some(span) = span(guess.indented) {
+                diagnostic = diagnostic.with_secondary(
+                    span,
+                    "this line is indented as if it were still inside the form the paren closes",
+                );
+            }
+            if let Some(span) = span(*stray) {
+                diagnostic = diagnostic.with_secondary(span, "which leaves this paren unmatched");
+            }
+            if let Some(span) = span(guess.close) {
+                diagnostic = diagnostic.with_fix(span, "", "remove the superfluous `)`");
+            }
+        }
+        CompilerError::UnexpectedToken(stray, None) => {
+            let unmatched = span(*stray).filter(|&span| source_map.source_text(span) == Some(")"));
+            if let Some(span) = unmatched {
+                diagnostic = diagnostic.with_fix(span, "", "remove the unmatched `)`");
+            }
+        }
+        CompilerError::MissingCloseParen(unclosed) => {
+            let mut count = unclosed.count;
+            match unclosed.missing {
+                Some(missing) => {
+                    diagnostic.message = format!(
+                        "Missing close paren at the end of line {}, judging by the indentation",
+                        missing.after.line
+                    );
+                    diagnostic.primary = span(missing.after);
+                    let labels = [
+                        (missing.open, "this list is still open"),
+                        (
+                            missing.dedented,
+                            "but this line is indented no further than the line the list starts \
+                             on",
+                        ),
+                    ];
+                    for (label, message) in labels {
+                        if let Some(span) = span(label) {
+                            diagnostic = diagnostic.with_secondary(span, message);
+                        }
+                    }
+                    if let Some(span) = after(missing.after) {
+                        let parens = ")".repeat(missing.count);
+                        let message = format!("add the missing `{}`", parens);
+                        diagnostic = diagnostic.with_fix(span, parens, message);
+                    }
+                    count -= missing.count;
+                }
+                None => {
+                    diagnostic.message =
+                        String::from("Missing close paren for the list opened here");
+                }
+            }
+            match after(unclosed.last) {
+                Some(span) if count > 0 => {
+                    let parens = ")".repeat(count);
+                    let message = format!("add the missing `{}`", parens);
+                    diagnostic = diagnostic.with_fix(span, parens, message);
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    diagnostic
+}
+
+/// The source and positions of a span, without the keys telling what the span is
+fn span_json(span: Span, source_map: &SourceMap) -> Option<Json> {
+    let start = span.start();
+    let end = source_map.end(span)?;
+    Some(json!({
+        "file": source_map.name(span.file?),
+        "line": start.line,
+        "column": start.column,
+        "end_line": end.line,
+        "end_column": end.column,
+    }))
+}
+
+fn label_json(
+    span: Span,
+    primary: bool,
+    label: Option<&str>,
+    source_map: &SourceMap,
+) -> Option<Json> {
+    let mut json = span_json(span, source_map)?;
+    json["primary"] = json!(primary);
+    json["label"] = json!(label);
+    Some(json)
+}
+
+/// The SARIF region of a span, with 1-based columns counted in bytes
+fn sarif_region(span: Span, source_map: &SourceMap) -> Option<Json> {
+    let start = span.start();
+    let end = source_map.end(span)?;
+    Some(json!({
+        "startLine": start.line,
+        "startColumn": start.column + 1,
+        "endLine": end.line,
+        "endColumn": end.column + 1,
+    }))
+}
+
+/// The SARIF location of a span
+fn sarif_location(span: Span, message: Option<&str>, source_map: &SourceMap) -> Option<Json> {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": source_map.name(span.file?) },
+            "region": sarif_region(span, source_map)?,
+        },
+    });
+    if let Some(message) = message {
+        location["message"] = json!({ "text": message });
+    }
+    Some(location)
+}
+
+/// The formats diagnostics can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Messages with the offending source lines, for people
+    Human,
+    /// One JSON object per line and diagnostic
+    Json,
+    /// A single SARIF 2.1.0 log with every diagnostic
+    Sarif,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            other => Err(format!(
+                "unknown error format {:?}, expected human, json or sarif",
+                other
+            )),
+        }
+    }
+}
+
+/// Writes diagnostics in a given format as they are reported
+///
+/// Human readable and JSON diagnostics are written immediately, while SARIF diagnostics are
+/// collected and written as a single log by `finish`.
+pub struct Emitter {
+    format: ErrorFormat,
+    output: Box<dyn Write>,
+    pending: Vec<Json>,
+    rules: Vec<&'static str>,
+}
+
+impl Emitter {
+    /// Creates an emitter writing to `output`
+    pub fn new(format: ErrorFormat, output: Box<dyn Write>) -> Self {
+        Emitter {
+            format,
+            output,
+            pending: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Reports a diagnostic
+    pub fn emit(&mut self, diagnostic: &Diagnostic, source_map: &SourceMap) -> io::Result<()> {
+        match self.format {
+            ErrorFormat::Human => writeln!(self.output, "{}", diagnostic.render(source_map)),
+            ErrorFormat::Json => writeln!(self.output, "{}", diagnostic.to_json(source_map)),
+            ErrorFormat::Sarif => {
+                if !self.rules.contains(&diagnostic.code) {
+                    self.rules.push(diagnostic.code);
+                }
+                let mut text = diagnostic.message.clone();
+                for note in &diagnostic.notes {
+                    text.push('\n');
+                    text.push_str(note);
+                }
+                let locations: Vec<Json> = diagnostic
+                    .primary
+                    .and_then(|span| sarif_location(span, None, source_map))
+                    .into_iter()
+                    .collect();
+                let related: Vec<Json> = diagnostic
+                    .secondary
+                    .iter()
+                    .filter_map(|label| {
+                        sarif_location(label.span, Some(&label.message), source_map)
+                    })
+                    .collect();
+                let fixes: Vec<Json> = diagnostic
+                    .fixes
+                    .iter()
+                    .filter_map(|fix| {
+                        Some(json!({
+                            "description": { "text": fix.message },
+                            "artifactChanges": [{
+                                "artifactLocation": { "uri": source_map.name(fix.span.file?) },
+                                "replacements": [{
+                                    "deletedRegion": sarif_region(fix.span, source_map)?,
+                                    "insertedContent": { "text": fix.replacement },
+                                }],
+                            }],
+                        }))
+                    })
+                    .collect();
+                self.pending.push(json!({
+                    "ruleId": diagnostic.code,
+                    "level": diagnostic.severity.sarif_level(),
+                    "message": { "text": text },
+                    "locations": locations,
+                    "relatedLocations": related,
+                    "fixes": fixes,
+                }));
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the diagnostics which were held back until every one was reported
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.format != ErrorFormat::Sarif {
+            return self.output.flush();
+        }
+        let rules: Vec<Json> = self.rules.iter().map(|id| json!({ "id": id })).collect();
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    },
+                },
+                "results": mem::take(&mut self.pending),
+            }],
+        });
+        writeln!(self.output, "{}", log)?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{closest_names, Diagnostic, Emitter, ErrorFormat, Severity};
+    use crate::builtins::test::eval_str_with;
+    use crate::interpreter::Interpreter;
+    use crate::reader::{DatumIterator, StringLexer};
+    use crate::source_map::SourceMap;
+    use crate::CompilerError;
+    use serde_json::Value as Json;
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn parse_error(map: &mut SourceMap, source: &str) -> Diagnostic {
+        let file = map.add_snippet("main.scm", source);
+        let lexer = StringLexer::new(source).into_iter().with_file(file);
+        let error: CompilerError = DatumIterator::new(lexer).find_map(Result::err).unwrap();
+        Diagnostic::from_compiler_error(&error, Some(file), map)
+    }
+
+    #[test]
+    fn compiler_error_test() {
+        let mut map = SourceMap::new();
+        let diagnostic = parse_error(&mut map, "(a b)\n  (c #\\nope d)");
+        assert_eq!(diagnostic.code, "E0001");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(
+            diagnostic.render(&map),
+            "main.scm, line 2, column 5: error[E0001]: Lex error at line 2, column 5, near \
+             \"#\\nope d)\" while lexing input\n    2 |   (c #\\nope d)\n      |      ^^^^^^^^^"
+        );
+        let diagnostic = parse_error(&mut map, "(a b))");
+        assert_eq!(diagnostic.code, "E0003");
+        let json = diagnostic.to_json(&map);
+        assert_eq!(json["spans"][0]["line"], 1);
+        assert_eq!(json["spans"][0]["column"], 5);
+        assert_eq!(json["spans"][0]["primary"], true);
+        assert_eq!(json["fixes"][0]["message"], "remove the unmatched `)`");
+        assert_eq!(json["fixes"][0]["column"], 5);
+        assert_eq!(json["fixes"][0]["end_column"], 6);
+        assert_eq!(json["fixes"][0]["replacement"], "");
+
+        let diagnostic = parse_error(&mut map, "(define (f x)\n  (g #(x");
+        assert_eq!(diagnostic.code, "E0004");
+        assert_eq!(diagnostic.fixes.len(), 1);
+        assert_eq!(diagnostic.fixes[0].replacement, ")))");
+        assert_eq!(
+            diagnostic.render(&map),
+            "main.scm, line 1, column 0: error[E0004]: Missing close paren for the list opened \
+             here\n    1 | (define (f x)\n      | ^\nmain.scm, line 2, column 8: help: add the \
+             missing `)))`\n    2 |   (g #(x\n      |         ^"
+        );
+    }
+
+    #[test]
+    fn indentation_test() {
+        let mut map = SourceMap::new();
+        let diagnostic = parse_error(
+            &mut map,
+            "(define (f x)\n  (let ((y 1))\n    (display y)\n(define (g) 1)\n",
+        );
+        assert_eq!(
+            diagnostic.message,
+            "Missing close paren at the end of line 3, judging by the indentation"
+        );
+        let lines: Vec<usize> = diagnostic
+            .secondary
+            .iter()
+            .map(|label| label.span.line)
+            .collect();
+        assert_eq!(lines, vec![2, 4]);
+        let json = diagnostic.to_json(&map);
+        assert_eq!(json["spans"][0]["line"], 3);
+        assert_eq!(json["fixes"][0]["line"], 3);
+        assert_eq!(json["fixes"][0]["column"], 15);
+        assert_eq!(json["fixes"][0]["replacement"], "))");
+
+        let diagnostic = parse_error(
+            &mut map,
+            "(define (f x)\n  (display x))\n  (newline))\n(f 1)\n",
+        );
+        assert_eq!(diagnostic.code, "E0003");
+        assert_eq!(
+            diagnostic.message,
+            "Superfluous close paren at line 2, column 13, judging by the indentation"
+        );
+        let json = diagnostic.to_json(&map);
+        assert_eq!(json["spans"][0]["line"], 2);
+        assert_eq!(
+            json["spans"][1]["label"],
+            "this line is indented as if it were still inside the form the paren closes"
+        );
+        assert_eq!(json["spans"][2]["line"], 3);
+        assert_eq!(json["spans"][2]["column"], 11);
+        assert_eq!(json["fixes"][0]["message"], "remove the superfluous `)`");
+        assert_eq!(json["fixes"][0]["line"], 2);
+        assert_eq!(json["fixes"][0]["column"], 13);
+    }
+
+    #[test]
+    fn suggestion_test() {
+        let names = ["car", "cdr", "caar", "display", "cons"];
+        assert_eq!(closest_names("cas", names.iter().copied()), vec!["car"]);
+        assert_eq!(
+            closest_names("cbr", names.iter().copied()),
+            vec!["car", "cdr"]
+        );
+        assert_eq!(
+            closest_names("dispaly", names.iter().copied()),
+            vec!["display"]
+        );
+        assert!(closest_names("x", names.iter().copied()).is_empty());
+        assert_eq!(
+            closest_names("car", names.iter().copied()),
+            vec!["caar", "cdr"]
+        );
+
+        let mut map = SourceMap::new();
+        let diagnostic = parse_error(&mut map, "(display #\\newlin)");
+        assert_eq!(diagnostic.notes, vec!["did you mean `#\\newline`?"]);
+        let diagnostic = parse_error(&mut map, "(display #\\nope)");
+        assert!(diagnostic.notes.is_empty());
+
+        let mut interpreter = Interpreter::new();
+        let error =
+            eval_str_with(&mut interpreter, "(define (f lst) (lenght lst))\n(f '())").unwrap_err();
+        let diagnostic = Diagnostic::from_runtime_error(&error, interpreter.source_map())
+            .with_suggestions(&interpreter.suggestions(&error));
+        assert_eq!(diagnostic.code, "E0103");
+        assert_eq!(diagnostic.notes.last().unwrap(), "did you mean `length`?");
+
+        // Suggestions come from the environment the variable was looked up in
+        let suggestions = |interpreter: &mut Interpreter, input: &str| {
+            let error = eval_str_with(interpreter, input).unwrap_err();
+            interpreter.suggestions(&error)
+        };
+        assert_eq!(
+            suggestions(&mut interpreter, "(open-output-fil \"x\")"),
+            vec!["open-output-file"]
+        );
+        let restricted = suggestions(
+            &mut interpreter,
+            "(eval '(open-output-fil \"x\") (scheme-report-environment 5))",
+        );
+        assert!(!restricted.contains(&String::from("open-output-file")));
+        assert_eq!(
+            suggestions(&mut interpreter, "(let ((counter 0)) (+ countr 1))"),
+            vec!["counter"]
+        );
+    }
+
+    #[test]
+    fn runtime_error_test() {
+        let mut interpreter = Interpreter::new();
+        let error =
+            eval_str_with(&mut interpreter, "(define (f x)\n  (car x))\n(f 1)").unwrap_err();
+        let diagnostic = Diagnostic::from_runtime_error(&error, interpreter.source_map());
+        assert_eq!(diagnostic.code, "E0101");
+        assert_eq!(diagnostic.primary, None);
+        assert_eq!(
+            diagnostic.render(interpreter.source_map()),
+            "error[E0101]: car: expected pair, got 1\nnote: at line 2, column 2\nnote: backtrace (most recent call first):\
+             \n  car, called at line 2, column 2\n    (1 tail call elided)"
+        );
+    }
+
+    #[test]
+    fn runtime_error_span_test() {
+        let mut interpreter = Interpreter::new();
+        let source = "(define (f x)\n  (car (cdr x)))\n(f '(1))";
+        let file = interpreter.source_map_mut().add_snippet("main.scm", source);
+        let lexer = StringLexer::new(source).into_iter().with_file(file);
+        let error = DatumIterator::new(lexer)
+            .with_positions()
+            .find_map(|datum| {
+                let (datum, positions) = datum.unwrap();
+                interpreter
+                    .eval_datum_with_positions(&datum, &positions)
+                    .err()
+            })
+            .unwrap();
+        let map = interpreter.source_map();
+        let diagnostic = Diagnostic::from_runtime_error(&error, map);
+        // The whole failing call is the region of the error
+        let span = diagnostic.primary.unwrap();
+        assert_eq!(map.source_text(span), Some("(car (cdr x))"));
+
+        let buffer = SharedBuffer::default();
+        let mut emitter = Emitter::new(ErrorFormat::Json, Box::new(buffer.clone()));
+        emitter.emit(&diagnostic, map).unwrap();
+        emitter.finish().unwrap();
+        let json: Json = serde_json::from_slice(&buffer.0.borrow()).unwrap();
+        assert_eq!(json["spans"][0]["line"], 2);
+        assert_eq!(json["spans"][0]["column"], 2);
+        assert_eq!(json["spans"][0]["end_line"], 2);
+        assert_eq!(json["spans"][0]["end_column"], 15);
+
+        let buffer = SharedBuffer::default();
+        let mut emitter = Emitter::new(ErrorFormat::Sarif, Box::new(buffer.clone()));
+        emitter.emit(&diagnostic, map).unwrap();
+        emitter.finish().unwrap();
+        let log: Json = serde_json::from_slice(&buffer.0.borrow()).unwrap();
+        let region = &log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 3);
+        assert_eq!(region["endLine"], 2);
+        assert_eq!(region["endColumn"], 16);
+    }
+
+    #[test]
+    fn emitter_test() {
+        let mut map = SourceMap::new();
+        let diagnostic = parse_error(&mut map, "(a b))")
+            .with_note("unbalanced")
+            .with_secondary(map.span(super::FileId(0), 1, 0, 1).unwrap(), "opened here");
+
+        let buffer = SharedBuffer::default();
+        let mut emitter = Emitter::new(ErrorFormat::Json, Box::new(buffer.clone()));
+        emitter.emit(&diagnostic, &map).unwrap();
+        emitter.emit(&diagnostic, &map).unwrap();
+        emitter.finish().unwrap();
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let lines: Vec<Json> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["code"], "E0003");
+        assert_eq!(lines[0]["notes"][0], "unbalanced");
+        assert_eq!(lines[0]["spans"][1]["label"], "opened here");
+
+        let buffer = SharedBuffer::default();
+        let mut emitter = Emitter::new(ErrorFormat::Sarif, Box::new(buffer.clone()));
+        emitter.emit(&diagnostic, &map).unwrap();
+        emitter.finish().unwrap();
+        let log: Json = serde_json::from_slice(&buffer.0.borrow()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "E0003");
+        assert_eq!(result["level"], "error");
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startLine"], 1);
+        assert_eq!(region["startColumn"], 6);
+        assert_eq!(log["runs"][0]["tool"]["driver"]["rules"][0]["id"], "E0003");
+        let replacement = &result["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["deletedRegion"]["startColumn"], 6);
+        assert_eq!(replacement["deletedRegion"]["endColumn"], 7);
+        assert_eq!(replacement["insertedContent"]["text"], "");
+
+        assert_eq!("sarif".parse(), Ok(ErrorFormat::Sarif));
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
+}
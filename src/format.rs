@@ -0,0 +1,193 @@
+//! Formatter for Scheme source code
+//!
+//! The formatter keeps the line breaks, comments and spacing between the tokens of each line,
+//! and recomputes the indentation of every line from the lists open at its start:
+//!
+//! * the bodies of binding and defining forms such as `define`, `lambda` and `let` are indented
+//!   by two columns from their opening parenthesis;
+//! * the arguments of other calls are aligned with the first argument, if it is on the same line
+//!   as the operator, and with the operator otherwise;
+//! * the elements of lists not starting with an identifier, and of vectors, are aligned with the
+//!   first element.
+//!
+//! Trailing whitespace is removed, runs of blank lines are collapsed into one, and the output
+//! ends with a single newline.
+
+use crate::lexer::{Token, TokenWithPosition};
+use crate::reader::StringLexer;
+use crate::CompilerError;
+
+/// Forms whose body is indented by two columns, however their first line is laid out
+const BODY_FORMS: &[&str] = &[
+    "begin",
+    "case",
+    "define",
+    "define-library",
+    "define-record-type",
+    "define-syntax",
+    "define-values",
+    "delay",
+    "do",
+    "guard",
+    "lambda",
+    "let",
+    "let*",
+    "let*-values",
+    "let-syntax",
+    "let-values",
+    "letrec",
+    "letrec*",
+    "letrec-syntax",
+    "parameterize",
+    "syntax-rules",
+    "unless",
+    "when",
+];
+
+/// A list or vector open at some point of the source
+struct OpenList {
+    /// The line the list was opened on
+    line: usize,
+    /// The output column of the first element, if it is on the opening line
+    first_column: Option<usize>,
+    /// The output column right after the opening delimiter
+    inner_column: usize,
+    /// The identifier the list starts with, if any
+    head: Option<String>,
+    /// The output column of the second element, if it is on the opening line
+    argument_column: Option<usize>,
+    /// The number of elements seen so far
+    elements: usize,
+}
+
+impl OpenList {
+    /// The indentation of a line starting inside the list
+    fn indentation(&self) -> usize {
+        match &self.head {
+            Some(head) if BODY_FORMS.contains(&head.as_str()) => self.inner_column + 1,
+            Some(_) => self.argument_column.unwrap_or(self.inner_column),
+            None => self.first_column.unwrap_or(self.inner_column),
+        }
+    }
+}
+
+/// Formats a whole source file
+pub fn format_source(source: &str) -> Result<String, CompilerError> {
+    let tokens: Vec<TokenWithPosition> = StringLexer::new(source)
+        .into_iter()
+        .collect::<Result<_, _>>()?;
+    let mut tokens = tokens.into_iter().peekable();
+    let mut open: Vec<OpenList> = Vec::new();
+    // Whether the next datum is the operand of a quote or similar prefix
+    let mut after_prefix = false;
+    let mut output = String::new();
+    let mut blank_lines = 0;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut line_tokens = Vec::new();
+        while let Some(token) = tokens.next_if(|token| token.span.line == line) {
+            line_tokens.push(token);
+        }
+        let first = line_tokens
+            .iter()
+            .find(|token| token.token != Token::Whitespace);
+        let first = match first {
+            Some(first) => first.span.column,
+            None => {
+                blank_lines += 1;
+                continue;
+            }
+        };
+        if !output.is_empty() && blank_lines > 0 {
+            output.push('\n');
+        }
+        blank_lines = 0;
+        let indentation = open.last().map_or(0, OpenList::indentation);
+        // Maps source columns of this line to output columns
+        let column = |token: &TokenWithPosition| token.span.column + indentation - first;
+        for token in &line_tokens {
+            let starts_datum = match &token.token {
+                Token::Whitespace | Token::Comment => continue,
+                Token::Punctuator(p) if p == ")" => {
+                    open.pop();
+                    after_prefix = false;
+                    continue;
+                }
+                Token::Punctuator(p) if p == "." => continue,
+                Token::Punctuator(p) if p != "(" && p != "#(" => {
+                    // A quote, quasiquote or unquote prefix starts the datum it applies to
+                    let starts = !after_prefix;
+                    after_prefix = true;
+                    if !starts {
+                        continue;
+                    }
+                    true
+                }
+                _ => {
+                    let starts = !after_prefix;
+                    after_prefix = false;
+                    starts
+                }
+            };
+            if starts_datum {
+                if let Some(list) = open.last_mut() {
+                    match list.elements {
+                        0 => {
+                            list.first_column = Some(column(token));
+                            if let Token::Identifier(name) = &token.token {
+                                list.head = Some(name.clone());
+                            }
+                        }
+                        1 if list.line == line => list.argument_column = Some(column(token)),
+                        _ => {}
+                    }
+                    list.elements += 1;
+                }
+            }
+            if let Token::Punctuator(p) = &token.token {
+                if p == "(" || p == "#(" {
+                    after_prefix = false;
+                    open.push(OpenList {
+                        line,
+                        first_column: None,
+                        inner_column: column(token) + p.len(),
+                        head: None,
+                        argument_column: None,
+                        elements: 0,
+                    });
+                }
+            }
+        }
+        output.push_str(&" ".repeat(indentation));
+        output.push_str(text[first..].trim_end());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_source;
+
+    #[test]
+    fn indentation_test() {
+        let source = "\n\n(define (f x)\n(let ((y 1)\n(z 2))\n     (+ x\ny z)))\n\n\n\n(f 1)   \n";
+        let expected =
+            "(define (f x)\n  (let ((y 1)\n        (z 2))\n    (+ x\n       y z)))\n\n(f 1)\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn lists_and_comments_test() {
+        let source = "(list\n1 2)\n'(a\nb ; the b\n   ; more\n#(c\nd))\n(cond ((null? x) 0)\n((pair? x) 1)\n(else 2))";
+        let expected = "(list\n 1 2)\n'(a\n  b ; the b\n  ; more\n  #(c\n    d))\n(cond ((null? x) 0)\n      ((pair? x) 1)\n      (else 2))\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+        // Formatting is idempotent
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn lex_error_test() {
+        assert!(format_source("(a #\\nope)").is_err());
+    }
+}
@@ -0,0 +1,705 @@
+//! R7RS libraries: `define-library` and `import`
+//!
+//! A library is identified by a name such as `(utils strings)`. Importing a library which has
+//! not been defined yet loads it from the file `utils/strings.sld`, or failing that
+//! `utils/strings.scm`, found in one of the directories of the library search path. Every
+//! library is loaded at most once per interpreter, and a library importing itself, directly or
+//! through other libraries, is reported as an import cycle.
+//!
+//! The standard libraries of R7RS, such as `(scheme base)` and `(scheme char)`, are built in and
+//! export the standard procedures belonging to them, and `(scheme r5rs)` exports every standard
+//! procedure. No other library name may start with `scheme`. Neither `import` nor
+//! `define-library` can be used in the restricted environments returned by
+//! `scheme-report-environment` and `null-environment`.
+//!
+//! The body of a library runs in an environment of its own, which only contains the bindings it
+//! imports and defines. Importing shares the bindings of the exported variables, so assignments
+//! to an exported variable, by the library or by an importer, are seen on both sides.
+
+use super::{compile, Environment, ErrorKind, Expr, Interpreter, RuntimeError, Unwind};
+use crate::builtins::{self, StandardLibrary};
+use crate::lexer::LispNum;
+use crate::parser::Position;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// The name of a library: a list of identifiers and exact non-negative integers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct LibraryName(Vec<String>);
+
+impl LibraryName {
+    /// The path of the file defining the library with the given extension, relative to a
+    /// directory of the search path
+    fn relative_path(&self, extension: &str) -> PathBuf {
+        let mut path: PathBuf = self.0.iter().collect();
+        path.set_extension(extension);
+        path
+    }
+
+    fn is_reserved(&self) -> bool {
+        self.0.first().map(String::as_str) == Some("scheme")
+    }
+
+    /// The standard library with this name, if it is one
+    fn standard_library(&self) -> Option<StandardLibrary> {
+        match self.0.as_slice() {
+            [scheme, name] if scheme == "scheme" => StandardLibrary::from_name(name),
+            _ => None,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::list(self.0.iter().map(|part| Value::symbol(part)))
+    }
+}
+
+impl fmt::Display for LibraryName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.0.join(" "))
+    }
+}
+
+/// An import set: a library, possibly with some of its bindings filtered or renamed
+pub(crate) enum ImportSet {
+    Library(LibraryName),
+    Only(Box<ImportSet>, Vec<Rc<str>>),
+    Except(Box<ImportSet>, Vec<Rc<str>>),
+    Prefix(Box<ImportSet>, Rc<str>),
+    Rename(Box<ImportSet>, Vec<(Rc<str>, Rc<str>)>),
+}
+
+/// An analysed `define-library` form
+pub(crate) struct LibraryDefinition {
+    name: LibraryName,
+    /// Pairs of internal and external names
+    exports: Vec<(Rc<str>, Rc<str>)>,
+    body: Vec<LibraryDeclaration>,
+}
+
+/// An `import` or `begin` declaration of a library, in the order they appear
+enum LibraryDeclaration {
+    /// The import sets of the declaration, with its position
+    Import(Vec<ImportSet>, Option<Position>),
+    Begin(Vec<Expr>),
+}
+
+/// An exported binding, by the name it is imported under
+type ImportedBinding = (Rc<str>, Rc<RefCell<Value>>);
+
+/// A defined library
+pub(crate) struct Library {
+    env: Rc<Environment>,
+    /// Pairs of internal and external names
+    exports: Vec<(Rc<str>, Rc<str>)>,
+}
+
+impl Library {
+    /// A built-in standard library, exporting its standard procedures
+    fn standard(library: StandardLibrary) -> Self {
+        Library {
+            env: Environment::standard(),
+            exports: builtins::all()
+                .filter(|builtin| library == StandardLibrary::R5rs || builtin.library == library)
+                .map(|builtin| (Rc::from(builtin.name), Rc::from(builtin.name)))
+                .collect(),
+        }
+    }
+
+    /// The cells of the exported bindings, by their external names
+    fn bindings(&self) -> Vec<ImportedBinding> {
+        self.exports
+            .iter()
+            .map(|(internal, external)| {
+                let cell = self.env.cell(internal);
+                let cell = cell.unwrap_or_else(|| Rc::new(RefCell::new(Value::Unspecified)));
+                (external.clone(), cell)
+            })
+            .collect()
+    }
+}
+
+fn syntax_items(form: &Value, message: &str) -> Result<Vec<Value>, RuntimeError> {
+    form.list_to_vec()
+        .ok_or_else(|| RuntimeError::syntax(message, form))
+}
+
+fn identifier(value: &Value, form: &Value) -> Result<Rc<str>, RuntimeError> {
+    match value {
+        Value::Symbol(name) => Ok(name.clone()),
+        _ => Err(RuntimeError::syntax("expected an identifier in", form)),
+    }
+}
+
+fn parse_library_name(value: &Value) -> Result<LibraryName, RuntimeError> {
+    let bad_name = || RuntimeError::syntax("bad library name", value);
+    let parts = value.list_to_vec().ok_or_else(bad_name)?;
+    if parts.is_empty() {
+        return Err(bad_name());
+    }
+    parts
+        .iter()
+        .map(|part| match part {
+            Value::Symbol(name) => Ok(name.to_string()),
+            Value::Number(LispNum::Integer(n)) if *n >= 0 => Ok(n.to_string()),
+            _ => Err(bad_name()),
+        })
+        .collect::<Result<_, _>>()
+        .map(LibraryName)
+}
+
+fn parse_import_set(value: &Value) -> Result<ImportSet, RuntimeError> {
+    let items = syntax_items(value, "bad import set")?;
+    let bad_set = || RuntimeError::syntax("bad import set", value);
+    let keyword = match items.first() {
+        Some(Value::Symbol(keyword)) => &**keyword,
+        _ => return Err(bad_set()),
+    };
+    let inner = || -> Result<Box<ImportSet>, RuntimeError> {
+        Ok(Box::new(parse_import_set(
+            items.get(1).ok_or_else(bad_set)?,
+        )?))
+    };
+    match keyword {
+        "only" | "except" if items.len() >= 2 => {
+            let names = items[2..]
+                .iter()
+                .map(|name| identifier(name, value))
+                .collect::<Result<_, _>>()?;
+            if keyword == "only" {
+                Ok(ImportSet::Only(inner()?, names))
+            } else {
+                Ok(ImportSet::Except(inner()?, names))
+            }
+        }
+        "prefix" if items.len() == 3 => {
+            Ok(ImportSet::Prefix(inner()?, identifier(&items[2], value)?))
+        }
+        "rename" if items.len() >= 2 => {
+            let renames = items[2..]
+                .iter()
+                .map(
+                    |rename| match syntax_items(rename, "bad rename")?.as_slice() {
+                        [from, to] => Ok((identifier(from, value)?, identifier(to, value)?)),
+                        _ => Err(RuntimeError::syntax("bad rename", rename)),
+                    },
+                )
+                .collect::<Result<_, _>>()?;
+            Ok(ImportSet::Rename(inner()?, renames))
+        }
+        "only" | "except" | "prefix" | "rename" => Err(bad_set()),
+        _ => Ok(ImportSet::Library(parse_library_name(value)?)),
+    }
+}
+
+/// Where an `import` form or declaration was read from, which errors finding the libraries it
+/// imports are reported at
+pub(crate) fn position(form: &Value) -> Option<Position> {
+    match form {
+        Value::Pair(pair) => pair.position(),
+        _ => None,
+    }
+}
+
+/// Analyses the import sets of an `import` form or declaration
+pub(crate) fn compile_import(form: &Value) -> Result<Vec<ImportSet>, RuntimeError> {
+    syntax_items(form, "bad import syntax")?[1..]
+        .iter()
+        .map(parse_import_set)
+        .collect()
+}
+
+/// Analyses a `define-library` form
+pub(crate) fn compile_define_library(form: &Value) -> Result<LibraryDefinition, RuntimeError> {
+    let items = syntax_items(form, "bad define-library syntax")?;
+    let name = match items.get(1) {
+        Some(name) => parse_library_name(name)?,
+        None => return Err(RuntimeError::syntax("bad define-library syntax", form)),
+    };
+    let mut exports = Vec::new();
+    let mut body = Vec::new();
+    for declaration in &items[2..] {
+        let parts = syntax_items(declaration, "bad library declaration")?;
+        match parts.first() {
+            Some(Value::Symbol(keyword)) if &**keyword == "export" => {
+                for spec in &parts[1..] {
+                    exports.push(match spec {
+                        Value::Symbol(name) => (name.clone(), name.clone()),
+                        _ => match syntax_items(spec, "bad export spec")?.as_slice() {
+                            [Value::Symbol(rename), from, to] if &**rename == "rename" => {
+                                (identifier(from, spec)?, identifier(to, spec)?)
+                            }
+                            _ => return Err(RuntimeError::syntax("bad export spec", spec)),
+                        },
+                    });
+                }
+            }
+            Some(Value::Symbol(keyword)) if &**keyword == "import" => {
+                let sets = compile_import(declaration)?;
+                body.push(LibraryDeclaration::Import(sets, position(declaration)));
+            }
+            Some(Value::Symbol(keyword)) if &**keyword == "begin" => {
+                let forms = parts[1..].iter().map(compile).collect::<Result<_, _>>()?;
+                body.push(LibraryDeclaration::Begin(forms));
+            }
+            _ => return Err(RuntimeError::syntax("bad library declaration", declaration)),
+        }
+    }
+    Ok(LibraryDefinition {
+        name,
+        exports,
+        body,
+    })
+}
+
+fn library_error(message: &str, irritants: Vec<Value>) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Library, message, irritants)
+}
+
+impl Interpreter {
+    /// Adds a directory to search for library files in
+    ///
+    /// Directories are searched in the order they were added, followed by the current directory.
+    pub fn add_library_path<P: Into<PathBuf>>(&mut self, path: P) {
+        self.library_path.push(path.into());
+    }
+
+    /// Signals an error about the `import` form or declaration at `position`
+    fn signal_at(&mut self, mut error: RuntimeError, position: Option<Position>) -> Unwind {
+        error.position = error.position.or(position);
+        self.signal(error)
+    }
+
+    /// Imports the bindings of the import sets of the form at `position` into `env`
+    pub(crate) fn import(
+        &mut self,
+        sets: &[ImportSet],
+        env: &Rc<Environment>,
+        position: Option<Position>,
+    ) -> Result<(), Unwind> {
+        for set in sets {
+            for (name, cell) in self.resolve_import_set(set, position)? {
+                env.define_cell(name, cell);
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_import_set(
+        &mut self,
+        set: &ImportSet,
+        position: Option<Position>,
+    ) -> Result<Vec<ImportedBinding>, Unwind> {
+        let check_exported = |this: &mut Self, bindings: &[ImportedBinding], name: &Rc<str>| {
+            if bindings.iter().any(|(bound, _)| bound == name) {
+                Ok(())
+            } else {
+                let error = library_error(
+                    "identifier not exported by import set",
+                    vec![Value::Symbol(name.clone())],
+                );
+                Err(this.signal_at(error, position))
+            }
+        };
+        Ok(match set {
+            ImportSet::Library(name) => self.library(name, position)?.bindings(),
+            ImportSet::Only(inner, names) => {
+                let bindings = self.resolve_import_set(inner, position)?;
+                for name in names {
+                    check_exported(self, &bindings, name)?;
+                }
+                bindings
+                    .into_iter()
+                    .filter(|(bound, _)| names.contains(bound))
+                    .collect()
+            }
+            ImportSet::Except(inner, names) => {
+                let bindings = self.resolve_import_set(inner, position)?;
+                for name in names {
+                    check_exported(self, &bindings, name)?;
+                }
+                bindings
+                    .into_iter()
+                    .filter(|(bound, _)| !names.contains(bound))
+                    .collect()
+            }
+            ImportSet::Prefix(inner, prefix) => self
+                .resolve_import_set(inner, position)?
+                .into_iter()
+                .map(|(name, cell)| (Rc::from(format!("{}{}", prefix, name)), cell))
+                .collect(),
+            ImportSet::Rename(inner, renames) => {
+                let mut bindings = self.resolve_import_set(inner, position)?;
+                for (from, to) in renames {
+                    check_exported(self, &bindings, from)?;
+                    for binding in bindings.iter_mut().filter(|(bound, _)| bound == from) {
+                        binding.0 = to.clone();
+                    }
+                }
+                bindings
+            }
+        })
+    }
+
+    /// Returns a defined library, loading it from its file if necessary
+    fn library(
+        &mut self,
+        name: &LibraryName,
+        position: Option<Position>,
+    ) -> Result<Rc<Library>, Unwind> {
+        if let Some(library) = self.libraries.get(name) {
+            return Ok(library.clone());
+        }
+        if let Some(standard) = name.standard_library() {
+            let library = Rc::new(Library::standard(standard));
+            self.libraries.insert(name.clone(), library.clone());
+            return Ok(library);
+        }
+        if name.is_reserved() {
+            let error = library_error("library not found", vec![name.to_value()]);
+            return Err(self.signal_at(error, position));
+        }
+        if let Some(index) = self.loading_libraries.iter().position(|n| n == name) {
+            let cycle = self.loading_libraries[index..]
+                .iter()
+                .chain(Some(name))
+                .map(LibraryName::to_value)
+                .collect();
+            let error = library_error("import cycle between libraries", cycle);
+            return Err(self.signal_at(error, position));
+        }
+        self.loading_libraries.push(name.clone());
+        let loaded = self.load_library(name, position);
+        self.loading_libraries.pop();
+        loaded?;
+        match self.libraries.get(name) {
+            Some(library) => Ok(library.clone()),
+            None => {
+                let error = library_error(
+                    "library file does not define library",
+                    vec![name.to_value()],
+                );
+                Err(self.signal_at(error, position))
+            }
+        }
+    }
+
+    /// Evaluates the file defining a library in an empty environment
+    fn load_library(
+        &mut self,
+        name: &LibraryName,
+        position: Option<Position>,
+    ) -> Result<(), Unwind> {
+        let current_directory = PathBuf::new();
+        let directories = self.library_path.iter().chain(Some(&current_directory));
+        let path = directories
+            .flat_map(|directory| {
+                vec![
+                    directory.join(name.relative_path("sld")),
+                    directory.join(name.relative_path("scm")),
+                ]
+            })
+            .find(|path| path.is_file());
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let error = library_error("library not found", vec![name.to_value()]);
+                return Err(self.signal_at(error, position));
+            }
+        };
+        self.load(&path, &Environment::new())
+    }
+
+    /// Runs the body of a library and registers it under its name
+    pub(crate) fn define_library(&mut self, definition: &LibraryDefinition) -> Result<(), Unwind> {
+        let env = Environment::new();
+        for declaration in &definition.body {
+            match declaration {
+                LibraryDeclaration::Import(sets, position) => self.import(sets, &env, *position)?,
+                LibraryDeclaration::Begin(body) => {
+                    for expr in body {
+                        self.execute(expr, &env)?;
+                    }
+                }
+            }
+        }
+        for (internal, _) in &definition.exports {
+            if env.lookup(internal).is_none() {
+                let error = library_error(
+                    "exported identifier is not defined in library",
+                    vec![Value::Symbol(internal.clone()), definition.name.to_value()],
+                );
+                return Err(self.signal(error));
+            }
+        }
+        let library = Library {
+            env,
+            exports: definition.exports.clone(),
+        };
+        self.libraries
+            .insert(definition.name.clone(), Rc::new(library));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::eval_str_with;
+    use crate::interpreter::{ErrorKind, Interpreter};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Creates a directory of library files, returning an interpreter searching it and the
+    /// directory, which the test removes once done
+    fn with_libraries(test: &str, files: &[(&str, &str)]) -> (Interpreter, PathBuf) {
+        let directory =
+            std::env::temp_dir().join(format!("scheme-library-{}-{}", test, std::process::id()));
+        for (file, contents) in files {
+            let path: PathBuf = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let mut interpreter = Interpreter::new();
+        interpreter.add_library_path(directory.clone());
+        (interpreter, directory)
+    }
+
+    fn eval_to_string(interpreter: &mut Interpreter, input: &str) -> String {
+        eval_str_with(interpreter, input)
+            .unwrap()
+            .written()
+            .to_string()
+    }
+
+    const POINTS: &str = "(define-library (geometry points)
+  (export make-point point-x (rename point-y y-coordinate))
+  (import (scheme base))
+  (begin
+    (define (make-point x y) (cons x y))
+    (define (point-x p) (car p))
+    (define (point-y p) (cdr p))))";
+
+    #[test]
+    fn import_test() {
+        let (mut interpreter, directory) =
+            with_libraries("import", &[("geometry/points.sld", POINTS)]);
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(import (geometry points))
+                 (let ((p (make-point 1 2))) (list (point-x p) (y-coordinate p)))"
+            ),
+            "(1 2)"
+        );
+        assert_eq!(
+            eval_str_with(&mut interpreter, "point-y").unwrap_err().kind,
+            ErrorKind::UnboundVariable
+        );
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(import (prefix (only (geometry points) make-point) geo:)
+                         (rename (except (geometry points) make-point) (point-x x-coordinate)))
+                 (x-coordinate (geo:make-point 3 4))"
+            ),
+            "3"
+        );
+        assert_eq!(
+            eval_str_with(
+                &mut interpreter,
+                "(import (only (geometry points) point-z))"
+            )
+            .unwrap_err()
+            .kind,
+            ErrorKind::Library
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn library_environment_test() {
+        let (mut interpreter, directory) = with_libraries(
+            "environment",
+            &[
+                (
+                    "counter.scm",
+                    "(define-library (counter)
+                       (export loads)
+                       (import (only (scheme base) +))
+                       (begin (define loads 0) (set! loads (+ loads 1))))",
+                ),
+                (
+                    "no-imports.sld",
+                    "(define-library (no-imports) (export x) (begin (define x (car '(1)))))",
+                ),
+            ],
+        );
+        // Libraries are only loaded once
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(import (counter)) (import (counter)) loads"
+            ),
+            "1"
+        );
+        // A library only sees the bindings it imports
+        let error = eval_str_with(&mut interpreter, "(import (no-imports))").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnboundVariable);
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(define-library (inline) (export double) (import (scheme base))
+                   (begin (define (double x) (* 2 x))))
+                 (import (inline))
+                 (double 21)"
+            ),
+            "42"
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn shared_bindings_test() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(define-library (tally) (export count bump!) (import (scheme base))
+                   (begin (define count 0) (define (bump!) (set! count (+ count 1)))))
+                 (import (tally) (prefix (tally) tally:))
+                 (bump!)
+                 (tally:bump!)
+                 (list count tally:count)"
+            ),
+            "(2 2)"
+        );
+        // Defining an imported name makes a new binding, leaving the library's alone
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(define count 10) (bump!) (list count tally:count)"
+            ),
+            "(10 3)"
+        );
+    }
+
+    #[test]
+    fn standard_libraries_test() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            eval_to_string(
+                &mut interpreter,
+                "(define-library (shout) (export shout)
+                   (import (scheme base) (scheme char))
+                   (begin (define (shout c) (list (char-upcase c) (cadr '(1 2 3))))))
+                 (import (shout))
+                 (shout #\\a)"
+            ),
+            "(#\\A 2)"
+        );
+        // (scheme base) does not export the procedures of the other standard libraries
+        let error = eval_str_with(
+            &mut interpreter,
+            "(define-library (loader) (export f) (import (scheme base))
+               (begin (define f load)))",
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnboundVariable);
+        let error =
+            eval_str_with(&mut interpreter, "(import (only (scheme base) load))").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Library);
+        let error = eval_str_with(&mut interpreter, "(import (scheme xyz))").unwrap_err();
+        assert_eq!(error.to_string(), "library not found (scheme xyz)");
+    }
+
+    #[test]
+    fn restricted_import_test() {
+        let mut interpreter = Interpreter::new();
+        for input in [
+            "(eval '(begin (import (scheme base)) (+ 1 2)) (null-environment 5))",
+            "(eval '(import (scheme file)) (scheme-report-environment 5))",
+            "(eval '(let () (import (scheme load)) load) (scheme-report-environment 5))",
+            "(eval '(define-library (x) (export)) (null-environment 5))",
+        ] {
+            assert_eq!(
+                eval_str_with(&mut interpreter, input).unwrap_err().kind,
+                ErrorKind::Restricted,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn library_errors_test() {
+        let (mut interpreter, directory) = with_libraries(
+            "errors",
+            &[
+                (
+                    "a.sld",
+                    "(define-library (a) (export a) (import (b)) (begin (define a 1)))",
+                ),
+                (
+                    "b.sld",
+                    "(define-library (b) (export b) (import (c)) (begin (define b 1)))",
+                ),
+                (
+                    "c.sld",
+                    "(define-library (c) (export c) (import (a)) (begin (define c 1)))",
+                ),
+                ("wrong.sld", "(define-library (right) (export))"),
+                (
+                    "missing-export.sld",
+                    "(define-library (missing-export) (export x))",
+                ),
+            ],
+        );
+        let error = eval_str_with(&mut interpreter, "(import (a))").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Library);
+        assert_eq!(
+            error.to_string(),
+            "import cycle between libraries (a) (b) (c) (a)"
+        );
+        // The cycle is reported at the import closing it
+        let position = error.position.unwrap();
+        assert_eq!((position.line, position.column), (1, 31));
+        let file = interpreter
+            .source_map()
+            .path(position.file.unwrap())
+            .unwrap();
+        assert_eq!(file, directory.join("c.sld"));
+        let error = eval_str_with(&mut interpreter, "(list 1)\n  (import (nowhere))").unwrap_err();
+        assert_eq!(error.to_string(), "library not found (nowhere)");
+        let position = error.position.unwrap();
+        assert_eq!((position.line, position.column), (2, 2));
+        let error = eval_str_with(&mut interpreter, "(import (only (scheme xyz) f))").unwrap_err();
+        assert_eq!(error.to_string(), "library not found (scheme xyz)");
+        assert!(error.position.is_some());
+        let error = eval_str_with(&mut interpreter, "(import (wrong))").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "library file does not define library (wrong)"
+        );
+        let error = eval_str_with(&mut interpreter, "(import (missing-export))").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "exported identifier is not defined in library x (missing-export)"
+        );
+        for input in [
+            "(import (only))",
+            "(import ())",
+            "(import (prefix (a) 1))",
+            "(define-library (x) (exports y))",
+        ] {
+            assert_eq!(
+                eval_str_with(&mut interpreter, input).unwrap_err().kind,
+                ErrorKind::Syntax,
+                "{}",
+                input
+            );
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,520 @@
+//! Source files: `load` at runtime and `include` at expansion time
+//!
+//! Every file the interpreter reads is registered in its `SourceMap`, under a `FileId` which the
+//! lexer attaches to each token, and the parser to each position, so that errors in code read
+//! from different files can be told apart and reported against the right file.
+//!
+//! `(load "file")` reads the file at runtime, and resolves a relative file name against the
+//! current directory of the process, wherever the call to `load` appears, as R7RS leaves it to
+//! the implementation. `include` differs on purpose: it is resolved while the including file is
+//! read, against the directory of that file.
+//!
+//! `(include "file" ...)` is replaced by `(begin datum ...)`, with the data of the named files,
+//! before the form containing it is compiled. File names are relative to the directory of the
+//! file containing the `include`, and a file including itself, directly or through other files,
+//! is reported as an include cycle. Only forms in expression or definition position are replaced:
+//! quoted data, the variables bound by `lambda`, `define` and the `let` family, and the calls of
+//! a variable named `include`, bound by them or defined in the environment, are left alone. Like
+//! `load`, `include` cannot be used in the restricted environments returned by
+//! `scheme-report-environment` and `null-environment`.
+
+use super::{Environment, ErrorKind, Interpreter, RuntimeError, Unwind};
+use crate::reader::{DatumIterator, FileLexer};
+use crate::value::Value;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+impl Interpreter {
+    /// Registers a file and reads every datum in it, recording the positions they were read from
+    pub(crate) fn read_file(&mut self, path: &Path) -> Result<Vec<Value>, RuntimeError> {
+        let file_name = || Value::string(path.to_string_lossy());
+        let io_error = |error: &dyn std::error::Error| {
+            RuntimeError::new(ErrorKind::Io, error.to_string(), vec![file_name()])
+        };
+        let (_, lexer) =
+            FileLexer::registered(&mut self.source_map, path).map_err(|error| io_error(&error))?;
+        DatumIterator::new(lexer)
+            .with_positions()
+            .map(|datum| match datum {
+                Ok((datum, positions)) => Ok(Value::from_positioned(&datum, &positions)),
+                Err(error) => Err(RuntimeError::new(
+                    ErrorKind::Read,
+                    error.to_string(),
+                    vec![file_name()],
+                )),
+            })
+            .collect()
+    }
+
+    /// Evaluates every datum of a file in the given environment, as done by `load`
+    pub(crate) fn load(&mut self, path: &Path, env: &Rc<Environment>) -> Result<(), Unwind> {
+        let forms = self.read_file(path).map_err(|error| self.signal(error))?;
+        for form in forms {
+            self.eval_form(&form, env)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the `include` forms within a form to be evaluated in `env`, returning `None` if
+    /// it contains none
+    pub(crate) fn expand_includes(
+        &mut self,
+        form: &Value,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, Unwind> {
+        // A variable named `include` defined at runtime shadows the keyword as well
+        let shadowed = env.lookup("include").is_some();
+        self.expand_form(form, shadowed, env)
+    }
+
+    /// Replaces the `include` forms within a form in expression or definition position, where
+    /// `shadowed` tells whether `include` is bound locally, making `(include ...)` a call
+    fn expand_form(
+        &mut self,
+        form: &Value,
+        shadowed: bool,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, Unwind> {
+        let keyword = match form {
+            Value::Pair(pair) => match pair.car() {
+                Value::Symbol(keyword) => keyword,
+                _ => Rc::from(""),
+            },
+            _ => return Ok(None),
+        };
+        match &*keyword {
+            "quote" | "quasiquote" | "import" => Ok(None),
+            "include" if !shadowed => self.include(form, env).map(Some),
+            "lambda" => {
+                let formals = element(form, 1);
+                let inner = shadowed || binds_include(&formals) || defines_include(form, 2);
+                self.expand_from(form, 2, inner, env)
+            }
+            "define" => match element(form, 1) {
+                Value::Pair(signature) => {
+                    let inner =
+                        shadowed || binds_include(&signature.cdr()) || defines_include(form, 2);
+                    self.expand_from(form, 2, inner, env)
+                }
+                _ => self.expand_from(form, 2, shadowed, env),
+            },
+            "set!" => self.expand_from(form, 2, shadowed, env),
+            "let" | "let*" | "letrec" => {
+                let name = element(form, 1);
+                let named = matches!(name, Value::Symbol(_));
+                let start = if named { 2 } else { 1 };
+                let inner = shadowed
+                    || name.is_symbol("include")
+                    || binds_include_in(&element(form, start))
+                    || defines_include(form, start + 1);
+                self.expand_elements(form, |this, index, element| match index {
+                    _ if index < start => Ok(None),
+                    _ if index == start => this.expand_bindings(element, &keyword, shadowed, env),
+                    _ => this.expand_form(element, inner, env),
+                })
+            }
+            "do" => {
+                let inner = shadowed || binds_include_in(&element(form, 1));
+                self.expand_elements(form, |this, index, element| match index {
+                    0 => Ok(None),
+                    1 => this.expand_bindings(element, "do", shadowed, env),
+                    2 => this.expand_from(element, 0, inner, env),
+                    _ => this.expand_form(element, inner, env),
+                })
+            }
+            "cond" => self.expand_elements(form, |this, index, clause| match index {
+                0 => Ok(None),
+                _ => this.expand_from(clause, 0, shadowed, env),
+            }),
+            // The data of the clauses are not expressions
+            "case" => self.expand_elements(form, |this, index, element| match index {
+                0 => Ok(None),
+                1 => this.expand_form(element, shadowed, env),
+                _ => this.expand_from(element, 1, shadowed, env),
+            }),
+            "guard" => {
+                let clauses = element(form, 1);
+                let inner = shadowed || element(&clauses, 0).is_symbol("include");
+                let body = shadowed || defines_include(form, 2);
+                self.expand_elements(form, |this, index, element| match index {
+                    0 => Ok(None),
+                    1 => this.expand_elements(element, |this, index, clause| match index {
+                        0 => Ok(None),
+                        _ => this.expand_from(clause, 0, inner, env),
+                    }),
+                    _ => this.expand_form(element, body, env),
+                })
+            }
+            // Only the bodies of a library are code, and they do not see the bindings of the
+            // code around the library
+            "define-library" => self.expand_elements(form, |this, index, declaration| {
+                let keyword = element(declaration, 0);
+                if index < 2 {
+                    Ok(None)
+                } else if keyword.is_symbol("begin") {
+                    let shadowed = defines_include(declaration, 1);
+                    this.expand_from(declaration, 1, shadowed, env)
+                } else if keyword.is_symbol("include") {
+                    this.include(declaration, env).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }),
+            _ => self.expand_from(form, 0, shadowed, env),
+        }
+    }
+
+    /// Replaces the `include` forms in the elements of a list from the one at index `start`,
+    /// which are expressions or definitions
+    fn expand_from(
+        &mut self,
+        list: &Value,
+        start: usize,
+        shadowed: bool,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, Unwind> {
+        self.expand_elements(list, |this, index, element| match index {
+            _ if index < start => Ok(None),
+            _ => this.expand_form(element, shadowed, env),
+        })
+    }
+
+    /// Replaces the `include` forms in the initial values of the bindings of a `let`, `let*`,
+    /// `letrec` or `do` form, and in the steps of `do`, leaving the variables alone
+    fn expand_bindings(
+        &mut self,
+        bindings: &Value,
+        keyword: &str,
+        shadowed: bool,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, Unwind> {
+        let all_bound = shadowed || binds_include_in(bindings);
+        // The initial values of `let*` see the variables bound before them
+        let mut bound_before = shadowed;
+        self.expand_elements(bindings, |this, _, binding| {
+            let init_shadowed = match keyword {
+                "let*" => bound_before,
+                "letrec" => all_bound,
+                _ => shadowed,
+            };
+            bound_before = bound_before || element(binding, 0).is_symbol("include");
+            this.expand_elements(binding, |this, index, element| match index {
+                0 => Ok(None),
+                1 => this.expand_form(element, init_shadowed, env),
+                _ => this.expand_form(element, all_bound, env),
+            })
+        })
+    }
+
+    /// Rebuilds a list with the elements `expand` replaces, given their index, returning `None`
+    /// if it replaces none
+    fn expand_elements(
+        &mut self,
+        list: &Value,
+        mut expand: impl FnMut(&mut Self, usize, &Value) -> Result<Option<Value>, Unwind>,
+    ) -> Result<Option<Value>, Unwind> {
+        let mut pairs = Vec::new();
+        let mut rest = list.clone();
+        while let Value::Pair(pair) = rest {
+            rest = pair.cdr();
+            pairs.push(pair);
+        }
+        let mut expanded = Vec::with_capacity(pairs.len());
+        for (index, pair) in pairs.iter().enumerate() {
+            expanded.push(expand(self, index, &pair.car())?);
+        }
+        if expanded.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let list = pairs
+            .iter()
+            .zip(expanded)
+            .rev()
+            .fold(rest, |cdr, (pair, car)| {
+                Value::cons_at(car.unwrap_or_else(|| pair.car()), cdr, pair.position())
+            });
+        Ok(Some(list))
+    }
+
+    /// Reads the files named by an `include` form, returning a `begin` form with their data
+    fn include(&mut self, form: &Value, env: &Rc<Environment>) -> Result<Value, Unwind> {
+        let position = match form {
+            Value::Pair(pair) => pair.position(),
+            _ => None,
+        };
+        let fail = |interpreter: &mut Interpreter, mut error: RuntimeError| {
+            error.position = error.position.or(position);
+            interpreter.signal(error)
+        };
+        if env.is_restricted() {
+            let error = RuntimeError::new(
+                ErrorKind::Restricted,
+                "files cannot be included in a restricted environment",
+                Vec::new(),
+            );
+            return Err(fail(self, error));
+        }
+        let names = match form.list_to_vec() {
+            Some(items) if items.len() > 1 => items[1..].to_vec(),
+            _ => return Err(fail(self, RuntimeError::syntax("bad include syntax", form))),
+        };
+        let including_file = position
+            .and_then(|position| position.file)
+            .and_then(|file| self.source_map.path(file))
+            .map(Path::to_path_buf);
+        let directory = including_file
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        // The outermost file is not on the stack of included files yet
+        let outermost = self.including.is_empty();
+        if let (true, Some(file)) = (outermost, &including_file) {
+            self.including
+                .push(fs::canonicalize(file).unwrap_or_else(|_| file.clone()));
+        }
+        let body = self.include_files(form, names, &directory, env, fail);
+        if outermost {
+            self.including.clear();
+        }
+        body
+    }
+
+    /// Reads and expands the named files, relative to `directory`, reporting errors with `fail`
+    fn include_files(
+        &mut self,
+        form: &Value,
+        names: Vec<Value>,
+        directory: &Path,
+        env: &Rc<Environment>,
+        fail: impl Fn(&mut Interpreter, RuntimeError) -> Unwind,
+    ) -> Result<Value, Unwind> {
+        let mut body = vec![Value::symbol("begin")];
+        for name in names {
+            let path = match &name {
+                Value::String(name) => directory.join(&*name.borrow()),
+                _ => return Err(fail(self, RuntimeError::syntax("bad include syntax", form))),
+            };
+            let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if self.including.contains(&canonical) {
+                let error = RuntimeError::new(ErrorKind::Syntax, "include cycle", vec![name]);
+                return Err(fail(self, error));
+            }
+            let data = self.read_file(&path).map_err(|error| fail(self, error))?;
+            self.including.push(canonical);
+            let expanded: Result<Vec<Value>, Unwind> = data
+                .iter()
+                .map(|datum| {
+                    Ok(self
+                        .expand_includes(datum, env)?
+                        .unwrap_or_else(|| datum.clone()))
+                })
+                .collect();
+            self.including.pop();
+            body.extend(expanded?);
+        }
+        Ok(Value::list(body))
+    }
+}
+
+/// The element of a list at `index`, or the empty list if it is shorter
+fn element(list: &Value, index: usize) -> Value {
+    let mut rest = list.clone();
+    for _ in 0..index {
+        rest = match rest {
+            Value::Pair(pair) => pair.cdr(),
+            _ => return Value::Null,
+        };
+    }
+    match rest {
+        Value::Pair(pair) => pair.car(),
+        _ => Value::Null,
+    }
+}
+
+/// Whether a list of formal parameters binds `include`
+fn binds_include(formals: &Value) -> bool {
+    let mut rest = formals.clone();
+    while let Value::Pair(pair) = rest {
+        if pair.car().is_symbol("include") {
+            return true;
+        }
+        rest = pair.cdr();
+    }
+    rest.is_symbol("include")
+}
+
+/// Whether a list of bindings of the form `(variable init ...)` binds `include`
+fn binds_include_in(bindings: &Value) -> bool {
+    let mut rest = bindings.clone();
+    while let Value::Pair(pair) = rest {
+        if element(&pair.car(), 0).is_symbol("include") {
+            return true;
+        }
+        rest = pair.cdr();
+    }
+    false
+}
+
+/// Whether the body made of the elements of a form from index `start` defines `include`
+fn defines_include(form: &Value, start: usize) -> bool {
+    let mut rest = form.clone();
+    let mut index = 0;
+    while let Value::Pair(pair) = rest {
+        let definition = pair.car();
+        if index >= start && element(&definition, 0).is_symbol("define") {
+            let target = element(&definition, 1);
+            if target.is_symbol("include") || element(&target, 0).is_symbol("include") {
+                return true;
+            }
+        }
+        rest = pair.cdr();
+        index += 1;
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::test::eval_str_with;
+    use crate::interpreter::{ErrorKind, Interpreter};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Creates a directory for the files of a test, which the test removes once done
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("scheme-loader-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_test() {
+        let dir = temp_dir("load");
+        let file = dir.join("defs.scm");
+        fs::write(&file, "(define x 20)\n(define (double n) (* 2 n))\n").unwrap();
+        let mut interpreter = Interpreter::new();
+        let program = format!("(load {:?}) (double (+ x 1))", file.to_str().unwrap());
+        let value = eval_str_with(&mut interpreter, &program).unwrap();
+        assert_eq!(value.to_string(), "42");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_test() {
+        let dir = temp_dir("include");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/a.scm"), "(define a 1)\n(include \"b.scm\")\n").unwrap();
+        fs::write(dir.join("lib/b.scm"), "(define b '(include \"c.scm\"))\n").unwrap();
+        fs::write(
+            dir.join("main.scm"),
+            "(include \"lib/a.scm\")\n(list a b)\n",
+        )
+        .unwrap();
+        let mut interpreter = Interpreter::new();
+        let program = format!("(load {:?})", dir.join("main.scm").to_str().unwrap());
+        eval_str_with(&mut interpreter, &program).unwrap();
+        let value = eval_str_with(&mut interpreter, "(list a b)").unwrap();
+        assert_eq!(value.to_string(), "(1 (include c.scm))");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_errors_test() {
+        let dir = temp_dir("include-errors");
+        fs::write(dir.join("a.scm"), "(include \"b.scm\")\n").unwrap();
+        fs::write(dir.join("b.scm"), "(define x 1)\n\n  (include \"a.scm\")\n").unwrap();
+        fs::write(dir.join("c.scm"), "(define y 2)\n(car y)\n").unwrap();
+        fs::write(dir.join("d.scm"), "(include \"c.scm\")\n").unwrap();
+        let mut interpreter = Interpreter::new();
+
+        let program = format!("(load {:?})", dir.join("a.scm").to_str().unwrap());
+        let error = eval_str_with(&mut interpreter, &program).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Syntax);
+        assert_eq!(error.to_string(), "include cycle \"a.scm\"");
+        let position = error.position.unwrap();
+        assert_eq!((position.line, position.column), (3, 2));
+        let file = interpreter
+            .source_map()
+            .path(position.file.unwrap())
+            .unwrap();
+        assert_eq!(file, dir.join("b.scm"));
+
+        // Errors in included code point at the included file
+        let program = format!("(load {:?})", dir.join("d.scm").to_str().unwrap());
+        let error = eval_str_with(&mut interpreter, &program).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::WrongType);
+        let position = error.position.unwrap();
+        assert_eq!((position.line, position.column), (2, 0));
+        let file = interpreter
+            .source_map()
+            .path(position.file.unwrap())
+            .unwrap();
+        assert_eq!(file, dir.join("c.scm"));
+
+        let error = eval_str_with(&mut interpreter, "(include \"missing.scm\")").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Io);
+        let error = eval_str_with(&mut interpreter, "(include 'a)").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Syntax);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_binding_positions_test() {
+        let dir = temp_dir("include-bindings");
+        let mut interpreter = Interpreter::new();
+        for (program, expected) in [
+            ("(define (include x) (* 2 x)) (include 21)", "42"),
+            ("((lambda (include y) (list include y)) 1 2)", "(1 2)"),
+            ("((lambda include include) 1 2)", "(1 2)"),
+            ("(let ((include 1)) include)", "1"),
+            ("(let* ((include list) (x (include 1))) x)", "(1)"),
+            ("(letrec ((include (lambda (n) n))) (include 3))", "3"),
+            (
+                "(let loop ((include 3)) (if (= include 0) 'done (loop (- include 1))))",
+                "done",
+            ),
+            (
+                "(do ((include 0 (+ include 1))) ((= include 2) include))",
+                "2",
+            ),
+            ("((lambda (include) (include \"file\")) string-length)", "4"),
+            ("(define (f) (define (include x) x) (include 5)) (f)", "5"),
+            ("(guard (include (#t (include 'x))) (raise list))", "(x)"),
+            ("(case 'include ((include) 1) (else 2))", "1"),
+        ] {
+            let value = eval_str_with(&mut interpreter, program).unwrap();
+            assert_eq!(value.written().to_string(), expected, "{}", program);
+        }
+        // Elsewhere in the same forms, `include` is still expanded
+        let mut interpreter = Interpreter::new();
+        fs::write(dir.join("x.scm"), "10\n").unwrap();
+        let program = format!(
+            "(list (let ((include 1) (y (include {0:?}))) (list include y)) (let () (include {0:?})))",
+            dir.join("x.scm").to_str().unwrap()
+        );
+        let value = eval_str_with(&mut interpreter, &program).unwrap();
+        assert_eq!(value.to_string(), "((1 10) 10)");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restricted_include_test() {
+        let dir = temp_dir("restricted-include");
+        let secret = dir.join("secret.scm");
+        fs::write(&secret, "(define secret 42)\n").unwrap();
+        let mut interpreter = Interpreter::new();
+        for env in ["(null-environment 5)", "(scheme-report-environment 5)"] {
+            let program = format!(
+                "(eval '(begin (include {:?}) secret) {})",
+                secret.to_str().unwrap(),
+                env
+            );
+            let error = eval_str_with(&mut interpreter, &program).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Restricted, "{}", env);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1435 @@
+//! Tree-walking interpreter for Scheme programs
+//!
+//! Every toplevel `Datum` is first turned into a runtime `Value`, which the `syntax` module then
+//! analyses into an `Expr` tree. The `Interpreter` executes `Expr`s in an `Environment`, calling
+//! into the procedures defined in the `builtins` module where necessary.
+//!
+//! Calls in tail position are not made by the expression containing them, but handed back to a
+//! trampoline loop, so that tail-recursive programs run in constant Rust stack space as R5RS
+//! requires. The interpreter is the only backend for now: the bytecode VM and LLVM IR backends
+//! are not written yet, so this is the only place the guarantee is made.
+//!
+//! The interpreter keeps a stack of the procedure calls in progress, which errors capture as
+//! their backtrace. A tail call replaces the frame of its caller, so the backtrace only counts
+//! the frames elided this way.
+
+mod library;
+mod loader;
+mod syntax;
+
+use crate::builtins::{self, Arity, Builtin};
+use crate::diagnostics;
+use crate::parser::{Datum, Position, PositionTree};
+use crate::port::{InputPort, OutputPort, Port};
+use crate::profiler::Profiler;
+use crate::source_map::SourceMap;
+use crate::value::Value;
+use library::{Library, LibraryName};
+use std::{cell::RefCell, collections::HashMap, fmt, path::PathBuf, rc::Rc};
+
+pub(crate) use syntax::{compile, Expr, LambdaTemplate};
+pub use syntax::{is_keyword, SpecialForm, SPECIAL_FORMS};
+
+/// Category of a `RuntimeError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A procedure received an argument of the wrong type
+    WrongType,
+    /// A procedure was called with the wrong number of arguments
+    Arity,
+    /// A variable was referenced or assigned before being bound
+    UnboundVariable,
+    /// An index or argument was outside of its allowed range
+    OutOfRange,
+    /// Exact division by zero
+    DivisionByZero,
+    /// A result cannot be represented by the numeric types this implementation supports
+    ImplementationRestriction,
+    /// A special form was used with the wrong shape
+    Syntax,
+    /// An escape continuation was invoked after its extent ended
+    Continuation,
+    /// A file could not be opened, read or written
+    Io,
+    /// `read` encountered malformed or incomplete external representation
+    Read,
+    /// The program called `error`
+    User,
+    /// Evaluation was stopped from outside of the program, e.g. by quitting the debugger
+    Aborted,
+    /// A library could not be found or imported, or libraries import each other in a cycle
+    Library,
+    /// A special form was used in an environment which does not allow it, such as `import` in
+    /// the environment returned by `null-environment`
+    Restricted,
+    /// An object other than a condition was raised without being handled, or an exception
+    /// handler returned from a non-continuable `raise`
+    Raise,
+}
+
+impl ErrorKind {
+    /// The stable code identifying the kind in diagnostics
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::WrongType => "E0101",
+            ErrorKind::Arity => "E0102",
+            ErrorKind::UnboundVariable => "E0103",
+            ErrorKind::OutOfRange => "E0104",
+            ErrorKind::DivisionByZero => "E0105",
+            ErrorKind::ImplementationRestriction => "E0106",
+            ErrorKind::Syntax => "E0107",
+            ErrorKind::Continuation => "E0108",
+            ErrorKind::Io => "E0109",
+            ErrorKind::Read => "E0110",
+            ErrorKind::User => "E0111",
+            ErrorKind::Aborted => "E0112",
+            ErrorKind::Library => "E0113",
+            ErrorKind::Raise => "E0114",
+            ErrorKind::Restricted => "E0115",
+        }
+    }
+}
+
+/// An error signalled while running a Scheme program
+///
+/// The `message` describes the error, and the `irritants` are the values that caused it. When
+/// displayed, the irritants are printed after the message in their `write` representation.
+/// Scheme code sees runtime errors as condition objects, which exception handlers can inspect.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    /// What went wrong
+    pub kind: ErrorKind,
+    /// Human readable description of the error
+    pub message: String,
+    /// The values the error is about
+    pub irritants: Vec<Value>,
+    /// The position of the failing call, if it was read from source code
+    pub position: Option<Position>,
+    /// The procedure calls in progress when the error was signalled, innermost first
+    pub backtrace: Vec<StackFrame>,
+    /// The environment an unbound variable was looked up in, which the names suggested instead
+    /// are taken from
+    pub environment: Option<Rc<Environment>>,
+}
+
+impl RuntimeError {
+    /// Creates a new `RuntimeError`
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S, irritants: Vec<Value>) -> Self {
+        RuntimeError {
+            kind,
+            message: message.into(),
+            irritants,
+            position: None,
+            backtrace: Vec::new(),
+            environment: None,
+        }
+    }
+
+    /// Error for a procedure argument of the wrong type
+    pub fn wrong_type(procedure: &str, expected: &str, found: &Value) -> Self {
+        RuntimeError::new(
+            ErrorKind::WrongType,
+            format!("{}: expected {}, got", procedure, expected),
+            vec![found.clone()],
+        )
+    }
+
+    /// Error for a call with the wrong number of arguments
+    pub fn arity(procedure: &str, arity: Arity, given: usize) -> Self {
+        RuntimeError::new(
+            ErrorKind::Arity,
+            format!(
+                "{}: expected {} argument(s), got {}",
+                procedure, arity, given
+            ),
+            Vec::new(),
+        )
+    }
+
+    /// Error for a reference to an unbound variable
+    pub fn unbound_variable(name: &str) -> Self {
+        RuntimeError::new(
+            ErrorKind::UnboundVariable,
+            "unbound variable",
+            vec![Value::symbol(name)],
+        )
+    }
+
+    /// Error for an argument outside of its allowed range
+    pub fn out_of_range(procedure: &str, found: &Value) -> Self {
+        RuntimeError::new(
+            ErrorKind::OutOfRange,
+            format!("{}: argument out of range", procedure),
+            vec![found.clone()],
+        )
+    }
+
+    /// Error for a special form with the wrong shape, positioned at the form
+    pub fn syntax(message: &str, form: &Value) -> Self {
+        let mut error = RuntimeError::new(ErrorKind::Syntax, message, vec![form.clone()]);
+        if let Value::Pair(pair) = form {
+            error.position = pair.position();
+        }
+        error
+    }
+
+    /// The error reported when `object` is raised and not handled
+    pub fn uncaught(object: &Value) -> Self {
+        match object {
+            Value::Condition(condition) => (**condition).clone(),
+            other => RuntimeError::new(ErrorKind::Raise, "uncaught exception", vec![other.clone()]),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for irritant in &self.irritants {
+            write!(f, " {}", irritant.written())?;
+        }
+        Ok(())
+    }
+}
+
+/// A procedure call in progress
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    /// The procedure being called
+    pub procedure: Value,
+    /// The position of the call, if it was read from source code
+    pub call_site: Option<Position>,
+    /// The number of tail calls made since the frame was entered, each of which replaced the
+    /// frame of the procedure making it
+    pub elided: usize,
+}
+
+impl StackFrame {
+    /// The name of the called procedure, if it has one
+    pub fn name(&self) -> Option<&str> {
+        match &self.procedure {
+            Value::Procedure(procedure) => procedure.name(),
+            _ => None,
+        }
+    }
+
+    /// Where the called procedure was defined, if it was created from source code
+    pub fn definition(&self) -> Option<Position> {
+        match &self.procedure {
+            Value::Procedure(procedure) => match &**procedure {
+                Procedure::Lambda(closure) => closure.template.position,
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The name of the called procedure, or for an anonymous one, the keyword of the form which
+    /// created it and where that was read from, as in `#<procedure let at main.scm:3:2>`
+    pub fn describe(&self, source_map: &SourceMap) -> String {
+        if let Some(name) = self.name() {
+            return name.to_string();
+        }
+        let template = match &self.procedure {
+            Value::Procedure(procedure) => match &**procedure {
+                Procedure::Lambda(closure) => &closure.template,
+                _ => return "#<procedure>".to_string(),
+            },
+            _ => return "#<procedure>".to_string(),
+        };
+        match template.position {
+            Some(position) => match position.file.and_then(|file| source_map.name(file)) {
+                Some(file) => format!(
+                    "#<procedure {} at {}:{}:{}>",
+                    template.keyword, file, position.line, position.column
+                ),
+                None => format!(
+                    "#<procedure {} at {}:{}>",
+                    template.keyword, position.line, position.column
+                ),
+            },
+            None => format!("#<procedure {}>", template.keyword),
+        }
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name().unwrap_or("#<procedure>"))?;
+        if let Some(position) = self.call_site {
+            write!(f, ", called at {}", position)?;
+        }
+        Ok(())
+    }
+}
+
+/// Non-local exit out of an evaluation
+#[derive(Debug)]
+pub enum Unwind {
+    /// An error was signalled by Rust code, and has not been passed to the exception handlers yet
+    Error(RuntimeError),
+    /// An object was raised, and no exception handler escaped from the raise, with the
+    /// backtrace of the raise
+    Uncaught(Value, Vec<StackFrame>),
+    /// The escape continuation with the given id was invoked with a value
+    Escape(usize, Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// Result type of evaluating an expression or applying a procedure
+pub type EvalResult = Result<Value, Unwind>;
+
+/// A chain of frames mapping variable names to values
+///
+/// A restricted environment, and every frame extending it, does not allow the special forms
+/// which reach files or libraries, such as `import`, so that code evaluated in it can only use
+/// the procedures bound in it.
+pub struct Environment {
+    /// Every binding is a cell of its own, which importing a library shares with the importer
+    bindings: RefCell<HashMap<Rc<str>, Rc<RefCell<Value>>>>,
+    parent: Option<Rc<Environment>>,
+    restricted: bool,
+}
+
+impl Environment {
+    /// Creates an empty environment without a parent
+    pub fn new() -> Rc<Self> {
+        Rc::new(Environment {
+            bindings: RefCell::new(HashMap::new()),
+            parent: None,
+            restricted: false,
+        })
+    }
+
+    /// Creates an empty restricted environment, as returned by `null-environment`
+    ///
+    /// Special forms do not depend on bindings, so code evaluated in it can use the syntax of the
+    /// language, but cannot reach any procedure it was not handed explicitly.
+    pub fn null() -> Rc<Self> {
+        Rc::new(Environment {
+            bindings: RefCell::new(HashMap::new()),
+            parent: None,
+            restricted: true,
+        })
+    }
+
+    /// Creates a fresh environment binding every standard procedure
+    pub fn standard() -> Rc<Self> {
+        let env = Environment::new();
+        env.define_builtins(|_| true);
+        env
+    }
+
+    /// Creates a fresh restricted environment binding the standard procedures which cannot reach
+    /// the file system or the evaluator, as returned by `scheme-report-environment`
+    pub fn report() -> Rc<Self> {
+        let env = Environment::null();
+        env.define_builtins(|builtin| !builtin.library.is_privileged());
+        env
+    }
+
+    fn define_builtins(&self, include: impl Fn(&Builtin) -> bool) {
+        for builtin in builtins::all().filter(|builtin| include(builtin)) {
+            self.define(
+                Rc::from(builtin.name),
+                Value::Procedure(Rc::new(Procedure::Builtin(builtin))),
+            );
+        }
+    }
+
+    /// Creates a new frame on top of `parent`
+    pub fn extend(parent: &Rc<Environment>, bindings: HashMap<Rc<str>, Value>) -> Rc<Self> {
+        let bindings = bindings
+            .into_iter()
+            .map(|(name, value)| (name, Rc::new(RefCell::new(value))))
+            .collect();
+        Rc::new(Environment {
+            bindings: RefCell::new(bindings),
+            parent: Some(parent.clone()),
+            restricted: parent.restricted,
+        })
+    }
+
+    /// Looks up a variable, starting at the innermost frame
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        let mut env = self;
+        loop {
+            if let Some(cell) = env.bindings.borrow().get(name) {
+                return Some(cell.borrow().clone());
+            }
+            env = env.parent.as_deref()?;
+        }
+    }
+
+    /// The cell holding the value of a variable, starting at the innermost frame
+    pub(crate) fn cell(&self, name: &str) -> Option<Rc<RefCell<Value>>> {
+        let mut env = self;
+        loop {
+            if let Some(cell) = env.bindings.borrow().get(name) {
+                return Some(cell.clone());
+            }
+            env = env.parent.as_deref()?;
+        }
+    }
+
+    /// The names of the variables bound in the environment and in the ones it extends
+    pub fn visible_names(&self) -> Vec<Rc<str>> {
+        let mut names = Vec::new();
+        let mut env = Some(self);
+        while let Some(frame) = env {
+            names.extend(frame.bindings.borrow().keys().cloned());
+            env = frame.parent.as_deref();
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The variables bound in the innermost frame with their values, sorted by name
+    pub fn bindings(&self) -> Vec<(Rc<str>, Value)> {
+        let mut bindings: Vec<_> = self
+            .bindings
+            .borrow()
+            .iter()
+            .map(|(name, cell)| (name.clone(), cell.borrow().clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// Returns `true` if the environment does not allow the special forms reaching files or
+    /// libraries
+    pub fn is_restricted(&self) -> bool {
+        self.restricted
+    }
+
+    /// The enclosing environment, `None` for the global environment
+    pub fn parent(&self) -> Option<&Rc<Environment>> {
+        self.parent.as_ref()
+    }
+
+    /// Binds a variable in the innermost frame, replacing any previous binding there
+    pub fn define(&self, name: Rc<str>, value: Value) {
+        self.define_cell(name, Rc::new(RefCell::new(value)));
+    }
+
+    /// Binds a variable in the innermost frame to a cell shared with another binding
+    pub(crate) fn define_cell(&self, name: Rc<str>, cell: Rc<RefCell<Value>>) {
+        self.bindings.borrow_mut().insert(name, cell);
+    }
+
+    /// Assigns to an existing binding, returning `false` if the variable is unbound
+    pub fn set(&self, name: &str, value: Value) -> bool {
+        let mut env = self;
+        loop {
+            if let Some(cell) = env.bindings.borrow().get(name) {
+                *cell.borrow_mut() = value;
+                return true;
+            }
+            match env.parent.as_deref() {
+                Some(parent) => env = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<environment>")
+    }
+}
+
+/// A procedure value
+pub enum Procedure {
+    /// A procedure implemented in Rust
+    Builtin(&'static Builtin),
+    /// A procedure created by evaluating a `lambda` expression
+    Lambda(Closure),
+    /// An escape-only continuation captured by `call-with-current-continuation`
+    Continuation(usize),
+}
+
+impl Procedure {
+    /// The name of the procedure, if it has one
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Procedure::Builtin(builtin) => Some(builtin.name),
+            Procedure::Lambda(closure) => closure.template.name.as_deref(),
+            Procedure::Continuation(_) => Some("continuation"),
+        }
+    }
+}
+
+/// A `lambda` expression together with the environment it was evaluated in
+pub struct Closure {
+    pub(crate) template: Rc<LambdaTemplate>,
+    pub(crate) env: Rc<Environment>,
+}
+
+impl Closure {
+    /// Creates the frame in which the body of the closure runs
+    fn bind(&self, mut args: Vec<Value>) -> Result<Rc<Environment>, RuntimeError> {
+        let template = &self.template;
+        let required = template.params.len();
+        if args.len() < required || (template.rest.is_none() && args.len() > required) {
+            let arity = match template.rest {
+                Some(_) => Arity::at_least(required),
+                None => Arity::exactly(required),
+            };
+            let name = template.name.as_deref().unwrap_or("#<procedure>");
+            return Err(RuntimeError::arity(name, arity, args.len()));
+        }
+        let rest = Value::list(args.split_off(required));
+        let mut bindings: HashMap<Rc<str>, Value> =
+            template.params.iter().cloned().zip(args).collect();
+        if let Some(name) = &template.rest {
+            bindings.insert(name.clone(), rest);
+        }
+        Ok(Environment::extend(&self.env, bindings))
+    }
+}
+
+/// A promise created by `delay`, memoizing its value once forced
+pub struct Promise {
+    pub(crate) state: RefCell<PromiseState>,
+}
+
+pub(crate) enum PromiseState {
+    Delayed(Rc<Expr>, Rc<Environment>),
+    Forced(Value),
+}
+
+/// An entry of the exception handler stack
+enum Handler {
+    /// A procedure installed by `with-exception-handler`
+    Procedure(Value),
+    /// A `guard` expression, which catches raised objects by escaping with the given id
+    Guard(usize),
+}
+
+/// Callback run by the interpreter before each call read from source code, used to implement
+/// debuggers
+pub trait DebugHook {
+    /// Runs before the operator and operands of the call at `position` are evaluated in `env`,
+    /// with `depth` procedure calls in progress
+    ///
+    /// The hook may evaluate code with the interpreter, which does not run hooks or exception
+    /// handlers meanwhile. Returning an error aborts the evaluation; it bypasses the exception
+    /// handlers of the program.
+    fn before_call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        position: Position,
+        depth: usize,
+        env: &Rc<Environment>,
+    ) -> Result<(), RuntimeError>;
+}
+
+/// The error reported for an unwind reaching the outermost evaluation
+fn uncaught_error(unwind: Unwind) -> RuntimeError {
+    match unwind {
+        Unwind::Error(error) => error,
+        Unwind::Uncaught(object, backtrace) => {
+            let mut error = RuntimeError::uncaught(&object);
+            if error.backtrace.is_empty() {
+                error.backtrace = backtrace;
+            }
+            error
+        }
+        Unwind::Escape(..) => RuntimeError::new(
+            ErrorKind::Continuation,
+            "continuation invoked outside of its extent",
+            Vec::new(),
+        ),
+    }
+}
+
+/// How many bytes of the native stack an evaluation uses at most by default, which fits in the
+/// stacks of the threads Rust spawns
+pub const DEFAULT_STACK_LIMIT: usize = 7 << 18;
+
+/// The interpreter state: the global environment, the active escape continuations, the
+/// exception handlers and the current ports
+pub struct Interpreter {
+    global: Rc<Environment>,
+    active_continuations: Vec<usize>,
+    next_continuation: usize,
+    handlers: Vec<Handler>,
+    call_site: Option<Position>,
+    stack: Vec<StackFrame>,
+    debug_hook: Option<Box<dyn DebugHook>>,
+    profiler: Option<Profiler>,
+    libraries: HashMap<LibraryName, Rc<Library>>,
+    loading_libraries: Vec<LibraryName>,
+    library_path: Vec<PathBuf>,
+    source_map: SourceMap,
+    including: Vec<PathBuf>,
+    current_input: Rc<Port>,
+    current_output: Rc<Port>,
+    /// The address of the native stack where the outermost evaluation in progress started
+    stack_base: Option<usize>,
+    stack_limit: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter whose global environment contains the standard procedures
+    pub fn new() -> Self {
+        Interpreter {
+            global: Environment::standard(),
+            active_continuations: Vec::new(),
+            next_continuation: 0,
+            handlers: Vec::new(),
+            call_site: None,
+            stack: Vec::new(),
+            debug_hook: None,
+            profiler: None,
+            libraries: HashMap::new(),
+            loading_libraries: Vec::new(),
+            library_path: Vec::new(),
+            source_map: SourceMap::new(),
+            including: Vec::new(),
+            current_input: Rc::new(Port::Input(RefCell::new(InputPort::stdin()))),
+            current_output: Rc::new(Port::Output(RefCell::new(OutputPort::stdout()))),
+            stack_base: None,
+            stack_limit: DEFAULT_STACK_LIMIT,
+        }
+    }
+
+    /// Sets how many bytes of the native stack an evaluation may use before non-tail recursion
+    /// is reported as too deep, which must leave room below the size of the thread's stack
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.stack_limit = bytes;
+    }
+
+    /// The port returned by `current-input-port`, standard input by default
+    pub fn current_input_port(&self) -> &Rc<Port> {
+        &self.current_input
+    }
+
+    /// The port returned by `current-output-port`, standard output by default
+    pub fn current_output_port(&self) -> &Rc<Port> {
+        &self.current_output
+    }
+
+    /// Replaces the current input port, returning the previous one
+    pub fn set_current_input_port(&mut self, port: Rc<Port>) -> Rc<Port> {
+        std::mem::replace(&mut self.current_input, port)
+    }
+
+    /// Replaces the current output port, returning the previous one
+    pub fn set_current_output_port(&mut self, port: Rc<Port>) -> Rc<Port> {
+        std::mem::replace(&mut self.current_output, port)
+    }
+
+    /// The sources read by the interpreter, which the positions of its errors refer to
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// The sources read by the interpreter, to register code evaluated from elsewhere
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
+    }
+
+    /// The global environment of the interpreter
+    pub fn global_environment(&self) -> &Rc<Environment> {
+        &self.global
+    }
+
+    /// The variables visible where the unbound variable an error is about was referenced, and
+    /// the keywords, closest to it, which may have been meant instead
+    pub fn suggestions(&self, error: &RuntimeError) -> Vec<String> {
+        let name = match (error.kind, error.irritants.first()) {
+            (ErrorKind::UnboundVariable, Some(Value::Symbol(name))) => name,
+            _ => return Vec::new(),
+        };
+        let env = error.environment.as_ref().unwrap_or(&self.global);
+        let names = env.visible_names();
+        let keywords = SPECIAL_FORMS.iter().map(|form| form.name);
+        let candidates = names.iter().map(|name| &**name).chain(keywords);
+        diagnostics::closest_names(name, candidates)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Evaluates a datum read by the parser in the global environment
+    pub fn eval_datum(&mut self, datum: &Datum) -> Result<Value, RuntimeError> {
+        self.eval(&Value::from(datum))
+    }
+
+    /// Evaluates a datum in the global environment, attributing runtime errors to the positions
+    /// the datum was read from
+    pub fn eval_datum_with_positions(
+        &mut self,
+        datum: &Datum,
+        positions: &PositionTree,
+    ) -> Result<Value, RuntimeError> {
+        self.eval(&Value::from_positioned(datum, positions))
+    }
+
+    /// Evaluates a form in the global environment
+    pub fn eval(&mut self, form: &Value) -> Result<Value, RuntimeError> {
+        let env = self.global.clone();
+        self.eval_in(form, &env)
+    }
+
+    /// Evaluates a form in the given environment
+    pub fn eval_in(&mut self, form: &Value, env: &Rc<Environment>) -> Result<Value, RuntimeError> {
+        self.eval_form(form, env).map_err(uncaught_error)
+    }
+
+    /// Expands the `include` forms within a form, then compiles and executes it, letting escapes
+    /// propagate to an enclosing `call/cc`
+    pub(crate) fn eval_form(&mut self, form: &Value, env: &Rc<Environment>) -> EvalResult {
+        let expanded = self.expand_includes(form, env)?;
+        let form = expanded.as_ref().unwrap_or(form);
+        let expr = compile(form).map_err(|error| self.signal(error))?;
+        self.execute(&expr, env)
+    }
+
+    /// Checks the syntax of a form without evaluating it, expanding the `include` forms within it
+    pub fn check(&mut self, form: &Value) -> Result<(), RuntimeError> {
+        let env = self.global.clone();
+        let expanded = self.expand_includes(form, &env).map_err(uncaught_error)?;
+        compile(expanded.as_ref().unwrap_or(form))?;
+        Ok(())
+    }
+
+    /// Applies a procedure to a list of arguments
+    pub fn apply(&mut self, procedure: &Value, args: Vec<Value>) -> EvalResult {
+        self.trampoline(Step::TailCall(procedure.clone(), args, None))
+    }
+
+    /// The procedure calls in progress, innermost first
+    pub fn backtrace(&self) -> Vec<StackFrame> {
+        self.stack.iter().rev().cloned().collect()
+    }
+
+    /// Installs a hook to run before every call read from source code, returning the previous one
+    pub fn set_debug_hook(
+        &mut self,
+        hook: Option<Box<dyn DebugHook>>,
+    ) -> Option<Box<dyn DebugHook>> {
+        std::mem::replace(&mut self.debug_hook, hook)
+    }
+
+    /// Installs a profiler to report every procedure call to, returning the previous one
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) -> Option<Profiler> {
+        std::mem::replace(&mut self.profiler, profiler)
+    }
+
+    /// The installed profiler, if any
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Calls `thunk` with `handler` installed, as done by `with-exception-handler`
+    pub(crate) fn with_exception_handler(&mut self, handler: Value, thunk: &Value) -> EvalResult {
+        let depth = self.handlers.len();
+        self.handlers.push(Handler::Procedure(handler));
+        let result = self.apply(thunk, Vec::new());
+        self.handlers.truncate(depth);
+        result
+    }
+
+    /// Passes `object` to the current exception handler, as done by `raise-continuable`, or by
+    /// `raise` if `continuable` is `false`
+    ///
+    /// The handler runs with the outer handlers installed. If it returns from a non-continuable
+    /// raise, a secondary error is raised to the outer handlers.
+    pub(crate) fn raise(&mut self, object: Value, continuable: bool) -> EvalResult {
+        let handler = match self.handlers.last() {
+            None => return Err(Unwind::Uncaught(object, self.backtrace())),
+            Some(Handler::Guard(id)) => return Err(Unwind::Escape(*id, object)),
+            Some(Handler::Procedure(handler)) => handler.clone(),
+        };
+        let current = self.handlers.pop().unwrap();
+        let mut result = self.apply(&handler, vec![object.clone()]);
+        if !continuable && result.is_ok() {
+            let mut error = RuntimeError::new(
+                ErrorKind::Raise,
+                "exception handler returned from non-continuable raise of",
+                vec![object],
+            );
+            error.position = self.call_site;
+            result = self.raise(Value::Condition(Rc::new(error)), false);
+        }
+        self.handlers.push(current);
+        result
+    }
+
+    /// Raises an error signalled by Rust code as a condition object, attributing it to the
+    /// current call if it has no position yet
+    fn signal(&mut self, mut error: RuntimeError) -> Unwind {
+        if error.position.is_none() {
+            error.position = self.call_site;
+        }
+        if error.backtrace.is_empty() {
+            error.backtrace = self.backtrace();
+        }
+        // Aborting also unwinds through builtins which call back into Scheme code, such as `map`
+        if error.kind == ErrorKind::Aborted {
+            return Unwind::Error(error);
+        }
+        match self.raise(Value::Condition(Rc::new(error)), false) {
+            Err(unwind) => unwind,
+            Ok(_) => unreachable!("non-continuable raises never return"),
+        }
+    }
+
+    /// Calls `receiver` with an escape continuation, as done by `call-with-current-continuation`
+    pub(crate) fn call_with_escape(&mut self, receiver: &Value) -> EvalResult {
+        let id = self.next_continuation;
+        self.next_continuation += 1;
+        self.active_continuations.push(id);
+        let continuation = Value::Procedure(Rc::new(Procedure::Continuation(id)));
+        let result = self.apply(receiver, vec![continuation]);
+        self.active_continuations.pop();
+        match result {
+            Err(Unwind::Escape(escaped, value)) if escaped == id => Ok(value),
+            result => result,
+        }
+    }
+
+    /// Forces a promise, memoizing its value
+    pub(crate) fn force(&mut self, promise: &Promise) -> EvalResult {
+        let (expr, env) = match &*promise.state.borrow() {
+            PromiseState::Forced(value) => return Ok(value.clone()),
+            PromiseState::Delayed(expr, env) => (expr.clone(), env.clone()),
+        };
+        let value = self.execute(&expr, &env)?;
+        // A promise may be forced again while computing its own value, in which case the value
+        // computed first wins
+        let mut state = promise.state.borrow_mut();
+        if let PromiseState::Forced(value) = &*state {
+            return Ok(value.clone());
+        }
+        *state = PromiseState::Forced(value.clone());
+        Ok(value)
+    }
+
+    /// Applies a procedure, stopping at the tail call of a closure body instead of performing it
+    ///
+    /// Errors signalled while applying a builtin are attributed to `position`, the position of
+    /// the call, or to the enclosing call if it has none.
+    fn apply_step(
+        &mut self,
+        procedure: &Value,
+        args: Vec<Value>,
+        position: Option<Position>,
+    ) -> Result<Step, Unwind> {
+        let outer = self.call_site;
+        self.call_site = position.or(outer);
+        let result = self.apply_step_at_call_site(procedure, args);
+        self.call_site = outer;
+        result
+    }
+
+    fn apply_step_at_call_site(
+        &mut self,
+        procedure: &Value,
+        args: Vec<Value>,
+    ) -> Result<Step, Unwind> {
+        let procedure = match procedure {
+            Value::Procedure(p) => p,
+            other => return Err(self.not_a_procedure(other)),
+        };
+        match &**procedure {
+            Procedure::Builtin(builtin) => {
+                if !builtin.arity.accepts(args.len()) {
+                    let error = RuntimeError::arity(builtin.name, builtin.arity, args.len());
+                    return Err(self.signal(error));
+                }
+                // `apply` calls its procedure in tail position
+                if builtin.name == "apply" {
+                    let (procedure, args) = builtins::spread_apply_arguments(args)
+                        .map_err(|error| self.signal(error))?;
+                    return Ok(Step::TailCall(procedure, args, None));
+                }
+                match (builtin.function)(self, args) {
+                    Ok(value) => Ok(Step::Value(value)),
+                    Err(Unwind::Error(error)) => Err(self.signal(error)),
+                    Err(unwind) => Err(unwind),
+                }
+            }
+            Procedure::Lambda(closure) => {
+                let env = closure.bind(args).map_err(|error| self.signal(error))?;
+                self.step_sequence(&closure.template.body, &env)
+            }
+            Procedure::Continuation(id) => {
+                if !self.active_continuations.contains(id) {
+                    return Err(self.signal(RuntimeError::new(
+                        ErrorKind::Continuation,
+                        "continuation invoked outside of its extent",
+                        Vec::new(),
+                    )));
+                }
+                Err(Unwind::Escape(*id, builtins::values_from_vec(args)))
+            }
+        }
+    }
+
+    /// Performs tail calls until a value is produced
+    ///
+    /// Tail calls return to this loop instead of recursing, which keeps the Rust stack from
+    /// growing with the number of tail calls a program makes. The first call pushes a stack
+    /// frame, which every later call replaces.
+    fn trampoline(&mut self, mut step: Step) -> EvalResult {
+        let depth = self.stack.len();
+        let result = loop {
+            match step {
+                Step::Value(value) => break Ok(value),
+                Step::TailCall(procedure, args, position) => {
+                    self.enter_frame(depth, &procedure, position);
+                    match self.apply_step(&procedure, args, position) {
+                        Ok(next) => step = next,
+                        Err(unwind) => break Err(unwind),
+                    }
+                }
+            }
+        };
+        if let (Some(profiler), true) = (&mut self.profiler, self.stack.len() > depth) {
+            profiler.exit();
+        }
+        self.stack.truncate(depth);
+        result
+    }
+
+    /// Pushes the frame of a call at `depth` of the stack, or replaces it if it is a tail call
+    fn enter_frame(&mut self, depth: usize, procedure: &Value, call_site: Option<Position>) {
+        match self.stack.get_mut(depth) {
+            Some(frame) => {
+                frame.procedure = procedure.clone();
+                frame.call_site = call_site;
+                frame.elided += 1;
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.tail_call(frame);
+                }
+            }
+            None => {
+                let frame = StackFrame {
+                    procedure: procedure.clone(),
+                    call_site,
+                    elided: 0,
+                };
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.enter(&frame);
+                }
+                self.stack.push(frame);
+            }
+        }
+    }
+
+    /// Executes an expression, performing its tail calls
+    ///
+    /// Every level of non-tail recursion of a program goes through here, so this is where the
+    /// native stack used by the evaluation is measured, to report recursion too deep for it as
+    /// an error instead of overflowing the stack.
+    fn execute(&mut self, expr: &Expr, env: &Rc<Environment>) -> EvalResult {
+        let marker = 0u8;
+        let here = &marker as *const u8 as usize;
+        match self.stack_base {
+            None => self.execute_outermost(expr, env, here),
+            Some(base) if base.abs_diff(here) > self.stack_limit => Err(self.recursion_too_deep()),
+            Some(_) => {
+                let step = self.step(expr, env)?;
+                self.trampoline(step)
+            }
+        }
+    }
+
+    /// Executes an expression outside of any other evaluation, measuring the stack from `base`
+    #[inline(never)]
+    fn execute_outermost(&mut self, expr: &Expr, env: &Rc<Environment>, base: usize) -> EvalResult {
+        self.stack_base = Some(base);
+        let result = self.step(expr, env).and_then(|step| self.trampoline(step));
+        self.stack_base = None;
+        result
+    }
+
+    // The functions reporting errors are kept out of line, so that their temporaries do not
+    // enlarge the stack frames of the functions every level of recursion goes through
+
+    #[cold]
+    #[inline(never)]
+    fn recursion_too_deep(&mut self) -> Unwind {
+        let error = RuntimeError::new(
+            ErrorKind::ImplementationRestriction,
+            "recursion too deep",
+            Vec::new(),
+        );
+        self.signal(error)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn restricted_library(&mut self) -> Unwind {
+        let error = RuntimeError::new(
+            ErrorKind::Restricted,
+            "libraries cannot be used in a restricted environment",
+            Vec::new(),
+        );
+        self.signal(error)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn unbound_variable(&mut self, name: &str, env: &Rc<Environment>) -> Unwind {
+        let mut error = RuntimeError::unbound_variable(name);
+        error.environment = Some(env.clone());
+        self.signal(error)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn not_a_procedure(&mut self, value: &Value) -> Unwind {
+        let error = RuntimeError::new(
+            ErrorKind::WrongType,
+            "attempt to apply non-procedure",
+            vec![value.clone()],
+        );
+        self.signal(error)
+    }
+
+    /// Executes every expression of a body, leaving the tail call of the last one pending
+    fn step_sequence(&mut self, body: &[Expr], env: &Rc<Environment>) -> Result<Step, Unwind> {
+        match body.split_last() {
+            Some((last, init)) => {
+                for expr in init {
+                    self.execute(expr, env)?;
+                }
+                self.step(last, env)
+            }
+            None => Ok(Step::Value(Value::Unspecified)),
+        }
+    }
+
+    /// Executes an expression, returning the call it makes in tail position instead of making it
+    ///
+    /// Each kind of expression is handled by its own function, which keeps the stack frame of
+    /// this function, entered at least once per level of non-tail recursion, small.
+    fn step(&mut self, expr: &Expr, env: &Rc<Environment>) -> Result<Step, Unwind> {
+        match expr {
+            Expr::Constant(value) => Ok(Step::Value(value.clone())),
+            Expr::Variable(name) => self.step_variable(name, env),
+            Expr::Define(name, value) => self.step_define(name, value, env),
+            Expr::Set(name, value) => self.step_set(name, value, env),
+            Expr::If(test, consequent, alternative) => {
+                self.step_if(test, consequent, alternative, env)
+            }
+            Expr::Lambda(template) => Ok(Step::Value(closure(template, env))),
+            Expr::Begin(body) => self.step_sequence(body, env),
+            Expr::Call(operator, operands, position) => {
+                self.step_call(operator, operands, *position, env)
+            }
+            Expr::And(exprs) => self.step_connective(exprs, true, env),
+            Expr::Or(exprs) => self.step_connective(exprs, false, env),
+            Expr::Cond(clauses) => Ok(self
+                .step_cond(clauses, env)?
+                .unwrap_or(Step::Value(Value::Unspecified))),
+            Expr::Case(key, clauses) => self.step_case(key, clauses, env),
+            Expr::Delay(expr) => Ok(Step::Value(promise(expr, env))),
+            Expr::Guard(var, clauses, body) => self.step_guard(var, clauses, body, env),
+            Expr::Import(..) | Expr::DefineLibrary(_) if env.is_restricted() => {
+                Err(self.restricted_library())
+            }
+            Expr::Import(sets, position) => self
+                .import(sets, env, *position)
+                .map(|()| Step::Value(Value::Unspecified)),
+            Expr::DefineLibrary(definition) => self
+                .define_library(definition)
+                .map(|()| Step::Value(Value::Unspecified)),
+        }
+    }
+
+    fn step_variable(&mut self, name: &str, env: &Rc<Environment>) -> Result<Step, Unwind> {
+        match env.lookup(name) {
+            Some(value) => Ok(Step::Value(value)),
+            None => Err(self.unbound_variable(name, env)),
+        }
+    }
+
+    fn step_define(
+        &mut self,
+        name: &Rc<str>,
+        value: &Expr,
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        let value = self.execute(value, env)?;
+        env.define(name.clone(), value);
+        Ok(Step::Value(Value::Unspecified))
+    }
+
+    fn step_set(
+        &mut self,
+        name: &str,
+        value: &Expr,
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        let value = self.execute(value, env)?;
+        if !env.set(name, value) {
+            return Err(self.unbound_variable(name, env));
+        }
+        Ok(Step::Value(Value::Unspecified))
+    }
+
+    fn step_if(
+        &mut self,
+        test: &Expr,
+        consequent: &Expr,
+        alternative: &Expr,
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        if self.execute(test, env)?.is_true() {
+            self.step(consequent, env)
+        } else {
+            self.step(alternative, env)
+        }
+    }
+
+    /// Evaluates the operator and operands of a call, attributing errors to the call's position
+    fn step_call(
+        &mut self,
+        operator: &Expr,
+        operands: &[Expr],
+        position: Option<Position>,
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        if let (Some(position), true) = (position, self.debug_hook.is_some()) {
+            self.run_debug_hook(position, env)?;
+        }
+        let outer = self.call_site;
+        self.call_site = position.or(outer);
+        let evaluated = self.evaluate_call(operator, operands, env);
+        self.call_site = outer;
+        let (procedure, args) = evaluated?;
+        Ok(Step::TailCall(procedure, args, position))
+    }
+
+    /// Runs the debug hook with the hook itself and the exception handlers of the program
+    /// uninstalled
+    fn run_debug_hook(&mut self, position: Position, env: &Rc<Environment>) -> Result<(), Unwind> {
+        let mut hook = self.debug_hook.take().unwrap();
+        let handlers = std::mem::take(&mut self.handlers);
+        let depth = self.stack.len();
+        let result = hook.before_call(self, position, depth, env);
+        self.handlers = handlers;
+        self.debug_hook = Some(hook);
+        result.map_err(Unwind::Error)
+    }
+
+    /// Executes the operands of `and` (when `conjunction` is set) or `or`, stopping at the first
+    /// false or true value respectively
+    fn step_connective(
+        &mut self,
+        exprs: &[Expr],
+        conjunction: bool,
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        match exprs.split_last() {
+            Some((last, init)) => {
+                for expr in init {
+                    let result = self.execute(expr, env)?;
+                    if result.is_true() != conjunction {
+                        return Ok(Step::Value(result));
+                    }
+                }
+                self.step(last, env)
+            }
+            None => Ok(Step::Value(Value::Boolean(conjunction))),
+        }
+    }
+
+    fn step_case(
+        &mut self,
+        key: &Expr,
+        clauses: &[syntax::CaseClause],
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        let key = self.execute(key, env)?;
+        for clause in clauses {
+            let matches = match &clause.data {
+                Some(data) => data.iter().any(|datum| datum.is_eqv(&key)),
+                None => true,
+            };
+            if matches {
+                return self.step_sequence(&clause.body, env);
+            }
+        }
+        Ok(Step::Value(Value::Unspecified))
+    }
+
+    /// Executes the body of a `guard` expression with a handler catching raised objects, stepping
+    /// into the matching clause if one is raised
+    fn step_guard(
+        &mut self,
+        var: &Rc<str>,
+        clauses: &[syntax::CondClause],
+        body: &[Expr],
+        env: &Rc<Environment>,
+    ) -> Result<Step, Unwind> {
+        let id = self.next_continuation;
+        self.next_continuation += 1;
+        let depth = self.handlers.len();
+        self.handlers.push(Handler::Guard(id));
+        let result = self
+            .step_sequence(body, env)
+            .and_then(|step| self.trampoline(step));
+        self.handlers.truncate(depth);
+        match result {
+            Err(Unwind::Escape(escaped, object)) if escaped == id => {
+                let mut frame = HashMap::new();
+                frame.insert(var.clone(), object.clone());
+                let env = Environment::extend(env, frame);
+                // With no matching clause, the object is raised again to the outer handlers
+                match self.step_cond(clauses, &env)? {
+                    Some(step) => Ok(step),
+                    None => self.raise(object, true).map(Step::Value),
+                }
+            }
+            result => result.map(Step::Value),
+        }
+    }
+
+    /// Evaluates the operator and operands of a call
+    fn evaluate_call(
+        &mut self,
+        operator: &Expr,
+        operands: &[Expr],
+        env: &Rc<Environment>,
+    ) -> Result<(Value, Vec<Value>), Unwind> {
+        let procedure = self.execute(operator, env)?;
+        let mut args = Vec::with_capacity(operands.len());
+        for operand in operands {
+            args.push(self.execute(operand, env)?);
+        }
+        Ok((procedure, args))
+    }
+
+    /// Steps into the first `cond` clause whose test succeeds, returning `None` if there is none
+    fn step_cond(
+        &mut self,
+        clauses: &[syntax::CondClause],
+        env: &Rc<Environment>,
+    ) -> Result<Option<Step>, Unwind> {
+        for clause in clauses {
+            let test = self.execute(&clause.test, env)?;
+            if !test.is_true() {
+                continue;
+            }
+            return match &clause.body {
+                syntax::CondBody::Test => Ok(Some(Step::Value(test))),
+                syntax::CondBody::Sequence(body) => self.step_sequence(body, env).map(Some),
+                syntax::CondBody::Receiver(receiver) => {
+                    let receiver = self.execute(receiver, env)?;
+                    Ok(Some(Step::TailCall(receiver, vec![test], None)))
+                }
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// The procedure a `lambda` expression evaluates to in `env`
+fn closure(template: &Rc<LambdaTemplate>, env: &Rc<Environment>) -> Value {
+    Value::Procedure(Rc::new(Procedure::Lambda(Closure {
+        template: template.clone(),
+        env: env.clone(),
+    })))
+}
+
+/// The promise a `delay` expression evaluates to in `env`
+fn promise(expr: &Rc<Expr>, env: &Rc<Environment>) -> Value {
+    Value::Promise(Rc::new(Promise {
+        state: RefCell::new(PromiseState::Delayed(expr.clone(), env.clone())),
+    }))
+}
+
+/// Outcome of executing an expression up to, but not including, its call in tail position
+enum Step {
+    /// The expression produced a value without a tail call
+    Value(Value),
+    /// The procedure to call in tail position, with its evaluated arguments and the position of
+    /// the call
+    TailCall(Value, Vec<Value>, Option<Position>),
+}
+
+#[cfg(test)]
+mod test {
+    use super::ErrorKind;
+    use crate::builtins::test::{eval_error, eval_str, eval_to_string};
+    #[test]
+    fn special_forms_test() {
+        assert_eq!(eval_to_string("(if #f 1 2)"), "2");
+        assert_eq!(eval_to_string("(define x 1) (set! x (+ x 1)) x"), "2");
+        assert_eq!(eval_to_string("(let ((x 1) (y 2)) (+ x y))"), "3");
+        assert_eq!(eval_to_string("(let* ((x 1) (y (+ x 1))) y)"), "2");
+        assert_eq!(
+            eval_to_string("(letrec ((even? (lambda (n) (if (= n 0) #t (odd? (- n 1))))) (odd? (lambda (n) (if (= n 0) #f (even? (- n 1)))))) (even? 10))"),
+            "#t"
+        );
+        assert_eq!(eval_to_string("(and 1 2 #f 3)"), "#f");
+        assert_eq!(eval_to_string("(or #f 2 3)"), "2");
+        assert_eq!(
+            eval_to_string("(cond ((assv 2 '((1 a) (2 b))) => cadr) (else 'c))"),
+            "b"
+        );
+        assert_eq!(
+            eval_to_string("(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))"),
+            "composite"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(do ((vec (make-vector 5)) (i 0 (+ i 1))) ((= i 5) vec) (vector-set! vec i i))"
+            ),
+            "#(0 1 2 3 4)"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(let loop ((i 0) (acc '())) (if (= i 3) acc (loop (+ i 1) (cons i acc))))"
+            ),
+            "(2 1 0)"
+        );
+    }
+
+    #[test]
+    fn quasiquote_test() {
+        assert_eq!(eval_to_string("`(list ,(+ 1 2) 4)"), "(list 3 4)");
+        assert_eq!(
+            eval_to_string("(let ((name 'a)) `(list ,name ',name))"),
+            "(list a (quote a))"
+        );
+        assert_eq!(
+            eval_to_string("`(a ,(+ 1 2) ,@(map abs '(4 -5 6)) b)"),
+            "(a 3 4 5 6 b)"
+        );
+        assert_eq!(
+            eval_to_string("`#(10 5 ,(sqrt 4) ,@(map sqrt '(16 9)) 8)"),
+            "#(10 5 2 4 3 8)"
+        );
+        assert_eq!(
+            eval_to_string("`(a `(b ,(+ 1 2) ,(foo ,(+ 1 3) d) e) f)"),
+            "(a (quasiquote (b (unquote (+ 1 2)) (unquote (foo 4 d)) e)) f)"
+        );
+    }
+
+    #[test]
+    fn closures_and_internal_defines_test() {
+        assert_eq!(
+            eval_to_string("(define (make-counter) (define n 0) (lambda () (set! n (+ n 1)) n)) (define c (make-counter)) (c) (c)"),
+            "2"
+        );
+        assert_eq!(eval_to_string("((lambda (a . rest) rest) 1 2 3)"), "(2 3)");
+        assert_eq!(eval_to_string("((lambda args args))"), "()");
+    }
+
+    #[test]
+    fn tail_calls_test() {
+        assert_eq!(
+            eval_to_string("(let loop ((i 0)) (if (< i 1000000) (loop (+ i 1)) i))"),
+            "1000000"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(define (count n)
+                   (cond ((= n 0) 'done)
+                         ((odd? n) (case (remainder n 4)
+                                     ((1) (and #t (count (- n 1))))
+                                     (else (or #f (count (- n 1))))))
+                         (else (begin (apply count (list (- n 1)))))))
+                 (count 10000)"
+            ),
+            "done"
+        );
+        assert_eq!(
+            eval_to_string(
+                "(define (down n) (cond ((= n 0) 'done) ((- n 1) => down)))
+                 (down 10000)"
+            ),
+            "done"
+        );
+        assert_eq!(
+            eval_to_string("(do ((i 0 (+ i 1))) ((= i 10000) i))"),
+            "10000"
+        );
+    }
+
+    #[test]
+    fn recursion_depth_test() {
+        let program = "(define (f n) (if (= n 0) 0 (+ 1 (f (- n 1)))))";
+        let error = eval_str(&format!("{} (f 100000000)", program)).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::ImplementationRestriction);
+        assert_eq!(error.to_string(), "recursion too deep");
+        assert_eq!(
+            eval_to_string(&format!(
+                "{} (list (guard (e (#t (error-object-message e))) (f 100000000)) (f 10))",
+                program
+            )),
+            "(\"recursion too deep\" 10)"
+        );
+    }
+
+    #[test]
+    fn runtime_errors_test() {
+        let error = eval_str("undefined-variable").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnboundVariable);
+        assert_eq!(error.to_string(), "unbound variable undefined-variable");
+        assert_eq!(eval_error("((lambda (x) x))"), ErrorKind::Arity);
+        assert_eq!(eval_error("(1 2)"), ErrorKind::WrongType);
+        assert_eq!(eval_error("(set! y 1)"), ErrorKind::UnboundVariable);
+        assert_eq!(eval_error("(if)"), ErrorKind::Syntax);
+    }
+
+    #[test]
+    fn backtrace_test() {
+        let error =
+            eval_str("(define (g x) (car x))\n(define (f x) (+ 1 (g x)))\n(f 5)").unwrap_err();
+        let frames: Vec<_> = error
+            .backtrace
+            .iter()
+            .map(|frame| {
+                let call_site = frame.call_site.unwrap();
+                (
+                    frame.name().unwrap(),
+                    call_site.line,
+                    call_site.column,
+                    frame.elided,
+                )
+            })
+            .collect();
+        // The frame of `g` was replaced by the tail call to `car`
+        assert_eq!(frames, vec![("car", 1, 14, 1), ("f", 3, 0, 0)]);
+        assert_eq!(
+            error.backtrace[0].to_string(),
+            "car, called at line 1, column 14"
+        );
+
+        let error =
+            eval_str("(define (loop n) (if (= n 0) (raise 'done) (loop (- n 1))))\n(loop 3)")
+                .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Raise);
+        assert_eq!(error.backtrace.len(), 1);
+        assert_eq!(error.backtrace[0].name(), Some("raise"));
+        assert_eq!(error.backtrace[0].elided, 4);
+
+        let error = eval_str("(list ((lambda () (vector-ref (vector) 0))))").unwrap_err();
+        assert_eq!(error.backtrace.len(), 1);
+        assert_eq!(
+            error.backtrace[0].to_string(),
+            "vector-ref, called at line 1, column 18"
+        );
+    }
+}
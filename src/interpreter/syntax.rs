@@ -0,0 +1,689 @@
+//! Analysis of Scheme forms into `Expr` trees
+//!
+//! Special forms are recognised by the symbol in operator position. Derived forms such as
+//! `let`, `let*`, `letrec`, named `let` and `do` are rewritten into simpler forms before being
+//! analysed, and `quasiquote` templates are turned into calls to the list construction builtins.
+//! Calls remember the position of the pair they were compiled from, so that errors can point at
+//! the failing call.
+use super::library::{self, ImportSet, LibraryDefinition};
+use super::RuntimeError;
+use crate::builtins;
+use crate::interpreter::Procedure;
+use crate::parser::Position;
+use crate::value::Value;
+use std::rc::Rc;
+
+/// An analysed expression, ready to be executed by the interpreter
+pub(crate) enum Expr {
+    /// A self-evaluating or quoted value
+    Constant(Value),
+    /// A variable reference
+    Variable(Rc<str>),
+    /// A definition in the current frame
+    Define(Rc<str>, Box<Expr>),
+    /// An assignment to an existing binding
+    Set(Rc<str>, Box<Expr>),
+    /// A conditional; a missing alternative is compiled to an unspecified constant
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// A `lambda` expression
+    Lambda(Rc<LambdaTemplate>),
+    /// A sequence of expressions
+    Begin(Vec<Expr>),
+    /// A procedure call, with the position of the call in the source if it was read from a file
+    Call(Box<Expr>, Vec<Expr>, Option<Position>),
+    /// Short-circuiting conjunction
+    And(Vec<Expr>),
+    /// Short-circuiting disjunction
+    Or(Vec<Expr>),
+    /// A `cond` expression
+    Cond(Vec<CondClause>),
+    /// A `case` expression
+    Case(Box<Expr>, Vec<CaseClause>),
+    /// A `delay` expression
+    Delay(Rc<Expr>),
+    /// A `guard` expression: the variable bound to the raised object, the clauses handling it,
+    /// and the body
+    Guard(Rc<str>, Vec<CondClause>, Vec<Expr>),
+    /// An `import` form, importing bindings into the current environment, with the position of
+    /// the form
+    Import(Vec<ImportSet>, Option<Position>),
+    /// A `define-library` form
+    DefineLibrary(Box<LibraryDefinition>),
+}
+
+/// The parameter list and body of a `lambda` expression
+pub(crate) struct LambdaTemplate {
+    pub(crate) name: Option<Rc<str>>,
+    /// Where the form defining the procedure was read from
+    pub(crate) position: Option<Position>,
+    /// The keyword of the form creating the procedure, `let` for the body of a `let`
+    pub(crate) keyword: &'static str,
+    pub(crate) params: Vec<Rc<str>>,
+    pub(crate) rest: Option<Rc<str>>,
+    pub(crate) body: Vec<Expr>,
+}
+
+/// A single clause of a `cond` expression
+pub(crate) struct CondClause {
+    pub(crate) test: Expr,
+    pub(crate) body: CondBody,
+}
+
+/// What a `cond` clause does once its test succeeds
+pub(crate) enum CondBody {
+    /// `(test)`: the value of the test is the result
+    Test,
+    /// `(test expression ...)`
+    Sequence(Vec<Expr>),
+    /// `(test => receiver)`
+    Receiver(Expr),
+}
+
+/// A single clause of a `case` expression; `data` is `None` for the `else` clause
+pub(crate) struct CaseClause {
+    pub(crate) data: Option<Vec<Value>>,
+    pub(crate) body: Vec<Expr>,
+}
+
+/// A keyword introducing a special form, with its description for tools such as the language
+/// server
+pub struct SpecialForm {
+    /// The keyword
+    pub name: &'static str,
+    /// The rest of the form, in the notation of `Builtin::params`
+    pub syntax: &'static str,
+    /// A one-sentence description
+    pub doc: &'static str,
+}
+
+/// Every special form, including `include`, which the loader expands before forms are analysed
+pub const SPECIAL_FORMS: &[SpecialForm] = &[
+    SpecialForm {
+        name: "quote",
+        syntax: "datum",
+        doc: "Returns datum without evaluating it; 'datum is an abbreviation.",
+    },
+    SpecialForm {
+        name: "quasiquote",
+        syntax: "template",
+        doc: "Builds a list structure from template, evaluating the parts marked by unquote and unquote-splicing; `template is an abbreviation.",
+    },
+    SpecialForm {
+        name: "if",
+        syntax: "test consequent [alternate]",
+        doc: "Evaluates consequent if test is true, and alternate otherwise.",
+    },
+    SpecialForm {
+        name: "define",
+        syntax: "variable expression",
+        doc: "Binds variable to the value of expression; (define (variable formals ...) body ...) defines a procedure.",
+    },
+    SpecialForm {
+        name: "set!",
+        syntax: "variable expression",
+        doc: "Stores the value of expression in the existing binding of variable.",
+    },
+    SpecialForm {
+        name: "lambda",
+        syntax: "formals body ...",
+        doc: "Returns a procedure taking the arguments named by formals and evaluating body.",
+    },
+    SpecialForm {
+        name: "begin",
+        syntax: "expression ...",
+        doc: "Evaluates the expressions in order, returning the value of the last one.",
+    },
+    SpecialForm {
+        name: "let",
+        syntax: "bindings body ...",
+        doc: "Evaluates body with the variables of bindings bound to the values of their initialisers; a name before the bindings names a loop.",
+    },
+    SpecialForm {
+        name: "let*",
+        syntax: "bindings body ...",
+        doc: "Like let, but each binding is visible to the initialisers following it.",
+    },
+    SpecialForm {
+        name: "letrec",
+        syntax: "bindings body ...",
+        doc: "Like let, but the bindings are visible to every initialiser, for mutually recursive procedures.",
+    },
+    SpecialForm {
+        name: "do",
+        syntax: "specs (test expression ...) command ...",
+        doc: "Loops, updating the variables of specs with their steps, until test is true.",
+    },
+    SpecialForm {
+        name: "and",
+        syntax: "test ...",
+        doc: "Returns the value of the first false test, or of the last one; #t if there are none.",
+    },
+    SpecialForm {
+        name: "or",
+        syntax: "test ...",
+        doc: "Returns the value of the first true test, or #f.",
+    },
+    SpecialForm {
+        name: "cond",
+        syntax: "clause1 clause2 ...",
+        doc: "Evaluates the body of the first clause whose test is true.",
+    },
+    SpecialForm {
+        name: "case",
+        syntax: "key clause1 clause2 ...",
+        doc: "Evaluates the body of the first clause whose data contain the value of key, compared with eqv?.",
+    },
+    SpecialForm {
+        name: "guard",
+        syntax: "(variable clause1 ...) body ...",
+        doc: "Evaluates body, handling the objects it raises with the clauses, with the object bound to variable.",
+    },
+    SpecialForm {
+        name: "import",
+        syntax: "import-set ...",
+        doc: "Imports the bindings exported by libraries.",
+    },
+    SpecialForm {
+        name: "define-library",
+        syntax: "library-name declaration ...",
+        doc: "Defines a library, with its exports, imports and body.",
+    },
+    SpecialForm {
+        name: "delay",
+        syntax: "expression",
+        doc: "Returns a promise that evaluates expression when forced for the first time.",
+    },
+    SpecialForm {
+        name: "include",
+        syntax: "filename1 filename2 ...",
+        doc: "Replaced by the data read from the files, before the form is analysed.",
+    },
+];
+
+/// Keywords that are not special forms of the interpreter, but that the resolver knows about or
+/// that are recognised within special forms
+const OTHER_KEYWORDS: &[&str] = &[
+    "define-syntax",
+    "let-syntax",
+    "letrec-syntax",
+    "letrec*",
+    "syntax-rules",
+    "else",
+    "=>",
+];
+
+/// Whether a name is the keyword of a special form, or another keyword the resolver knows about
+/// or that is recognised within special forms
+pub fn is_keyword(name: &str) -> bool {
+    SPECIAL_FORMS.iter().any(|form| form.name == name) || OTHER_KEYWORDS.contains(&name)
+}
+
+/// Analyses a form into an `Expr`
+pub(crate) fn compile(form: &Value) -> Result<Expr, RuntimeError> {
+    match form {
+        Value::Symbol(name) => Ok(Expr::Variable(name.clone())),
+        Value::Pair(pair) => {
+            if let Value::Symbol(keyword) = pair.car() {
+                if let Some(expr) = compile_special_form(&keyword, form)? {
+                    return Ok(expr);
+                }
+            }
+            let items = list_items(form, "bad procedure call")?;
+            let operator = compile(&items[0])?;
+            let operands = compile_all(&items[1..])?;
+            Ok(Expr::Call(Box::new(operator), operands, pair.position()))
+        }
+        Value::Null => Err(RuntimeError::syntax("empty combination", form)),
+        other => Ok(Expr::Constant(other.clone())),
+    }
+}
+
+fn compile_all(forms: &[Value]) -> Result<Vec<Expr>, RuntimeError> {
+    forms.iter().map(compile).collect()
+}
+
+fn list_items(form: &Value, message: &str) -> Result<Vec<Value>, RuntimeError> {
+    form.list_to_vec()
+        .ok_or_else(|| RuntimeError::syntax(message, form))
+}
+
+fn symbol_name(value: &Value, form: &Value) -> Result<Rc<str>, RuntimeError> {
+    match value {
+        Value::Symbol(name) => Ok(name.clone()),
+        _ => Err(RuntimeError::syntax("expected an identifier in", form)),
+    }
+}
+
+fn compile_special_form(keyword: &str, form: &Value) -> Result<Option<Expr>, RuntimeError> {
+    let bad_syntax = || RuntimeError::syntax(&format!("bad {} syntax", keyword), form);
+    let expr = match keyword {
+        "quote" => match list_items(form, "bad quote syntax")?.as_slice() {
+            [_, datum] => Expr::Constant(datum.clone()),
+            _ => return Err(bad_syntax()),
+        },
+        "quasiquote" => match list_items(form, "bad quasiquote syntax")?.as_slice() {
+            [_, template] => compile_quasiquote(template, 1)?,
+            _ => return Err(bad_syntax()),
+        },
+        "if" => match list_items(form, "bad if syntax")?.as_slice() {
+            [_, test, consequent] => Expr::If(
+                Box::new(compile(test)?),
+                Box::new(compile(consequent)?),
+                Box::new(Expr::Constant(Value::Unspecified)),
+            ),
+            [_, test, consequent, alternative] => Expr::If(
+                Box::new(compile(test)?),
+                Box::new(compile(consequent)?),
+                Box::new(compile(alternative)?),
+            ),
+            _ => return Err(bad_syntax()),
+        },
+        "define" => compile_define(form)?,
+        "set!" => match list_items(form, "bad set! syntax")?.as_slice() {
+            [_, name, value] => Expr::Set(symbol_name(name, form)?, Box::new(compile(value)?)),
+            _ => return Err(bad_syntax()),
+        },
+        "lambda" => {
+            let items = list_items(form, "bad lambda syntax")?;
+            if items.len() < 3 {
+                return Err(bad_syntax());
+            }
+            Expr::Lambda(Rc::new(compile_lambda(None, &items[1], &items[2..], form)?))
+        }
+        "begin" => Expr::Begin(compile_all(&list_items(form, "bad begin syntax")?[1..])?),
+        "let" => mark_body(compile(&expand_let(form)?)?, "let", form),
+        "let*" => mark_body(compile(&expand_let_star(form)?)?, "let*", form),
+        "letrec" => mark_body(compile(&expand_letrec(form)?)?, "letrec", form),
+        "do" => compile(&expand_do(form)?)?,
+        "and" => Expr::And(compile_all(&list_items(form, "bad and syntax")?[1..])?),
+        "or" => Expr::Or(compile_all(&list_items(form, "bad or syntax")?[1..])?),
+        "cond" => compile_cond(form)?,
+        "case" => compile_case(form)?,
+        "guard" => compile_guard(form)?,
+        "import" => Expr::Import(library::compile_import(form)?, library::position(form)),
+        "define-library" => Expr::DefineLibrary(Box::new(library::compile_define_library(form)?)),
+        "delay" => match list_items(form, "bad delay syntax")?.as_slice() {
+            [_, expr] => Expr::Delay(Rc::new(compile(expr)?)),
+            _ => return Err(bad_syntax()),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(expr))
+}
+
+fn compile_define(form: &Value) -> Result<Expr, RuntimeError> {
+    let items = list_items(form, "bad define syntax")?;
+    match items.as_slice() {
+        [_, Value::Symbol(name), value] => {
+            let value = match compile(value)? {
+                Expr::Lambda(template) => Expr::Lambda(rename(template, name)),
+                expr => expr,
+            };
+            Ok(Expr::Define(name.clone(), Box::new(value)))
+        }
+        // (define (name . formals) body ...)
+        [_, Value::Pair(signature), _, ..] => {
+            let name = symbol_name(&signature.car(), form)?;
+            let template = compile_lambda(Some(name.clone()), &signature.cdr(), &items[2..], form)?;
+            Ok(Expr::Define(
+                name,
+                Box::new(Expr::Lambda(Rc::new(template))),
+            ))
+        }
+        _ => Err(RuntimeError::syntax("bad define syntax", form)),
+    }
+}
+
+fn rename(template: Rc<LambdaTemplate>, name: &Rc<str>) -> Rc<LambdaTemplate> {
+    match Rc::try_unwrap(template) {
+        Ok(mut template) => {
+            template.name.get_or_insert_with(|| name.clone());
+            Rc::new(template)
+        }
+        Err(template) => template,
+    }
+}
+
+fn compile_lambda(
+    name: Option<Rc<str>>,
+    formals: &Value,
+    body: &[Value],
+    form: &Value,
+) -> Result<LambdaTemplate, RuntimeError> {
+    let mut params = Vec::new();
+    let mut current = formals.clone();
+    let rest = loop {
+        match current {
+            Value::Null => break None,
+            Value::Symbol(rest) => break Some(rest),
+            Value::Pair(pair) => {
+                let param = symbol_name(&pair.car(), form)?;
+                if params.contains(&param) {
+                    return Err(RuntimeError::syntax("duplicate parameter in", form));
+                }
+                params.push(param);
+                current = pair.cdr();
+            }
+            _ => return Err(RuntimeError::syntax("bad parameter list in", form)),
+        }
+    };
+    if body.is_empty() {
+        return Err(RuntimeError::syntax("empty body in", form));
+    }
+    Ok(LambdaTemplate {
+        name,
+        position: form_position(form),
+        keyword: "lambda",
+        params,
+        rest,
+        body: compile_all(body)?,
+    })
+}
+
+/// Where a form was read from, if it was read from source code
+fn form_position(form: &Value) -> Option<Position> {
+    match form {
+        Value::Pair(pair) => pair.position(),
+        _ => None,
+    }
+}
+
+/// Marks the anonymous procedure called by the expansion of a `let`-like form as created by
+/// `form`, so frames of its body are told apart from those of plain lambdas
+fn mark_body(mut expr: Expr, keyword: &'static str, form: &Value) -> Expr {
+    if let Expr::Call(operator, _, _) = &mut expr {
+        if let Expr::Lambda(template) = &mut **operator {
+            if let Some(template) = Rc::get_mut(template).filter(|t| t.name.is_none()) {
+                template.keyword = keyword;
+                template.position = form_position(form);
+            }
+        }
+    }
+    expr
+}
+
+/// Splits `((name init) ...)` into names and inits
+fn bindings(list: &Value, form: &Value) -> Result<(Vec<Value>, Vec<Value>), RuntimeError> {
+    let mut names = Vec::new();
+    let mut inits = Vec::new();
+    for binding in list_items(list, "bad bindings in")? {
+        match binding.list_to_vec().as_deref() {
+            Some([name @ Value::Symbol(_), init]) => {
+                names.push(name.clone());
+                inits.push(init.clone());
+            }
+            _ => return Err(RuntimeError::syntax("bad binding in", form)),
+        }
+    }
+    Ok((names, inits))
+}
+
+fn keyword_form(keyword: &str, rest: Vec<Value>) -> Value {
+    Value::list_with_tail(vec![Value::symbol(keyword)], Value::list(rest))
+}
+
+/// `(let ((name init) ...) body ...)` becomes `((lambda (name ...) body ...) init ...)`, and the
+/// named `(let tag ((name init) ...) body ...)` becomes
+/// `((letrec ((tag (lambda (name ...) body ...))) tag) init ...)`
+fn expand_let(form: &Value) -> Result<Value, RuntimeError> {
+    let items = list_items(form, "bad let syntax")?;
+    match items.as_slice() {
+        [_, tag @ Value::Symbol(_), bindings_list, body @ ..] if !body.is_empty() => {
+            let (names, inits) = bindings(bindings_list, form)?;
+            let mut lambda = vec![Value::list(names)];
+            lambda.extend_from_slice(body);
+            let procedure = Value::list(vec![tag.clone(), keyword_form("lambda", lambda)]);
+            let letrec = keyword_form("letrec", vec![Value::list(vec![procedure]), tag.clone()]);
+            Ok(Value::list_with_tail(vec![letrec], Value::list(inits)))
+        }
+        [_, bindings_list, body @ ..] if !body.is_empty() => {
+            let (names, inits) = bindings(bindings_list, form)?;
+            let mut lambda = vec![Value::list(names)];
+            lambda.extend_from_slice(body);
+            Ok(Value::list_with_tail(
+                vec![keyword_form("lambda", lambda)],
+                Value::list(inits),
+            ))
+        }
+        _ => Err(RuntimeError::syntax("bad let syntax", form)),
+    }
+}
+
+/// `(let* (binding ...) body ...)` becomes nested `let`s
+fn expand_let_star(form: &Value) -> Result<Value, RuntimeError> {
+    let items = list_items(form, "bad let* syntax")?;
+    if items.len() < 3 {
+        return Err(RuntimeError::syntax("bad let* syntax", form));
+    }
+    let mut bindings_list = list_items(&items[1], "bad let* syntax")?;
+    let last = bindings_list.pop();
+    let mut innermost = vec![Value::list(last.into_iter().collect::<Vec<_>>())];
+    innermost.extend_from_slice(&items[2..]);
+    Ok(bindings_list
+        .into_iter()
+        .rev()
+        .fold(keyword_form("let", innermost), |inner, binding| {
+            keyword_form("let", vec![Value::list(vec![binding]), inner])
+        }))
+}
+
+/// `(letrec ((name init) ...) body ...)` becomes
+/// `((lambda () (define name init) ... (let () body ...))))`
+fn expand_letrec(form: &Value) -> Result<Value, RuntimeError> {
+    let items = list_items(form, "bad letrec syntax")?;
+    if items.len() < 3 {
+        return Err(RuntimeError::syntax("bad letrec syntax", form));
+    }
+    let (names, inits) = bindings(&items[1], form)?;
+    let mut body = vec![Value::Null];
+    for (name, init) in names.into_iter().zip(inits) {
+        body.push(keyword_form("define", vec![name, init]));
+    }
+    let mut inner = vec![Value::Null];
+    inner.extend_from_slice(&items[2..]);
+    body.push(keyword_form("let", inner));
+    Ok(Value::list(vec![keyword_form("lambda", body)]))
+}
+
+/// `(do ((var init step) ...) (test expr ...) command ...)` becomes a named `let` that loops
+/// until `test` is true
+fn expand_do(form: &Value) -> Result<Value, RuntimeError> {
+    let items = list_items(form, "bad do syntax")?;
+    if items.len() < 3 {
+        return Err(RuntimeError::syntax("bad do syntax", form));
+    }
+    let mut bindings = Vec::new();
+    let mut steps = Vec::new();
+    for spec in list_items(&items[1], "bad do syntax")? {
+        match spec.list_to_vec().as_deref() {
+            Some([var @ Value::Symbol(_), init]) => {
+                bindings.push(Value::list(vec![var.clone(), init.clone()]));
+                steps.push(var.clone());
+            }
+            Some([var @ Value::Symbol(_), init, step]) => {
+                bindings.push(Value::list(vec![var.clone(), init.clone()]));
+                steps.push(step.clone());
+            }
+            _ => return Err(RuntimeError::syntax("bad do syntax", form)),
+        }
+    }
+    let exit = list_items(&items[2], "bad do syntax")?;
+    if exit.is_empty() {
+        return Err(RuntimeError::syntax("bad do syntax", form));
+    }
+    // The loop variable can't clash with user code, since the lexer never produces identifiers
+    // containing spaces
+    let tag = Value::symbol("do loop");
+    let mut result = vec![];
+    result.extend_from_slice(&exit[1..]);
+    let mut commands = items[3..].to_vec();
+    commands.push(Value::list_with_tail(vec![tag.clone()], Value::list(steps)));
+    let body = keyword_form(
+        "if",
+        vec![
+            exit[0].clone(),
+            keyword_form("begin", result),
+            keyword_form("begin", commands),
+        ],
+    );
+    Ok(keyword_form("let", vec![tag, Value::list(bindings), body]))
+}
+
+fn compile_cond(form: &Value) -> Result<Expr, RuntimeError> {
+    let items = list_items(form, "bad cond syntax")?;
+    Ok(Expr::Cond(compile_cond_clauses(&items[1..], form)?))
+}
+
+fn compile_cond_clauses(items: &[Value], form: &Value) -> Result<Vec<CondClause>, RuntimeError> {
+    let mut clauses = Vec::new();
+    for (i, clause) in items.iter().enumerate() {
+        let clause_items = match clause.list_to_vec() {
+            Some(clause_items) if !clause_items.is_empty() => clause_items,
+            _ => return Err(RuntimeError::syntax("bad cond clause", clause)),
+        };
+        if clause_items[0].is_symbol("else") {
+            if i + 1 != items.len() || clause_items.len() < 2 {
+                return Err(RuntimeError::syntax("bad else clause in", form));
+            }
+            clauses.push(CondClause {
+                test: Expr::Constant(Value::Boolean(true)),
+                body: CondBody::Sequence(compile_all(&clause_items[1..])?),
+            });
+            continue;
+        }
+        let test = compile(&clause_items[0])?;
+        let body = match &clause_items[1..] {
+            [] => CondBody::Test,
+            [arrow, receiver] if arrow.is_symbol("=>") => CondBody::Receiver(compile(receiver)?),
+            body => CondBody::Sequence(compile_all(body)?),
+        };
+        clauses.push(CondClause { test, body });
+    }
+    Ok(clauses)
+}
+
+/// `(guard (var clause ...) body ...)`, where the clauses are those of `cond`
+fn compile_guard(form: &Value) -> Result<Expr, RuntimeError> {
+    let items = list_items(form, "bad guard syntax")?;
+    if items.len() < 3 {
+        return Err(RuntimeError::syntax("bad guard syntax", form));
+    }
+    let spec = list_items(&items[1], "bad guard syntax")?;
+    let var = match spec.first() {
+        Some(var) => symbol_name(var, form)?,
+        None => return Err(RuntimeError::syntax("bad guard syntax", form)),
+    };
+    Ok(Expr::Guard(
+        var,
+        compile_cond_clauses(&spec[1..], form)?,
+        compile_all(&items[2..])?,
+    ))
+}
+
+fn compile_case(form: &Value) -> Result<Expr, RuntimeError> {
+    let items = list_items(form, "bad case syntax")?;
+    if items.len() < 2 {
+        return Err(RuntimeError::syntax("bad case syntax", form));
+    }
+    let key = compile(&items[1])?;
+    let mut clauses = Vec::new();
+    for (i, clause) in items[2..].iter().enumerate() {
+        let clause_items = match clause.list_to_vec() {
+            Some(clause_items) if clause_items.len() >= 2 => clause_items,
+            _ => return Err(RuntimeError::syntax("bad case clause", clause)),
+        };
+        let data = if clause_items[0].is_symbol("else") {
+            if i + 3 != items.len() {
+                return Err(RuntimeError::syntax("bad else clause in", form));
+            }
+            None
+        } else {
+            Some(list_items(&clause_items[0], "bad case clause")?)
+        };
+        clauses.push(CaseClause {
+            data,
+            body: compile_all(&clause_items[1..])?,
+        });
+    }
+    Ok(Expr::Case(Box::new(key), clauses))
+}
+
+fn builtin_call(name: &str, args: Vec<Expr>) -> Expr {
+    let builtin = builtins::lookup(name).expect("quasiquote helpers are builtins");
+    let procedure = Value::Procedure(Rc::new(Procedure::Builtin(builtin)));
+    Expr::Call(Box::new(Expr::Constant(procedure)), args, None)
+}
+
+fn tagged(form: &Value, tag: &str) -> Option<Value> {
+    match form.list_to_vec().as_deref() {
+        Some([head, argument]) if head.is_symbol(tag) => Some(argument.clone()),
+        _ => None,
+    }
+}
+
+/// Compiles a quasiquote template at the given nesting depth
+///
+/// The builtins are referenced directly, so redefining `cons` or `append` in user code does not
+/// change the meaning of quasiquote.
+fn compile_quasiquote(template: &Value, depth: usize) -> Result<Expr, RuntimeError> {
+    let quoted = |tag: &str, inner: Expr| {
+        builtin_call("list", vec![Expr::Constant(Value::symbol(tag)), inner])
+    };
+    if let Some(inner) = tagged(template, "unquote") {
+        return if depth == 1 {
+            compile(&inner)
+        } else {
+            Ok(quoted("unquote", compile_quasiquote(&inner, depth - 1)?))
+        };
+    }
+    if let Some(inner) = tagged(template, "quasiquote") {
+        return Ok(quoted("quasiquote", compile_quasiquote(&inner, depth + 1)?));
+    }
+    match template {
+        Value::Pair(pair) => {
+            let car = pair.car();
+            let rest = compile_quasiquote(&pair.cdr(), depth)?;
+            if let Some(spliced) = tagged(&car, "unquote-splicing") {
+                if depth == 1 {
+                    return Ok(builtin_call("append", vec![compile(&spliced)?, rest]));
+                }
+                let inner = quoted("unquote-splicing", compile_quasiquote(&spliced, depth - 1)?);
+                return Ok(builtin_call("cons", vec![inner, rest]));
+            }
+            Ok(builtin_call(
+                "cons",
+                vec![compile_quasiquote(&car, depth)?, rest],
+            ))
+        }
+        Value::Vector(vector) => {
+            let list = Value::list(vector.borrow().clone());
+            Ok(builtin_call(
+                "list->vector",
+                vec![compile_quasiquote(&list, depth)?],
+            ))
+        }
+        other => Ok(Expr::Constant(other.clone())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compile_special_form, SPECIAL_FORMS};
+    use crate::value::Value;
+
+    #[test]
+    fn registry_test() {
+        for form in SPECIAL_FORMS.iter().filter(|form| form.name != "include") {
+            let empty = Value::list(vec![Value::symbol(form.name)]);
+            assert!(
+                !matches!(compile_special_form(form.name, &empty), Ok(None)),
+                "{}",
+                form.name
+            );
+        }
+        assert!(matches!(
+            compile_special_form("include", &Value::list(vec![Value::symbol("include")])),
+            Ok(None)
+        ));
+    }
+}
@@ -10,18 +10,17 @@ use nom::{
     IResult,
 };
 
+use crate::source_map::Span;
 use nom::error::Error as NomErrorStruct;
 use nom::Err::Error as NomErrorEnum;
 
-/// Wrapper around `Token` that keeps track of line and column
-#[derive(Debug)]
+/// Wrapper around `Token` that keeps track of the text it was read from
+#[derive(Debug, Clone, PartialEq)]
 pub struct TokenWithPosition {
     /// Contains the actual token
     pub token: Token,
-    /// The line number of the token
-    pub line: usize,
-    /// The column number of the token
-    pub column: usize,
+    /// The text of the token, which never extends over several lines
+    pub span: Span,
 }
 
 /// Terminal token types for the lexer
@@ -32,7 +31,7 @@ pub struct TokenWithPosition {
 /// unnecessary heap copying and heap allocation. In particular, this means that the token cannot
 /// be dropped before the input string. `Whitespace` and `Comment` are representative of whitespaces
 /// and comments without wrapping around anything.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     /// Wraps a string
     String(String),
@@ -52,6 +51,36 @@ pub enum Token {
     Comment,
 }
 
+impl Token {
+    /// The name of the kind of token, as printed by `oxyscheme lex`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Token::String(_) => "string",
+            Token::Character(_) => "character",
+            Token::Boolean(_) => "boolean",
+            Token::Number(_) => "number",
+            Token::Identifier(_) => "identifier",
+            Token::Punctuator(_) => "punctuator",
+            Token::Whitespace => "whitespace",
+            Token::Comment => "comment",
+        }
+    }
+}
+
+impl TokenWithPosition {
+    /// Formats the token as `line:column kind text`, where `text` is the text it was read from,
+    /// quoted and escaped so that whitespace and control characters stay visible
+    pub fn dump(&self, text: &str) -> String {
+        format!(
+            "{}:{} {} {:?}",
+            self.span.line,
+            self.span.column,
+            self.token.kind(),
+            text
+        )
+    }
+}
+
 /// Internal representation of numeric types in Scheme
 ///
 /// `LispNum` is an enum wrapping around Rust's `i32` and `f32` types; the only two numeric types
@@ -85,7 +114,8 @@ pub fn lex_input(input: &str) -> LexResult<'_> {
 
 fn lex_string(input: &str) -> LexResult<'_> {
     let (input, _) = tag("\"")(input)?;
-    let (leftover, parsed) = escaped_transform(
+    // `escaped_transform` fails on empty input, hence the `opt` for the empty string
+    let (leftover, parsed) = opt(escaped_transform(
         is_not("\\\""),
         '\\',
         alt((
@@ -93,9 +123,9 @@ fn lex_string(input: &str) -> LexResult<'_> {
             value("\"", tag("\"")),
             value("\n", tag("n")),
         )),
-    )(input)?;
+    ))(input)?;
     let (input, _) = tag("\"")(leftover)?;
-    Ok((input, Token::String(parsed)))
+    Ok((input, Token::String(parsed.unwrap_or_default())))
 }
 
 fn lex_boolean(input: &str) -> LexResult<'_> {
@@ -114,11 +144,20 @@ fn peek_delimiter(input: &str) -> IResult<&str, ()> {
     map(peek(delimiter), |_: char| ())(input)
 }
 
+/// The names of the characters written `#\name`, with the characters they stand for
+pub const CHARACTER_NAMES: &[(&str, char)] = &[("space", ' '), ("newline", '\n')];
+
+fn character_name(input: &str) -> IResult<&str, char> {
+    CHARACTER_NAMES
+        .iter()
+        .find(|(name, _)| input.starts_with(name))
+        .map(|&(name, character)| (&input[name.len()..], character))
+        .ok_or_else(|| NomErrorEnum(NomErrorStruct::new(input, ErrorKind::Tag)))
+}
+
 fn lex_character(input: &str) -> LexResult<'_> {
     let (input, _) = tag("#\\")(input)?;
-    let space_parser = map(tag("space"), |_| ' ');
-    let newline_parser = map(tag("newline"), |_| '\n');
-    let (leftover, parsed) = alt((space_parser, newline_parser, anychar))(input)?;
+    let (leftover, parsed) = alt((character_name, anychar))(input)?;
     peek_delimiter(leftover)?;
     Ok((leftover, Token::Character(parsed)))
 }
@@ -201,6 +240,24 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn dump_test() {
+        let token = TokenWithPosition {
+            token: Token::Identifier(String::from("car")),
+            span: Span {
+                file: None,
+                line: 3,
+                column: 4,
+                len: 3,
+            },
+        };
+        assert_eq!(token.token.kind(), "identifier");
+        assert_eq!(token.dump("car"), "3:4 identifier \"car\"");
+        assert_eq!(token.dump("\t\"a\""), "3:4 identifier \"\\t\\\"a\\\"\"");
+        assert_eq!(Token::Whitespace.kind(), "whitespace");
+        assert_eq!(Token::Comment.kind(), "comment");
+    }
+
     #[test]
     fn lex_string_test() {
         assert_eq!(
@@ -211,6 +268,7 @@ mod test {
             lex_string(r#""st\"ring""#),
             Ok(("", Token::String(String::from("st\"ring"))))
         );
+        assert_eq!(lex_string(r#""""#), Ok(("", Token::String(String::new()))));
         assert_eq!(
             lex_string(r#""fail"#),
             Err(NomErrorEnum(NomErrorStruct::new("", ErrorKind::Tag)))
@@ -311,6 +369,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn lex_number_test() {
         assert_eq!(
             lex_number("+3.14;"),
@@ -6,10 +6,26 @@
 
 #![warn(missing_docs, unused_variables, rust_2018_idioms)]
 
+pub mod builtins;
+pub mod debugger;
+pub mod diagnostics;
+pub mod format;
+pub mod interpreter;
 pub mod lexer;
+pub mod lint;
+pub mod lsp;
+pub mod paredit;
 pub mod parser;
+pub mod port;
+pub mod profiler;
 pub mod reader;
+pub mod scope;
+pub mod source_map;
+pub mod syntax_tree;
+pub mod value;
 
+use parser::{SuperfluousParen, Unclosed};
+use source_map::Span;
 use thiserror::Error;
 
 /// The toplevel error type for the crate
@@ -17,22 +33,45 @@ use thiserror::Error;
 pub enum CompilerError {
     /// Indicates a lexing error
     ///
-    /// `LexError` wraps around a `String` and a `usize`. The first `usize` is the line number in the input,
-    /// the second `usize` is the column number, and the `String` is a copy of the leftover unlexed input from the line.
-    #[error("Lex error at line {1}, column {2}, near \"{0}\" while lexing input")]
-    LexError(String, usize, usize),
+    /// `LexError` wraps around a `String` and a `Span`. The `String` is a copy of the leftover
+    /// unlexed input from the line, and the `Span` covers it.
+    #[error("Lex error at {1}, near \"{0}\" while lexing input")]
+    LexError(String, Span),
 
     /// Error variant handling the token stream ending too early
     #[error("Token stream ended unexpectedly")]
     TokenStreamEnded,
 
-    /// Error variant handling unexpected tokens
-    #[error("Unexpected token encountered at line {0}, column {1} while parsing input")]
-    UnexpectedToken(usize, usize),
+    /// Error variant handling unexpected tokens, wrapping the span of the token
+    ///
+    /// If the token is an unmatched close paren, the variant also holds where the indentation
+    /// suggests a paren is superfluous instead, if it does.
+    #[error(
+        "Unexpected token encountered at {0} while parsing input{}",
+        judging_by_indentation(.1)
+    )]
+    UnexpectedToken(Span, Option<Box<SuperfluousParen>>),
+
+    /// Error variant handling unclosed lists or vectors, wrapping the lists left open and where
+    /// the indentation suggests their close parens are missing
+    #[error(
+        "Missing close paren for the list opened at {}{}",
+        .0.open,
+        judging_by_indentation(&.0.missing)
+    )]
+    MissingCloseParen(Box<Unclosed>),
 
-    /// Error variant handling unclosed lists or vectors
-    #[error("Missing close paren at unknown position")]
-    MissingCloseParen,
+    /// Error variant for runtime values that have no external representation as a `Datum`
+    ///
+    /// The wrapped `String` describes the offending value, e.g. a circular list.
+    #[error("Cannot convert {0} to a datum")]
+    UnrepresentableValue(String),
+
+    /// Error variant for structural edits that cannot be carried out
+    ///
+    /// The wrapped `String` explains why, e.g. that there is no list around the cursor.
+    #[error("Cannot edit the structure: {0}")]
+    StructuralEdit(String),
 
     /// Indicates an IO error
     ///
@@ -40,3 +79,26 @@ pub enum CompilerError {
     #[error("I/O error")]
     IOError(#[from] std::io::Error),
 }
+
+/// The end of an error message telling what the indentation suggests, if anything
+fn judging_by_indentation<T: std::fmt::Display>(guess: &Option<T>) -> String {
+    match guess {
+        Some(guess) => format!("; judging by the indentation, {}", guess),
+        None => String::new(),
+    }
+}
+
+impl CompilerError {
+    /// The stable code identifying the variant in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompilerError::LexError(..) => "E0001",
+            CompilerError::TokenStreamEnded => "E0002",
+            CompilerError::UnexpectedToken(..) => "E0003",
+            CompilerError::MissingCloseParen(_) => "E0004",
+            CompilerError::UnrepresentableValue(_) => "E0005",
+            CompilerError::IOError(_) => "E0006",
+            CompilerError::StructuralEdit(_) => "E0007",
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! The expressions of a program, as opposed to its quoted data, binding lists and clauses
+
+use super::Context;
+use crate::syntax_tree::{Node, NodeKind};
+
+pub(super) fn expressions<'a>(context: &Context<'a>) -> Vec<(&'a Node, bool)> {
+    let mut walker = Walker {
+        context,
+        expressions: Vec::new(),
+    };
+    walker.body(context.tree.forms(), false);
+    walker.expressions
+}
+
+/// Walks expressions, knowing whether their values are used
+struct Walker<'c, 'a> {
+    context: &'c Context<'a>,
+    expressions: Vec<(&'a Node, bool)>,
+}
+
+impl<'a> Walker<'_, 'a> {
+    fn all(&mut self, nodes: &'a [Node], used: bool) {
+        for node in nodes {
+            self.expression(node, used);
+        }
+    }
+
+    /// Walks a sequence of expressions, the last one of which gives the value of the sequence
+    fn body(&mut self, nodes: &'a [Node], used: bool) {
+        if let Some((last, init)) = nodes.split_last() {
+            self.all(init, false);
+            self.expression(last, used);
+        }
+    }
+
+    /// Walks the clauses of `cond`, `case` or `guard`, the first element of which is a test if
+    /// `tests` is `true`
+    fn clauses(&mut self, clauses: &'a [Node], tests: bool, used: bool) {
+        for clause in clauses {
+            let elements = &clause.children;
+            if tests && !self.context.is_else_clause(clause) {
+                self.all(elements.get(..1).unwrap_or_default(), true);
+            }
+            let rest = elements.get(1..).unwrap_or_default();
+            match rest.first() {
+                Some(arrow) if self.context.tree.identifier(arrow) == Some("=>") => {
+                    self.all(&rest[1..], true)
+                }
+                _ => self.body(rest, used),
+            }
+        }
+    }
+
+    fn expression(&mut self, node: &'a Node, used: bool) {
+        if !matches!(node.kind, NodeKind::List { .. }) {
+            return;
+        }
+        let elements = &node.children;
+        let rest = elements.get(1..).unwrap_or_default();
+        self.expressions.push((node, used));
+        if self.context.is_call(node) {
+            return self.all(elements, true);
+        }
+        match self.context.keyword(node) {
+            Some("if") => {
+                self.all(rest.get(..1).unwrap_or_default(), true);
+                self.all(rest.get(1..).unwrap_or_default(), used);
+            }
+            Some("define") => match rest.first() {
+                Some(header) if matches!(header.kind, NodeKind::List { .. }) => {
+                    self.body(&rest[1..], false)
+                }
+                _ => self.all(rest.get(1..).unwrap_or_default(), true),
+            },
+            Some("set!") | Some("delay") => self.all(rest, true),
+            Some("lambda") => self.body(rest.get(1..).unwrap_or_default(), false),
+            Some("let") | Some("let*") | Some("letrec") | Some("letrec*") => {
+                let named = rest
+                    .first()
+                    .is_some_and(|first| self.context.tree.identifier(first).is_some());
+                let rest = if named { &rest[1..] } else { rest };
+                if let Some(bindings) = rest.first() {
+                    for binding in &bindings.children {
+                        self.all(binding.children.get(1..).unwrap_or_default(), true);
+                    }
+                }
+                self.body(rest.get(1..).unwrap_or_default(), used);
+            }
+            Some("begin") => self.body(rest, used),
+            Some("and") | Some("or") => self.all(rest, true),
+            Some("cond") => self.clauses(rest, true, used),
+            Some("case") => {
+                self.all(rest.get(..1).unwrap_or_default(), true);
+                self.clauses(rest.get(1..).unwrap_or_default(), false, used);
+            }
+            Some("do") => {
+                let specs = rest.first().map_or(&[][..], |specs| &specs.children[..]);
+                for spec in specs {
+                    self.all(spec.children.get(1..).unwrap_or_default(), true);
+                }
+                if let Some(exit) = rest.get(1) {
+                    self.all(exit.children.get(..1).unwrap_or_default(), true);
+                    self.body(exit.children.get(1..).unwrap_or_default(), used);
+                }
+                self.all(rest.get(2..).unwrap_or_default(), false);
+            }
+            Some("guard") => {
+                if let Some(spec) = rest.first() {
+                    self.clauses(spec.children.get(1..).unwrap_or_default(), true, used);
+                }
+                self.body(rest.get(1..).unwrap_or_default(), used);
+            }
+            Some("define-library") => {
+                for declaration in rest.get(1..).unwrap_or_default() {
+                    let parts = &declaration.children;
+                    let head = parts
+                        .first()
+                        .and_then(|head| self.context.tree.identifier(head));
+                    if head == Some("begin") {
+                        self.body(&parts[1..], false);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
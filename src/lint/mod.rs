@@ -0,0 +1,462 @@
+//! Warnings about programs that read and run, but probably not as intended
+//!
+//! A `Linter` runs a set of `Rule`s over the syntax tree of a text and the resolution of its
+//! identifiers. Each rule has a name, such as `unused-binding`, by which it is enabled or
+//! disabled, and a stable code for diagnostics. The standard rules are in `RULES`, and tools can
+//! add rules of their own with `Linter::with_rule`.
+//!
+//! Warnings can also be suppressed in the source, with a comment such as
+//! `; lint: allow unused-binding, redefinition`, or `; lint: allow all`. At the end of a line,
+//! the comment applies to the warnings on that line; on a line of its own, to the warnings within
+//! the datum following it.
+
+mod expressions;
+mod rules;
+mod signatures;
+
+pub use rules::RULES;
+
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::interpreter::is_keyword;
+use crate::lexer::Token;
+use crate::scope::{definition_name, BindingKind, Resolution, Target};
+use crate::source_map::{FileId, SourceMap, Span};
+use crate::syntax_tree::{Node, NodeKind, SyntaxTree};
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+/// Signature of the function checking a rule
+pub type RuleCheck = fn(&Context<'_>) -> Vec<Finding>;
+
+/// A kind of problem the linter looks for
+#[derive(Clone, Copy)]
+pub struct Rule {
+    /// The name by which the rule is enabled, disabled or suppressed
+    pub name: &'static str,
+    /// The stable code of the warnings of the rule
+    pub code: &'static str,
+    /// A one-sentence description of what the rule warns about
+    pub description: &'static str,
+    /// Whether the rule is enabled unless disabled explicitly
+    pub default: bool,
+    /// The implementation
+    pub check: RuleCheck,
+}
+
+/// What rules look at: a text, its syntax tree and the resolution of its identifiers
+pub struct Context<'a> {
+    /// The text being linted
+    pub text: &'a str,
+    /// The syntax tree of the text
+    pub tree: &'a SyntaxTree,
+    /// The bindings of the tree, and the identifiers referring to them
+    pub resolution: &'a Resolution,
+    lines: Vec<usize>,
+    /// The keywords defined at the top level by `define-syntax`
+    syntax: HashSet<&'a str>,
+}
+
+impl<'a> Context<'a> {
+    /// Creates the context of a text and its tree
+    pub fn new(text: &'a str, tree: &'a SyntaxTree, resolution: &'a Resolution) -> Self {
+        let lines = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let mut context = Context {
+            text,
+            tree,
+            resolution,
+            lines,
+            syntax: HashSet::new(),
+        };
+        context.syntax = context
+            .lists()
+            .into_iter()
+            .filter(|list| context.keyword(list) == Some("define-syntax"))
+            .filter_map(|list| definition_name(&list.children))
+            .filter_map(|name| tree.identifier(name))
+            .collect();
+        context
+    }
+
+    /// The keyword of a list, unless its first element is not an identifier or is locally bound
+    pub fn keyword(&self, list: &Node) -> Option<&'a str> {
+        let head = list.children.first()?;
+        let name = self.tree.identifier(head)?;
+        match self.resolution.occurrence(head.first) {
+            Some(occurrence) if matches!(occurrence.target, Target::Local(_)) => None,
+            _ => Some(name),
+        }
+    }
+
+    /// Every list of the text that is code rather than quoted data, parents before children
+    pub fn lists(&self) -> Vec<&'a Node> {
+        let mut lists = Vec::new();
+        let mut pending: Vec<&'a Node> = self.tree.forms().iter().rev().collect();
+        while let Some(node) = pending.pop() {
+            if !matches!(node.kind, NodeKind::List { .. }) {
+                continue;
+            }
+            match self.keyword(node) {
+                Some("quote") | Some("quasiquote") | Some("syntax-rules") => {}
+                _ => {
+                    lists.push(node);
+                    pending.extend(node.children.iter().rev());
+                }
+            }
+        }
+        lists
+    }
+
+    /// The lists that are expressions, rather than quoted data, binding lists or clauses, parents
+    /// before children, with whether their values are used
+    ///
+    /// The values of the last expressions of procedure bodies are taken as unused, as procedures
+    /// are often called for their effects.
+    pub fn expressions(&self) -> Vec<(&'a Node, bool)> {
+        expressions::expressions(self)
+    }
+
+    /// Whether a list is a procedure call, rather than a special form or the use of a macro
+    pub fn is_call(&self, list: &Node) -> bool {
+        let head = match list.children.first() {
+            Some(head) => head,
+            None => return false,
+        };
+        let name = match self.tree.identifier(head) {
+            Some(name) => name,
+            None => return true,
+        };
+        match self
+            .resolution
+            .occurrence(head.first)
+            .map(|found| &found.target)
+        {
+            Some(Target::Local(index)) => {
+                self.resolution.bindings[*index].kind != BindingKind::Syntax
+            }
+            _ => !is_keyword(name) && !self.syntax.contains(name),
+        }
+    }
+
+    /// Whether a clause of `cond`, `case` or `guard` is an `else` clause
+    pub fn is_else_clause(&self, clause: &Node) -> bool {
+        let head = clause.children.first();
+        head.and_then(|head| self.tree.identifier(head)) == Some("else")
+    }
+
+    /// The text of a node
+    pub fn text_of(&self, node: &Node) -> &'a str {
+        &self.text[self.offset(node.first)..self.end(node.last)]
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        let token = self.tree.token(index);
+        self.lines[token.span.line - 1] + token.span.column
+    }
+
+    fn end(&self, index: usize) -> usize {
+        self.offset(index) + self.tree.token(index).span.len
+    }
+
+    fn location(&self, first: usize, last: usize) -> Span {
+        Span {
+            len: self.end(last) - self.offset(first),
+            ..self.tree.token(first).span
+        }
+    }
+}
+
+/// A problem a rule found, as the tokens it is about
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// The index of the first token of the problem
+    pub first: usize,
+    /// The index of the last token of the problem
+    pub last: usize,
+    /// What the problem is
+    pub message: String,
+    /// Other parts of the text involved, as their first and last tokens and a message
+    pub related: Vec<(usize, usize, String)>,
+}
+
+impl Finding {
+    /// Creates a finding about the tokens from `first` to `last`
+    pub fn new<S: Into<String>>(first: usize, last: usize, message: S) -> Self {
+        Finding {
+            first,
+            last,
+            message: message.into(),
+            related: Vec::new(),
+        }
+    }
+
+    /// Adds a related part of the text
+    pub fn with_related<S: Into<String>>(mut self, first: usize, last: usize, message: S) -> Self {
+        self.related.push((first, last, message.into()));
+        self
+    }
+}
+
+/// A warning of the linter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// The name of the rule that found the problem
+    pub rule: &'static str,
+    /// The code of the rule
+    pub code: &'static str,
+    /// What the problem is
+    pub message: String,
+    /// Where the problem is
+    pub location: Span,
+    /// Other parts of the text involved, with what they have to do with the problem
+    pub related: Vec<(Span, String)>,
+}
+
+impl Lint {
+    /// Describes the warning as a diagnostic about the source `file`
+    pub fn to_diagnostic(&self, file: FileId, source_map: &SourceMap) -> Diagnostic {
+        let span =
+            |location: &Span| source_map.span(file, location.line, location.column, location.len);
+        let mut diagnostic = Diagnostic::new(self.code, Severity::Warning, self.message.clone())
+            .with_primary(span(&self.location));
+        for (location, message) in &self.related {
+            if let Some(span) = span(location) {
+                diagnostic = diagnostic.with_secondary(span, message.clone());
+            }
+        }
+        diagnostic.with_note(format!(
+            "reported by the {0} rule, which `; lint: allow {0}` suppresses",
+            self.rule
+        ))
+    }
+}
+
+/// Runs the enabled rules over texts
+pub struct Linter {
+    rules: Vec<Rule>,
+    enabled: Vec<bool>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// Creates a linter with the standard rules, enabling those enabled by default
+    pub fn new() -> Self {
+        Linter {
+            rules: RULES.to_vec(),
+            enabled: RULES.iter().map(|rule| rule.default).collect(),
+        }
+    }
+
+    /// Adds a rule, enabled
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self.enabled.push(true);
+        self
+    }
+
+    /// Enables the rule named `name`, returning `false` if there is no such rule
+    pub fn enable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, true)
+    }
+
+    /// Disables the rule named `name`, returning `false` if there is no such rule
+    pub fn disable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, false)
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.rules.iter().position(|rule| rule.name == name) {
+            Some(index) => {
+                self.enabled[index] = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The rules of the linter, and whether each one is enabled
+    pub fn rules(&self) -> impl Iterator<Item = (&Rule, bool)> {
+        self.rules.iter().zip(self.enabled.iter().copied())
+    }
+
+    /// Lints a text, returning the warnings in the order of their positions
+    pub fn lint(&self, text: &str) -> Vec<Lint> {
+        let tree = SyntaxTree::parse(text);
+        let resolution = Resolution::new(&tree);
+        let context = Context::new(text, &tree, &resolution);
+        let suppressions = suppressions(&context);
+        let mut lints = Vec::new();
+        for (rule, _) in self.rules().filter(|(_, enabled)| *enabled) {
+            for finding in (rule.check)(&context) {
+                let suppressed = suppressions.iter().any(|(tokens, names)| {
+                    tokens.contains(&finding.first)
+                        && names.iter().any(|&name| name == rule.name || name == "all")
+                });
+                if suppressed {
+                    continue;
+                }
+                lints.push(Lint {
+                    rule: rule.name,
+                    code: rule.code,
+                    message: finding.message,
+                    location: context.location(finding.first, finding.last),
+                    related: finding
+                        .related
+                        .into_iter()
+                        .map(|(first, last, message)| (context.location(first, last), message))
+                        .collect(),
+                });
+            }
+        }
+        lints.sort_by_key(|lint| (lint.location.line, lint.location.column));
+        lints
+    }
+}
+
+/// The tokens each suppression comment of a text applies to, and the rules it names
+fn suppressions<'a>(context: &Context<'a>) -> Vec<(RangeInclusive<usize>, Vec<&'a str>)> {
+    let tokens = context.tree.tokens();
+    let mut suppressions = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if token.token != Token::Comment {
+            continue;
+        }
+        let comment = context.text[context.offset(index)..context.end(index)]
+            .trim_start_matches(';')
+            .trim();
+        let names = match comment
+            .strip_prefix("lint:")
+            .and_then(|rest| rest.trim_start().strip_prefix("allow"))
+        {
+            Some(names) => names
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            None => continue,
+        };
+        let line_start = tokens[..index]
+            .iter()
+            .rposition(|other| other.span.line < token.span.line)
+            .map_or(0, |previous| previous + 1);
+        let trailing = tokens[line_start..index]
+            .iter()
+            .any(|other| !matches!(other.token, Token::Whitespace | Token::Comment));
+        let applies_to = if trailing {
+            line_start..=index
+        } else {
+            let next = match tokens[index..]
+                .iter()
+                .position(|other| !matches!(other.token, Token::Whitespace | Token::Comment))
+            {
+                Some(offset) => index + offset,
+                None => continue,
+            };
+            next..=outermost_starting_at(context.tree.forms(), next).map_or(next, |node| node.last)
+        };
+        suppressions.push((applies_to, names));
+    }
+    suppressions
+}
+
+/// The outermost node starting at the token at `index`
+fn outermost_starting_at(nodes: &[Node], index: usize) -> Option<&Node> {
+    let node = nodes
+        .iter()
+        .find(|node| node.first <= index && index <= node.last)?;
+    if node.first == index {
+        Some(node)
+    } else {
+        outermost_starting_at(&node.children, index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Context, Finding, Lint, Linter, Rule, Span};
+
+    /// The rule, line and column of each warning about `text`
+    fn warnings(linter: &Linter, text: &str) -> Vec<(&'static str, usize, usize)> {
+        linter
+            .lint(text)
+            .iter()
+            .map(|lint| (lint.rule, lint.location.line, lint.location.column))
+            .collect()
+    }
+
+    #[test]
+    fn configuration_test() {
+        let text = "(define (f list unused) (let ((x 1)) 2))";
+        let mut linter = Linter::new();
+        assert_eq!(
+            warnings(&linter, text),
+            vec![("shadowed-builtin", 1, 11), ("unused-binding", 1, 31)]
+        );
+        assert!(linter.enable("unused-parameter"));
+        assert!(linter.disable("shadowed-builtin"));
+        assert!(!linter.disable("no-such-rule"));
+        assert_eq!(
+            warnings(&linter, text),
+            vec![
+                ("unused-parameter", 1, 11),
+                ("unused-parameter", 1, 16),
+                ("unused-binding", 1, 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn suppression_test() {
+        let text = "\
+(define x 1)
+(define x 2) ; lint: allow redefinition
+; lint: allow all
+(define (f)
+  (let ((y 1)) 2))
+(define x 3) ; lint: allow unused-binding
+";
+        assert_eq!(warnings(&Linter::new(), text), vec![("redefinition", 6, 8)]);
+    }
+
+    #[test]
+    fn custom_rule_test() {
+        fn long_names(context: &Context<'_>) -> Vec<Finding> {
+            context
+                .resolution
+                .occurrences
+                .iter()
+                .filter(|occurrence| occurrence.definition)
+                .filter(|occurrence| context.tree.token(occurrence.token).span.len > 8)
+                .map(|occurrence| Finding::new(occurrence.token, occurrence.token, "too long"))
+                .collect()
+        }
+        let linter = Linter::new().with_rule(Rule {
+            name: "long-name",
+            code: "W9999",
+            description: "A name is longer than 8 bytes.",
+            default: true,
+            check: long_names,
+        });
+        let lints = linter.lint("(define (distances x) x)");
+        assert_eq!(
+            lints,
+            vec![Lint {
+                rule: "long-name",
+                code: "W9999",
+                message: "too long".to_string(),
+                location: Span {
+                    file: None,
+                    line: 1,
+                    column: 9,
+                    len: 9,
+                },
+                related: Vec::new(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! The standard rules of the linter
+
+use super::signatures::{argument_type, arity, not_a_procedure};
+use super::{Context, Finding, Rule};
+use crate::builtins;
+use crate::scope::{BindingKind, Target};
+use crate::syntax_tree::{Node, NodeKind};
+use std::collections::HashMap;
+
+/// The rules of `Linter::new`
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "unused-binding",
+        code: "W0001",
+        description: "A local variable or keyword is bound but never used.",
+        default: true,
+        check: unused_binding,
+    },
+    Rule {
+        name: "unused-parameter",
+        code: "W0002",
+        description: "A parameter of a procedure is never used.",
+        default: false,
+        check: unused_parameter,
+    },
+    Rule {
+        name: "shadowed-builtin",
+        code: "W0003",
+        description: "A binding or definition hides a standard procedure.",
+        default: true,
+        check: shadowed_builtin,
+    },
+    Rule {
+        name: "clause-after-else",
+        code: "W0004",
+        description: "A cond or case clause follows the else clause, so it is never reached.",
+        default: true,
+        check: clause_after_else,
+    },
+    Rule {
+        name: "duplicate-case-datum",
+        code: "W0005",
+        description: "A datum is listed in more than one clause of a case, or twice in one.",
+        default: true,
+        check: duplicate_case_datum,
+    },
+    Rule {
+        name: "if-without-alternative",
+        code: "W0006",
+        description: "The value of an if without an alternative is used, though it may be \
+                      unspecified.",
+        default: true,
+        check: if_without_alternative,
+    },
+    Rule {
+        name: "redefinition",
+        code: "W0007",
+        description: "A top-level name is defined more than once in the same file.",
+        default: true,
+        check: redefinition,
+    },
+    Rule {
+        name: "arity",
+        code: "W0008",
+        description: "A procedure is called with a number of arguments it does not accept.",
+        default: true,
+        check: arity,
+    },
+    Rule {
+        name: "argument-type",
+        code: "W0009",
+        description: "A builtin is given a literal argument of a type it does not accept.",
+        default: true,
+        check: argument_type,
+    },
+    Rule {
+        name: "not-a-procedure",
+        code: "W0010",
+        description: "A literal that is not a procedure is called.",
+        default: true,
+        check: not_a_procedure,
+    },
+];
+
+/// Local bindings, other than parameters if `parameters` is `false`, and only parameters
+/// otherwise, that no identifier refers to; names starting with `_` are meant to be unused
+fn unused(context: &Context<'_>, parameters: bool) -> Vec<Finding> {
+    let resolution = context.resolution;
+    resolution
+        .bindings
+        .iter()
+        .enumerate()
+        .filter(|(_, binding)| (binding.kind == BindingKind::Parameter) == parameters)
+        .filter(|(_, binding)| !binding.name.starts_with('_'))
+        .filter(|&(index, _)| {
+            !resolution
+                .occurrences_of(&Target::Local(index))
+                .any(|occurrence| !occurrence.definition)
+        })
+        .map(|(_, binding)| {
+            Finding::new(
+                binding.token,
+                binding.token,
+                format!("`{}` is never used", binding.name),
+            )
+        })
+        .collect()
+}
+
+fn unused_binding(context: &Context<'_>) -> Vec<Finding> {
+    unused(context, false)
+}
+
+fn unused_parameter(context: &Context<'_>) -> Vec<Finding> {
+    unused(context, true)
+}
+
+fn shadowed_builtin(context: &Context<'_>) -> Vec<Finding> {
+    let resolution = context.resolution;
+    let local = resolution
+        .bindings
+        .iter()
+        .filter(|binding| builtins::lookup(&binding.name).is_some())
+        .map(|binding| {
+            Finding::new(
+                binding.token,
+                binding.token,
+                format!(
+                    "`{}` shadows the standard procedure of the same name",
+                    binding.name
+                ),
+            )
+        });
+    let top_level = resolution
+        .occurrences
+        .iter()
+        .filter_map(|occurrence| match &occurrence.target {
+            Target::TopLevel(name) if occurrence.definition => Some((occurrence.token, name)),
+            _ => None,
+        })
+        .filter(|(_, name)| builtins::lookup(name).is_some())
+        .map(|(token, name)| {
+            Finding::new(
+                token,
+                token,
+                format!(
+                    "`{}` redefines the standard procedure of the same name",
+                    name
+                ),
+            )
+        });
+    local.chain(top_level).collect()
+}
+
+fn clause_after_else(context: &Context<'_>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for list in context.lists() {
+        let clauses = match context.keyword(list) {
+            Some("cond") => list.children.get(1..),
+            Some("case") => list.children.get(2..),
+            _ => None,
+        }
+        .unwrap_or_default();
+        let position = clauses
+            .iter()
+            .position(|clause| context.is_else_clause(clause));
+        let (else_clause, after) = match position {
+            Some(position) => (&clauses[position], &clauses[position + 1..]),
+            None => continue,
+        };
+        if let (Some(first), Some(last)) = (after.first(), after.last()) {
+            let message = match after.len() {
+                1 => "this clause follows the `else` clause, so it is never reached",
+                _ => "these clauses follow the `else` clause, so they are never reached",
+            };
+            findings.push(Finding::new(first.first, last.last, message).with_related(
+                else_clause.first,
+                else_clause.last,
+                "the `else` clause",
+            ));
+        }
+    }
+    findings
+}
+
+fn duplicate_case_datum(context: &Context<'_>) -> Vec<Finding> {
+    let tree = context.tree;
+    let mut findings = Vec::new();
+    for list in context.lists() {
+        if context.keyword(list) != Some("case") {
+            continue;
+        }
+        let mut seen: Vec<&Node> = Vec::new();
+        for clause in list.children.iter().skip(2) {
+            let data = match clause.children.first() {
+                Some(data) if matches!(data.kind, NodeKind::List { .. }) => &data.children,
+                _ => continue,
+            };
+            for datum in data.iter().filter(|datum| datum.kind == NodeKind::Atom) {
+                let token = &tree.token(datum.first).token;
+                match seen
+                    .iter()
+                    .find(|other| tree.token(other.first).token == *token)
+                {
+                    Some(first) => findings.push(
+                        Finding::new(
+                            datum.first,
+                            datum.last,
+                            format!("`{}` is listed more than once", context.text_of(datum)),
+                        )
+                        .with_related(
+                            first.first,
+                            first.last,
+                            "first listed here",
+                        ),
+                    ),
+                    None => seen.push(datum),
+                }
+            }
+        }
+    }
+    findings
+}
+
+fn if_without_alternative(context: &Context<'_>) -> Vec<Finding> {
+    context
+        .expressions()
+        .into_iter()
+        .filter(|&(list, used)| {
+            used && list.children.len() == 3 && context.keyword(list) == Some("if")
+        })
+        .map(|(list, _)| {
+            Finding::new(
+                list.first,
+                list.last,
+                "the value of this `if` is used, but it has no alternative to give a value \
+                 when its test is false",
+            )
+        })
+        .collect()
+}
+
+fn redefinition(context: &Context<'_>) -> Vec<Finding> {
+    let mut first_definitions: HashMap<&str, usize> = HashMap::new();
+    let mut findings = Vec::new();
+    for occurrence in &context.resolution.occurrences {
+        let name = match &occurrence.target {
+            Target::TopLevel(name) if occurrence.definition => name,
+            _ => continue,
+        };
+        match first_definitions.get(name.as_str()) {
+            Some(&first) => findings.push(
+                Finding::new(
+                    occurrence.token,
+                    occurrence.token,
+                    format!("`{}` is already defined", name),
+                )
+                .with_related(first, first, "first defined here"),
+            ),
+            None => {
+                first_definitions.insert(name, occurrence.token);
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lint::Linter;
+
+    /// The messages of the warnings of the rule `name` about `text`, with their lines and columns
+    fn lint(name: &str, text: &str) -> Vec<(usize, usize, String)> {
+        let mut linter = Linter::new();
+        assert!(linter.enable(name));
+        linter
+            .lint(text)
+            .into_iter()
+            .filter(|lint| lint.rule == name)
+            .map(|lint| (lint.location.line, lint.location.column, lint.message))
+            .collect()
+    }
+
+    fn warning(line: usize, column: usize, message: &str) -> (usize, usize, String) {
+        (line, column, message.to_string())
+    }
+
+    #[test]
+    fn unused_test() {
+        let text = "(define (f x _y) (define (g) 1) (let loop ((i 0) (j 1)) (loop j 2)))";
+        assert_eq!(
+            lint("unused-binding", text),
+            vec![
+                warning(1, 26, "`g` is never used"),
+                warning(1, 44, "`i` is never used"),
+            ]
+        );
+        assert_eq!(
+            lint("unused-parameter", text),
+            vec![warning(1, 11, "`x` is never used")]
+        );
+        assert_eq!(lint("unused-binding", "(define x 1) (let () x)"), vec![]);
+    }
+
+    #[test]
+    fn shadowed_builtin_test() {
+        assert_eq!(
+            lint(
+                "shadowed-builtin",
+                "(define (car p) p) (lambda (list) '(car list))"
+            ),
+            vec![
+                warning(
+                    1,
+                    9,
+                    "`car` redefines the standard procedure of the same name"
+                ),
+                warning(
+                    1,
+                    28,
+                    "`list` shadows the standard procedure of the same name"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn clause_after_else_test() {
+        assert_eq!(
+            lint("clause-after-else", "(cond (a 1) (else 2) (b 3) (c 4))"),
+            vec![warning(
+                1,
+                21,
+                "these clauses follow the `else` clause, so they are never reached"
+            )]
+        );
+        assert_eq!(
+            lint("clause-after-else", "(case x ((1) 1) (else 2) ((2) 3))"),
+            vec![warning(
+                1,
+                25,
+                "this clause follows the `else` clause, so it is never reached"
+            )]
+        );
+        assert_eq!(lint("clause-after-else", "'(cond (else 1) (a 2))"), vec![]);
+    }
+
+    #[test]
+    fn duplicate_case_datum_test() {
+        assert_eq!(
+            lint(
+                "duplicate-case-datum",
+                "(case x ((1 2) 'a) ((3 2) 'b) ((#\\a a) 'c) ((a #\\a) 'd))"
+            ),
+            vec![
+                warning(1, 23, "`2` is listed more than once"),
+                warning(1, 45, "`a` is listed more than once"),
+                warning(1, 47, "`#\\a` is listed more than once"),
+            ]
+        );
+    }
+
+    #[test]
+    fn if_without_alternative_test() {
+        let message = "the value of this `if` is used, but it has no alternative to give a value \
+                       when its test is false";
+        assert_eq!(
+            lint(
+                "if-without-alternative",
+                "(define x (if a 1))\n(display (cond ((if a b) => f)))\n(let ((y (or (if b 2))))\n  (if y (display y)))"
+            ),
+            vec![
+                warning(1, 10, message),
+                warning(2, 16, message),
+                warning(3, 13, message),
+            ]
+        );
+        assert_eq!(
+            lint(
+                "if-without-alternative",
+                "(define (f) (if a (display 1)) (if b 2))\n(begin (if c 3))"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn redefinition_test() {
+        let text = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/inputs/good-inputs/ex_01.11.scm"
+        ))
+        .unwrap();
+        let linter = Linter::new();
+        let lints = linter.lint(&text);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].rule, "redefinition");
+        assert_eq!(lints[0].message, "`f` is already defined");
+        assert_eq!((lints[0].location.line, lints[0].location.column), (20, 9));
+        assert_eq!(lints[0].related[0].0.line, 9);
+    }
+}
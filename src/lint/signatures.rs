@@ -0,0 +1,497 @@
+//! The procedures calls refer to, and what they accept
+//!
+//! Builtins are described by their `Arity` and their parameters, whose names follow the
+//! conventions of R5RS section 1.3.3: `z`, `x`, `q`, `n` and `k` are numbers, `pair`, `list`,
+//! `string`, `char`, `vector` and `symbol` are what they say, and `proc` is a procedure. The arity
+//! of the procedures a file defines, at the top level or internally, is read from their formals,
+//! unless they are assigned with `set!` or defined again differently.
+
+use super::{Context, Finding};
+use crate::builtins::{self, Arity};
+use crate::lexer::Token;
+use crate::scope::{definition_name, Target};
+use crate::syntax_tree::{Node, NodeKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The type of a literal, or that expected of an argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    String,
+    Character,
+    Boolean,
+    Symbol,
+    EmptyList,
+    /// A non-empty proper list
+    List,
+    /// An improper list
+    Pair,
+    Vector,
+    Procedure,
+}
+
+impl Type {
+    /// Whether a value of type `actual` is acceptable where `self` is expected
+    fn accepts(self, actual: Type) -> bool {
+        match self {
+            Type::List => matches!(actual, Type::List | Type::EmptyList),
+            Type::Pair => matches!(actual, Type::List | Type::Pair),
+            expected => expected == actual,
+        }
+    }
+
+    /// The type a builtin expects of a parameter, given its name in R5RS notation
+    fn of_parameter(name: &str) -> Option<Type> {
+        let name = name.trim_matches(|c| c == '[' || c == ']');
+        Some(match name.trim_end_matches(|c: char| c.is_ascii_digit()) {
+            "z" | "x" | "y" | "q" | "n" | "k" | "radix" | "start" | "end" => Type::Number,
+            "string" | "filename" => Type::String,
+            "char" | "letter" => Type::Character,
+            "symbol" => Type::Symbol,
+            "list" | "alist" | "args" => Type::List,
+            "pair" => Type::Pair,
+            "vector" => Type::Vector,
+            "proc" | "thunk" | "handler" | "producer" | "consumer" | "before" | "after" => {
+                Type::Procedure
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Number => "a number",
+            Type::String => "a string",
+            Type::Character => "a character",
+            Type::Boolean => "a boolean",
+            Type::Symbol => "a symbol",
+            Type::EmptyList => "the empty list",
+            Type::List => "a list",
+            Type::Pair => "a pair",
+            Type::Vector => "a vector",
+            Type::Procedure => "a procedure",
+        })
+    }
+}
+
+/// The type of an expression, if it is a literal or a `lambda` expression
+fn literal_type(context: &Context<'_>, node: &Node) -> Option<Type> {
+    match node.kind {
+        NodeKind::Atom => atom_type(&context.tree.token(node.first).token),
+        NodeKind::Vector { .. } => Some(Type::Vector),
+        NodeKind::Abbreviation if context.tree.prefix(node) == Some("'") => {
+            quoted_type(context, node.children.first()?)
+        }
+        NodeKind::List { .. } => match context.keyword(node) {
+            Some("quote") => quoted_type(context, node.children.get(1)?),
+            Some("lambda") => Some(Type::Procedure),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The type of a quoted datum
+fn quoted_type(context: &Context<'_>, datum: &Node) -> Option<Type> {
+    match datum.kind {
+        NodeKind::Atom => match &context.tree.token(datum.first).token {
+            Token::Identifier(_) => Some(Type::Symbol),
+            token => atom_type(token),
+        },
+        NodeKind::List { closed: true } if datum.children.is_empty() => Some(Type::EmptyList),
+        NodeKind::List { closed: true } => {
+            match datum
+                .children
+                .iter()
+                .any(|child| child.kind == NodeKind::Dot)
+            {
+                true => Some(Type::Pair),
+                false => Some(Type::List),
+            }
+        }
+        NodeKind::Vector { .. } => Some(Type::Vector),
+        _ => None,
+    }
+}
+
+fn atom_type(token: &Token) -> Option<Type> {
+    match token {
+        Token::Number(_) => Some(Type::Number),
+        Token::String(_) => Some(Type::String),
+        Token::Character(_) => Some(Type::Character),
+        Token::Boolean(_) => Some(Type::Boolean),
+        _ => None,
+    }
+}
+
+/// What is known of the procedure a call refers to
+struct Signature<'a> {
+    arity: Arity,
+    /// The parameters of a builtin, in the notation of `Builtin::params`
+    params: Option<&'static str>,
+    /// The name in the definition of a procedure of the file
+    definition: Option<&'a Node>,
+}
+
+/// The procedures a file defines, as their arity and the name in their definition, or `None`
+/// for names that may not be bound to such a procedure
+#[derive(Default)]
+struct Definitions<'a> {
+    top_level: HashMap<&'a str, Option<(Arity, &'a Node)>>,
+    local: HashMap<usize, Option<(Arity, &'a Node)>>,
+}
+
+impl<'a> Definitions<'a> {
+    fn new(context: &Context<'a>) -> Self {
+        let mut definitions = Definitions::default();
+        for list in context.lists() {
+            let elements = &list.children;
+            match context.keyword(list) {
+                Some("define") => {
+                    let name = match definition_name(elements) {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    let arity = match &elements[1].kind {
+                        NodeKind::List { .. } => formals_arity(&elements[1].children[1..]),
+                        _ => elements
+                            .get(2)
+                            .filter(|value| context.keyword(value) == Some("lambda"))
+                            .and_then(|lambda| lambda.children.get(1))
+                            .and_then(|formals| match formals.kind {
+                                NodeKind::Atom => Some(Arity::at_least(0)),
+                                NodeKind::List { .. } => formals_arity(&formals.children),
+                                _ => None,
+                            }),
+                    };
+                    definitions.define(context, name, arity.map(|arity| (arity, name)));
+                }
+                Some("set!") => {
+                    if let Some(name) = elements.get(1) {
+                        definitions.define(context, name, None);
+                    }
+                }
+                _ => {}
+            }
+        }
+        definitions
+    }
+
+    /// Records a definition or assignment, which makes the name unknown if it was already
+    /// defined differently
+    fn define(&mut self, context: &Context<'a>, name: &Node, value: Option<(Arity, &'a Node)>) {
+        let target = match context.resolution.occurrence(name.first) {
+            Some(occurrence) => &occurrence.target,
+            None => return,
+        };
+        let existing = match target {
+            Target::Local(index) => self.local.get_mut(index),
+            Target::TopLevel(name) => self.top_level.get_mut(name.as_str()),
+        };
+        match existing {
+            Some(existing) => {
+                if existing.map(|(arity, _)| arity) != value.map(|(arity, _)| arity) {
+                    *existing = None;
+                }
+            }
+            None => match target {
+                Target::Local(index) => {
+                    self.local.insert(*index, value);
+                }
+                Target::TopLevel(name) => {
+                    self.top_level.insert(name, value);
+                }
+            },
+        }
+    }
+
+    /// The signature of the procedure the head of a call refers to
+    fn signature(&self, context: &Context<'a>, head: &Node) -> Option<Signature<'a>> {
+        let known = |entry: &Option<(Arity, &'a Node)>| {
+            entry.map(|(arity, name)| Signature {
+                arity,
+                params: None,
+                definition: Some(name),
+            })
+        };
+        match &context.resolution.occurrence(head.first)?.target {
+            Target::Local(index) => known(self.local.get(index)?),
+            Target::TopLevel(name) => match self.top_level.get(name.as_str()) {
+                Some(entry) => known(entry),
+                None => builtins::lookup(name).map(|builtin| Signature {
+                    arity: builtin.arity,
+                    params: Some(builtin.params),
+                    definition: None,
+                }),
+            },
+        }
+    }
+}
+
+/// The arity of a procedure with the given formals, which may be dotted
+fn formals_arity(formals: &[Node]) -> Option<Arity> {
+    if formals
+        .iter()
+        .any(|formal| !matches!(formal.kind, NodeKind::Atom | NodeKind::Dot))
+    {
+        return None;
+    }
+    match formals
+        .iter()
+        .position(|formal| formal.kind == NodeKind::Dot)
+    {
+        Some(dot) => Some(Arity::at_least(dot)),
+        None => Some(Arity::exactly(formals.len())),
+    }
+}
+
+/// The names of the parameters of a builtin that the arguments of a call with `count` arguments
+/// are passed to
+fn parameters_of(params: &'static str, count: usize) -> Vec<&'static str> {
+    let words: Vec<&str> = params.split_whitespace().collect();
+    let repeated = words.iter().position(|&word| word == "...");
+    let (before, repeated, after) = match repeated {
+        Some(index) => (
+            &words[..index - 1],
+            Some(words[index - 1]),
+            &words[index + 1..],
+        ),
+        None => (&words[..], None, &[][..]),
+    };
+    (0..count)
+        .map(|index| {
+            if index < before.len() {
+                before[index]
+            } else if index + after.len() >= count && repeated.is_some() {
+                after[index + after.len() - count]
+            } else {
+                repeated.unwrap_or("")
+            }
+        })
+        .collect()
+}
+
+/// The calls of a text with a known signature
+fn calls<'a>(context: &Context<'a>) -> Vec<(&'a Node, &'a str, Signature<'a>)> {
+    let definitions = Definitions::new(context);
+    context
+        .expressions()
+        .into_iter()
+        .filter(|(list, _)| context.is_call(list))
+        .filter_map(|(list, _)| {
+            let head = list.children.first()?;
+            let name = context.tree.identifier(head)?;
+            let signature = definitions.signature(context, head)?;
+            Some((list, name, signature))
+        })
+        .collect()
+}
+
+pub(super) fn arity(context: &Context<'_>) -> Vec<Finding> {
+    calls(context)
+        .into_iter()
+        .filter(|(list, _, signature)| !signature.arity.accepts(list.children.len() - 1))
+        .map(|(list, name, signature)| {
+            let finding = Finding::new(
+                list.first,
+                list.last,
+                format!(
+                    "`{}` expects {} argument(s), but is given {}",
+                    name,
+                    signature.arity,
+                    list.children.len() - 1
+                ),
+            );
+            match signature.definition {
+                Some(definition) => {
+                    finding.with_related(definition.first, definition.last, "defined here")
+                }
+                None => finding,
+            }
+        })
+        .collect()
+}
+
+pub(super) fn argument_type(context: &Context<'_>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (list, name, signature) in calls(context) {
+        let params = match signature.params {
+            Some(params) => params,
+            None => continue,
+        };
+        let arguments = &list.children[1..];
+        let parameters = parameters_of(params, arguments.len());
+        for (index, (argument, parameter)) in arguments.iter().zip(parameters).enumerate() {
+            let expected = Type::of_parameter(parameter);
+            let actual = literal_type(context, argument);
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                if !expected.accepts(actual) {
+                    findings.push(Finding::new(
+                        argument.first,
+                        argument.last,
+                        format!(
+                            "`{}` expects {} as argument {}, but is given {}",
+                            name,
+                            expected,
+                            index + 1,
+                            actual
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    findings
+}
+
+pub(super) fn not_a_procedure(context: &Context<'_>) -> Vec<Finding> {
+    context
+        .expressions()
+        .into_iter()
+        .filter_map(|(list, _)| {
+            let head = list.children.first()?;
+            match literal_type(context, head)? {
+                Type::Procedure => None,
+                actual => Some(Finding::new(
+                    head.first,
+                    head.last,
+                    format!(
+                        "`{}` is called, but it is {}, not a procedure",
+                        context.text_of(head),
+                        actual
+                    ),
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parameters_of;
+    use crate::lint::Linter;
+
+    /// The messages of the warnings of the rule `name` about `text`, with their lines and columns
+    fn lint(name: &str, text: &str) -> Vec<(usize, usize, String)> {
+        Linter::new()
+            .lint(text)
+            .into_iter()
+            .filter(|lint| lint.rule == name)
+            .map(|lint| (lint.location.line, lint.location.column, lint.message))
+            .collect()
+    }
+
+    fn warning(line: usize, column: usize, message: &str) -> (usize, usize, String) {
+        (line, column, message.to_string())
+    }
+
+    #[test]
+    fn parameters_test() {
+        assert_eq!(parameters_of("z1 z2 ...", 3), vec!["z1", "z2", "z2"]);
+        assert_eq!(
+            parameters_of("proc arg1 ... args", 4),
+            vec!["proc", "arg1", "arg1", "args"]
+        );
+        assert_eq!(parameters_of("proc arg1 ... args", 2), vec!["proc", "args"]);
+        assert_eq!(
+            parameters_of("string [radix]", 2),
+            vec!["string", "[radix]"]
+        );
+    }
+
+    #[test]
+    fn arity_test() {
+        let text = "\
+(define (f a b . c) a)
+(define g (lambda (x) x))
+(define (h) (define (i y) y) (i 1 2))
+(f 1) (f 1 2 3 4) (g) (car 1 2)
+(define g 5)";
+        assert_eq!(
+            lint("arity", text),
+            vec![
+                warning(3, 29, "`i` expects 1 argument(s), but is given 2"),
+                warning(4, 0, "`f` expects at least 2 argument(s), but is given 1"),
+                warning(4, 22, "`car` expects 1 argument(s), but is given 2"),
+            ]
+        );
+        // Locally bound and reassigned names are not checked against builtins
+        assert_eq!(
+            lint(
+                "arity",
+                "(let ((car cdr)) (car 1 2)) (set! cons list) (cons 1 2 3)"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn argument_type_test() {
+        assert_eq!(
+            lint(
+                "argument-type",
+                "(+ \"a\" 1) (car 5) (car '(1)) (cdr '()) (vector-ref #(1) 'x) (apply + 1 '(2))\n\
+                 (string-append \"a\" #\\b) (map car 5) (length '(1 . 2)) (display (lambda () 1))"
+            ),
+            vec![
+                warning(
+                    1,
+                    3,
+                    "`+` expects a number as argument 1, but is given a string"
+                ),
+                warning(
+                    1,
+                    15,
+                    "`car` expects a pair as argument 1, but is given a number"
+                ),
+                warning(
+                    1,
+                    34,
+                    "`cdr` expects a pair as argument 1, but is given the empty list"
+                ),
+                warning(
+                    1,
+                    56,
+                    "`vector-ref` expects a number as argument 2, but is given a symbol"
+                ),
+                warning(
+                    2,
+                    19,
+                    "`string-append` expects a string as argument 2, but is given a character"
+                ),
+                warning(
+                    2,
+                    33,
+                    "`map` expects a list as argument 2, but is given a number"
+                ),
+                warning(
+                    2,
+                    44,
+                    "`length` expects a list as argument 1, but is given a pair"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_a_procedure_test() {
+        assert_eq!(
+            lint(
+                "not-a-procedure",
+                "(1 2) (\"f\") ('(a) 1) ((lambda (x) x) 1)\n(cond (#t 1)) (case 1 ((1 2) 3))"
+            ),
+            vec![
+                warning(1, 1, "`1` is called, but it is a number, not a procedure"),
+                warning(
+                    1,
+                    7,
+                    "`\"f\"` is called, but it is a string, not a procedure"
+                ),
+                warning(1, 13, "`'(a)` is called, but it is a list, not a procedure"),
+            ]
+        );
+    }
+}
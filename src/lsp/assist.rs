@@ -0,0 +1,512 @@
+//! Completion, hover and signature help
+//!
+//! Procedures defined in the workspace are described by their definition: the parameters of
+//! `(define (name parameter ...) ...)` or `(define name (lambda ...))`, and the `;;` comment lines
+//! right before it. Builtins and special forms are described by the registries the interpreter
+//! is built from, so that the server knows about exactly the procedures the runtime has.
+
+use super::{Document, ResponseError, Server};
+use crate::builtins;
+use crate::interpreter::SPECIAL_FORMS;
+use crate::lexer::Token;
+use crate::scope::Target;
+use crate::syntax_tree::{Node, NodeKind, SyntaxTree};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// `CompletionItemKind.Function`
+const COMPLETION_FUNCTION: u32 = 3;
+/// `CompletionItemKind.Variable`
+const COMPLETION_VARIABLE: u32 = 6;
+/// `CompletionItemKind.Keyword`, used for special forms and syntax definitions
+const COMPLETION_KEYWORD: u32 = 14;
+
+/// What an identifier refers to, as shown to the user
+struct Description {
+    /// `(name parameter ...)` for procedures and special forms, or the name of a variable
+    label: String,
+    /// The byte ranges of the parameters in `label`
+    parameters: Vec<Range<usize>>,
+    /// The index of the parameter that may be repeated, if any
+    repeated: Option<usize>,
+    /// The `CompletionItemKind` of the binding
+    kind: u32,
+    /// The documentation, in Markdown
+    doc: Option<String>,
+}
+
+impl Description {
+    fn variable(name: &str) -> Self {
+        Description {
+            label: name.to_string(),
+            parameters: Vec::new(),
+            repeated: None,
+            kind: COMPLETION_VARIABLE,
+            doc: None,
+        }
+    }
+
+    /// Describes a builtin or special form from the parameters listed in its registry, in the
+    /// notation of `Builtin::params`
+    fn from_params(name: &str, params: &str, kind: u32, doc: &str) -> Self {
+        let mut description = Description {
+            label: format!("({}", name),
+            parameters: Vec::new(),
+            repeated: None,
+            kind,
+            doc: Some(doc.to_string()),
+        };
+        for param in split_params(params) {
+            description.label.push(' ');
+            if param == "..." {
+                description.repeated = description.parameters.len().checked_sub(1);
+            } else {
+                let start = description.label.len();
+                description.parameters.push(start..start + param.len());
+            }
+            description.label.push_str(param);
+        }
+        description.label.push(')');
+        description
+    }
+
+    /// Describes a procedure from its name and parameter list; the parameter following a dot, or
+    /// a single identifier instead of a list, takes the remaining arguments
+    fn procedure(tree: &SyntaxTree, name: &str, formals: &Node, skip: usize) -> Self {
+        let mut description = Description::variable(name);
+        description.label.insert(0, '(');
+        description.kind = COMPLETION_FUNCTION;
+        let mut rest = formals.kind == NodeKind::Atom;
+        let formals = match formals.kind {
+            NodeKind::Atom => std::slice::from_ref(formals),
+            _ => formals.children.get(skip..).unwrap_or_default(),
+        };
+        if rest {
+            description.label.push_str(" .");
+        }
+        for formal in formals {
+            if formal.kind == NodeKind::Dot {
+                description.label.push_str(" .");
+                rest = true;
+            } else if let Some(formal) = tree.identifier(formal) {
+                description.label.push(' ');
+                let start = description.label.len();
+                description.parameters.push(start..start + formal.len());
+                description.label.push_str(formal);
+                if rest {
+                    description.repeated = Some(description.parameters.len() - 1);
+                }
+            }
+        }
+        description.label.push(')');
+        description
+    }
+
+    /// Describes the binding introduced by a `define` or `define-syntax` form
+    fn definition(document: &Document, form: &Node, name: &str) -> Self {
+        let tree = document.tree();
+        let elements = &form.children;
+        let keyword = tree.identifier(&elements[0]);
+        let lambda = elements.get(2).filter(|value| {
+            let head = value.children.first();
+            matches!(value.kind, NodeKind::List { .. })
+                && head.and_then(|head| tree.identifier(head)) == Some("lambda")
+        });
+        let mut description = match (keyword, elements[1].kind, lambda) {
+            (Some("define-syntax"), _, _) => Description {
+                kind: COMPLETION_KEYWORD,
+                ..Description::variable(name)
+            },
+            (_, NodeKind::List { .. }, _) => Description::procedure(tree, name, &elements[1], 1),
+            (_, _, Some(lambda)) => match lambda.children.get(1) {
+                Some(formals) => Description::procedure(tree, name, formals, 0),
+                None => Description::variable(name),
+            },
+            _ => Description::variable(name),
+        };
+        description.doc = document.doc_comment(form);
+        description
+    }
+
+    /// The index of the parameter receiving the argument at `index`
+    fn parameter_of(&self, index: usize) -> usize {
+        match self.repeated {
+            Some(repeated) if index > repeated => repeated,
+            _ => index,
+        }
+    }
+
+    /// The Markdown shown when hovering over an identifier
+    fn markdown(&self) -> String {
+        let mut markdown = format!("```scheme\n{}\n```", self.label);
+        if let Some(doc) = &self.doc {
+            markdown.push_str("\n\n");
+            markdown.push_str(doc);
+        }
+        markdown
+    }
+
+    fn completion_item(&self, name: &str) -> Value {
+        let mut item = json!({"label": name, "kind": self.kind});
+        if self.label != name {
+            item["detail"] = json!(self.label);
+        }
+        if let Some(doc) = &self.doc {
+            item["documentation"] = json!({"kind": "markdown", "value": doc});
+        }
+        item
+    }
+}
+
+/// Splits a parameter list on whitespace outside of parentheses
+fn split_params(params: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (index, c) in params.char_indices() {
+        match c {
+            ' ' if depth == 0 => {
+                parts.extend(start.take().map(|start| &params[start..index]));
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    parts.extend(start.map(|start| &params[start..]));
+    parts
+}
+
+impl Server {
+    /// Describes what the identifier token at `index` of `document` refers to
+    fn describe(&self, document: &Document, index: usize) -> Option<Description> {
+        let name = match &document.tree().token(index).token {
+            Token::Identifier(name) => name.as_str(),
+            _ => return None,
+        };
+        match document
+            .resolution()
+            .occurrence(index)
+            .map(|occurrence| &occurrence.target)
+        {
+            Some(Target::Local(binding)) => {
+                let token = document.resolution().bindings[*binding].token;
+                Some(self.describe_binding(document, token, name))
+            }
+            Some(target @ Target::TopLevel(_)) => self
+                .documents
+                .values()
+                .find_map(|document| {
+                    let definition = document
+                        .resolution()
+                        .occurrences_of(target)
+                        .find(|occurrence| occurrence.definition)?;
+                    let form = document.definition_form(definition.token)?;
+                    Some(Description::definition(document, form, name))
+                })
+                .or_else(|| describe_global(name)),
+            // Keywords of special forms are not resolved
+            None => describe_global(name),
+        }
+    }
+
+    /// Describes a local binding from the identifier introducing it
+    fn describe_binding(&self, document: &Document, token: usize, name: &str) -> Description {
+        match document.definition_form(token) {
+            Some(form) => Description::definition(document, form, name),
+            None => Description::variable(name),
+        }
+    }
+
+    /// Answers `textDocument/completion` with the identifiers starting like the one being typed:
+    /// the local bindings in scope, the top-level definitions of the workspace, the builtins and
+    /// the keywords of special forms
+    pub(super) fn completion(&self, params: &Value) -> Result<Value, ResponseError> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        let cursor = match document.line_and_column(position) {
+            Some(cursor) => cursor,
+            None => return Ok(Value::Null),
+        };
+        let before = document.token_before(cursor);
+        if let Some(index) = before {
+            let inside = match document.tree().token(index).token {
+                Token::Comment => cursor <= document.token_end(index),
+                Token::String(_) | Token::Character(_) => cursor < document.token_end(index),
+                _ => false,
+            };
+            if inside {
+                return Ok(json!([]));
+            }
+        }
+        let prefix = match document.name_at(position) {
+            Some((name, index)) => {
+                let typed = cursor.1 - document.token_start(index).1;
+                name.get(..typed).unwrap_or(name)
+            }
+            None => "",
+        };
+        let mut names = HashSet::new();
+        let mut items = Vec::new();
+        let mut add = |name: &str, description: Description| {
+            if name.starts_with(prefix) && names.insert(name.to_string()) {
+                items.push(description.completion_item(name));
+            }
+        };
+        for binding in
+            before.map_or_else(Vec::new, |index| document.resolution().bindings_at(index))
+        {
+            add(
+                &binding.name,
+                self.describe_binding(document, binding.token, &binding.name),
+            );
+        }
+        for document in self.documents.values() {
+            for occurrence in &document.resolution().occurrences {
+                let name = match &occurrence.target {
+                    Target::TopLevel(name) if occurrence.definition => name,
+                    _ => continue,
+                };
+                if let Some(form) = document.definition_form(occurrence.token) {
+                    add(name, Description::definition(document, form, name));
+                }
+            }
+        }
+        for builtin in builtins::all() {
+            add(builtin.name, describe_global(builtin.name).unwrap());
+        }
+        for form in SPECIAL_FORMS {
+            add(form.name, describe_global(form.name).unwrap());
+        }
+        Ok(Value::from(items))
+    }
+
+    /// Answers `textDocument/hover` with the parameters and documentation of what the identifier
+    /// refers to
+    pub(super) fn hover(&self, params: &Value) -> Result<Value, ResponseError> {
+        let document = self.document(params)?;
+        let index = match document.name_at(&params["position"]) {
+            Some((_, index)) => index,
+            None => return Ok(Value::Null),
+        };
+        Ok(match self.describe(document, index) {
+            Some(description) => json!({
+                "contents": {"kind": "markdown", "value": description.markdown()},
+                "range": document.token_range(index),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    /// Answers `textDocument/signatureHelp` with the parameters of the procedure called by the
+    /// innermost list around the cursor, and the one receiving the argument being typed
+    pub(super) fn signature_help(&self, params: &Value) -> Result<Value, ResponseError> {
+        let document = self.document(params)?;
+        let cursor = match document.line_and_column(&params["position"]) {
+            Some(cursor) => cursor,
+            None => return Ok(Value::Null),
+        };
+        let list = match document.enclosing_list(cursor) {
+            Some(list) => list,
+            None => return Ok(Value::Null),
+        };
+        let (head, arguments) = match list.children.split_first() {
+            // The operator itself is being typed
+            Some((head, _)) if cursor <= document.token_end(head.last) => return Ok(Value::Null),
+            Some((head, arguments)) if head.kind == NodeKind::Atom => (head, arguments),
+            _ => return Ok(Value::Null),
+        };
+        let description = match self.describe(document, head.first) {
+            Some(description) if description.label.starts_with('(') => description,
+            _ => return Ok(Value::Null),
+        };
+        // Arguments ending right at the cursor are still being typed
+        let argument = arguments
+            .iter()
+            .filter(|argument| document.token_end(argument.last) < cursor)
+            .count();
+        let utf16 = |offset: usize| description.label[..offset].encode_utf16().count();
+        let parameters: Vec<Value> = description
+            .parameters
+            .iter()
+            .map(|range| json!({"label": [utf16(range.start), utf16(range.end)]}))
+            .collect();
+        let mut signature = json!({"label": description.label, "parameters": parameters});
+        if let Some(doc) = &description.doc {
+            signature["documentation"] = json!({"kind": "markdown", "value": doc});
+        }
+        Ok(json!({
+            "signatures": [signature],
+            "activeSignature": 0,
+            "activeParameter": description.parameter_of(argument),
+        }))
+    }
+}
+
+/// Describes a builtin or special form
+fn describe_global(name: &str) -> Option<Description> {
+    if let Some(builtin) = builtins::lookup(name) {
+        return Some(Description::from_params(
+            name,
+            builtin.params,
+            COMPLETION_FUNCTION,
+            builtin.doc,
+        ));
+    }
+    let form = SPECIAL_FORMS.iter().find(|form| form.name == name)?;
+    Some(Description::from_params(
+        name,
+        form.syntax,
+        COMPLETION_KEYWORD,
+        form.doc,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::split_params;
+    use crate::lsp::Server;
+    use serde_json::{json, Value};
+
+    fn server(text: &str) -> Server {
+        let mut server = Server::new();
+        server.handle(&json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {}}));
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {
+                "uri": "file:///a.scm", "languageId": "scheme", "version": 1, "text": text,
+            }},
+        }));
+        server
+    }
+
+    fn request(server: &mut Server, method: &str, line: u32, character: u32) -> Value {
+        let response = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": method,
+            "params": {
+                "textDocument": {"uri": "file:///a.scm"},
+                "position": {"line": line, "character": character},
+            },
+        }));
+        response[0]["result"].clone()
+    }
+
+    fn labels(items: &Value) -> Vec<&str> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn split_params_test() {
+        assert_eq!(split_params("obj [port]"), vec!["obj", "[port]"]);
+        assert_eq!(
+            split_params("specs (test expression ...) command ..."),
+            vec!["specs", "(test expression ...)", "command", "..."]
+        );
+        assert!(split_params("").is_empty());
+    }
+
+    #[test]
+    fn completion_test() {
+        let text = "(define (square value) (* value value))\n\
+                    (define (f vector-size) (let ((ve 1)) (ve)))\n(f 'vec) ; ve";
+        let mut server = server(text);
+        let items = request(&mut server, "textDocument/completion", 1, 41);
+        assert_eq!(
+            labels(&items),
+            vec![
+                "ve",
+                "vector-size",
+                "vector?",
+                "vector",
+                "vector-length",
+                "vector-ref",
+                "vector-set!",
+                "vector->list",
+                "vector-fill!"
+            ]
+        );
+        assert_eq!(items[2]["detail"], "(vector? obj)");
+        assert_eq!(items[2]["kind"], 3);
+        let items = request(&mut server, "textDocument/completion", 1, 4);
+        assert_eq!(labels(&items), vec!["define", "define-library"]);
+        assert_eq!(items[0]["kind"], 14);
+        let items = request(&mut server, "textDocument/completion", 0, 24);
+        assert_eq!(labels(&items)[..2], ["value", "square"]);
+        assert_eq!(items[1]["detail"], "(square value)");
+        // Nothing is completed in comments
+        let items = request(&mut server, "textDocument/completion", 2, 13);
+        assert_eq!(items, json!([]));
+    }
+
+    #[test]
+    fn hover_test() {
+        let text = ";; Squares a number.\n;; Works on any number.\n\
+                    (define (square x) (* x x))\n(define area (lambda (r . rest) (square r)))\n";
+        let mut server = server(text);
+        let hover = request(&mut server, "textDocument/hover", 3, 34);
+        assert_eq!(
+            hover["contents"]["value"],
+            "```scheme\n(square x)\n```\n\nSquares a number.\nWorks on any number."
+        );
+        assert_eq!(hover["range"]["start"], json!({"line": 3, "character": 33}));
+        let hover = request(&mut server, "textDocument/hover", 3, 9);
+        assert_eq!(
+            hover["contents"]["value"],
+            "```scheme\n(area r . rest)\n```"
+        );
+        let hover = request(&mut server, "textDocument/hover", 2, 20);
+        assert_eq!(
+            hover["contents"]["value"],
+            "```scheme\n(* z1 ...)\n```\n\nReturns the product of the numbers, 1 if there are none."
+        );
+        let hover = request(&mut server, "textDocument/hover", 3, 14);
+        assert_eq!(
+            hover["contents"]["value"],
+            "```scheme\n(lambda formals body ...)\n```\n\n\
+             Returns a procedure taking the arguments named by formals and evaluating body."
+        );
+        assert_eq!(
+            request(&mut server, "textDocument/hover", 0, 3),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn signature_help_test() {
+        let text = "(define (f a b . rest) a)\n(f 1 (car x) 3 4)\n(display \"x\" \n";
+        let mut server = server(text);
+        let help = request(&mut server, "textDocument/signatureHelp", 1, 5);
+        assert_eq!(help["signatures"][0]["label"], "(f a b . rest)");
+        assert_eq!(
+            help["signatures"][0]["parameters"],
+            json!([{"label": [3, 4]}, {"label": [5, 6]}, {"label": [9, 13]}])
+        );
+        assert_eq!(help["activeParameter"], 1);
+        // Rest arguments all go to the rest parameter
+        let help = request(&mut server, "textDocument/signatureHelp", 1, 16);
+        assert_eq!(help["activeParameter"], 2);
+        // The innermost call is the one described
+        let help = request(&mut server, "textDocument/signatureHelp", 1, 11);
+        assert_eq!(help["signatures"][0]["label"], "(car pair)");
+        assert_eq!(help["activeParameter"], 0);
+        // Still typing `1`
+        let help = request(&mut server, "textDocument/signatureHelp", 1, 4);
+        assert_eq!(help["activeParameter"], 0);
+        let help = request(&mut server, "textDocument/signatureHelp", 2, 13);
+        assert_eq!(help["signatures"][0]["label"], "(display obj [port])");
+        assert_eq!(help["activeParameter"], 1);
+        assert_eq!(
+            request(&mut server, "textDocument/signatureHelp", 1, 1),
+            Value::Null
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! Documents open in the editor or found in the workspace, and what the language server reports
+//! about them
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Token;
+use crate::reader::DatumIterator;
+use crate::scope::{self, Occurrence, Resolution};
+use crate::source_map::{SourceMap, Span};
+use crate::syntax_tree::{Node, NodeKind, SyntaxTree, TextEdit};
+use crate::CompilerError;
+use serde_json::{json, Value};
+use std::iter;
+
+/// `DiagnosticSeverity.Error`
+const SEVERITY_ERROR: u32 = 1;
+/// `SymbolKind.Function`, also used for syntax
+const SYMBOL_FUNCTION: u32 = 12;
+/// `SymbolKind.Variable`
+const SYMBOL_VARIABLE: u32 = 13;
+
+/// The text of a document, with its syntax tree, the bindings of its identifiers, and the errors
+/// met reading it
+pub struct Document {
+    /// The URI the client knows the document by
+    pub uri: String,
+    /// The version of the text given by the client
+    pub version: i64,
+    text: String,
+    /// The byte offset at which each line starts
+    line_starts: Vec<usize>,
+    tree: SyntaxTree,
+    resolution: Resolution,
+    /// The parse errors, if the text could be lexed
+    parse_errors: Vec<CompilerError>,
+}
+
+impl Document {
+    /// Lexes, parses and resolves `text`
+    pub fn new(uri: String, text: String, version: i64) -> Self {
+        let tree = SyntaxTree::parse(&text);
+        Document::with_tree(uri, text, version, tree)
+    }
+
+    /// Applies a `TextDocumentContentChangeEvent`, which replaces either a range of the text or
+    /// all of it; only the lines and forms a range touches are lexed and parsed again
+    pub fn change(self, change: &Value, version: i64) -> Self {
+        let replacement = change["text"].as_str().unwrap_or_default();
+        let range = &change["range"];
+        let (start, end) = match (
+            self.line_and_column(&range["start"]),
+            self.line_and_column(&range["end"]),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Document::new(self.uri, replacement.to_string(), version),
+        };
+        let Document {
+            uri,
+            mut text,
+            mut tree,
+            ..
+        } = self;
+        let edit = TextEdit {
+            start,
+            end,
+            text: replacement.to_string(),
+        };
+        tree.edit(&mut text, &edit);
+        Document::with_tree(uri, text, version, tree)
+    }
+
+    /// Resolves the tree of `text`
+    fn with_tree(uri: String, text: String, version: i64, tree: SyntaxTree) -> Self {
+        let line_starts = iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let parse_errors = match tree.lex_error() {
+            Some(_) => Vec::new(),
+            None => parse_errors(&tree),
+        };
+        let resolution = Resolution::new(&tree);
+        Document {
+            uri,
+            version,
+            text,
+            line_starts,
+            tree,
+            resolution,
+            parse_errors,
+        }
+    }
+
+    /// The syntax tree of the document
+    pub fn tree(&self) -> &SyntaxTree {
+        &self.tree
+    }
+
+    /// The bindings of the identifiers of the document
+    pub fn resolution(&self) -> &Resolution {
+        &self.resolution
+    }
+
+    /// The text of a 1-based line, without its line terminator
+    fn line_text(&self, line: usize) -> &str {
+        let start = match self.line_starts.get(line.wrapping_sub(1)) {
+            Some(&start) => start,
+            None => return "",
+        };
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |&next| next - 1);
+        self.text[start..end].trim_end_matches('\r')
+    }
+
+    /// The LSP position of a byte column on a 1-based line, which counts UTF-16 code units
+    pub fn position(&self, line: usize, column: usize) -> Value {
+        json!({
+            "line": line.saturating_sub(1),
+            "character": self.utf16_column(line, column),
+        })
+    }
+
+    /// The number of UTF-16 code units before a byte column on a 1-based line
+    pub fn utf16_column(&self, line: usize, column: usize) -> usize {
+        let text = self.line_text(line);
+        let mut column = column.min(text.len());
+        while !text.is_char_boundary(column) {
+            column -= 1;
+        }
+        text[..column].encode_utf16().count()
+    }
+
+    /// The 1-based line and byte column of an LSP position
+    pub fn line_and_column(&self, position: &Value) -> Option<(usize, usize)> {
+        let line = position["line"].as_u64()? as usize + 1;
+        let character = position["character"].as_u64()? as usize;
+        let text = self.line_text(line);
+        let mut units = 0;
+        for (column, c) in text.char_indices() {
+            if units >= character {
+                return Some((line, column));
+            }
+            units += c.len_utf16();
+        }
+        Some((line, text.len()))
+    }
+
+    /// The LSP range of `len` bytes from a byte column on a 1-based line
+    pub fn range(&self, line: usize, column: usize, len: usize) -> Value {
+        json!({
+            "start": self.position(line, column),
+            "end": self.position(line, column + len),
+        })
+    }
+
+    /// The LSP range of the token at `index`
+    pub fn token_range(&self, index: usize) -> Value {
+        let token = self.tree.token(index);
+        self.range(token.span.line, token.span.column, token.span.len)
+    }
+
+    /// The LSP range of a node, from its first token to its last
+    pub fn node_range(&self, node: &Node) -> Value {
+        let first = self.tree.token(node.first);
+        let last = self.tree.token(node.last);
+        json!({
+            "start": self.position(first.span.line, first.span.column),
+            "end": self.position(last.span.line, last.span.column + last.span.len),
+        })
+    }
+
+    /// The LSP location of the token at `index`
+    pub fn location(&self, index: usize) -> Value {
+        json!({"uri": self.uri, "range": self.token_range(index)})
+    }
+
+    /// The empty LSP range at the end of the text
+    fn end_range(&self) -> Value {
+        let line = self.line_starts.len();
+        self.range(line, self.line_text(line).len(), 0)
+    }
+
+    /// The index of the identifier token at an LSP position, or ending right before it
+    fn identifier_at(&self, position: &Value) -> Option<usize> {
+        let (line, column) = self.line_and_column(position)?;
+        let tokens = self.tree.tokens();
+        let start = tokens.partition_point(|token| {
+            (token.span.line, token.span.column + token.span.len) < (line, column)
+        });
+        (start..tokens.len().min(start + 2)).find(|&index| {
+            let token = &tokens[index];
+            matches!(token.token, Token::Identifier(_))
+                && token.span.line == line
+                && token.span.column <= column
+                && column <= token.span.column + token.span.len
+        })
+    }
+
+    /// The identifier used as a variable at an LSP position, and the index of its token
+    pub fn occurrence_at(&self, position: &Value) -> Option<&Occurrence> {
+        self.resolution.occurrence(self.identifier_at(position)?)
+    }
+
+    /// The name of the identifier at an LSP position, or ending right before it, and the index of
+    /// its token
+    pub fn name_at(&self, position: &Value) -> Option<(&str, usize)> {
+        let index = self.identifier_at(position)?;
+        match &self.tree.token(index).token {
+            Token::Identifier(name) => Some((name, index)),
+            _ => None,
+        }
+    }
+
+    /// The index of the last token starting before a 1-based line and byte column
+    pub fn token_before(&self, cursor: (usize, usize)) -> Option<usize> {
+        let tokens = self.tree.tokens();
+        tokens
+            .partition_point(|token| (token.span.line, token.span.column) < cursor)
+            .checked_sub(1)
+    }
+
+    /// The 1-based line and byte column at which the token at `index` starts
+    pub fn token_start(&self, index: usize) -> (usize, usize) {
+        let token = self.tree.token(index);
+        (token.span.line, token.span.column)
+    }
+
+    /// The 1-based line and byte column at which the token at `index` ends
+    pub fn token_end(&self, index: usize) -> (usize, usize) {
+        let token = self.tree.token(index);
+        (token.span.line, token.span.column + token.span.len)
+    }
+
+    /// The innermost list containing a 1-based line and byte column, between its parentheses,
+    /// without looking into quoted data
+    pub fn enclosing_list(&self, cursor: (usize, usize)) -> Option<&Node> {
+        let mut nodes = self.tree.forms();
+        let mut list = None;
+        while let Some(node) = nodes.iter().find(|node| self.contains(node, cursor)) {
+            if let NodeKind::List { .. } = node.kind {
+                list = Some(node);
+            }
+            if self.tree.prefix(node) == Some("'") {
+                break;
+            }
+            nodes = &node.children;
+        }
+        list
+    }
+
+    /// Whether a 1-based line and byte column lies within a node with children, after its first
+    /// token
+    fn contains(&self, node: &Node, cursor: (usize, usize)) -> bool {
+        let inside = match node.kind {
+            NodeKind::List { closed: true } | NodeKind::Vector { closed: true } => {
+                cursor <= self.token_start(node.last)
+            }
+            NodeKind::List { closed: false } | NodeKind::Vector { closed: false } => true,
+            NodeKind::Abbreviation => cursor <= self.token_end(node.last),
+            _ => false,
+        };
+        inside && self.token_start(node.first) < cursor
+    }
+
+    /// The `define` or `define-syntax` form whose name is the identifier token at `index`
+    pub fn definition_form(&self, index: usize) -> Option<&Node> {
+        let mut nodes = self.tree.forms();
+        loop {
+            let node = nodes
+                .iter()
+                .find(|node| node.first < index && index <= node.last)?;
+            let keyword = node
+                .children
+                .first()
+                .and_then(|head| self.tree.identifier(head));
+            let name = scope::definition_name(&node.children);
+            if matches!(keyword, Some("define") | Some("define-syntax"))
+                && name.is_some_and(|name| name.first == index)
+            {
+                return Some(node);
+            }
+            nodes = &node.children;
+        }
+    }
+
+    /// The text of the `;;` comment lines right before a form, if it starts its line
+    pub fn doc_comment(&self, form: &Node) -> Option<String> {
+        let (line, column) = self.token_start(form.first);
+        if !self.line_text(line)[..column].trim().is_empty() {
+            return None;
+        }
+        let mut lines = Vec::new();
+        for previous in (1..line).rev() {
+            match self.line_text(previous).trim_start().strip_prefix(";;") {
+                Some(comment) => lines.push(comment.trim_start_matches(';').trim()),
+                None => break,
+            }
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        lines.reverse();
+        Some(lines.join("\n"))
+    }
+
+    /// The diagnostics to publish for the document
+    ///
+    /// They are those the command line reports, with their secondary spans as related
+    /// information, for every parse error rather than only the first. Text that cannot be lexed
+    /// stops the lexer, so only its error is reported.
+    pub fn diagnostics(&self) -> Vec<Value> {
+        let errors: Vec<&CompilerError> = match self.tree.lex_error() {
+            Some(error) => vec![error],
+            None => self.parse_errors.iter().collect(),
+        };
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_snippet(self.uri.as_str(), self.text.as_str());
+        errors
+            .into_iter()
+            .map(|error| {
+                let diagnostic = Diagnostic::from_compiler_error(error, Some(file), &source_map);
+                self.publish(&diagnostic, &source_map)
+            })
+            .collect()
+    }
+
+    /// A `Diagnostic` of the document in the form LSP publishes it
+    fn publish(&self, diagnostic: &Diagnostic, source_map: &SourceMap) -> Value {
+        let range = diagnostic
+            .primary
+            .and_then(|span| self.span_range(span, source_map))
+            .unwrap_or_else(|| self.end_range());
+        let related: Vec<Value> = diagnostic
+            .secondary
+            .iter()
+            .filter_map(|label| {
+                Some(json!({
+                    "location": {
+                        "uri": self.uri,
+                        "range": self.span_range(label.span, source_map)?,
+                    },
+                    "message": label.message,
+                }))
+            })
+            .collect();
+        let mut published = json!({
+            "range": range,
+            "severity": SEVERITY_ERROR,
+            "code": diagnostic.code,
+            "source": "oxyscheme",
+            "message": diagnostic.message,
+        });
+        if !related.is_empty() {
+            published["relatedInformation"] = Value::from(related);
+        }
+        published
+    }
+
+    /// The LSP range of a span of the text, registered in `source_map`
+    fn span_range(&self, span: Span, source_map: &SourceMap) -> Option<Value> {
+        let end = source_map.end(span)?;
+        Some(json!({
+            "start": self.position(span.line, span.column),
+            "end": self.position(end.line, end.column),
+        }))
+    }
+
+    /// The symbols defined by the top-level `define` and `define-syntax` forms
+    pub fn symbols(&self) -> Vec<Value> {
+        self.tree
+            .forms()
+            .iter()
+            .filter_map(|form| self.definition(form))
+            .collect()
+    }
+
+    /// The symbol defined by a top-level form, if it is a definition
+    fn definition(&self, form: &Node) -> Option<Value> {
+        let elements = &form.children;
+        let keyword = self.tree.identifier(elements.first()?)?;
+        if keyword != "define" && keyword != "define-syntax" {
+            return None;
+        }
+        let name = scope::definition_name(elements)?;
+        let (kind, detail) = match (keyword, elements[1].kind, elements.get(2)) {
+            ("define-syntax", _, _) => (SYMBOL_FUNCTION, Some("syntax")),
+            (_, NodeKind::List { .. }, _) => (SYMBOL_FUNCTION, None),
+            (_, _, Some(value)) if self.is_lambda(value) => (SYMBOL_FUNCTION, None),
+            _ => (SYMBOL_VARIABLE, None),
+        };
+        let mut symbol = json!({
+            "name": self.tree.identifier(name)?,
+            "kind": kind,
+            "range": self.node_range(form),
+            "selectionRange": self.token_range(name.first),
+        });
+        if let Some(detail) = detail {
+            symbol["detail"] = json!(detail);
+        }
+        Some(symbol)
+    }
+
+    fn is_lambda(&self, node: &Node) -> bool {
+        let head = node.children.first();
+        matches!(node.kind, NodeKind::List { .. })
+            && head.and_then(|head| self.tree.identifier(head)) == Some("lambda")
+    }
+}
+
+/// The errors met parsing the top-level forms of `tree`: after each error, parsing resumes with
+/// the form following the one it is in
+fn parse_errors(tree: &SyntaxTree) -> Vec<CompilerError> {
+    let mut errors = Vec::new();
+    let mut forms = tree.forms();
+    while let Some(form) = forms.first() {
+        let tokens = tree.tokens()[form.first..].iter().cloned().map(Ok);
+        let error = match DatumIterator::new(tokens).find_map(Result::err) {
+            Some(error) => error,
+            None => break,
+        };
+        let span = match &error {
+            CompilerError::UnexpectedToken(span, _) => Some(*span),
+            CompilerError::MissingCloseParen(unclosed) => Some(unclosed.last),
+            _ => None,
+        };
+        let resumed = span.and_then(|span| {
+            forms.iter().position(|form| {
+                let last = tree.token(form.last).span;
+                (last.line, last.column) >= (span.line, span.column)
+            })
+        });
+        errors.push(error);
+        match resumed {
+            Some(index) => forms = &forms[index + 1..],
+            None => break,
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::Document;
+    use serde_json::json;
+
+    fn open(text: &str) -> Document {
+        Document::new(String::from("file:///a.scm"), String::from(text), 1)
+    }
+
+    #[test]
+    fn utf16_position_test() {
+        let document = open("(display \"λé😀\") #\\nope\n");
+        // The error starts at byte 21, after two 2-byte characters and a 4-byte one, which are
+        // one, one and two UTF-16 code units
+        assert_eq!(
+            document.diagnostics()[0]["range"],
+            json!({
+                "start": {"line": 0, "character": 17},
+                "end": {"line": 0, "character": 23},
+            })
+        );
+        assert_eq!(document.diagnostics()[0]["code"], "E0001");
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let document = open("(define x 1)\n(f (g x)\n  (h y)\n");
+        let diagnostic = &document.diagnostics()[0];
+        assert_eq!(diagnostic["code"], "E0004");
+        assert_eq!(
+            diagnostic["range"],
+            json!({
+                "start": {"line": 1, "character": 0},
+                "end": {"line": 1, "character": 1},
+            })
+        );
+        let document = open("(a b))\n(b . c d)\n#(e . f)\n(g");
+        let codes: Vec<_> = document
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| diagnostic["code"].clone())
+            .collect();
+        assert_eq!(codes, ["E0003", "E0004", "E0003", "E0004"]);
+        assert_eq!(document.diagnostics()[0]["code"], "E0003");
+        assert_eq!(
+            document.diagnostics()[0]["range"]["start"],
+            json!({"line": 0, "character": 5})
+        );
+        let document = open("(define (f)\n  (display 1)\n(f)\n");
+        let diagnostic = &document.diagnostics()[0];
+        assert_eq!(
+            diagnostic["message"],
+            "Missing close paren at the end of line 2, judging by the indentation"
+        );
+        assert_eq!(
+            diagnostic["range"]["start"],
+            json!({"line": 1, "character": 12})
+        );
+        assert_eq!(
+            diagnostic["relatedInformation"][1]["location"]["range"]["start"],
+            json!({"line": 2, "character": 0})
+        );
+        let document = open("(a b) ; fine\n");
+        assert!(document.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn symbols_test() {
+        let text = "(define (square x)\n  (* x x))\n'(define quoted 1)\n(define pi 3.14)\n\
+                    (define-syntax swap! (syntax-rules ()))\n(define id (lambda (x) x))\n\
+                    (let () (define inner 1))\n";
+        let symbols = open(text).symbols();
+        let names: Vec<_> = symbols
+            .iter()
+            .map(|symbol| {
+                (
+                    symbol["name"].as_str().unwrap(),
+                    symbol["kind"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![("square", 12), ("pi", 13), ("swap!", 12), ("id", 12)]
+        );
+        assert_eq!(
+            symbols[0]["range"],
+            json!({
+                "start": {"line": 0, "character": 0},
+                "end": {"line": 1, "character": 10},
+            })
+        );
+        assert_eq!(
+            symbols[0]["selectionRange"],
+            json!({
+                "start": {"line": 0, "character": 9},
+                "end": {"line": 0, "character": 15},
+            })
+        );
+        assert_eq!(symbols[2]["detail"], "syntax");
+    }
+}
@@ -0,0 +1,459 @@
+//! Language server, speaking the Language Server Protocol over standard input and output
+//!
+//! The server keeps the text of the documents the client has open, and publishes its lexer error
+//! or parser errors as diagnostics whenever it changes. Clients send the ranges that
+//! change, and only the lines and top-level forms around them are lexed and parsed again. The
+//! server also lists the top-level definitions of a document as its symbols. `TokenWithPosition`
+//! counts columns in bytes, while LSP counts them in UTF-16 code units, so every position sent is
+//! converted.
+//!
+//! The Scheme files of the workspace are read when the server is initialized, so that
+//! go-to-definition, find references and rename, which follow the bindings found by
+//! `scope::Resolution`, see the top-level bindings of every file and not only of the open ones.
+//! Completion, hover and signature help also describe the builtins and special forms, from the
+//! registries the interpreter uses. Semantic tokens, folding ranges and selection ranges give
+//! editors the structure of the text.
+
+mod assist;
+mod document;
+mod navigation;
+mod structure;
+mod transport;
+mod workspace;
+
+use document::Document;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+pub use transport::{read_message, write_message};
+
+/// JSON-RPC error code of a message that is not valid JSON
+const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code of a message that is not a valid request
+const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code of a request for a method the server does not implement
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code of a request with invalid parameters
+const INVALID_PARAMS: i64 = -32602;
+/// LSP error code of a request received before `initialize`
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+/// LSP error code of a valid request the server could not carry out
+const REQUEST_FAILED: i64 = -32803;
+
+/// `TextDocumentSyncKind.Incremental`: the client sends the ranges of text that change
+const SYNC_INCREMENTAL: u32 = 2;
+
+/// An error answering a request
+#[derive(Debug)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        ResponseError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// The state of a language server session
+#[derive(Default)]
+pub struct Server {
+    /// The documents open in the client or found in the workspace, by `document_key`
+    documents: BTreeMap<String, Document>,
+    /// The keys of the documents open in the client
+    open: HashSet<String>,
+    /// The root directories of the workspace
+    roots: Vec<PathBuf>,
+    initialized: bool,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    /// Creates a server waiting for `initialize`
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    /// Whether the client asked the server to shut down
+    pub fn shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Whether the client asked the server to exit
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles a message from the client, returning the messages to send back
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").unwrap_or(&Value::Null);
+        match (method, message.get("id")) {
+            (Some(method), Some(id)) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Err(error) => error_response(id.clone(), error),
+                };
+                vec![response]
+            }
+            // Notifications other than `exit` are dropped until the server is initialized
+            (Some(method), None) if self.initialized || method == "exit" => {
+                self.notification(method, params)
+            }
+            (Some(_), None) => Vec::new(),
+            // The server sends no requests, so there are no responses to handle
+            (None, _) => Vec::new(),
+        }
+    }
+
+    /// Answers a request
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        if method == "initialize" {
+            self.initialized = true;
+            self.index_workspace(params);
+            return Ok(json!({
+                "capabilities": {
+                    "positionEncoding": "utf-16",
+                    "textDocumentSync": SYNC_INCREMENTAL,
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": {"prepareProvider": true},
+                    "completionProvider": {"triggerCharacters": ["("]},
+                    "hoverProvider": true,
+                    "signatureHelpProvider": {"triggerCharacters": ["(", " "]},
+                    "semanticTokensProvider": {
+                        "legend": {
+                            "tokenTypes": structure::TOKEN_TYPES,
+                            "tokenModifiers": structure::TOKEN_MODIFIERS,
+                        },
+                        "full": true,
+                    },
+                    "foldingRangeProvider": true,
+                    "selectionRangeProvider": true,
+                },
+                "serverInfo": {"name": "oxyscheme", "version": env!("CARGO_PKG_VERSION")},
+            }));
+        }
+        if !self.initialized {
+            return Err(ResponseError::new(
+                SERVER_NOT_INITIALIZED,
+                "the server is not initialized",
+            ));
+        }
+        if self.shut_down {
+            return Err(ResponseError::new(
+                INVALID_REQUEST,
+                "the server is shutting down",
+            ));
+        }
+        match method {
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/documentSymbol" => Ok(Value::from(self.document(params)?.symbols())),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/prepareRename" => self.prepare_rename(params),
+            "textDocument/rename" => self.rename(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/signatureHelp" => self.signature_help(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            "textDocument/foldingRange" => self.folding_ranges(params),
+            "textDocument/selectionRange" => self.selection_ranges(params),
+            _ => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            )),
+        }
+    }
+
+    /// Handles a notification, returning the notifications it causes
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None if method == "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            None => return Vec::new(),
+        };
+        let key = document_key(&uri);
+        let version = params["textDocument"]["version"].as_i64().unwrap_or(0);
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.open.insert(key.clone());
+                let document = Document::new(uri.clone(), text.to_string(), version);
+                self.documents.insert(key.clone(), document);
+            }
+            "textDocument/didChange" => {
+                // The changes apply one after the other
+                let changes = match params["contentChanges"].as_array() {
+                    Some(changes) => changes,
+                    None => return Vec::new(),
+                };
+                let mut document = match self.documents.remove(&key) {
+                    Some(document) => document,
+                    None => Document::new(uri.clone(), String::new(), version),
+                };
+                for change in changes {
+                    document = document.change(change, version);
+                }
+                self.documents.insert(key.clone(), document);
+            }
+            "textDocument/didClose" => {
+                self.open.remove(&key);
+                // The files of the workspace stay known as they are on disk
+                self.documents.remove(&key);
+                self.index_file(&uri);
+            }
+            _ => return Vec::new(),
+        }
+        vec![self.publish_diagnostics(&uri)]
+    }
+
+    /// Reads the Scheme files under the workspace folders, or the root directory, given to
+    /// `initialize`
+    fn index_workspace(&mut self, params: &Value) {
+        let folders = params["workspaceFolders"].as_array();
+        let mut uris: Vec<&str> = folders
+            .into_iter()
+            .flatten()
+            .filter_map(|folder| folder["uri"].as_str())
+            .collect();
+        if uris.is_empty() {
+            uris.extend(params["rootUri"].as_str());
+        }
+        self.roots = uris
+            .into_iter()
+            .filter_map(workspace::uri_to_path)
+            .collect();
+        let files: Vec<PathBuf> = self
+            .roots
+            .iter()
+            .flat_map(|root| workspace::source_files(root))
+            .collect();
+        for file in files {
+            self.index_file(&workspace::path_to_uri(&file));
+        }
+    }
+
+    /// Reads a file of the workspace that is not open, if it exists
+    fn index_file(&mut self, uri: &str) {
+        let key = document_key(uri);
+        let path = match workspace::uri_to_path(uri) {
+            Some(path) => path,
+            None => return,
+        };
+        if self.open.contains(&key) || !self.roots.iter().any(|root| path.starts_with(root)) {
+            return;
+        }
+        if let Ok(text) = fs::read_to_string(&path) {
+            self.documents
+                .insert(key, Document::new(uri.to_string(), text, 0));
+        }
+    }
+
+    /// The notification publishing the diagnostics of a document, which clears them once it is
+    /// closed
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let key = document_key(uri);
+        let mut params = json!({"uri": uri, "diagnostics": []});
+        if let (true, Some(document)) = (self.open.contains(&key), self.documents.get(&key)) {
+            params["diagnostics"] = Value::from(document.diagnostics());
+            params["version"] = Value::from(document.version);
+        }
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": params,
+        })
+    }
+
+    /// The open document a request is about
+    fn document(&self, params: &Value) -> Result<&Document, ResponseError> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or_else(|| {
+            ResponseError::new(INVALID_PARAMS, "missing textDocument.uri parameter")
+        })?;
+        self.documents
+            .get(&document_key(uri))
+            .ok_or_else(|| ResponseError::new(INVALID_PARAMS, format!("{} is not open", uri)))
+    }
+}
+
+/// The key of the document named by a URI, which is the same whichever way the client encodes
+/// the URI of a file
+fn document_key(uri: &str) -> String {
+    match workspace::uri_to_path(uri) {
+        Some(path) => workspace::path_to_uri(&path),
+        None => uri.to_string(),
+    }
+}
+
+fn error_response(id: Value, error: ResponseError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+/// Serves the client talking on `input` and `output` until it asks the server to exit, or closes
+/// the input
+///
+/// Returns whether the client asked the server to shut down first, as LSP requires for a
+/// successful exit.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match serde_json::from_str(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![error_response(
+                Value::Null,
+                ResponseError::new(PARSE_ERROR, error.to_string()),
+            )],
+        };
+        for reply in &replies {
+            write_message(&mut output, reply)?;
+        }
+        if server.exited() {
+            break;
+        }
+    }
+    Ok(server.shut_down())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_message, run, write_message, Server};
+    use serde_json::{json, Value};
+    use std::io::Cursor;
+
+    fn initialized() -> Server {
+        let mut server = Server::new();
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let response = server.handle(&initialize);
+        assert_eq!(
+            response[0]["result"]["capabilities"]["documentSymbolProvider"],
+            true
+        );
+        server
+    }
+
+    fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Value> {
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {
+                "uri": uri, "languageId": "scheme", "version": 1, "text": text,
+            }},
+        }))
+    }
+
+    #[test]
+    fn diagnostics_test() {
+        let mut server = initialized();
+        let published = open(&mut server, "file:///a.scm", "(display \"é\"))\n");
+        assert_eq!(published[0]["method"], "textDocument/publishDiagnostics");
+        let diagnostics = &published[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E0003");
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({"line": 0, "character": 13})
+        );
+        let published = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": {"uri": "file:///a.scm", "version": 2},
+                "contentChanges": [{"text": "(display \"é\")\n"}],
+            },
+        }));
+        assert_eq!(published[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(published[0]["params"]["version"], 2);
+        // Ranges count UTF-16 code units, and apply one after the other
+        let published = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": {"uri": "file:///a.scm", "version": 3},
+                "contentChanges": [
+                    {
+                        "range": {
+                            "start": {"line": 0, "character": 12},
+                            "end": {"line": 0, "character": 13},
+                        },
+                        "text": ")\n(f \"é\")",
+                    },
+                    {
+                        "range": {
+                            "start": {"line": 1, "character": 7},
+                            "end": {"line": 1, "character": 7},
+                        },
+                        "text": ")",
+                    },
+                ],
+            },
+        }));
+        let diagnostics = &published[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E0003");
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({"line": 1, "character": 7})
+        );
+    }
+
+    #[test]
+    fn requests_test() {
+        let mut server = Server::new();
+        let symbols = json!({
+            "jsonrpc": "2.0", "id": 2, "method": "textDocument/documentSymbol",
+            "params": {"textDocument": {"uri": "file:///a.scm"}},
+        });
+        assert_eq!(server.handle(&symbols)[0]["error"]["code"], -32002);
+        let mut server = initialized();
+        assert_eq!(server.handle(&symbols)[0]["error"]["code"], -32602);
+        open(&mut server, "file:///a.scm", "(define (f) 1)");
+        assert_eq!(server.handle(&symbols)[0]["result"][0]["name"], "f");
+        let unknown = json!({"jsonrpc": "2.0", "id": 3, "method": "workspace/frobnicate"});
+        assert_eq!(server.handle(&unknown)[0]["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn session_test() {
+        let mut input = Vec::new();
+        for message in &[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+        ] {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        assert!(run(Cursor::new(input), &mut output).unwrap());
+        let mut output = Cursor::new(output);
+        let mut responses = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            responses.push(serde_json::from_str::<Value>(&body).unwrap());
+        }
+        // Nothing is read after `exit`
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[1],
+            json!({"jsonrpc": "2.0", "id": 2, "result": null})
+        );
+    }
+}
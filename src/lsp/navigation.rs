@@ -0,0 +1,361 @@
+//! Go-to-definition, find references and rename, which follow the bindings found by
+//! `scope::Resolution`
+//!
+//! Local bindings are only referred to from the document they are in, while top-level bindings
+//! are shared by every document of the workspace.
+
+use super::{Document, ResponseError, Server, INVALID_PARAMS, REQUEST_FAILED};
+use crate::lexer::Token;
+use crate::reader::StringLexer;
+use crate::scope::Target;
+use serde_json::{json, Map, Value};
+
+/// An identifier referring to a binding: the document it is in, the index of its token, and
+/// whether it introduces the binding
+type Reference<'a> = (&'a Document, usize, bool);
+
+impl Server {
+    /// The document a request is about, and the binding the identifier at the requested position
+    /// refers to, if there is one
+    fn target(&self, params: &Value) -> Result<Option<(&Document, &Target)>, ResponseError> {
+        let document = self.document(params)?;
+        let occurrence = document.occurrence_at(&params["position"]);
+        Ok(occurrence.map(|occurrence| (document, &occurrence.target)))
+    }
+
+    /// The identifiers referring to a binding of `document`, in every document for top-level
+    /// bindings
+    fn references_to<'a>(
+        &'a self,
+        document: &'a Document,
+        target: &'a Target,
+    ) -> Vec<Reference<'a>> {
+        let documents: Vec<&Document> = match target {
+            Target::Local(_) => vec![document],
+            Target::TopLevel(_) => self.documents.values().collect(),
+        };
+        documents
+            .into_iter()
+            .flat_map(|document| {
+                document
+                    .resolution()
+                    .occurrences_of(target)
+                    .map(move |occurrence| (document, occurrence.token, occurrence.definition))
+            })
+            .collect()
+    }
+
+    /// Answers `textDocument/definition` with the identifiers introducing the binding
+    pub(super) fn definition(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (document, target) = match self.target(params)? {
+            Some(found) => found,
+            None => return Ok(Value::Null),
+        };
+        let locations: Vec<Value> = self
+            .references_to(document, target)
+            .into_iter()
+            .filter(|&(_, _, definition)| definition)
+            .map(|(document, token, _)| document.location(token))
+            .collect();
+        Ok(Value::from(locations))
+    }
+
+    /// Answers `textDocument/references`
+    pub(super) fn references(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (document, target) = match self.target(params)? {
+            Some(found) => found,
+            None => return Ok(Value::Null),
+        };
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let locations: Vec<Value> = self
+            .references_to(document, target)
+            .into_iter()
+            .filter(|&(_, _, definition)| include_declaration || !definition)
+            .map(|(document, token, _)| document.location(token))
+            .collect();
+        Ok(Value::from(locations))
+    }
+
+    /// Answers `textDocument/prepareRename` with the range of the identifier to rename
+    pub(super) fn prepare_rename(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (document, target) = match self.target(params)? {
+            Some(found) => found,
+            None => return Ok(Value::Null),
+        };
+        let references = self.references_to(document, target);
+        check_renamable(target, &references)?;
+        let occurrence = document.occurrence_at(&params["position"]).unwrap();
+        Ok(document.token_range(occurrence.token))
+    }
+
+    /// Answers `textDocument/rename` with the edits renaming every identifier referring to the
+    /// same binding, and nothing else
+    pub(super) fn rename(&self, params: &Value) -> Result<Value, ResponseError> {
+        let new_name = params["newName"]
+            .as_str()
+            .ok_or_else(|| ResponseError::new(INVALID_PARAMS, "missing newName parameter"))?;
+        if !is_identifier(new_name) {
+            return Err(ResponseError::new(
+                INVALID_PARAMS,
+                format!("{} is not a valid identifier", new_name),
+            ));
+        }
+        let (document, target) = match self.target(params)? {
+            Some(found) => found,
+            None => return Ok(Value::Null),
+        };
+        let references = self.references_to(document, target);
+        check_renamable(target, &references)?;
+        let mut changes = Map::new();
+        for (document, token, _) in references {
+            let edit = json!({"range": document.token_range(token), "newText": new_name});
+            let edits = changes
+                .entry(document.uri.clone())
+                .or_insert_with(|| Value::from(Vec::<Value>::new()));
+            edits.as_array_mut().unwrap().push(edit);
+        }
+        Ok(json!({ "changes": changes }))
+    }
+}
+
+/// Refuses to rename top-level bindings defined outside of the workspace, such as builtins
+fn check_renamable(target: &Target, references: &[Reference<'_>]) -> Result<(), ResponseError> {
+    match target {
+        Target::TopLevel(name) if !references.iter().any(|&(_, _, definition)| definition) => {
+            Err(ResponseError::new(
+                REQUEST_FAILED,
+                format!("{} is not defined in the workspace", name),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether `name` reads as a single identifier
+fn is_identifier(name: &str) -> bool {
+    let tokens: Result<Vec<_>, _> = StringLexer::new(name).into_iter().collect();
+    match tokens.as_deref() {
+        Ok([token]) => matches!(token.token, Token::Identifier(_)) && token.span.len == name.len(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lsp::Server;
+    use serde_json::{json, Value};
+
+    fn server(files: &[(&str, &str)]) -> Server {
+        let mut server = Server::new();
+        server.handle(&json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {}}));
+        for (uri, text) in files {
+            server.handle(&json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {"textDocument": {
+                    "uri": uri, "languageId": "scheme", "version": 1, "text": text,
+                }},
+            }));
+        }
+        server
+    }
+
+    fn request(server: &mut Server, method: &str, uri: &str, line: u32, character: u32) -> Value {
+        let response = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": method,
+            "params": {
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character},
+                "context": {"includeDeclaration": true},
+                "newName": "renamed",
+            },
+        }));
+        response[0].clone()
+    }
+
+    /// The `(uri, line, character)` of each location or edit
+    fn places(values: &Value) -> Vec<(String, u64, u64)> {
+        let mut places: Vec<_> = values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| {
+                let start = &value["range"]["start"];
+                let uri = value["uri"].as_str().unwrap_or_default().to_string();
+                (
+                    uri,
+                    start["line"].as_u64().unwrap(),
+                    start["character"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        places.sort();
+        places
+    }
+
+    fn place(uri: &str, line: u64, character: u64) -> (String, u64, u64) {
+        (uri.to_string(), line, character)
+    }
+
+    #[test]
+    fn definition_test() {
+        let mut server = server(&[
+            ("file:///lib.scm", "(define (square x) (* x x))\n"),
+            (
+                "file:///main.scm",
+                "(display (square 2))\n(define (f square) square)\n",
+            ),
+        ]);
+        let response = request(
+            &mut server,
+            "textDocument/definition",
+            "file:///main.scm",
+            0,
+            12,
+        );
+        assert_eq!(
+            places(&response["result"]),
+            vec![place("file:///lib.scm", 0, 9)]
+        );
+        // A parameter shadows the top-level binding
+        let response = request(
+            &mut server,
+            "textDocument/definition",
+            "file:///main.scm",
+            1,
+            20,
+        );
+        assert_eq!(
+            places(&response["result"]),
+            vec![place("file:///main.scm", 1, 11)]
+        );
+        let response = request(
+            &mut server,
+            "textDocument/definition",
+            "file:///main.scm",
+            0,
+            0,
+        );
+        assert_eq!(response["result"], Value::Null);
+    }
+
+    #[test]
+    fn references_test() {
+        let mut server = server(&[
+            ("file:///lib.scm", "(define (square x) (* x x))\n"),
+            (
+                "file:///main.scm",
+                "(display (square 2))\n'(square)\n(let ((square 1)) square)\n",
+            ),
+        ]);
+        let response = request(
+            &mut server,
+            "textDocument/references",
+            "file:///lib.scm",
+            0,
+            10,
+        );
+        assert_eq!(
+            places(&response["result"]),
+            vec![
+                place("file:///lib.scm", 0, 9),
+                place("file:///main.scm", 0, 10)
+            ]
+        );
+        let response = request(
+            &mut server,
+            "textDocument/references",
+            "file:///lib.scm",
+            0,
+            22,
+        );
+        assert_eq!(
+            places(&response["result"]),
+            vec![
+                place("file:///lib.scm", 0, 16),
+                place("file:///lib.scm", 0, 22),
+                place("file:///lib.scm", 0, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_test() {
+        let mut server = server(&[
+            ("file:///lib.scm", "(define (square x) (* x x))\n"),
+            (
+                "file:///main.scm",
+                "(display (square 2))\n'(square)\n(let ((square 1)) square)\n",
+            ),
+        ]);
+        let response = request(
+            &mut server,
+            "textDocument/rename",
+            "file:///main.scm",
+            0,
+            12,
+        );
+        let changes = &response["result"]["changes"];
+        assert_eq!(places(&changes["file:///lib.scm"]), vec![place("", 0, 9)]);
+        assert_eq!(places(&changes["file:///main.scm"]), vec![place("", 0, 10)]);
+        assert_eq!(changes["file:///main.scm"][0]["newText"], "renamed");
+        // Builtins cannot be renamed
+        let response = request(
+            &mut server,
+            "textDocument/prepareRename",
+            "file:///lib.scm",
+            0,
+            20,
+        );
+        assert_eq!(
+            response["error"]["message"],
+            "* is not defined in the workspace"
+        );
+        let response = request(
+            &mut server,
+            "textDocument/prepareRename",
+            "file:///main.scm",
+            2,
+            19,
+        );
+        assert_eq!(
+            response["result"],
+            json!({"start": {"line": 2, "character": 18}, "end": {"line": 2, "character": 24}})
+        );
+        let response = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 2, "method": "textDocument/rename",
+            "params": {
+                "textDocument": {"uri": "file:///lib.scm"},
+                "position": {"line": 0, "character": 10},
+                "newName": "two words",
+            },
+        }));
+        assert_eq!(response[0]["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn workspace_test() {
+        let root = std::env::temp_dir().join(format!("scheme-lsp-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("lib.scm"), "(define answer 42)\n").unwrap();
+        let root_uri = crate::lsp::workspace::path_to_uri(&root);
+        let mut server = Server::new();
+        server.handle(&json!({
+            "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"rootUri": root_uri},
+        }));
+        std::fs::remove_dir_all(&root).unwrap();
+        let main = format!("{}/main.scm", root_uri);
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {
+                "uri": main, "languageId": "scheme", "version": 1, "text": "(display answer)",
+            }},
+        }));
+        let response = request(&mut server, "textDocument/definition", &main, 0, 10);
+        let lib = format!("{}/lib.scm", root_uri);
+        assert_eq!(places(&response["result"]), vec![place(&lib, 0, 8)]);
+    }
+}
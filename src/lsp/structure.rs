@@ -0,0 +1,439 @@
+//! Semantic tokens, folding ranges and selection ranges, which follow the tokens and the syntax
+//! tree of a document
+//!
+//! Semantic tokens start from the kind of each token, as `oxyscheme lex` prints it, and refine
+//! identifiers with what they refer to: the keywords of special forms, syntax definitions, local
+//! variables and parameters, and top-level variables, builtins among them. Scheme has no block
+//! comments, so runs of comment lines fold instead.
+
+use super::{Document, ResponseError, Server};
+use crate::builtins;
+use crate::interpreter::is_keyword;
+use crate::lexer::Token;
+use crate::scope::{BindingKind, Target};
+use crate::syntax_tree::{Node, NodeKind};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// The semantic token types of the legend, in order; a token type is encoded as its index here
+pub(super) const TOKEN_TYPES: &[&str] = &[
+    "string",
+    "character",
+    "boolean",
+    "number",
+    "identifier",
+    "punctuator",
+    "comment",
+    "keyword",
+    "macro",
+    "variable",
+    "parameter",
+];
+
+/// The semantic token modifiers of the legend, in order; modifiers are encoded as a bit set of
+/// their indices here
+pub(super) const TOKEN_MODIFIERS: &[&str] = &["definition", "local", "global", "defaultLibrary"];
+
+fn token_type(name: &str) -> u32 {
+    TOKEN_TYPES.iter().position(|&kind| kind == name).unwrap() as u32
+}
+
+fn token_modifiers(names: &[&str]) -> u32 {
+    TOKEN_MODIFIERS
+        .iter()
+        .enumerate()
+        .filter(|(_, modifier)| names.contains(modifier))
+        .fold(0, |bits, (bit, _)| bits | 1 << bit)
+}
+
+impl Server {
+    /// Whether each top-level binding defined in the workspace is defined by `define-syntax`
+    fn top_level_definitions(&self) -> HashMap<&str, bool> {
+        let mut definitions = HashMap::new();
+        for document in self.documents.values() {
+            for occurrence in &document.resolution().occurrences {
+                let name = match &occurrence.target {
+                    Target::TopLevel(name) if occurrence.definition => name,
+                    _ => continue,
+                };
+                let syntax = document
+                    .definition_form(occurrence.token)
+                    .is_some_and(|form| {
+                        let head = form.children.first();
+                        head.and_then(|head| document.tree().identifier(head))
+                            == Some("define-syntax")
+                    });
+                *definitions.entry(name.as_str()).or_insert(false) |= syntax;
+            }
+        }
+        definitions
+    }
+
+    /// Answers `textDocument/semanticTokens/full` with the type and modifiers of every token
+    /// other than whitespace, in the relative encoding of LSP
+    pub(super) fn semantic_tokens(&self, params: &Value) -> Result<Value, ResponseError> {
+        let document = self.document(params)?;
+        let definitions = self.top_level_definitions();
+        let keywords = keywords(document);
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_start) = (0, 0);
+        for (index, token) in document.tree().tokens().iter().enumerate() {
+            let (kind, modifiers) = match &token.token {
+                Token::Whitespace => continue,
+                Token::Identifier(name) => classify(document, index, name, &keywords, &definitions),
+                other => (other.kind(), Vec::new()),
+            };
+            let line = token.span.line - 1;
+            let start = document.utf16_column(token.span.line, token.span.column);
+            let end = document.utf16_column(token.span.line, token.span.column + token.span.len);
+            if line != previous_line {
+                previous_start = 0;
+            }
+            data.extend(vec![
+                line - previous_line,
+                start - previous_start,
+                end - start,
+                token_type(kind) as usize,
+                token_modifiers(&modifiers) as usize,
+            ]);
+            previous_line = line;
+            previous_start = start;
+        }
+        Ok(json!({ "data": data }))
+    }
+
+    /// Answers `textDocument/foldingRange`
+    pub(super) fn folding_ranges(&self, params: &Value) -> Result<Value, ResponseError> {
+        Ok(Value::from(folding_ranges(self.document(params)?)))
+    }
+
+    /// Answers `textDocument/selectionRange` with, for each position, the token there and the
+    /// data enclosing it, innermost first
+    pub(super) fn selection_ranges(&self, params: &Value) -> Result<Value, ResponseError> {
+        let document = self.document(params)?;
+        let positions = params["positions"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        let ranges: Vec<Value> = positions
+            .iter()
+            .map(|position| selection_range(document, position))
+            .collect();
+        Ok(Value::from(ranges))
+    }
+}
+
+/// The semantic token type and modifiers of an identifier
+fn classify(
+    document: &Document,
+    index: usize,
+    name: &str,
+    keywords: &HashSet<usize>,
+    definitions: &HashMap<&str, bool>,
+) -> (&'static str, Vec<&'static str>) {
+    let occurrence = match document.resolution().occurrence(index) {
+        Some(occurrence) => occurrence,
+        None if keywords.contains(&index) => return ("keyword", Vec::new()),
+        None => return ("identifier", Vec::new()),
+    };
+    let mut modifiers = Vec::new();
+    if occurrence.definition {
+        modifiers.push("definition");
+    }
+    let kind = match &occurrence.target {
+        Target::Local(binding) => {
+            modifiers.push("local");
+            match document.resolution().bindings[*binding].kind {
+                BindingKind::Variable => "variable",
+                BindingKind::Parameter => "parameter",
+                BindingKind::Syntax => "macro",
+            }
+        }
+        Target::TopLevel(_) => match definitions.get(name) {
+            Some(true) => {
+                modifiers.push("global");
+                "macro"
+            }
+            Some(false) => {
+                modifiers.push("global");
+                "variable"
+            }
+            // The resolver leaves keywords that bind nothing, such as `if`, as variables
+            None if is_keyword(name) => "keyword",
+            None => {
+                modifiers.push("global");
+                if builtins::lookup(name).is_some() {
+                    modifiers.push("defaultLibrary");
+                }
+                "variable"
+            }
+        },
+    };
+    (kind, modifiers)
+}
+
+/// The indices of the identifiers naming the special form of a list, outside of quoted data
+fn keywords(document: &Document) -> HashSet<usize> {
+    let tree = document.tree();
+    let mut keywords = HashSet::new();
+    let mut nodes: Vec<&Node> = tree.forms().iter().collect();
+    while let Some(node) = nodes.pop() {
+        if matches!(tree.prefix(node), Some("'") | Some("`")) {
+            continue;
+        }
+        if let (NodeKind::List { .. }, Some(head)) = (node.kind, node.children.first()) {
+            let name = tree.identifier(head).filter(|&name| is_keyword(name));
+            if let Some(name) = name {
+                if document.resolution().occurrence(head.first).is_none() {
+                    keywords.insert(head.first);
+                    if name == "quote" || name == "quasiquote" {
+                        continue;
+                    }
+                }
+            }
+        }
+        nodes.extend(&node.children);
+    }
+    keywords
+}
+
+/// The lists and vectors spanning several lines, and the runs of lines holding nothing but a
+/// comment
+fn folding_ranges(document: &Document) -> Vec<Value> {
+    let tree = document.tree();
+    let mut ranges = Vec::new();
+    let mut nodes: Vec<&Node> = tree.forms().iter().collect();
+    while let Some(node) = nodes.pop() {
+        let (start, end) = (
+            tree.token(node.first).span.line,
+            tree.token(node.last).span.line,
+        );
+        if matches!(node.kind, NodeKind::List { .. } | NodeKind::Vector { .. }) && start < end {
+            ranges.push(json!({"startLine": start - 1, "endLine": end - 1}));
+        }
+        nodes.extend(&node.children);
+    }
+    // The lines on which a comment is the first significant token
+    let mut comment_lines = Vec::new();
+    let mut line_started = (0, false);
+    for token in tree.tokens() {
+        if line_started.0 != token.span.line {
+            line_started = (token.span.line, false);
+        }
+        match token.token {
+            Token::Whitespace => {}
+            Token::Comment if !line_started.1 => comment_lines.push(token.span.line),
+            _ => line_started.1 = true,
+        }
+    }
+    let mut lines = comment_lines.into_iter().peekable();
+    while let Some(start) = lines.next() {
+        let mut end = start;
+        while lines.peek() == Some(&(end + 1)) {
+            end = lines.next().unwrap();
+        }
+        if start < end {
+            ranges.push(json!({"startLine": start - 1, "endLine": end - 1, "kind": "comment"}));
+        }
+    }
+    ranges.sort_by_key(|range| {
+        (
+            range["startLine"].as_u64(),
+            std::cmp::Reverse(range["endLine"].as_u64()),
+        )
+    });
+    ranges
+}
+
+/// The selection range of a position: the token at the position, within each datum enclosing
+/// it
+fn selection_range(document: &Document, position: &Value) -> Value {
+    let tokens = document.tree().tokens();
+    let mut ranges = Vec::new();
+    if let Some(cursor) = document.line_and_column(position) {
+        let containing = tokens
+            .partition_point(|token| (token.span.line, token.span.column) <= cursor)
+            .checked_sub(1);
+        // Prefers the token ending at the position to whitespace or a parenthesis starting there
+        let index = match containing {
+            Some(index)
+                if index > 0
+                    && document.token_start(index) == cursor
+                    && document.token_end(index - 1) == cursor
+                    && matches!(
+                        tokens[index].token,
+                        Token::Whitespace | Token::Punctuator(_)
+                    ) =>
+            {
+                Some(index - 1)
+            }
+            other => other,
+        };
+        if let Some(index) = index {
+            let mut nodes = document.tree().forms();
+            while let Some(node) = nodes
+                .iter()
+                .find(|node| node.first <= index && index <= node.last)
+            {
+                ranges.push(document.node_range(node));
+                nodes = &node.children;
+            }
+            let token = document.token_range(index);
+            if tokens[index].token != Token::Whitespace && ranges.last() != Some(&token) {
+                ranges.push(token);
+            }
+        }
+    }
+    if ranges.is_empty() {
+        ranges.push(json!({"start": position, "end": position}));
+    }
+    let mut selection = Value::Null;
+    for range in ranges {
+        selection = match selection {
+            Value::Null => json!({ "range": range }),
+            parent => json!({"range": range, "parent": parent}),
+        };
+    }
+    selection
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TOKEN_MODIFIERS, TOKEN_TYPES};
+    use crate::lsp::Server;
+    use serde_json::{json, Value};
+
+    fn server(text: &str) -> Server {
+        let mut server = Server::new();
+        server.handle(&json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {}}));
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {
+                "uri": "file:///a.scm", "languageId": "scheme", "version": 1, "text": text,
+            }},
+        }));
+        server
+    }
+
+    fn request(server: &mut Server, method: &str, params: Value) -> Value {
+        let mut params = params;
+        params["textDocument"] = json!({"uri": "file:///a.scm"});
+        let response = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": method, "params": params,
+        }));
+        response[0]["result"].clone()
+    }
+
+    /// Decodes semantic tokens into `(line, character, length, type, modifiers)`
+    fn decode(data: &Value) -> Vec<(u64, u64, u64, &'static str, Vec<&'static str>)> {
+        let data: Vec<u64> = data
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|number| number.as_u64().unwrap())
+            .collect();
+        let (mut line, mut start) = (0, 0);
+        data.chunks(5)
+            .map(|chunk| {
+                if chunk[0] > 0 {
+                    start = 0;
+                }
+                line += chunk[0];
+                start += chunk[1];
+                let modifiers = (0..TOKEN_MODIFIERS.len())
+                    .filter(|bit| chunk[4] & 1 << bit != 0)
+                    .map(|bit| TOKEN_MODIFIERS[bit])
+                    .collect();
+                (
+                    line,
+                    start,
+                    chunk[2],
+                    TOKEN_TYPES[chunk[3] as usize],
+                    modifiers,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn semantic_tokens_test() {
+        let text = "(define (f x) (if x '(if) \"é\"))\n\
+                    (define-syntax swap! (syntax-rules ()))\n; done\n(swap! car #\\a)";
+        let mut server = server(text);
+        let result = request(&mut server, "textDocument/semanticTokens/full", json!({}));
+        let tokens = decode(&result["data"]);
+        let find = |line: u64, character: u64| {
+            let token = tokens
+                .iter()
+                .find(|token| token.0 == line && token.1 == character)
+                .unwrap();
+            (token.3, token.4.clone())
+        };
+        assert_eq!(find(0, 0), ("punctuator", vec![]));
+        assert_eq!(find(0, 1), ("keyword", vec![]));
+        assert_eq!(find(0, 9), ("variable", vec!["definition", "global"]));
+        assert_eq!(find(0, 11), ("parameter", vec!["definition", "local"]));
+        assert_eq!(find(0, 15), ("keyword", vec![]));
+        assert_eq!(find(0, 18), ("parameter", vec!["local"]));
+        // Quoted data are not keywords
+        assert_eq!(find(0, 22), ("identifier", vec![]));
+        assert_eq!(
+            tokens.iter().find(|token| token.3 == "string").unwrap().2,
+            3
+        );
+        assert_eq!(find(1, 1), ("keyword", vec![]));
+        assert_eq!(find(1, 15), ("macro", vec!["definition", "global"]));
+        assert_eq!(find(2, 0), ("comment", vec![]));
+        assert_eq!(find(3, 1), ("macro", vec!["global"]));
+        assert_eq!(find(3, 7), ("variable", vec!["global", "defaultLibrary"]));
+        assert_eq!(find(3, 11), ("character", vec![]));
+        // Whitespace is left out
+        assert_eq!(tokens.len(), 31);
+    }
+
+    #[test]
+    fn folding_range_test() {
+        let text = ";; A\n;; B\n(define (f)\n  #(1\n    2))\n; lone\n(g)\n";
+        let mut server = server(text);
+        let result = request(&mut server, "textDocument/foldingRange", json!({}));
+        assert_eq!(
+            result,
+            json!([
+                {"startLine": 0, "endLine": 1, "kind": "comment"},
+                {"startLine": 2, "endLine": 4},
+                {"startLine": 3, "endLine": 4},
+            ])
+        );
+    }
+
+    #[test]
+    fn selection_range_test() {
+        let mut server = server("(define (f x)\n  (+ x 1))");
+        let result = request(
+            &mut server,
+            "textDocument/selectionRange",
+            json!({"positions": [{"line": 1, "character": 6}, {"line": 0, "character": 12}]}),
+        );
+        let mut ranges = Vec::new();
+        let mut selection = &result[0];
+        while !selection.is_null() {
+            let range = &selection["range"];
+            ranges.push((
+                range["start"]["line"].as_u64().unwrap(),
+                range["start"]["character"].as_u64().unwrap(),
+                range["end"]["character"].as_u64().unwrap(),
+            ));
+            selection = &selection["parent"];
+        }
+        assert_eq!(ranges, vec![(1, 5, 6), (1, 2, 9), (0, 0, 10)]);
+        // The identifier ending at the position is preferred to the parenthesis
+        assert_eq!(
+            result[1]["range"],
+            json!({"start": {"line": 0, "character": 11}, "end": {"line": 0, "character": 12}})
+        );
+        assert_eq!(
+            result[1]["parent"]["range"]["start"],
+            json!({"line": 0, "character": 8})
+        );
+    }
+}
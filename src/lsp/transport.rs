@@ -0,0 +1,67 @@
+//! Base protocol of LSP: JSON-RPC messages preceded by `Content-Length` headers
+
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Reads the body of the next message, or `None` at the end of the input
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return match content_length {
+                None => Ok(None),
+                Some(_) => Err(invalid_data("input ended inside message headers")),
+            };
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let length = value.trim().parse::<usize>();
+                content_length = Some(length.map_err(|_| invalid_data("invalid Content-Length"))?);
+            }
+        }
+    }
+    let content_length = content_length.ok_or_else(|| invalid_data("missing Content-Length"))?;
+    let mut body = vec![0; content_length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| invalid_data("message is not UTF-8"))
+}
+
+/// Writes a message with its headers
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_message, write_message};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn framing_test() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+        write_message(&mut output, &json!("λ")).unwrap();
+        assert!(output.starts_with(b"Content-Length: 33\r\n\r\n{"));
+        let mut input = Cursor::new(output);
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(first, r#"{"jsonrpc":"2.0","method":"exit"}"#);
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), "\"λ\"");
+        assert_eq!(read_message(&mut input).unwrap(), None);
+        let mut truncated = Cursor::new(b"Content-Length: 10\r\n".to_vec());
+        assert!(read_message(&mut truncated).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Files of the workspace, and the `file:` URIs naming them
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The extensions of the files indexed in the workspace
+const SOURCE_EXTENSIONS: &[&str] = &["scm", "ss", "sld", "sls"];
+
+/// The path named by a `file:` URI
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    // Skips the authority, usually empty or `localhost`
+    let path = &path[path.find('/')?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail) {
+            (b'%', [high, low, tail @ ..]) => {
+                let hex = std::str::from_utf8(&[*high, *low]).ok()?.to_string();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// The `file:` URI of an absolute path
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The Scheme source files under a directory, skipping hidden files and directories, and build
+/// output
+pub fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let skipped = name.to_string_lossy().starts_with('.') || name == "target";
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() && !skipped => directories.push(path),
+                Ok(kind) if kind.is_file() && is_source_file(&path) => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn is_source_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    extension.is_some_and(|extension| SOURCE_EXTENSIONS.contains(&extension))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{path_to_uri, source_files, uri_to_path};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn uri_test() {
+        let path = Path::new("/home/me/my lib/λ.scm");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/me/my%20lib/%CE%BB.scm");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+        assert_eq!(
+            uri_to_path("file://localhost/a%3Ab.scm").unwrap(),
+            Path::new("/a:b.scm")
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn source_files_test() {
+        let root = std::env::temp_dir().join(format!("scheme-workspace-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        for file in &["main.scm", "lib/util.sld", "notes.txt", ".git/hook.scm"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let files = source_files(&root);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("lib/util.sld"), root.join("main.scm")]
+        );
+    }
+}
@@ -1,17 +1,497 @@
-use anyhow::Result;
+mod cli;
+
+use cli::{Command, Input, Options, Request};
+use debugger::Debugger;
+use diagnostics::{Diagnostic, Emitter};
+use interpreter::{ErrorKind, Interpreter, RuntimeError};
+use lexer::TokenWithPosition;
+use lint::Linter;
 use oxyscheme::*;
-use reader::{DatumIterator, FileLexer};
-use std::env;
+use port::{OutputPort, Port};
+use profiler::Profiler;
+use reader::{DatumIterator, FileLexer, StringLexer};
+use source_map::FileId;
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::{env, fs, process, thread};
+use syntax_tree::SyntaxTree;
+use value::Value;
+
+/// Exit status when the program raised an error
+const EXIT_RUNTIME: i32 = 1;
+/// Exit status for an invalid command line
+const EXIT_USAGE: i32 = 2;
+/// Exit status when a file could not be lexed or parsed, or contains a syntax error
+const EXIT_READ: i32 = 3;
+/// Exit status when a file could not be read or written
+const EXIT_IO: i32 = 4;
+/// Exit status when lint reported warnings
+const EXIT_LINT: i32 = 5;
+/// Exit status when the process panicked, as Rust exits with by default
+const EXIT_PANIC: i32 = 101;
+
+/// The size of the stack commands run on, which bounds how deep the non-tail recursion of
+/// programs goes; the main thread's stack allows little of it
+const STACK_SIZE: usize = 512 << 20;
+
+/// Why a command failed, once the error has been reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    Read,
+    Runtime,
+    Io,
+    Unsupported,
+    Lint,
+}
+
+impl Failure {
+    fn exit_code(self) -> i32 {
+        match self {
+            Failure::Read => EXIT_READ,
+            Failure::Runtime => EXIT_RUNTIME,
+            Failure::Io => EXIT_IO,
+            Failure::Unsupported => EXIT_USAGE,
+            Failure::Lint => EXIT_LINT,
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(_: io::Error) -> Self {
+        Failure::Io
+    }
+}
+
+type Outcome = Result<(), Failure>;
+
+type TokenStream = Box<dyn Iterator<Item = Result<TokenWithPosition, CompilerError>>>;
+
+fn main() {
+    let runner = thread::Builder::new().stack_size(STACK_SIZE).spawn(run);
+    match runner.map(thread::JoinHandle::join) {
+        Ok(Ok(())) => {}
+        // The panic message has been printed already
+        Ok(Err(_)) => process::exit(EXIT_PANIC),
+        Err(error) => {
+            eprintln!("oxyscheme: cannot start: {}", error);
+            process::exit(EXIT_IO);
+        }
+    }
+}
+
+fn run() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse_args(&args) {
+        Ok(Request::Command(options)) => options,
+        Ok(Request::Help) => {
+            let _ = writeln!(io::stdout(), "{}", cli::USAGE);
+            return;
+        }
+        Ok(Request::Version) => {
+            let _ = writeln!(io::stdout(), "oxyscheme {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(message) => {
+            eprintln!("oxyscheme: {}\n\n{}", message, cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let mut interpreter = Interpreter::new();
+    // Leave room for what runs outside of evaluations, and for reporting the error
+    interpreter.set_stack_limit(STACK_SIZE / 4 * 3);
+    let mut driver = Driver {
+        interpreter,
+        emitter: Emitter::new(options.error_format, Box::new(io::stderr())),
+    };
+    let outcome = driver.execute(&options);
+    let finished = driver.emitter.finish();
+    match (outcome, finished) {
+        (Err(failure), _) => process::exit(failure.exit_code()),
+        (Ok(()), Err(_)) => process::exit(EXIT_IO),
+        (Ok(()), Ok(())) => {}
+    }
+}
+
+/// Runs commands, reporting their errors as diagnostics
+struct Driver {
+    interpreter: Interpreter,
+    emitter: Emitter,
+}
+
+impl Driver {
+    fn execute(&mut self, options: &Options) -> Outcome {
+        match options.command {
+            Command::Lex | Command::Parse | Command::Fmt => {
+                let mut output = self.create_output(options.output.as_deref())?;
+                for input in &options.inputs {
+                    match options.command {
+                        Command::Lex => self.lex(input, options.json, &mut output)?,
+                        Command::Parse => self.parse(input, &mut output)?,
+                        _ => self.fmt(input, &mut output)?,
+                    }
+                }
+                output.flush().map_err(|error| self.io_error(error, None))
+            }
+            Command::Check => options
+                .inputs
+                .iter()
+                .try_for_each(|input| self.check(input, options.fix)),
+            Command::Lint => {
+                let mut linter = Linter::new();
+                for name in &options.enable {
+                    linter.enable(name);
+                }
+                for name in &options.disable {
+                    linter.disable(name);
+                }
+                let mut outcome = Ok(());
+                for input in &options.inputs {
+                    outcome = outcome.and(self.lint(input, &linter));
+                }
+                outcome
+            }
+            Command::Compile => {
+                for input in &options.inputs {
+                    self.check(input, false)?;
+                }
+                eprintln!("oxyscheme: compile: no code generator is available yet, use run");
+                Err(Failure::Unsupported)
+            }
+            Command::Lsp => {
+                let stdin = io::stdin();
+                match lsp::run(stdin.lock(), io::stdout()) {
+                    Ok(true) => Ok(()),
+                    // LSP asks for a failure status when the client exits without shutting down
+                    Ok(false) => Err(Failure::Runtime),
+                    Err(error) => Err(self.io_error(error, None)),
+                }
+            }
+            Command::Run | Command::Debug | Command::Profile | Command::Repl => {
+                self.redirect_output(options.output.as_deref())?;
+                if options.command == Command::Profile {
+                    self.interpreter.set_profiler(Some(Profiler::new()));
+                }
+                let outcome = match options.command {
+                    Command::Repl => self.repl(),
+                    Command::Debug => self.debug(&options.inputs[0]),
+                    _ => options.inputs.iter().try_for_each(|input| self.run(input)),
+                };
+                self.close_output()?;
+                if let Some(profiler) = self.interpreter.profiler() {
+                    profiler.write_flat(self.interpreter.source_map(), &mut io::stderr())?;
+                    if let Some(folded) = &options.folded {
+                        let file = fs::File::create(folded);
+                        let mut file = file.map_err(|error| self.io_error(error, Some(folded)))?;
+                        let profiler = self.interpreter.profiler().unwrap();
+                        profiler.write_folded(&mut file)?;
+                    }
+                }
+                outcome
+            }
+        }
+    }
+
+    /// Prints the tokens of a file with the text they were read from, one per line, as
+    /// `line:column kind text` or as JSON objects
+    fn lex(&mut self, input: &Input, json: bool, output: &mut dyn Write) -> Outcome {
+        let (file, tokens) = self.open(input)?;
+        for token in tokens {
+            let token = token.map_err(|error| self.read_error(error, Some(file)))?;
+            let source_map = self.interpreter.source_map();
+            let text = source_map.source_text(token.span).unwrap_or_default();
+            if json {
+                let object = serde_json::json!({
+                    "file": source_map.name(file),
+                    "line": token.span.line,
+                    "column": token.span.column,
+                    "kind": token.token.kind(),
+                    "text": text,
+                });
+                writeln!(output, "{}", object)?;
+            } else {
+                writeln!(output, "{}", token.dump(text))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the data of a file
+    fn parse(&mut self, input: &Input, output: &mut dyn Write) -> Outcome {
+        let (file, tokens) = self.open(input)?;
+        for datum in DatumIterator::new(tokens) {
+            match datum {
+                Ok(datum) => writeln!(output, "{:#?}", datum)?,
+                Err(error) => return Err(self.read_error(error, Some(file))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the syntax of every form of a file, reporting every error found, after applying
+    /// the fix-its of its unmatched parentheses to it if `fix` is set
+    fn check(&mut self, input: &Input, fix: bool) -> Outcome {
+        if let (true, Input::File(filename)) = (fix, input) {
+            self.fix(filename)?;
+        }
+        let (file, tokens) = self.open(input)?;
+        let mut outcome = Ok(());
+        for datum in DatumIterator::new(tokens).with_positions() {
+            let (datum, positions) = datum.map_err(|error| self.read_error(error, Some(file)))?;
+            let form = Value::from_positioned(&datum, &positions);
+            if let Err(error) = self.interpreter.check(&form) {
+                let failure = self.runtime_error(&error);
+                outcome = outcome.and(Err(failure));
+            }
+        }
+        outcome
+    }
+
+    /// Rewrites a file with its unmatched parentheses removed or closed
+    fn fix(&mut self, filename: &str) -> Outcome {
+        let text = fs::read_to_string(filename);
+        let mut text = text.map_err(|error| self.io_error(error, Some(filename)))?;
+        let edits = SyntaxTree::parse(&text).balancing_edits();
+        if edits.is_empty() {
+            return Ok(());
+        }
+        paredit::apply_edits(&mut text, &edits);
+        fs::write(filename, text).map_err(|error| self.io_error(error, Some(filename)))?;
+        eprintln!(
+            "oxyscheme: check: fixed {} unmatched parenthes{} in {}",
+            edits.len(),
+            if edits.len() == 1 { "is" } else { "es" },
+            filename
+        );
+        Ok(())
+    }
+
+    /// Reports the warnings of the linter about a file
+    fn lint(&mut self, input: &Input, linter: &Linter) -> Outcome {
+        let (file, tokens) = self.open(input)?;
+        for token in tokens {
+            token.map_err(|error| self.read_error(error, Some(file)))?;
+        }
+        let source_map = self.interpreter.source_map();
+        let lints = linter.lint(source_map.text(file).unwrap_or_default());
+        for lint in &lints {
+            let diagnostic = lint.to_diagnostic(file, source_map);
+            self.emitter.emit(&diagnostic, source_map)?;
+        }
+        match lints.is_empty() {
+            true => Ok(()),
+            false => Err(Failure::Lint),
+        }
+    }
+
+    /// Prints a file with its indentation fixed
+    fn fmt(&mut self, input: &Input, output: &mut dyn Write) -> Outcome {
+        let (file, _) = self.open(input)?;
+        let source = self.interpreter.source_map().text(file).unwrap_or_default();
+        match format::format_source(source) {
+            Ok(formatted) => Ok(output.write_all(formatted.as_bytes())?),
+            Err(error) => Err(self.read_error(error, Some(file))),
+        }
+    }
+
+    /// Evaluates every datum of a file, stopping with a backtrace at the first uncaught error
+    ///
+    /// Libraries are searched for in the directory of the file.
+    fn run(&mut self, input: &Input) -> Outcome {
+        if let Input::File(filename) = input {
+            if let Some(directory) = Path::new(filename).parent() {
+                self.interpreter.add_library_path(directory);
+            }
+        }
+        let (file, tokens) = self.open(input)?;
+        for datum in DatumIterator::new(tokens).with_positions() {
+            let (datum, positions) = datum.map_err(|error| self.read_error(error, Some(file)))?;
+            match self
+                .interpreter
+                .eval_datum_with_positions(&datum, &positions)
+            {
+                Ok(_) => {}
+                Err(error) if error.kind == ErrorKind::Aborted => return Err(Failure::Runtime),
+                Err(error) => return Err(self.runtime_error(&error)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a file under the debugger, reading commands from standard input
+    fn debug(&mut self, input: &Input) -> Outcome {
+        let (file, _) = self.open(input)?;
+        let source_map = self.interpreter.source_map();
+        let debugger = Debugger::new(
+            source_map.name(file).unwrap_or_default(),
+            source_map.text(file).unwrap_or_default(),
+            Box::new(BufReader::new(io::stdin())),
+            Box::new(io::stderr()),
+        );
+        self.interpreter.set_debug_hook(Some(Box::new(debugger)));
+        self.run(input)
+    }
+
+    /// Reads expressions from standard input, printing the value of each one
+    ///
+    /// Errors are reported and do not end the session, which lasts until the end of the input.
+    fn repl(&mut self) -> Outcome {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock();
+        let mut buffer = String::new();
+        let mut count = 0;
+        loop {
+            print!("{}", if buffer.is_empty() { "> " } else { "  " });
+            io::stdout().flush()?;
+            let mut line = String::new();
+            if lines.read_line(&mut line)? == 0 {
+                println!();
+                return Ok(());
+            }
+            buffer.push_str(&line);
+            // Input ending inside a list is continued on the next line
+            let complete =
+                DatumIterator::new(StringLexer::new(buffer.as_str()).into_iter()).all(|datum| {
+                    !matches!(
+                        datum,
+                        Err(CompilerError::TokenStreamEnded)
+                            | Err(CompilerError::MissingCloseParen(_))
+                    )
+                });
+            if !complete {
+                continue;
+            }
+            count += 1;
+            let input = std::mem::take(&mut buffer);
+            let file = self
+                .interpreter
+                .source_map_mut()
+                .add_snippet(format!("<repl:{}>", count), input.as_str());
+            let tokens = StringLexer::new(input).into_iter().with_file(file);
+            for datum in DatumIterator::new(tokens).with_positions() {
+                let (datum, positions) = match datum {
+                    Ok(datum) => datum,
+                    Err(error) => {
+                        self.read_error(error, Some(file));
+                        break;
+                    }
+                };
+                match self
+                    .interpreter
+                    .eval_datum_with_positions(&datum, &positions)
+                {
+                    Ok(Value::Unspecified) => {}
+                    Ok(value) => self.print_result(&value)?,
+                    Err(error) => {
+                        self.runtime_error(&error);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Registers an input in the source map and starts lexing it
+    fn open(&mut self, input: &Input) -> Result<(FileId, TokenStream), Failure> {
+        match input {
+            Input::File(filename) => {
+                let source_map = self.interpreter.source_map_mut();
+                let (file, lexer) = FileLexer::registered(source_map, filename)
+                    .map_err(|error| self.io_error(error, Some(filename)))?;
+                Ok((file, Box::new(lexer)))
+            }
+            Input::Stdin => {
+                let mut text = String::new();
+                io::stdin()
+                    .read_to_string(&mut text)
+                    .map_err(|error| self.io_error(error, Some("-")))?;
+                let source_map = self.interpreter.source_map_mut();
+                let file = source_map.add_snippet("<stdin>", text.as_str());
+                let lexer = StringLexer::new(text).into_iter().with_file(file);
+                Ok((file, Box::new(lexer)))
+            }
+        }
+    }
+
+    /// Where lex, parse and fmt write their output
+    fn create_output(&mut self, output: Option<&str>) -> Result<Box<dyn Write>, Failure> {
+        match output {
+            Some(filename) => match fs::File::create(filename) {
+                Ok(file) => Ok(Box::new(io::BufWriter::new(file))),
+                Err(error) => Err(self.io_error(error, Some(filename))),
+            },
+            None => Ok(Box::new(io::stdout())),
+        }
+    }
+
+    /// Makes the program write its output to a file
+    fn redirect_output(&mut self, output: Option<&str>) -> Outcome {
+        if let Some(filename) = output {
+            let port = OutputPort::create(filename);
+            let port = port.map_err(|error| self.io_error(error, Some(filename)))?;
+            let port = Rc::new(Port::Output(RefCell::new(port)));
+            self.interpreter.set_current_output_port(port);
+        }
+        Ok(())
+    }
+
+    /// Writes the value of an expression read by the REPL to the output of the program, which `-o`
+    /// may redirect
+    fn print_result(&mut self, value: &Value) -> Outcome {
+        if let Port::Output(port) = &**self.interpreter.current_output_port() {
+            port.borrow_mut()
+                .write_str(&format!("{}\n", value.written()))?;
+        }
+        Ok(())
+    }
 
-fn main() -> Result<()> {
-    let filename = env::args().nth(1).unwrap();
+    /// Flushes the output of the program, as the process exits without dropping it
+    fn close_output(&mut self) -> Outcome {
+        if let Port::Output(port) = &**self.interpreter.current_output_port() {
+            port.borrow_mut().close()?;
+        }
+        Ok(())
+    }
+
+    /// Reports a diagnostic, returning `failure`, or an I/O failure if it could not be reported
+    fn report(&mut self, diagnostic: Diagnostic, failure: Failure) -> Failure {
+        match self
+            .emitter
+            .emit(&diagnostic, self.interpreter.source_map())
+        {
+            Ok(()) => failure,
+            Err(_) => Failure::Io,
+        }
+    }
+
+    fn read_error(&mut self, error: CompilerError, file: Option<FileId>) -> Failure {
+        let failure = match error {
+            CompilerError::IOError(_) => Failure::Io,
+            _ => Failure::Read,
+        };
+        let source_map = self.interpreter.source_map();
+        let diagnostic = Diagnostic::from_compiler_error(&error, file, source_map);
+        self.report(diagnostic, failure)
+    }
+
+    fn io_error(&mut self, error: io::Error, filename: Option<&str>) -> Failure {
+        let source_map = self.interpreter.source_map();
+        let mut diagnostic = Diagnostic::from_compiler_error(&error.into(), None, source_map);
+        if let Some(filename) = filename {
+            diagnostic = diagnostic.with_note(format!("while accessing {}", filename));
+        }
+        self.report(diagnostic, Failure::Io)
+    }
 
-    let file_lexer = FileLexer::new(&filename)?;
-    let token_stream = file_lexer.into_iter();
-    let datum_stream = DatumIterator::new(token_stream);
-    for datum_res in datum_stream {
-        let datum = datum_res?;
-        println!("{:#?}", datum);
+    fn runtime_error(&mut self, error: &RuntimeError) -> Failure {
+        let failure = match error.kind {
+            ErrorKind::Syntax | ErrorKind::Read => Failure::Read,
+            ErrorKind::Io => Failure::Io,
+            _ => Failure::Runtime,
+        };
+        let diagnostic = Diagnostic::from_runtime_error(error, self.interpreter.source_map())
+            .with_suggestions(&self.interpreter.suggestions(error));
+        self.report(diagnostic, failure)
     }
-    Ok(())
 }
@@ -0,0 +1,591 @@
+//! Structural editing, which changes the shape of the data of a text rather than its characters
+//!
+//! The operations are those of paredit: slurping the next or previous datum into a list, barfing
+//! the last or first element out of it, wrapping a datum in a new list, splicing a list into the
+//! one around it, raising a datum in place of its list, and splitting or joining lists. They only
+//! move, insert or remove parentheses, except for raising, which removes the rest of the list.
+//!
+//! `edits` returns the `TextEdit`s carrying out an operation rather than the edited text, so that
+//! an editor can apply them to its buffer. They are checked before being returned: an operation
+//! is refused if it would leave parentheses unmatched that were not already, or would remove a
+//! comment.
+
+use crate::lexer::Token;
+use crate::syntax_tree::{Node, NodeKind, SyntaxTree, TextEdit};
+use crate::CompilerError;
+use std::ops::Range;
+
+/// A structural editing operation, carried out around a cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Moves the closing parenthesis of the innermost list that has a datum after it past that
+    /// datum: `(a |b) c` becomes `(a b c)`
+    SlurpForward,
+    /// Moves the opening parenthesis of the innermost list that has a datum before it before that
+    /// datum: `a (b| c)` becomes `(a b c)`
+    SlurpBackward,
+    /// Moves the last element of the innermost list out of it: `(a |b c)` becomes `(a b) c`
+    BarfForward,
+    /// Moves the first element of the innermost list out of it: `(a |b c)` becomes `a (b c)`
+    BarfBackward,
+    /// Wraps the datum at the cursor in a list: `(a |b)` becomes `(a (b))`
+    Wrap,
+    /// Removes the parentheses of the innermost list: `(a (b| c))` becomes `(a b c)`
+    Splice,
+    /// Replaces the innermost list with the datum at the cursor: `(a (b |c))` becomes `(a c)`
+    Raise,
+    /// Splits the innermost list in two at the cursor: `(a b| c)` becomes `(a b) (c)`
+    Split,
+    /// Joins the lists before and after the cursor: `(a b)| (c)` becomes `(a b c)`
+    Join,
+}
+
+/// Computes the edits carrying out `operation` around the byte `offset` of `text`
+///
+/// The edits do not overlap, are sorted by position, and refer to positions in `text`, as a
+/// language server `WorkspaceEdit` does; `apply_edits` applies them.
+pub fn edits(
+    text: &str,
+    offset: usize,
+    operation: Operation,
+) -> Result<Vec<TextEdit>, CompilerError> {
+    let editor = Editor::new(text, offset.min(text.len()));
+    if let Some(error) = editor.tree.lex_error() {
+        return Err(refuse(&format!("the text cannot be lexed: {}", error)));
+    }
+    let edits = match operation {
+        Operation::SlurpForward => editor.slurp_forward(),
+        Operation::SlurpBackward => editor.slurp_backward(),
+        Operation::BarfForward => editor.barf_forward(),
+        Operation::BarfBackward => editor.barf_backward(),
+        Operation::Wrap => editor.wrap(),
+        Operation::Splice => editor.splice(),
+        Operation::Raise => editor.raise(),
+        Operation::Split => editor.split(),
+        Operation::Join => editor.join(),
+    }?;
+    let mut edited = text.to_string();
+    apply_edits(&mut edited, &edits);
+    let after = SyntaxTree::parse(&edited);
+    if after.lex_error().is_some() || unmatched(&after) > unmatched(&editor.tree) {
+        return Err(refuse("the parentheses would not match"));
+    }
+    if comments(&after, &edited) != comments(&editor.tree, text) {
+        return Err(refuse("a comment would be removed"));
+    }
+    Ok(edits)
+}
+
+/// Applies edits that refer to positions in `text` and do not overlap, such as those `edits`
+/// returns
+pub fn apply_edits(text: &mut String, edits: &[TextEdit]) {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| edit.start);
+    for edit in edits.into_iter().rev() {
+        edit.apply(text);
+    }
+}
+
+fn refuse(reason: &str) -> CompilerError {
+    CompilerError::StructuralEdit(reason.to_string())
+}
+
+/// The number of unclosed lists and stray closing parentheses in a tree
+fn unmatched(tree: &SyntaxTree) -> usize {
+    fn count(node: &Node) -> usize {
+        let own = match node.kind {
+            NodeKind::List { closed } | NodeKind::Vector { closed } => !closed as usize,
+            NodeKind::StrayClose => 1,
+            NodeKind::Atom | NodeKind::Abbreviation | NodeKind::Dot => 0,
+        };
+        own + node.children.iter().map(count).sum::<usize>()
+    }
+    tree.forms().iter().map(count).sum()
+}
+
+/// The text of every comment of a tree, sorted
+fn comments<'a>(tree: &SyntaxTree, text: &'a str) -> Vec<&'a str> {
+    let lines = line_starts(text);
+    let mut comments: Vec<&str> = tree
+        .tokens()
+        .iter()
+        .filter(|token| token.token == Token::Comment)
+        .map(|token| {
+            let start = lines[token.span.line - 1] + token.span.column;
+            text[start..start + token.span.len].trim_end()
+        })
+        .collect();
+    comments.sort_unstable();
+    comments
+}
+
+/// The byte offset at which each line of a text starts
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+/// A text, its tree, and a cursor in it
+struct Editor<'a> {
+    text: &'a str,
+    tree: SyntaxTree,
+    lines: Vec<usize>,
+    offset: usize,
+}
+
+impl<'a> Editor<'a> {
+    fn new(text: &'a str, offset: usize) -> Self {
+        Editor {
+            text,
+            tree: SyntaxTree::parse(text),
+            lines: line_starts(text),
+            offset,
+        }
+    }
+
+    /// The byte offset at which a token starts
+    fn start(&self, index: usize) -> usize {
+        let token = self.tree.token(index);
+        self.lines[token.span.line - 1] + token.span.column
+    }
+
+    /// The byte offset at which a token ends
+    fn end(&self, index: usize) -> usize {
+        self.start(index) + self.tree.token(index).span.len
+    }
+
+    /// The bytes a node spans
+    fn span(&self, node: &Node) -> Range<usize> {
+        self.start(node.first)..self.end(node.last)
+    }
+
+    /// The 1-based line and byte column of a byte offset
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.lines.partition_point(|&start| start <= offset);
+        (line, offset - self.lines[line - 1])
+    }
+
+    fn replace(&self, range: Range<usize>, text: &str) -> TextEdit {
+        TextEdit {
+            start: self.position(range.start),
+            end: self.position(range.end),
+            text: text.to_string(),
+        }
+    }
+
+    fn insert(&self, offset: usize, text: &str) -> TextEdit {
+        self.replace(offset..offset, text)
+    }
+
+    fn delete(&self, range: Range<usize>) -> TextEdit {
+        self.replace(range, "")
+    }
+
+    /// The text of the opening token of a list or vector
+    fn open(&self, list: &Node) -> &'a str {
+        &self.text[self.start(list.first)..self.end(list.first)]
+    }
+
+    /// Whether the cursor is between the parentheses of a list, or within an abbreviation
+    fn surrounds_cursor(&self, node: &Node) -> bool {
+        match node.kind {
+            NodeKind::List { closed } | NodeKind::Vector { closed } => {
+                self.end(node.first) <= self.offset
+                    && (!closed || self.offset <= self.start(node.last))
+            }
+            NodeKind::Abbreviation => {
+                let span = self.span(node);
+                span.start < self.offset && self.offset < span.end
+            }
+            NodeKind::Atom | NodeKind::Dot | NodeKind::StrayClose => false,
+        }
+    }
+
+    /// The nodes around the cursor, from the top-level form down to the innermost one
+    fn path(&self) -> Vec<&Node> {
+        let mut path = Vec::new();
+        let mut nodes = self.tree.forms();
+        while let Some(node) = nodes.iter().find(|node| self.surrounds_cursor(node)) {
+            path.push(node);
+            nodes = &node.children;
+        }
+        path
+    }
+
+    /// The index in `path` of the innermost list or vector around the cursor
+    fn innermost(path: &[&Node]) -> Result<usize, CompilerError> {
+        path.iter()
+            .rposition(|node| is_list(node))
+            .ok_or_else(|| refuse("there is no list around the cursor"))
+    }
+
+    /// The index in `path` of the innermost closed list that is not dotted
+    fn innermost_proper(path: &[&Node]) -> Result<usize, CompilerError> {
+        let index = Editor::innermost(path)?;
+        check_proper(path[index])?;
+        Ok(index)
+    }
+
+    /// The siblings of the node at `index` in `path`, counting abbreviations of it as the node,
+    /// and its position among them
+    fn siblings<'b>(&'b self, path: &[&'b Node], index: usize) -> (&'b [Node], usize) {
+        let mut unit = index;
+        while unit > 0 && path[unit - 1].kind == NodeKind::Abbreviation {
+            unit -= 1;
+        }
+        let siblings = match unit {
+            0 => self.tree.forms(),
+            _ => &path[unit - 1].children[..],
+        };
+        let position = siblings
+            .iter()
+            .position(|sibling| sibling.first == path[unit].first)
+            .unwrap();
+        (siblings, position)
+    }
+
+    /// The data at the level of the cursor: the elements of the innermost list, or the top-level
+    /// forms
+    fn level<'b>(&'b self, path: &[&'b Node]) -> &'b [Node] {
+        match path.iter().rposition(|node| is_list(node)) {
+            Some(index) => &path[index].children,
+            None => self.tree.forms(),
+        }
+    }
+
+    /// The datum at the cursor, or the one after it
+    fn datum_at<'b>(&self, level: &'b [Node]) -> Result<&'b Node, CompilerError> {
+        level
+            .iter()
+            .find(|node| self.offset <= self.span(node).end)
+            .filter(|node| node.kind != NodeKind::StrayClose)
+            .ok_or_else(|| refuse("there is no datum at the cursor"))
+    }
+
+    /// Whether a part of the text is only whitespace
+    fn is_blank(&self, range: Range<usize>) -> bool {
+        self.text[range].trim().is_empty()
+    }
+
+    fn slurp_forward(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        for index in (0..path.len()).rev().filter(|&index| is_list(path[index])) {
+            let (siblings, position) = self.siblings(&path, index);
+            let next = match siblings.get(position + 1) {
+                Some(next) if next.kind != NodeKind::StrayClose => next,
+                _ => continue,
+            };
+            let list = path[index];
+            check_proper(list)?;
+            check_not_dot(next)?;
+            let mut removed = self.start(list.last)..self.end(list.last);
+            if list.children.is_empty() && self.is_blank(removed.end..self.span(next).start) {
+                removed.end = self.span(next).start;
+            }
+            return Ok(vec![
+                self.delete(removed),
+                self.insert(self.span(next).end, ")"),
+            ]);
+        }
+        Err(refuse("there is nothing to slurp"))
+    }
+
+    fn slurp_backward(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        for index in (0..path.len()).rev().filter(|&index| is_list(path[index])) {
+            let (siblings, position) = self.siblings(&path, index);
+            let previous = match position.checked_sub(1).map(|position| &siblings[position]) {
+                Some(previous) if previous.kind != NodeKind::StrayClose => previous,
+                _ => continue,
+            };
+            let list = path[index];
+            check_proper(list)?;
+            check_not_dot(previous)?;
+            let mut removed = self.start(list.first)..self.end(list.first);
+            if list.children.is_empty() && self.is_blank(self.span(previous).end..removed.start) {
+                removed.start = self.span(previous).end;
+            }
+            return Ok(vec![
+                self.insert(self.span(previous).start, self.open(list)),
+                self.delete(removed),
+            ]);
+        }
+        Err(refuse("there is nothing to slurp"))
+    }
+
+    fn barf_forward(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let list = path[Editor::innermost_proper(&path)?];
+        let last = list
+            .children
+            .last()
+            .ok_or_else(|| refuse("there is nothing to barf"))?;
+        let close = match list.children.len() {
+            1 => self.end(list.first),
+            n => self.span(&list.children[n - 2]).end,
+        };
+        let text = if close == self.span(last).start {
+            ") "
+        } else {
+            ")"
+        };
+        Ok(vec![
+            self.insert(close, text),
+            self.delete(self.start(list.last)..self.end(list.last)),
+        ])
+    }
+
+    fn barf_backward(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let list = path[Editor::innermost_proper(&path)?];
+        let first = list
+            .children
+            .first()
+            .ok_or_else(|| refuse("there is nothing to barf"))?;
+        let open = match list.children.get(1) {
+            Some(second) => self.span(second).start,
+            None => self.start(list.last),
+        };
+        let text = if open == self.span(first).end {
+            format!(" {}", self.open(list))
+        } else {
+            self.open(list).to_string()
+        };
+        Ok(vec![
+            self.delete(self.start(list.first)..self.end(list.first)),
+            self.insert(open, &text),
+        ])
+    }
+
+    fn wrap(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let datum = self.datum_at(self.level(&path))?;
+        check_not_dot(datum)?;
+        let span = self.span(datum);
+        Ok(vec![
+            self.insert(span.start, "("),
+            self.insert(span.end, ")"),
+        ])
+    }
+
+    fn splice(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let list = path[Editor::innermost_proper(&path)?];
+        Ok(vec![
+            self.delete(self.start(list.first)..self.end(list.first)),
+            self.delete(self.start(list.last)..self.end(list.last)),
+        ])
+    }
+
+    fn raise(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let list = path[Editor::innermost(&path)?];
+        if list.kind != (NodeKind::List { closed: true })
+            && list.kind != (NodeKind::Vector { closed: true })
+        {
+            return Err(refuse("the list is not closed"));
+        }
+        let datum = self.datum_at(&list.children)?;
+        check_not_dot(datum)?;
+        let removes_comment = (list.first..=list.last)
+            .filter(|&index| index < datum.first || index > datum.last)
+            .any(|index| self.tree.token(index).token == Token::Comment);
+        if removes_comment {
+            return Err(refuse("a comment would be removed"));
+        }
+        Ok(vec![
+            self.replace(self.span(list), &self.text[self.span(datum)])
+        ])
+    }
+
+    fn split(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let list = path[Editor::innermost_proper(&path)?];
+        let within_token = (list.first..=list.last).any(|index| {
+            self.start(index) < self.offset
+                && self.offset < self.end(index)
+                && self.tree.token(index).token != Token::Whitespace
+        });
+        if within_token {
+            return Err(refuse("the cursor is within a token"));
+        }
+        let close = list
+            .children
+            .iter()
+            .rev()
+            .find(|child| self.span(child).end <= self.offset)
+            .map_or(self.end(list.first), |child| self.span(child).end);
+        let open = list
+            .children
+            .iter()
+            .find(|child| self.span(child).start >= self.offset)
+            .map_or(self.start(list.last), |child| self.span(child).start);
+        if close == open {
+            Ok(vec![self.insert(close, &format!(") {}", self.open(list)))])
+        } else {
+            Ok(vec![
+                self.insert(close, ")"),
+                self.insert(open, self.open(list)),
+            ])
+        }
+    }
+
+    fn join(&self) -> Result<Vec<TextEdit>, CompilerError> {
+        let path = self.path();
+        let level = self.level(&path);
+        let before = level
+            .iter()
+            .rev()
+            .find(|node| self.span(node).end <= self.offset);
+        let after = level
+            .iter()
+            .find(|node| self.span(node).start >= self.offset);
+        let (before, after) = match (before, after) {
+            (Some(before), Some(after))
+                if is_list(before)
+                    && std::mem::discriminant(&before.kind)
+                        == std::mem::discriminant(&after.kind) =>
+            {
+                (before, after)
+            }
+            _ => return Err(refuse("there are no lists to join around the cursor")),
+        };
+        check_proper(before)?;
+        check_proper(after)?;
+        let close = self.start(before.last)..self.end(before.last);
+        let open = self.start(after.first)..self.end(after.first);
+        if close.end == open.start {
+            Ok(vec![self.replace(close.start..open.end, " ")])
+        } else {
+            Ok(vec![self.delete(close), self.delete(open)])
+        }
+    }
+}
+
+fn is_list(node: &Node) -> bool {
+    matches!(node.kind, NodeKind::List { .. } | NodeKind::Vector { .. })
+}
+
+/// Refuses to edit a list that is not closed, or is dotted
+fn check_proper(list: &Node) -> Result<(), CompilerError> {
+    if list.kind == (NodeKind::List { closed: false })
+        || list.kind == (NodeKind::Vector { closed: false })
+    {
+        Err(refuse("the list is not closed"))
+    } else if list
+        .children
+        .iter()
+        .any(|child| child.kind == NodeKind::Dot)
+    {
+        Err(refuse("the list is dotted"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Refuses to move the dot of a dotted list
+fn check_not_dot(node: &Node) -> Result<(), CompilerError> {
+    match node.kind {
+        NodeKind::Dot => Err(refuse("the dot of a dotted list cannot be moved")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_edits, edits, Operation};
+    use crate::CompilerError;
+
+    /// Carries out an operation at the `|` in `text`, returning the edited text
+    fn edit(text: &str, operation: Operation) -> Result<String, CompilerError> {
+        let offset = text.find('|').unwrap();
+        let text = text.replacen('|', "", 1);
+        let edits = edits(&text, offset, operation)?;
+        let mut edited = text;
+        apply_edits(&mut edited, &edits);
+        Ok(edited)
+    }
+
+    #[test]
+    fn slurp_test() {
+        use Operation::{SlurpBackward, SlurpForward};
+        assert_eq!(edit("(a |b) c", SlurpForward).unwrap(), "(a b c)");
+        assert_eq!(edit("((a |b)) c", SlurpForward).unwrap(), "((a b) c)");
+        assert_eq!(edit("(|) c", SlurpForward).unwrap(), "(c)");
+        assert_eq!(
+            edit("(f (g |x) ; why\n   y)", SlurpForward).unwrap(),
+            "(f (g x ; why\n   y))"
+        );
+        assert_eq!(edit("(a '(|b) c)", SlurpForward).unwrap(), "(a '(b c))");
+        assert_eq!(edit("a (b| c)", SlurpBackward).unwrap(), "(a b c)");
+        assert_eq!(edit("a #(|)", SlurpBackward).unwrap(), "#(a)");
+        assert!(edit("(a |b)", SlurpForward).is_err());
+        assert!(edit("(a . |b) c", SlurpForward).is_err());
+    }
+
+    #[test]
+    fn barf_test() {
+        use Operation::{BarfBackward, BarfForward};
+        assert_eq!(edit("(a |b c)", BarfForward).unwrap(), "(a b) c");
+        assert_eq!(edit("(|a)", BarfForward).unwrap(), "() a");
+        assert_eq!(
+            edit("(a ; keep\n |b)", BarfForward).unwrap(),
+            "(a) ; keep\n b"
+        );
+        assert_eq!(edit("(a |b c)", BarfBackward).unwrap(), "a (b c)");
+        assert_eq!(edit("(|a)", BarfBackward).unwrap(), "a ()");
+        assert!(edit("(|)", BarfForward).is_err());
+    }
+
+    #[test]
+    fn wrap_splice_raise_test() {
+        assert_eq!(edit("(a |b)", Operation::Wrap).unwrap(), "(a (b))");
+        assert_eq!(edit("(a |'b)", Operation::Wrap).unwrap(), "(a ('b))");
+        assert_eq!(edit("(a b|)", Operation::Wrap).unwrap(), "(a (b))");
+        assert!(edit("(a b |)", Operation::Wrap).is_err());
+        assert_eq!(
+            edit("(a (b| c) d)", Operation::Splice).unwrap(),
+            "(a b c d)"
+        );
+        assert_eq!(edit("(a #(b| c))", Operation::Splice).unwrap(), "(a b c)");
+        assert_eq!(edit("(a (b |c))", Operation::Raise).unwrap(), "(a c)");
+        assert_eq!(
+            edit("(if x (|g\n y) z)", Operation::Raise).unwrap(),
+            "(if x g z)"
+        );
+        assert!(edit("(a (b ; note\n |c))", Operation::Raise).is_err());
+    }
+
+    #[test]
+    fn split_join_test() {
+        assert_eq!(edit("(a b| c)", Operation::Split).unwrap(), "(a b) (c)");
+        assert_eq!(edit("(a b |c)", Operation::Split).unwrap(), "(a b) (c)");
+        assert_eq!(
+            edit("#(a| \"b\")", Operation::Split).unwrap(),
+            "#(a) #(\"b\")"
+        );
+        assert_eq!(edit("(a\"b\"|)", Operation::Split).unwrap(), "(a\"b\") ()");
+        assert!(edit("(ab|c)", Operation::Split).is_err());
+        assert!(edit("(a ;|b\n)", Operation::Split).is_err());
+        assert_eq!(edit("(a b)| (c)", Operation::Join).unwrap(), "(a b c)");
+        assert_eq!(edit("(a b)|(c)", Operation::Join).unwrap(), "(a b c)");
+        assert_eq!(
+            edit("(x (a) ; two\n |(b))", Operation::Join).unwrap(),
+            "(x (a ; two\n b))"
+        );
+        assert!(edit("(a)| #(b)", Operation::Join).is_err());
+    }
+
+    #[test]
+    fn refusal_test() {
+        assert!(edit("|a b", Operation::Splice).is_err());
+        assert!(edit("(a |#\\nope)", Operation::Wrap).is_err());
+        let error = edit("(a (b c) |d", Operation::Splice).unwrap_err();
+        assert_eq!(error.code(), "E0007");
+        assert_eq!(
+            error.to_string(),
+            "Cannot edit the structure: the list is not closed"
+        );
+    }
+}
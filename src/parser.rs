@@ -3,7 +3,8 @@
 
 use crate::lexer::Token;
 use crate::lexer::TokenWithPosition;
-use std::iter::Peekable;
+use crate::source_map::{FileId, Span};
+use std::{fmt, iter::Peekable};
 
 use crate::{lexer::LispNum, CompilerError};
 
@@ -37,38 +38,238 @@ pub enum Datum {
     Vector(Vec<Datum>),
 }
 
+/// A line and column in the input, as tracked by `TokenWithPosition`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    /// The line number, starting from 1
+    pub line: usize,
+    /// The byte offset into the line, starting from 0
+    pub column: usize,
+    /// The file the input was read from, if it was read from a registered file
+    pub file: Option<FileId>,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// The positions of a `Datum` and of the data nested inside it
+///
+/// `children` follows the layout of the `Datum`: one entry per element of a list or vector,
+/// followed by the tail of a dotted pair, or a single entry for the datum inside a quote or
+/// other abbreviation. Simple data have no children.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionTree {
+    /// Where the first token of the datum starts
+    pub position: Position,
+    /// The positions of the nested data
+    pub children: Vec<PositionTree>,
+}
+
+impl PositionTree {
+    fn leaf(position: Position) -> Self {
+        PositionTree {
+            position,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Where the indentation suggests close parens are missing: before the first line inside a list
+/// left open that is indented no further than the line the list starts on
+///
+/// ```text
+/// (define (f x)
+///   (display x)
+/// (f 1)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingParens {
+    /// The opening paren of the list the dedented line does not seem to belong to
+    pub open: Span,
+    /// The last token before the dedented line, right after which the parens are missing
+    pub after: Span,
+    /// The first token of the dedented line
+    pub dedented: Span,
+    /// How many close parens are missing there
+    pub count: usize,
+}
+
+impl fmt::Display for MissingParens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "close parens are missing at the end of line {}",
+            self.after.line
+        )
+    }
+}
+
+/// Where the indentation suggests a close paren is superfluous: at the end of a top-level form
+/// followed by a line indented further than its first one, as if the line were still inside it
+///
+/// ```text
+/// (define (f x)
+///   (display x))
+///   (newline))
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperfluousParen {
+    /// The superfluous close paren
+    pub close: Span,
+    /// The first token of the line indented as if it were still inside the form the paren closes
+    pub indented: Span,
+}
+
+impl fmt::Display for SuperfluousParen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the close paren at {} is superfluous", self.close)
+    }
+}
+
+/// The lists and vectors left open at the end of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unclosed {
+    /// The opening paren of the outermost list left open
+    pub open: Span,
+    /// The last token of the input, after which the lists can be closed
+    pub last: Span,
+    /// How many lists are left open
+    pub count: usize,
+    /// Where the indentation suggests some of the close parens are missing, if it does
+    pub missing: Option<MissingParens>,
+}
+
+/// A `Datum`, its positions and the span of its last token
+type Parsed = (Datum, PositionTree, Span);
+
+/// The state of a list or vector being parsed, telling how its lines are indented
+struct Frame {
+    /// The opening paren
+    open: Span,
+    /// The column of the first token on the line the list starts on
+    indentation: usize,
+    /// The column of the first token on the line of the last token read
+    line_indentation: usize,
+    /// The last token read
+    last: Span,
+    /// The first element starting a line indented no further than `indentation`, along with
+    /// the last token before it
+    dedented: Option<(Span, Span)>,
+}
+
+impl Frame {
+    fn new(open: Span, indentation: usize) -> Self {
+        Frame {
+            open,
+            indentation,
+            line_indentation: indentation,
+            last: open,
+            dedented: None,
+        }
+    }
+
+    /// Notes that an element starts at `first`, returning the indentation of its line
+    fn element(&mut self, first: Span) -> usize {
+        if first.line > self.last.line {
+            self.line_indentation = first.column;
+            if first.column <= self.indentation && self.dedented.is_none() {
+                self.dedented = Some((self.last, first));
+            }
+        }
+        self.line_indentation
+    }
+
+    fn missing(&self) -> Option<MissingParens> {
+        self.dedented.map(|(after, dedented)| MissingParens {
+            open: self.open,
+            after,
+            dedented,
+            count: 1,
+        })
+    }
+
+    /// The error for the input ending inside the list
+    fn unclosed(&self) -> CompilerError {
+        CompilerError::MissingCloseParen(Box::new(Unclosed {
+            open: self.open,
+            last: self.last,
+            count: 1,
+            missing: self.missing(),
+        }))
+    }
+
+    /// Carries an error of an element over to the list: a list left open inside it leaves it
+    /// open too, and the outermost dedented line is the one telling where parens are missing
+    fn enclose(&self, error: CompilerError) -> CompilerError {
+        match error {
+            CompilerError::MissingCloseParen(mut unclosed) => {
+                unclosed.open = self.open;
+                unclosed.count += 1;
+                match (self.missing(), &mut unclosed.missing) {
+                    (Some(missing), _) => unclosed.missing = Some(missing),
+                    // The list ends before the dedented line too, unless the line is indented
+                    // further than the list's own
+                    (None, Some(missing)) if self.indentation >= missing.dedented.column => {
+                        missing.count += 1;
+                    }
+                    _ => {}
+                }
+                CompilerError::MissingCloseParen(unclosed)
+            }
+            error => error,
+        }
+    }
+}
+
 /// Parses a single `Datum` from the token stream
 pub fn parse_datum<I>(token_stream: &mut Peekable<I>) -> Result<Datum, CompilerError>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
+    parse_datum_with_positions(token_stream).map(|(datum, _)| datum)
+}
+
+/// Parses a single `Datum` from the token stream, along with the positions it was read from
+pub fn parse_datum_with_positions<I>(
+    token_stream: &mut Peekable<I>,
+) -> Result<(Datum, PositionTree), CompilerError>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    parse_form(token_stream, None).map(|(datum, tree, _)| (datum, tree))
+}
+
+/// Parses a single `Datum` starting on a line indented to `indentation`, or to the column of the
+/// datum if it is not given, returning the span of its last token too
+pub(crate) fn parse_form<I>(
+    token_stream: &mut Peekable<I>,
+    indentation: Option<usize>,
+) -> Result<Parsed, CompilerError>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    skip_atmosphere(token_stream);
     match token_stream.peek() {
-        Some(Ok(TokenWithPosition {
-            token,
-            line,
-            column,
-        })) => match token {
-            Token::Boolean(_) => parse_simple_datum(token_stream),
-            Token::String(_) => parse_simple_datum(token_stream),
-            Token::Character(_) => parse_simple_datum(token_stream),
-            Token::Number(_) => parse_simple_datum(token_stream),
-            Token::Identifier(_) => parse_simple_datum(token_stream),
-            Token::Whitespace => {
-                token_stream.next();
-                parse_datum(token_stream)
+        Some(Ok(TokenWithPosition { token, span })) => {
+            let indentation = indentation.unwrap_or(span.column);
+            match token {
+                Token::Boolean(_) => parse_simple_datum(token_stream),
+                Token::String(_) => parse_simple_datum(token_stream),
+                Token::Character(_) => parse_simple_datum(token_stream),
+                Token::Number(_) => parse_simple_datum(token_stream),
+                Token::Identifier(_) => parse_simple_datum(token_stream),
+                Token::Punctuator(p) if p == "(" => parse_list(token_stream, indentation),
+                Token::Punctuator(p) if p == "#(" => parse_vector(token_stream, indentation),
+                Token::Punctuator(p) if p == "'" => parse_abbrev(token_stream, indentation),
+                Token::Punctuator(p) if p == "`" => parse_abbrev(token_stream, indentation),
+                Token::Punctuator(p) if p == "," => parse_abbrev(token_stream, indentation),
+                Token::Punctuator(p) if p == ",@" => parse_abbrev(token_stream, indentation),
+                _ => Err(CompilerError::UnexpectedToken(*span, None)),
             }
-            Token::Comment => {
-                token_stream.next();
-                parse_datum(token_stream)
-            }
-            Token::Punctuator(p) if p == "(" => parse_list(token_stream),
-            Token::Punctuator(p) if p == "#(" => parse_vector(token_stream),
-            Token::Punctuator(p) if p == "'" => parse_abbrev(token_stream),
-            Token::Punctuator(p) if p == "`" => parse_abbrev(token_stream),
-            Token::Punctuator(p) if p == "," => parse_abbrev(token_stream),
-            Token::Punctuator(p) if p == ",@" => parse_abbrev(token_stream),
-            _ => Err(CompilerError::UnexpectedToken(*line, *column)),
-        },
+        }
 
         Some(Err(_)) => Err(token_stream.next().unwrap().unwrap_err()),
 
@@ -76,42 +277,80 @@ where
     }
 }
 
-fn parse_simple_datum<I>(token_stream: &mut Peekable<I>) -> Result<Datum, CompilerError>
+/// Consumes whitespace and comment tokens, which carry no meaning between the elements of a list
+pub(crate) fn skip_atmosphere<I>(token_stream: &mut Peekable<I>)
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
-    let TokenWithPosition { token, .. } = token_stream.next().unwrap()?;
-    match token {
-        Token::Boolean(b) => Ok(Datum::Boolean(b)),
-        Token::String(s) => Ok(Datum::String(s)),
-        Token::Character(c) => Ok(Datum::Character(c)),
-        Token::Number(l) => Ok(Datum::Number(l)),
-        Token::Identifier(i) => Ok(Datum::Identifier(i)),
-        _ => unreachable!(),
+    while let Some(Ok(TokenWithPosition {
+        token: Token::Whitespace,
+        ..
+    }))
+    | Some(Ok(TokenWithPosition {
+        token: Token::Comment,
+        ..
+    })) = token_stream.peek()
+    {
+        token_stream.next();
     }
 }
 
-fn parse_vector<I>(token_stream: &mut Peekable<I>) -> Result<Datum, CompilerError>
+/// Consumes the opening token of a compound datum, returning its span
+fn consume_opening<I>(token_stream: &mut Peekable<I>) -> Result<(Token, Span), CompilerError>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    let TokenWithPosition { token, span } = token_stream.next().unwrap()?;
+    Ok((token, span))
+}
+
+fn parse_simple_datum<I>(token_stream: &mut Peekable<I>) -> Result<Parsed, CompilerError>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    let (token, span) = consume_opening(token_stream)?;
+    let datum = match token {
+        Token::Boolean(b) => Datum::Boolean(b),
+        Token::String(s) => Datum::String(s),
+        Token::Character(c) => Datum::Character(c),
+        Token::Number(l) => Datum::Number(l),
+        Token::Identifier(i) => Datum::Identifier(i),
+        _ => unreachable!(),
+    };
+    Ok((datum, PositionTree::leaf(span.start()), span))
+}
+
+fn parse_vector<I>(
+    token_stream: &mut Peekable<I>,
+    indentation: usize,
+) -> Result<Parsed, CompilerError>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
     let mut vector = Vec::new();
 
     // Consuming the "#("
-    token_stream.next();
+    let (_, open) = consume_opening(token_stream)?;
+    let mut tree = PositionTree::leaf(open.start());
+    let mut frame = Frame::new(open, indentation);
 
     loop {
+        skip_atmosphere(token_stream);
         match token_stream.peek() {
             Some(Ok(token_with_position)) => {
-                let token = &token_with_position.token;
-                match token {
+                let span = token_with_position.span;
+                match &token_with_position.token {
                     Token::Punctuator(p) if p == ")" => {
                         token_stream.next();
-                        break;
+                        return Ok((Datum::Vector(vector), tree, span));
                     }
                     _ => {
-                        let datum = parse_datum(token_stream)?;
+                        let indentation = frame.element(span);
+                        let (datum, child, last) = parse_form(token_stream, Some(indentation))
+                            .map_err(|error| frame.enclose(error))?;
+                        frame.last = last;
                         vector.push(datum);
+                        tree.children.push(child);
                     }
                 }
             }
@@ -121,27 +360,31 @@ where
             }
 
             None => {
-                // Figure out a way to include the line and column number of the error
-                return Err(CompilerError::MissingCloseParen);
+                return Err(frame.unclosed());
             }
         }
     }
-
-    Ok(Datum::Vector(vector))
 }
 
-fn parse_abbrev<I>(token_stream: &mut Peekable<I>) -> Result<Datum, CompilerError>
+fn parse_abbrev<I>(
+    token_stream: &mut Peekable<I>,
+    indentation: usize,
+) -> Result<Parsed, CompilerError>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
-    let TokenWithPosition { token, .. } = token_stream.next().unwrap()?;
-    let datum = parse_datum(token_stream)?;
+    let (token, span) = consume_opening(token_stream)?;
+    let (datum, child, last) = parse_form(token_stream, Some(indentation))?;
+    let tree = PositionTree {
+        position: span.start(),
+        children: vec![child],
+    };
     if let Token::Punctuator(s) = token {
         match s.as_str() {
-            "'" => Ok(Datum::Quote(Box::new(datum))),
-            "`" => Ok(Datum::Backquote(Box::new(datum))),
-            "," => Ok(Datum::Unquote(Box::new(datum))),
-            ",@" => Ok(Datum::UnquoteSplice(Box::new(datum))),
+            "'" => Ok((Datum::Quote(Box::new(datum)), tree, last)),
+            "`" => Ok((Datum::Backquote(Box::new(datum)), tree, last)),
+            "," => Ok((Datum::Unquote(Box::new(datum)), tree, last)),
+            ",@" => Ok((Datum::UnquoteSplice(Box::new(datum)), tree, last)),
             _ => unreachable!(),
         }
     } else {
@@ -149,30 +392,40 @@ where
     }
 }
 
-fn parse_list<I>(token_stream: &mut Peekable<I>) -> Result<Datum, CompilerError>
+fn parse_list<I>(
+    token_stream: &mut Peekable<I>,
+    indentation: usize,
+) -> Result<Parsed, CompilerError>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
     let mut car: Vec<Datum> = Vec::new();
 
     // Consuming the "("
-    token_stream.next();
+    let (_, open) = consume_opening(token_stream)?;
+    let mut tree = PositionTree::leaf(open.start());
+    let mut frame = Frame::new(open, indentation);
 
     loop {
+        skip_atmosphere(token_stream);
         match token_stream.peek() {
             Some(Ok(token_with_position)) => {
-                let token = &token_with_position.token;
-                match token {
+                let span = token_with_position.span;
+                match &token_with_position.token {
                     Token::Punctuator(p) if p == ")" => {
                         token_stream.next();
-                        return Ok(Datum::List(car));
+                        return Ok((Datum::List(car), tree, span));
                     }
                     Token::Punctuator(p) if p == "." => {
-                        return parse_cdr(token_stream, car);
+                        return parse_cdr(token_stream, car, tree, frame);
                     }
                     _ => {
-                        let next_datum = parse_datum(token_stream)?;
+                        let indentation = frame.element(span);
+                        let (next_datum, child, last) = parse_form(token_stream, Some(indentation))
+                            .map_err(|error| frame.enclose(error))?;
+                        frame.last = last;
                         car.push(next_datum);
+                        tree.children.push(child);
                     }
                 }
             }
@@ -180,46 +433,67 @@ where
                 return Err(token_stream.next().unwrap().unwrap_err());
             }
             None => {
-                // Figure out a way to include the line and column number of the error
-                return Err(CompilerError::MissingCloseParen);
+                return Err(frame.unclosed());
             }
         }
     }
 }
 
-fn parse_cdr<I>(token_stream: &mut Peekable<I>, car: Vec<Datum>) -> Result<Datum, CompilerError>
+fn parse_cdr<I>(
+    token_stream: &mut Peekable<I>,
+    car: Vec<Datum>,
+    mut tree: PositionTree,
+    mut frame: Frame,
+) -> Result<Parsed, CompilerError>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
-    token_stream.next();
-    let cdr = parse_datum(token_stream)?;
+    let (_, dot) = consume_opening(token_stream)?;
+    frame.element(dot);
+    frame.last = dot;
+    skip_atmosphere(token_stream);
+    let indentation = match token_stream.peek() {
+        Some(Ok(token_with_position)) => frame.element(token_with_position.span),
+        _ => frame.line_indentation,
+    };
+    let (cdr, child, last) =
+        parse_form(token_stream, Some(indentation)).map_err(|error| frame.enclose(error))?;
+    frame.last = last;
+    tree.children.push(child);
+    skip_atmosphere(token_stream);
     match token_stream.next() {
         Some(Ok(TokenWithPosition {
             token: Token::Punctuator(p),
-            ..
-        })) if p == ")" => Ok(Datum::DottedPair(car, Box::new(cdr))),
-        _ => {
-            // Figure out a way to include the line and column number of the error
-            Err(CompilerError::MissingCloseParen)
-        }
+            span,
+        })) if p == ")" => Ok((Datum::DottedPair(car, Box::new(cdr)), tree, span)),
+        _ => Err(frame.unclosed()),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse_datum, Datum};
+    use super::{
+        parse_datum, parse_datum_with_positions, Datum, MissingParens, Position, PositionTree,
+    };
     use crate::{
         lexer::{Token, TokenWithPosition},
+        source_map::Span,
         CompilerError,
     };
 
+    const SPAN: Span = Span {
+        file: None,
+        line: 0,
+        column: 0,
+        len: 0,
+    };
+
     #[test]
     fn parse_simple_datum_test() {
         let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> =
             vec![Ok(TokenWithPosition {
                 token: Token::Boolean(true),
-                line: 0,
-                column: 0,
+                span: SPAN,
             })];
         let mut token_stream = vec_of_res.into_iter().peekable();
         assert_eq!(
@@ -233,28 +507,23 @@ mod test {
         let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> = vec![
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from("#(")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from("#(")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Boolean(true),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(")")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(")")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
         ];
         let mut token_stream = vec_of_res.into_iter().peekable();
@@ -269,28 +538,23 @@ mod test {
         let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> = vec![
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from("(")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from("#(")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Boolean(true),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(")")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(")")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
         ];
         let mut token_stream = vec_of_res.into_iter().peekable();
@@ -305,28 +569,23 @@ mod test {
         let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> = vec![
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from("(")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Identifier(String::from("a")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(".")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Identifier(String::from("a")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Punctuator(String::from(")")),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
         ];
         let mut token_stream = vec_of_res.into_iter().peekable();
@@ -341,13 +600,11 @@ mod test {
         let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> = vec![
             Ok(TokenWithPosition {
                 token: Token::Punctuator("'".to_string()),
-                line: 0,
-                column: 0,
+                span: SPAN,
             }),
             Ok(TokenWithPosition {
                 token: Token::Boolean(true),
-                line: 0,
-                column: 1,
+                span: SPAN,
             }),
         ];
         let mut token_stream = vec_of_res.into_iter().peekable();
@@ -356,4 +613,136 @@ mod test {
             Datum::Quote(Box::new(Datum::Boolean(true)))
         );
     }
+
+    #[test]
+    fn parse_list_with_atmosphere_test() {
+        let tokens = vec![
+            Token::Punctuator(String::from("(")),
+            Token::Identifier(String::from("a")),
+            Token::Whitespace,
+            Token::Punctuator(String::from(".")),
+            Token::Whitespace,
+            Token::Identifier(String::from("b")),
+            Token::Comment,
+            Token::Punctuator(String::from(")")),
+        ];
+        let vec_of_res: Vec<Result<TokenWithPosition, CompilerError>> = tokens
+            .into_iter()
+            .map(|token| Ok(TokenWithPosition { token, span: SPAN }))
+            .collect();
+        let mut token_stream = vec_of_res.into_iter().peekable();
+        let car = vec![Datum::Identifier(String::from("a"))];
+        let cdr = Box::new(Datum::Identifier(String::from("b")));
+        assert_eq!(
+            parse_datum(&mut token_stream).unwrap(),
+            Datum::DottedPair(car, cdr)
+        );
+    }
+
+    #[test]
+    fn parse_positions_test() {
+        let tokens: Vec<Result<TokenWithPosition, CompilerError>> =
+            crate::reader::StringLexer::new("(a\n  '(b . c))")
+                .into_iter()
+                .collect();
+        let mut token_stream = tokens.into_iter().peekable();
+        let (_, tree) = parse_datum_with_positions(&mut token_stream).unwrap();
+        let leaf = |line, column| PositionTree {
+            position: Position {
+                line,
+                column,
+                file: None,
+            },
+            children: Vec::new(),
+        };
+        assert_eq!(
+            tree,
+            PositionTree {
+                position: Position {
+                    line: 1,
+                    column: 0,
+                    file: None
+                },
+                children: vec![
+                    leaf(1, 1),
+                    PositionTree {
+                        position: Position {
+                            line: 2,
+                            column: 2,
+                            file: None
+                        },
+                        children: vec![PositionTree {
+                            position: Position {
+                                line: 2,
+                                column: 3,
+                                file: None
+                            },
+                            children: vec![leaf(2, 4), leaf(2, 8)],
+                        }],
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn indentation_guess_test() {
+        let error = |text: &str| {
+            crate::reader::DatumIterator::new(crate::reader::StringLexer::new(text).into_iter())
+                .find_map(Result::err)
+                .unwrap()
+        };
+        let span = |line, column, len| Span {
+            file: None,
+            line,
+            column,
+            len,
+        };
+
+        let text = "(define (f x)\n  (let ((y 1))\n    (display y)\n(define (g) 1)\n";
+        match error(text) {
+            CompilerError::MissingCloseParen(unclosed) => {
+                assert_eq!(unclosed.open, span(1, 0, 1));
+                assert_eq!(unclosed.count, 2);
+                assert_eq!(
+                    unclosed.missing,
+                    Some(MissingParens {
+                        open: span(2, 2, 1),
+                        after: span(3, 14, 1),
+                        dedented: span(4, 0, 1),
+                        count: 2,
+                    })
+                );
+            }
+            error => panic!("{:?}", error),
+        }
+        assert_eq!(
+            error(text).to_string(),
+            "Missing close paren for the list opened at line 1, column 0; judging by the \
+             indentation, close parens are missing at the end of line 3"
+        );
+        match error("(begin\n  (display 1)\n  (newline)") {
+            CompilerError::MissingCloseParen(unclosed) => assert_eq!(unclosed.missing, None),
+            error => panic!("{:?}", error),
+        }
+
+        let text = "(define (f x)\n  (display x))\n  (newline))\n(f 1)\n";
+        match error(text) {
+            CompilerError::UnexpectedToken(stray, Some(guess)) => {
+                assert_eq!(stray, span(3, 11, 1));
+                assert_eq!(guess.close, span(2, 13, 1));
+                assert_eq!(guess.indented, span(3, 2, 1));
+            }
+            error => panic!("{:?}", error),
+        }
+        assert_eq!(
+            error(text).to_string(),
+            "Unexpected token encountered at line 3, column 11 while parsing input; judging by \
+             the indentation, the close paren at line 2, column 13 is superfluous"
+        );
+        assert!(matches!(
+            error("(a b))"),
+            CompilerError::UnexpectedToken(_, None)
+        ));
+    }
 }
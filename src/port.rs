@@ -0,0 +1,384 @@
+//! Input and output ports, as described in R5RS section 6.6
+//!
+//! Input ports read their source a line at a time into a character buffer, which lets `read`
+//! hand complete lines to the crate's own lexer and parser, and push back whatever follows the
+//! datum it read. Output ports either write to a file or standard output, or accumulate their
+//! output in a `String` so that it can be retrieved with `get-output-string`.
+use crate::lexer::{Token, TokenWithPosition};
+use crate::parser::{parse_datum, Datum};
+use crate::reader::StringLexer;
+use crate::CompilerError;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Cursor, Write},
+};
+
+/// A port, which is either an input or an output port
+pub enum Port {
+    /// A port characters and data can be read from
+    Input(RefCell<InputPort>),
+    /// A port characters and data can be written to
+    Output(RefCell<OutputPort>),
+}
+
+/// The state of an input port
+pub struct InputPort {
+    source: Option<Box<dyn BufRead>>,
+    buffer: VecDeque<char>,
+    interactive: bool,
+}
+
+impl InputPort {
+    fn new(source: Box<dyn BufRead>, interactive: bool) -> Self {
+        InputPort {
+            source: Some(source),
+            buffer: VecDeque::new(),
+            interactive,
+        }
+    }
+
+    /// Creates a port reading from an in-memory string
+    pub fn from_string(input: &str) -> Self {
+        InputPort::new(Box::new(Cursor::new(input.to_string())), false)
+    }
+
+    /// Opens a file for reading
+    pub fn open(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        Ok(InputPort::new(Box::new(BufReader::new(file)), false))
+    }
+
+    /// Creates a port reading from standard input
+    pub fn stdin() -> Self {
+        InputPort::new(Box::new(BufReader::new(io::stdin())), true)
+    }
+
+    /// Closes the port, discarding any buffered input
+    pub fn close(&mut self) {
+        self.source = None;
+        self.buffer.clear();
+    }
+
+    /// Reads another line from the source into the buffer, returning `false` at end of file
+    fn fill(&mut self) -> io::Result<bool> {
+        let source = match &mut self.source {
+            Some(source) => source,
+            None => return Ok(false),
+        };
+        let mut line = String::new();
+        if source.read_line(&mut line)? == 0 {
+            self.source = None;
+            return Ok(false);
+        }
+        self.buffer.extend(line.chars());
+        Ok(true)
+    }
+
+    /// Reads the next character, returning `None` at end of file
+    pub fn read_char(&mut self) -> io::Result<Option<char>> {
+        if self.buffer.is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+        Ok(self.buffer.pop_front())
+    }
+
+    /// Returns the next character without consuming it, or `None` at end of file
+    pub fn peek_char(&mut self) -> io::Result<Option<char>> {
+        if self.buffer.is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+        Ok(self.buffer.front().copied())
+    }
+
+    /// Returns `true` if reading a character would not block
+    ///
+    /// Only ports reading from standard input can block; they are ready when characters are
+    /// already buffered.
+    pub fn char_ready(&self) -> bool {
+        !self.interactive || !self.buffer.is_empty() || self.source.is_none()
+    }
+
+    /// Takes the rest of the current line, including the newline, or `None` at end of file
+    fn take_line(&mut self) -> io::Result<Option<String>> {
+        if self.buffer.is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+        let mut line = String::new();
+        while let Some(c) = self.buffer.pop_front() {
+            line.push(c);
+            if c == '\n' {
+                break;
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Puts characters back at the front of the buffer
+    fn unread(&mut self, text: &str) {
+        for c in text.chars().rev() {
+            self.buffer.push_front(c);
+        }
+    }
+
+    /// Reads the next datum from the port, returning `None` at end of file
+    ///
+    /// Lines are lexed one at a time until the parentheses opened by their tokens are closed, or
+    /// until the lexer fails, and then parsed. The characters following the datum, including any
+    /// the lexer failed on, are pushed back into the port.
+    pub fn read_datum(&mut self) -> Result<Option<Datum>, CompilerError> {
+        let mut text = String::new();
+        let mut tokens: Vec<TokenWithPosition> = Vec::new();
+        let mut lines = 0;
+        // Parentheses opened and not closed yet by the tokens read so far
+        let mut depth = 0isize;
+        loop {
+            let line = self.take_line()?;
+            let at_eof = line.is_none();
+            let mut lex_error = None;
+            if let Some(line) = line {
+                lines += 1;
+                // Tokens never extend over several lines, so each line is lexed once
+                for token in StringLexer::new(line.as_str()) {
+                    match token {
+                        Ok(mut token) => {
+                            token.span.line = lines;
+                            match &token.token {
+                                Token::Punctuator(p) if p.ends_with('(') => depth += 1,
+                                Token::Punctuator(p) if p == ")" => depth -= 1,
+                                _ => {}
+                            }
+                            tokens.push(token);
+                        }
+                        Err(CompilerError::LexError(leftover, mut span)) => {
+                            span.line = lines;
+                            lex_error = Some(CompilerError::LexError(leftover, span));
+                            break;
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                text.push_str(&line);
+            }
+
+            let significant = tokens
+                .iter()
+                .any(|t| !matches!(t.token, Token::Whitespace | Token::Comment));
+            if !significant {
+                if let Some(error) = lex_error {
+                    return Err(error);
+                }
+                if at_eof {
+                    return Ok(None);
+                }
+                text.clear();
+                tokens.clear();
+                lines = 0;
+                continue;
+            }
+            if depth > 0 && lex_error.is_none() && !at_eof {
+                continue;
+            }
+
+            let mut token_stream = tokens.iter().cloned().map(Ok).peekable();
+            match parse_datum(&mut token_stream) {
+                Ok(datum) => {
+                    let rest = match (token_stream.next(), &lex_error) {
+                        (Some(Ok(next)), _) => Some(next.span),
+                        (_, Some(CompilerError::LexError(_, span))) => Some(*span),
+                        _ => None,
+                    };
+                    if let Some(rest) = rest {
+                        let offset = byte_offset(&text, rest.line, rest.column);
+                        self.unread(&text[offset..]);
+                    }
+                    return Ok(Some(datum));
+                }
+                // The datum is incomplete because of the text the lexer failed on, or goes on
+                // in the following lines
+                Err(error @ CompilerError::TokenStreamEnded)
+                | Err(error @ CompilerError::MissingCloseParen(_)) => match lex_error {
+                    Some(lex_error) => return Err(lex_error),
+                    None if !at_eof => continue,
+                    None => return Err(error),
+                },
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+/// Converts a 1-based line number and a byte column, as found in `TokenWithPosition`, to a byte
+/// offset into `text`
+fn byte_offset(text: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    line_start + column
+}
+
+enum OutputSink {
+    Stdout,
+    File(BufWriter<File>),
+    String(String),
+}
+
+/// The state of an output port
+pub struct OutputPort {
+    sink: OutputSink,
+    closed: bool,
+}
+
+impl OutputPort {
+    /// Creates a port accumulating its output in a string
+    pub fn string() -> Self {
+        OutputPort {
+            sink: OutputSink::String(String::new()),
+            closed: false,
+        }
+    }
+
+    /// Creates a port writing to standard output
+    pub fn stdout() -> Self {
+        OutputPort {
+            sink: OutputSink::Stdout,
+            closed: false,
+        }
+    }
+
+    /// Creates or truncates a file for writing
+    pub fn create(filename: &str) -> io::Result<Self> {
+        Ok(OutputPort {
+            sink: OutputSink::File(BufWriter::new(File::create(filename)?)),
+            closed: false,
+        })
+    }
+
+    /// Writes a string to the port
+    pub fn write_str(&mut self, s: &str) -> io::Result<()> {
+        if self.closed {
+            return Err(io::Error::other("port is closed"));
+        }
+        match &mut self.sink {
+            OutputSink::Stdout => {
+                let mut stdout = io::stdout();
+                stdout.write_all(s.as_bytes())?;
+                stdout.flush()
+            }
+            OutputSink::File(file) => file.write_all(s.as_bytes()),
+            OutputSink::String(buffer) => {
+                buffer.push_str(s);
+                Ok(())
+            }
+        }
+    }
+
+    /// The output accumulated so far, if this is a string port
+    pub fn contents(&self) -> Option<&str> {
+        match &self.sink {
+            OutputSink::String(buffer) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    /// Flushes any buffered output and closes the port
+    pub fn close(&mut self) -> io::Result<()> {
+        self.closed = true;
+        match &mut self.sink {
+            OutputSink::File(file) => file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexer::LispNum;
+
+    #[test]
+    fn read_char_test() {
+        let mut port = InputPort::from_string("ab\nc");
+        assert_eq!(port.peek_char().unwrap(), Some('a'));
+        assert_eq!(port.read_char().unwrap(), Some('a'));
+        assert_eq!(port.read_char().unwrap(), Some('b'));
+        assert_eq!(port.read_char().unwrap(), Some('\n'));
+        assert_eq!(port.read_char().unwrap(), Some('c'));
+        assert_eq!(port.read_char().unwrap(), None);
+        assert!(port.char_ready());
+    }
+
+    #[test]
+    fn read_datum_test() {
+        let mut port = InputPort::from_string("1 (a\n b) ; comment\n  \"s\" x");
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::Number(LispNum::Integer(1)))
+        );
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::List(vec![
+                Datum::Identifier(String::from("a")),
+                Datum::Identifier(String::from("b"))
+            ]))
+        );
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::String(String::from("s")))
+        );
+        assert_eq!(port.read_char().unwrap(), Some(' '));
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::Identifier(String::from("x")))
+        );
+        assert_eq!(port.read_datum().unwrap(), None);
+    }
+
+    #[test]
+    fn read_before_bad_text_test() {
+        let mut port = InputPort::from_string("1 #<bad\n(a\n b) #<worse");
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::Number(LispNum::Integer(1)))
+        );
+        assert!(matches!(
+            port.read_datum(),
+            Err(CompilerError::LexError(..))
+        ));
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Datum::List(vec![
+                Datum::Identifier(String::from("a")),
+                Datum::Identifier(String::from("b"))
+            ]))
+        );
+        assert_eq!(port.read_char().unwrap(), Some(' '));
+        assert!(matches!(
+            port.read_datum(),
+            Err(CompilerError::LexError(..))
+        ));
+        assert_eq!(port.read_datum().unwrap(), None);
+    }
+
+    #[test]
+    fn read_incomplete_datum_test() {
+        let mut port = InputPort::from_string("(a b");
+        assert!(matches!(
+            port.read_datum(),
+            Err(CompilerError::MissingCloseParen(_))
+        ));
+    }
+
+    #[test]
+    fn string_output_test() {
+        let mut port = OutputPort::string();
+        port.write_str("hello").unwrap();
+        port.write_str(" world").unwrap();
+        assert_eq!(port.contents(), Some("hello world"));
+        port.close().unwrap();
+        assert!(port.write_str("!").is_err());
+    }
+}
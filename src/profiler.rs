@@ -0,0 +1,396 @@
+//! Instrumenting profiler for the interpreter
+//!
+//! An `Interpreter` with a `Profiler` installed reports every procedure call to it: when a frame
+//! is entered, when a tail call replaces it, and when it is left. The profiler measures the
+//! time between these events, and attributes it to the procedure in the innermost frame, to the
+//! line of the call which entered that frame, and to the whole stack of procedures in progress.
+//! Procedures are told apart by where they were defined as well as by name, and lines by the
+//! file they are in, so that programs made of several files are profiled correctly.
+//!
+//! The results can be written as a flat table, or as folded stacks, the input format of
+//! flamegraph tools such as `flamegraph.pl` and `inferno`, with times in microseconds.
+
+use crate::interpreter::StackFrame;
+use crate::parser::Position;
+use crate::source_map::{FileId, SourceMap};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Identifies a procedure: its name, and where it was defined if it was created from source code
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProcedureId {
+    /// The name of the procedure, `#<procedure>` for anonymous ones
+    pub name: String,
+    /// The position of the form defining the procedure
+    pub definition: Option<Position>,
+}
+
+/// A source line: the file it is in, if it was read from a registered one, and its number
+pub type Line = (Option<FileId>, usize);
+
+/// Calls and time spent in a procedure
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcedureStats {
+    /// The number of calls to the procedure, including tail calls
+    pub calls: usize,
+    /// Time spent in the procedure itself, excluding the procedures it called
+    pub self_time: Duration,
+    /// Time spent between entering and leaving the procedure, counting recursive calls once
+    pub total_time: Duration,
+}
+
+/// Calls made from a source line and the time spent in them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineStats {
+    /// The number of calls made from the line
+    pub calls: usize,
+    /// Time spent in the procedures called from the line, excluding the procedures they called
+    pub self_time: Duration,
+}
+
+/// A frame of the profiled program, as seen by the profiler
+struct Entry {
+    procedure: ProcedureId,
+    line: Option<Line>,
+    entered: Instant,
+    /// Length of the folded stack without this frame
+    path_len: usize,
+}
+
+/// Collects call counts and timings of a running program
+pub struct Profiler {
+    procedures: HashMap<ProcedureId, ProcedureStats>,
+    lines: BTreeMap<Line, LineStats>,
+    folded: HashMap<String, Duration>,
+    stack: Vec<Entry>,
+    /// Number of frames of each procedure on the stack, to count recursive calls once
+    active: HashMap<ProcedureId, usize>,
+    /// The names of the procedures on the stack, outermost first, separated by `;`
+    path: String,
+    last_event: Instant,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+impl Profiler {
+    /// Creates a profiler which has not seen any calls yet
+    pub fn new() -> Self {
+        Profiler {
+            procedures: HashMap::new(),
+            lines: BTreeMap::new(),
+            folded: HashMap::new(),
+            stack: Vec::new(),
+            active: HashMap::new(),
+            path: String::new(),
+            last_event: Instant::now(),
+        }
+    }
+
+    /// Records that `frame` was pushed onto the stack
+    pub fn enter(&mut self, frame: &StackFrame) {
+        let now = self.charge();
+        let procedure = ProcedureId {
+            name: frame_name(frame),
+            definition: frame.definition(),
+        };
+        self.procedures.entry(procedure.clone()).or_default().calls += 1;
+        let line = frame
+            .call_site
+            .map(|position| (position.file, position.line));
+        if let Some(line) = line {
+            self.lines.entry(line).or_default().calls += 1;
+        }
+        *self.active.entry(procedure.clone()).or_default() += 1;
+        let path_len = self.path.len();
+        if !self.path.is_empty() {
+            self.path.push(';');
+        }
+        self.path.push_str(&procedure.name);
+        self.stack.push(Entry {
+            procedure,
+            line,
+            entered: now,
+            path_len,
+        });
+    }
+
+    /// Records that the innermost frame was replaced by `frame` in a tail call
+    pub fn tail_call(&mut self, frame: &StackFrame) {
+        self.exit();
+        self.enter(frame);
+    }
+
+    /// Records that the innermost frame was popped off the stack
+    pub fn exit(&mut self) {
+        let now = self.charge();
+        let entry = match self.stack.pop() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.path.truncate(entry.path_len);
+        let active = self.active.get_mut(&entry.procedure).unwrap();
+        *active -= 1;
+        if *active == 0 {
+            let stats = self.procedures.get_mut(&entry.procedure).unwrap();
+            stats.total_time += now - entry.entered;
+        }
+    }
+
+    /// Attributes the time since the last event to the innermost frame, returning the current
+    /// time
+    fn charge(&mut self) -> Instant {
+        let now = Instant::now();
+        let elapsed = now - self.last_event;
+        self.last_event = now;
+        if let Some(entry) = self.stack.last() {
+            self.procedures.get_mut(&entry.procedure).unwrap().self_time += elapsed;
+            if let Some(line) = entry.line {
+                self.lines.get_mut(&line).unwrap().self_time += elapsed;
+            }
+            *self.folded.entry(self.path.clone()).or_default() += elapsed;
+        }
+        now
+    }
+
+    /// The statistics of every procedure called so far
+    pub fn procedures(&self) -> &HashMap<ProcedureId, ProcedureStats> {
+        &self.procedures
+    }
+
+    /// The statistics of every line calls were made from so far
+    pub fn lines(&self) -> &BTreeMap<Line, LineStats> {
+        &self.lines
+    }
+
+    /// The time spent in each stack of procedures, keyed by their names separated by `;`
+    pub fn folded_stacks(&self) -> &HashMap<String, Duration> {
+        &self.folded
+    }
+
+    /// Writes the procedures sorted by their self time, and then the lines calls were made from,
+    /// naming the files they are in as `source_map` does
+    pub fn write_flat(&self, source_map: &SourceMap, out: &mut dyn Write) -> io::Result<()> {
+        let total: Duration = self.procedures.values().map(|stats| stats.self_time).sum();
+        let mut procedures: Vec<_> = self
+            .procedures
+            .iter()
+            .map(|(procedure, stats)| {
+                let name = match procedure.definition {
+                    Some(position) => format!(
+                        "{} ({})",
+                        procedure.name,
+                        location(source_map, position.file, position.line)
+                    ),
+                    None => procedure.name.clone(),
+                };
+                (name, stats)
+            })
+            .collect();
+        procedures.sort_by(|a, b| b.1.self_time.cmp(&a.1.self_time).then(a.0.cmp(&b.0)));
+        writeln!(
+            out,
+            "{:>10} {:>12} {:>7} {:>12}  procedure",
+            "calls", "self (ms)", "self %", "total (ms)"
+        )?;
+        for (name, stats) in procedures {
+            writeln!(
+                out,
+                "{:>10} {:>12.3} {:>7.2} {:>12.3}  {}",
+                stats.calls,
+                milliseconds(stats.self_time),
+                percentage(stats.self_time, total),
+                milliseconds(stats.total_time),
+                name
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{:>10} {:>12} {:>7}  line",
+            "calls", "self (ms)", "self %"
+        )?;
+        for ((file, line), stats) in &self.lines {
+            writeln!(
+                out,
+                "{:>10} {:>12.3} {:>7.2}  {}",
+                stats.calls,
+                milliseconds(stats.self_time),
+                percentage(stats.self_time, total),
+                location(source_map, *file, *line)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one line per stack of procedures, with the time spent in it in microseconds
+    pub fn write_folded(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut stacks: Vec<_> = self.folded.iter().collect();
+        stacks.sort();
+        for (stack, time) in stacks {
+            writeln!(out, "{} {}", stack, time.as_micros())?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the procedure of a frame, with characters that are special in folded stacks
+/// replaced
+fn frame_name(frame: &StackFrame) -> String {
+    frame
+        .name()
+        .unwrap_or("#<procedure>")
+        .replace(|c: char| c == ';' || c.is_whitespace(), "_")
+}
+
+/// A line as shown in the flat profile: `file:line`, or `line N` out of registered files
+fn location(source_map: &SourceMap, file: Option<FileId>, line: usize) -> String {
+    match file.and_then(|file| source_map.name(file)) {
+        Some(name) => format!("{}:{}", name, line),
+        None => format!("line {}", line),
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn percentage(part: Duration, total: Duration) -> f64 {
+    if total.as_nanos() == 0 {
+        0.0
+    } else {
+        part.as_secs_f64() / total.as_secs_f64() * 100.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Profiler;
+    use crate::builtins::test::eval_str_with;
+    use crate::interpreter::Interpreter;
+    use std::fs;
+
+    const PROGRAM: &str = "(define (fib n)
+  (if (< n 2)
+      n
+      (+ (fib (- n 1))
+         (fib (- n 2)))))
+(define (loop n) (if (> n 0) (loop (- n 1))))
+(fib 5)
+(loop 3)";
+
+    fn profile() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_profiler(Some(Profiler::new()));
+        eval_str_with(&mut interpreter, PROGRAM).unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn call_counts_test() {
+        let interpreter = profile();
+        let profiler = interpreter.profiler().unwrap();
+        let calls = |name: &str| -> usize {
+            let procedures = profiler.procedures().iter();
+            procedures
+                .filter(|(procedure, _)| procedure.name == name)
+                .map(|(_, stats)| stats.calls)
+                .sum()
+        };
+        assert_eq!(calls("fib"), 15);
+        assert_eq!(calls("+"), 7);
+        assert_eq!(calls("loop"), 4);
+        assert_eq!(calls(">"), 4);
+        // Calls to `+`, `fib` and `-` are made from line 4, and to `fib` and `-` from line 5
+        assert_eq!(profiler.lines()[&(None, 4)].calls, 21);
+        assert_eq!(profiler.lines()[&(None, 5)].calls, 14);
+        assert_eq!(profiler.lines()[&(None, 7)].calls, 1);
+        for stats in profiler.procedures().values() {
+            assert!(stats.self_time <= stats.total_time);
+        }
+    }
+
+    #[test]
+    fn output_test() {
+        let interpreter = profile();
+        let profiler = interpreter.profiler().unwrap();
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        let stacks: Vec<_> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert!(stacks.contains(&"fib"));
+        assert!(stacks.contains(&"fib;fib;fib;<"));
+        // The tail calls of `loop` replace its frame
+        assert!(stacks.contains(&"loop"));
+        assert!(stacks.contains(&"loop;-"));
+        assert!(!stacks.iter().any(|stack| stack.contains("loop;loop")));
+
+        let mut flat = Vec::new();
+        profiler
+            .write_flat(interpreter.source_map(), &mut flat)
+            .unwrap();
+        let flat = String::from_utf8(flat).unwrap();
+        assert!(flat.starts_with("     calls    self (ms)  self %   total (ms)  procedure\n"));
+        assert!(flat
+            .lines()
+            .any(|line| line.starts_with("        15 ") && line.ends_with("  fib (line 1)")));
+        assert!(flat
+            .lines()
+            .any(|line| line.starts_with("        21 ") && line.ends_with("  line 4")));
+    }
+
+    #[test]
+    fn several_files_test() {
+        let dir = std::env::temp_dir().join(format!("scheme-profiler-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.scm");
+        let second = dir.join("second.scm");
+        fs::write(&first, "(define (helper) 1)\n(helper)\n").unwrap();
+        fs::write(&second, "(define (helper) 2)\n(helper) (helper)\n").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_profiler(Some(Profiler::new()));
+        let program = format!("(load {:?}) (load {:?})", first, second);
+        eval_str_with(&mut interpreter, &program).unwrap();
+        let profiler = interpreter.profiler().unwrap();
+        let source_map = interpreter.source_map();
+
+        // The procedures with the same name defined in each file are counted apart
+        let mut helpers: Vec<_> = profiler
+            .procedures()
+            .iter()
+            .filter(|(procedure, _)| procedure.name == "helper")
+            .map(|(procedure, stats)| {
+                let file = procedure.definition.unwrap().file.unwrap();
+                (source_map.path(file).unwrap().to_path_buf(), stats.calls)
+            })
+            .collect();
+        helpers.sort();
+        assert_eq!(helpers, vec![(first.clone(), 1), (second.clone(), 2)]);
+        // And so are the same lines of each file
+        let lines: Vec<_> = profiler
+            .lines()
+            .iter()
+            .filter_map(|((file, line), stats)| {
+                let path = source_map.path((*file)?)?;
+                Some((path.to_path_buf(), *line, stats.calls))
+            })
+            .collect();
+        assert_eq!(lines, vec![(first.clone(), 2, 1), (second.clone(), 2, 2)]);
+
+        let mut flat = Vec::new();
+        profiler.write_flat(source_map, &mut flat).unwrap();
+        let flat = String::from_utf8(flat).unwrap();
+        let first = first.display();
+        assert!(flat.contains(&format!("  helper ({}:1)\n", first)));
+        assert!(flat.contains(&format!("  helper ({}:1)\n", second.display())));
+        assert!(flat.contains(&format!("  {}:2\n", first)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,14 @@
 //! Handles reading files, and annotating tokens with line and column numbers
 use crate::lexer::*;
-use crate::parser::{parse_datum, Datum};
+use crate::parser::{parse_form, skip_atmosphere, Datum, PositionTree, SuperfluousParen};
+use crate::source_map::{FileId, SourceMap, Span};
 use crate::*;
 use anyhow::Result;
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Lines},
+    io::{self, BufRead, BufReader, Cursor, Lines},
     iter::{Enumerate, Peekable},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 /// `FileLexer` can be turned into an iterator of `Ok(TokenWithPosition)` and `Err(_)`
@@ -37,6 +38,7 @@ use std::{
 /// ```
 pub struct FileLexer {
     file: File,
+    file_id: Option<FileId>,
 }
 
 impl FileLexer {
@@ -44,8 +46,29 @@ impl FileLexer {
     pub fn new(filename: &str) -> Result<Self, CompilerError> {
         Ok(FileLexer {
             file: File::open(PathBuf::from(filename))?,
+            file_id: None,
         })
     }
+
+    /// Tags every token read from the file with `file_id`
+    pub fn with_file(mut self, file_id: FileId) -> Self {
+        self.file_id = Some(file_id);
+        self
+    }
+
+    /// Registers a file in `source_map` and lexes the text registered for it, tagging every
+    /// token with the id it was given
+    pub fn registered<P: AsRef<Path>>(
+        source_map: &mut SourceMap,
+        path: P,
+    ) -> io::Result<(FileId, LineLexer<Cursor<String>>)> {
+        let file_id = source_map.add_file(path)?;
+        let text = source_map.text(file_id).unwrap_or_default().to_string();
+        Ok((
+            file_id,
+            LineLexer::new(Cursor::new(text)).with_file(file_id),
+        ))
+    }
 }
 
 impl IntoIterator for FileLexer {
@@ -53,28 +76,95 @@ impl IntoIterator for FileLexer {
     type IntoIter = FileLexerIntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        let line_enumerator = BufReader::new(self.file).lines().enumerate();
-        let input_string = String::from("");
-        FileLexerIntoIter {
-            line_enumerator,
-            input_string,
-            cursor_position: 0,
-            line_number: 0,
-            encountered_error: false,
+        let lexer = LineLexer::new(BufReader::new(self.file));
+        match self.file_id {
+            Some(file_id) => lexer.with_file(file_id),
+            None => lexer,
         }
     }
 }
 
 /// The associated Iterator type for FileLexer
-pub struct FileLexerIntoIter {
-    line_enumerator: Enumerate<Lines<BufReader<File>>>,
+pub type FileLexerIntoIter = LineLexer<BufReader<File>>;
+
+/// `StringLexer` is the in-memory counterpart of `FileLexer`
+///
+/// It lexes a `String` instead of the contents of a file, and produces the same stream of
+/// `Result<TokenWithPosition, CompilerError>` when turned into an iterator.
+///
+/// ```
+/// # use oxyscheme::CompilerError;
+/// # use oxyscheme::reader::StringLexer;
+/// # use oxyscheme::lexer::TokenWithPosition;
+/// let string_lexer = StringLexer::new("(display \"Hello\")");
+/// let vec_of_tokens_res: Result<Vec<TokenWithPosition>, CompilerError> = string_lexer.into_iter().collect();
+/// assert_eq!(vec_of_tokens_res.unwrap().len(), 5);
+/// ```
+pub struct StringLexer {
+    input: String,
+}
+
+impl StringLexer {
+    /// Creates a `StringLexer` from the given input
+    pub fn new<S: Into<String>>(input: S) -> Self {
+        StringLexer {
+            input: input.into(),
+        }
+    }
+}
+
+impl IntoIterator for StringLexer {
+    type Item = Result<TokenWithPosition, CompilerError>;
+    type IntoIter = LineLexer<Cursor<String>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        LineLexer::new(Cursor::new(self.input))
+    }
+}
+
+/// Iterator lexing any buffered reader line by line, used by both `FileLexer` and `StringLexer`
+pub struct LineLexer<R: BufRead> {
+    line_enumerator: Enumerate<Lines<R>>,
     input_string: String,
     cursor_position: usize,
     line_number: usize,
+    file: Option<FileId>,
     encountered_error: bool,
 }
 
-impl Iterator for FileLexerIntoIter {
+impl<R: BufRead> LineLexer<R> {
+    /// Creates a `LineLexer` reading from `reader`
+    pub fn new(reader: R) -> Self {
+        LineLexer {
+            line_enumerator: reader.lines().enumerate(),
+            input_string: String::from(""),
+            cursor_position: 0,
+            line_number: 0,
+            file: None,
+            encountered_error: false,
+        }
+    }
+
+    /// Tags every token read with `file_id`
+    pub fn with_file(mut self, file_id: FileId) -> Self {
+        self.file = Some(file_id);
+        self
+    }
+}
+
+impl<R: BufRead> LineLexer<R> {
+    /// The span of `len` bytes at the cursor
+    fn span(&self, len: usize) -> Span {
+        Span {
+            file: self.file,
+            line: self.line_number,
+            column: self.cursor_position,
+            len,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LineLexer<R> {
     type Item = Result<TokenWithPosition, CompilerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -102,21 +192,21 @@ impl Iterator for FileLexerIntoIter {
 
         match lex_input(&self.input_string[self.cursor_position..]) {
             Ok((leftover, parsed)) => {
+                let end = self.input_string.len() - leftover.len();
                 let token_with_position = TokenWithPosition {
                     token: parsed,
-                    line: self.line_number,
-                    column: self.cursor_position,
+                    span: self.span(end - self.cursor_position),
                 };
-                self.cursor_position = self.input_string.len() - leftover.len();
+                self.cursor_position = end;
 
                 Some(Ok(token_with_position))
             }
             Err(_) => {
                 self.encountered_error = true;
+                let leftover = &self.input_string[self.cursor_position..];
                 Some(Err(CompilerError::LexError(
-                    String::from(&self.input_string[self.cursor_position..]),
-                    self.line_number,
-                    self.cursor_position,
+                    String::from(leftover),
+                    self.span(leftover.len()),
                 )))
             }
         }
@@ -150,6 +240,14 @@ where
 {
     token_stream: Peekable<I>,
     encountered_error: bool,
+    /// The last token of the previous datum
+    last: Option<Span>,
+    /// The column of the first token on the line of the last token read
+    line_indentation: usize,
+    /// The indentation of the previous datum and its last token, if the token is a close paren
+    closed: Option<(usize, Span)>,
+    /// Where the indentation suggests a close paren is superfluous, should one be unmatched
+    superfluous: Option<SuperfluousParen>,
 }
 
 impl<I> DatumIterator<I>
@@ -161,27 +259,123 @@ where
         DatumIterator {
             token_stream: token_stream.peekable(),
             encountered_error: false,
+            last: None,
+            line_indentation: 0,
+            closed: None,
+            superfluous: None,
         }
     }
 }
 
-impl<I> Iterator for DatumIterator<I>
+impl<I> DatumIterator<I>
 where
     I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
 {
-    type Item = Result<Datum, CompilerError>;
+    /// Turns the iterator into one that also yields the positions each `Datum` was read from
+    pub fn with_positions(self) -> PositionedDatumIterator<I> {
+        PositionedDatumIterator { data: self }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_with_positions(&mut self) -> Option<Result<(Datum, PositionTree), CompilerError>> {
         if self.encountered_error {
             return None;
         }
 
-        self.token_stream.peek()?;
+        // Whitespace and comments after the last datum do not start another one
+        skip_atmosphere(&mut self.token_stream);
+        let (first, stray) = match self.token_stream.peek()? {
+            Ok(TokenWithPosition { token, span }) => (
+                Some(*span),
+                matches!(token, Token::Punctuator(p) if p == ")"),
+            ),
+            Err(_) => (None, false),
+        };
+        // The stray paren itself does not tell which paren before it is superfluous
+        let indentation = first
+            .filter(|_| !stray)
+            .map(|first| self.indentation(first));
+
+        match parse_form(&mut self.token_stream, indentation) {
+            Ok((datum, tree, last)) => {
+                self.closed = indentation
+                    .filter(|_| ends_with_close(&datum))
+                    .map(|indentation| (indentation, last));
+                self.last = Some(last);
+                Some(Ok((datum, tree)))
+            }
+            Err(error) => {
+                self.encountered_error = true;
+                match error {
+                    CompilerError::UnexpectedToken(span, None) if stray => Some(Err(
+                        CompilerError::UnexpectedToken(span, self.superfluous.map(Box::new)),
+                    )),
+                    error => Some(Err(error)),
+                }
+            }
+        }
+    }
 
-        let datum_res = parse_datum(&mut self.token_stream);
-        if datum_res.is_err() {
-            self.encountered_error = true;
+    /// The indentation of the line of a datum starting at `first`, noting where a close paren
+    /// seems superfluous if the line starts with it
+    fn indentation(&mut self, first: Span) -> usize {
+        if self.last.is_none_or(|last| first.line > last.line) {
+            self.line_indentation = first.column;
+            match self.closed {
+                Some((indentation, close))
+                    if first.column > indentation && self.superfluous.is_none() =>
+                {
+                    self.superfluous = Some(SuperfluousParen {
+                        close,
+                        indented: first,
+                    });
+                }
+                _ => {}
+            }
         }
-        Some(datum_res)
+        self.line_indentation
+    }
+}
+
+/// Whether the last token of `datum` is a close paren
+fn ends_with_close(datum: &Datum) -> bool {
+    match datum {
+        Datum::List(_) | Datum::DottedPair(..) | Datum::Vector(_) => true,
+        Datum::Quote(datum)
+        | Datum::Backquote(datum)
+        | Datum::Unquote(datum)
+        | Datum::UnquoteSplice(datum) => ends_with_close(datum),
+        _ => false,
+    }
+}
+
+impl<I> Iterator for DatumIterator<I>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    type Item = Result<Datum, CompilerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_positions()
+            .map(|datum_res| datum_res.map(|(datum, _)| datum))
+    }
+}
+
+/// Iterator adapter yielding each `Datum` together with its `PositionTree`, created by
+/// `DatumIterator::with_positions`
+pub struct PositionedDatumIterator<I>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    data: DatumIterator<I>,
+}
+
+impl<I> Iterator for PositionedDatumIterator<I>
+where
+    I: Iterator<Item = Result<TokenWithPosition, CompilerError>>,
+{
+    type Item = Result<(Datum, PositionTree), CompilerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.data.next_with_positions()
     }
 }
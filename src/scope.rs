@@ -0,0 +1,631 @@
+//! Resolution of the identifiers of a syntax tree to the bindings they refer to
+//!
+//! Resolution follows the scoping rules of the special forms the interpreter knows about, without
+//! running or expanding anything: `lambda`, the `let` family, `do` and `guard` introduce local
+//! bindings, internal definitions are visible throughout the body they appear in, and
+//! definitions at the top level, or in the `begin` declarations of a library, are top-level
+//! bindings shared by every file. Quoted data are not resolved, except for what is unquoted in
+//! quasiquote templates, and neither are the transformers of syntax definitions.
+
+use crate::syntax_tree::{Node, NodeKind, SyntaxTree};
+use std::collections::{HashMap, HashSet};
+
+/// A local binding, introduced by a binding form or an internal definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    /// The name of the binding
+    pub name: String,
+    /// The index of the token of the identifier introducing the binding
+    pub token: usize,
+    /// What introduces the binding
+    pub kind: BindingKind,
+}
+
+/// The kinds of `Binding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    /// A variable bound by a definition or a binding form such as `let`
+    Variable,
+    /// A parameter of a procedure
+    Parameter,
+    /// A keyword bound by a syntax definition or binding form
+    Syntax,
+}
+
+/// What an identifier refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The local binding at this index in `Resolution::bindings`
+    Local(usize),
+    /// The top-level binding of this name, wherever it is defined, if it is
+    TopLevel(String),
+}
+
+/// An identifier used as a variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    /// The index of the token of the identifier
+    pub token: usize,
+    /// The binding the identifier refers to
+    pub target: Target,
+    /// Whether the identifier introduces the binding, rather than referring to it
+    pub definition: bool,
+}
+
+/// The extent of a local scope, and the bindings introduced in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    /// The index of the first token of the form the scope belongs to
+    pub first: usize,
+    /// The index of the closing parenthesis of the form, or `None` if the text ends first
+    pub last: Option<usize>,
+    /// The indices in `Resolution::bindings` of the bindings introduced in the scope
+    pub bindings: Vec<usize>,
+}
+
+/// The bindings of a syntax tree, and the identifiers referring to them
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// The local bindings, in the order they are introduced
+    pub bindings: Vec<Binding>,
+    /// The local scopes, in the order they are entered, so that enclosing scopes come first
+    pub scopes: Vec<Scope>,
+    /// The identifiers used as variables, sorted by token
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl Resolution {
+    /// Resolves the identifiers of a syntax tree
+    pub fn new(tree: &SyntaxTree) -> Self {
+        let mut resolver = Resolver {
+            tree,
+            scopes: Vec::new(),
+            extents: Vec::new(),
+            resolution: Resolution::default(),
+        };
+        resolver.top_level(tree.forms());
+        let mut resolution = resolver.resolution;
+        resolution
+            .occurrences
+            .sort_by_key(|occurrence| occurrence.token);
+        resolution
+    }
+
+    /// The occurrence of an identifier at the token at `index`, if it is used as a variable
+    pub fn occurrence(&self, index: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .binary_search_by_key(&index, |occurrence| occurrence.token)
+            .ok()
+            .map(|found| &self.occurrences[found])
+    }
+
+    /// The local bindings in scope right after the token at `index`, innermost first, leaving
+    /// out those shadowed by a binding of the same name
+    pub fn bindings_at(&self, index: usize) -> Vec<&Binding> {
+        let mut names = HashSet::new();
+        self.scopes
+            .iter()
+            .rev()
+            .filter(|scope| scope.first <= index && scope.last.is_none_or(|last| index < last))
+            .flat_map(|scope| {
+                scope
+                    .bindings
+                    .iter()
+                    .map(|&binding| &self.bindings[binding])
+            })
+            .filter(|binding| names.insert(binding.name.as_str()))
+            .collect()
+    }
+
+    /// The occurrences referring to `target`, definitions included
+    pub fn occurrences_of<'a>(
+        &'a self,
+        target: &'a Target,
+    ) -> impl Iterator<Item = &'a Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(move |occurrence| occurrence.target == *target)
+    }
+}
+
+/// Walks a syntax tree, keeping track of the local bindings in scope
+struct Resolver<'a> {
+    tree: &'a SyntaxTree,
+    /// The innermost scope is last; each maps names to indices in `resolution.bindings`
+    scopes: Vec<HashMap<String, usize>>,
+    /// The index in `resolution.scopes` of each scope of `scopes`
+    extents: Vec<usize>,
+    resolution: Resolution,
+}
+
+impl<'a> Resolver<'a> {
+    fn lookup(&self, name: &str) -> Target {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map_or_else(
+                || Target::TopLevel(name.to_string()),
+                |&index| Target::Local(index),
+            )
+    }
+
+    fn record(&mut self, node: &Node, target: Target, definition: bool) {
+        self.resolution.occurrences.push(Occurrence {
+            token: node.first,
+            target,
+            definition,
+        });
+    }
+
+    /// Introduces a binding in the innermost scope, or returns the one of the same name already
+    /// there
+    fn declare(&mut self, node: &Node, kind: BindingKind) -> Option<usize> {
+        let name = self.tree.identifier(node)?;
+        let scope = self.scopes.last_mut()?;
+        if let Some(&index) = scope.get(name) {
+            return Some(index);
+        }
+        let index = self.resolution.bindings.len();
+        self.resolution.bindings.push(Binding {
+            name: name.to_string(),
+            token: node.first,
+            kind,
+        });
+        scope.insert(name.to_string(), index);
+        let extent = *self.extents.last()?;
+        self.resolution.scopes[extent].bindings.push(index);
+        Some(index)
+    }
+
+    /// Introduces a binding in the innermost scope for an identifier
+    fn bind(&mut self, node: &Node, kind: BindingKind) {
+        if let Some(index) = self.declare(node, kind) {
+            self.record(node, Target::Local(index), true);
+        }
+    }
+
+    /// Binds the identifiers of a parameter list, which may be a single identifier or a dotted
+    /// list
+    fn bind_formals(&mut self, formals: &Node) {
+        match formals.kind {
+            NodeKind::Atom => self.bind(formals, BindingKind::Parameter),
+            NodeKind::List { .. } => {
+                for formal in &formals.children {
+                    self.bind(formal, BindingKind::Parameter);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Enters a scope extending over `form`
+    fn push_scope(&mut self, form: &Node) {
+        let closed = matches!(form.kind, NodeKind::List { closed: true });
+        self.extents.push(self.resolution.scopes.len());
+        self.resolution.scopes.push(Scope {
+            first: form.first,
+            last: if closed { Some(form.last) } else { None },
+            bindings: Vec::new(),
+        });
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.extents.pop();
+    }
+
+    /// The keyword of a special form, unless its name is shadowed by a local binding
+    fn keyword(&self, node: &Node) -> Option<&'a str> {
+        let name = self.tree.identifier(node.children.first()?)?;
+        match self.lookup(name) {
+            Target::TopLevel(_) => Some(name),
+            Target::Local(_) => None,
+        }
+    }
+
+    /// Resolves forms at the top level, where definitions are top-level bindings
+    fn top_level(&mut self, forms: &[Node]) {
+        for form in forms {
+            match self.keyword(form) {
+                Some("begin") => self.top_level(&form.children[1..]),
+                Some("define") | Some("define-syntax") => self.definition(form),
+                Some("define-library") => self.library(form),
+                _ => self.expression(form),
+            }
+        }
+    }
+
+    /// Resolves a body: its definitions are bound throughout it, in the innermost scope
+    fn body(&mut self, forms: &[Node]) {
+        self.declare_definitions(forms);
+        for form in forms {
+            self.expression(form);
+        }
+    }
+
+    fn declare_definitions(&mut self, forms: &[Node]) {
+        for form in forms {
+            match self.keyword(form) {
+                Some("begin") => self.declare_definitions(&form.children[1..]),
+                Some("define") | Some("define-syntax") => {
+                    if let Some(name) = definition_name(&form.children) {
+                        self.declare(name, self.definition_kind(form));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Resolves a `define` or `define-syntax` form, whose name is a top-level binding outside of
+    /// any scope
+    fn definition(&mut self, form: &Node) {
+        let elements = &form.children;
+        if let Some(name) = definition_name(elements) {
+            match self.tree.identifier(name) {
+                Some(identifier) if self.scopes.is_empty() => {
+                    self.record(name, Target::TopLevel(identifier.to_string()), true)
+                }
+                _ => self.bind(name, self.definition_kind(form)),
+            }
+        }
+        if self.definition_kind(form) == BindingKind::Syntax {
+            return;
+        }
+        match elements.get(1) {
+            // `(define (name . formals) body ...)`
+            Some(header) if matches!(header.kind, NodeKind::List { .. }) => {
+                self.push_scope(form);
+                for formal in header.children.iter().skip(1) {
+                    self.bind(formal, BindingKind::Parameter);
+                }
+                self.body(&elements[2..]);
+                self.pop_scope();
+            }
+            _ => self.expressions(elements.get(2..).unwrap_or_default()),
+        }
+    }
+
+    /// The kind of binding a `define` or `define-syntax` form introduces
+    fn definition_kind(&self, form: &Node) -> BindingKind {
+        match self.keyword(form) {
+            Some("define-syntax") => BindingKind::Syntax,
+            _ => BindingKind::Variable,
+        }
+    }
+
+    /// Resolves the exports and the `begin` declarations of a `define-library` form
+    fn library(&mut self, form: &Node) {
+        for declaration in form.children.iter().skip(2) {
+            let parts = &declaration.children;
+            match parts.first().and_then(|head| self.tree.identifier(head)) {
+                Some("begin") => self.top_level(&parts[1..]),
+                Some("export") => {
+                    for spec in &parts[1..] {
+                        match self.tree.identifier(spec) {
+                            Some(_) => self.expression(spec),
+                            // `(rename internal external)` refers to the internal name
+                            None => self.expressions(spec.children.get(1..2).unwrap_or_default()),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn expressions(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.expression(node);
+        }
+    }
+
+    fn expression(&mut self, node: &Node) {
+        match node.kind {
+            NodeKind::Atom => {
+                if let Some(name) = self.tree.identifier(node) {
+                    let target = self.lookup(name);
+                    self.record(node, target, false);
+                }
+            }
+            NodeKind::Abbreviation => match self.tree.prefix(node) {
+                Some("`") => self.quasiquote(&node.children, 1),
+                Some(",") | Some(",@") => self.expressions(&node.children),
+                _ => {}
+            },
+            NodeKind::List { .. } => self.list(node),
+            NodeKind::Vector { .. } | NodeKind::Dot | NodeKind::StrayClose => {}
+        }
+    }
+
+    fn list(&mut self, node: &Node) {
+        let elements = &node.children;
+        let rest = elements.get(1..).unwrap_or_default();
+        match self.keyword(node) {
+            Some("quote") | Some("syntax-rules") | Some("import") | Some("include") => {}
+            Some("quasiquote") => self.quasiquote(rest, 1),
+            Some("define") | Some("define-syntax") => self.definition(node),
+            Some("lambda") => {
+                if let Some(formals) = rest.first() {
+                    self.push_scope(node);
+                    self.bind_formals(formals);
+                    self.body(&rest[1..]);
+                    self.pop_scope();
+                }
+            }
+            Some("let") => self.let_form(node, rest),
+            Some("let*") => {
+                let scopes = self.scopes.len();
+                if let Some(bindings) = rest.first() {
+                    for binding in &bindings.children {
+                        self.expressions(binding.children.get(1..).unwrap_or_default());
+                        self.push_scope(node);
+                        self.bind_formals_of(binding, BindingKind::Variable);
+                    }
+                }
+                self.push_scope(node);
+                self.body(rest.get(1..).unwrap_or_default());
+                self.scopes.truncate(scopes);
+                self.extents.truncate(scopes);
+            }
+            Some("letrec") | Some("letrec*") | Some("let-syntax") | Some("letrec-syntax") => {
+                let syntax = self.keyword(node).is_some_and(|k| k.ends_with("-syntax"));
+                let kind = if syntax {
+                    BindingKind::Syntax
+                } else {
+                    BindingKind::Variable
+                };
+                self.push_scope(node);
+                if let Some(bindings) = rest.first() {
+                    for binding in &bindings.children {
+                        self.bind_formals_of(binding, kind);
+                    }
+                    if !syntax {
+                        for binding in &bindings.children {
+                            self.expressions(binding.children.get(1..).unwrap_or_default());
+                        }
+                    }
+                }
+                self.body(rest.get(1..).unwrap_or_default());
+                self.pop_scope();
+            }
+            Some("do") => {
+                let specs = rest.first().map_or(&[][..], |specs| &specs.children[..]);
+                for spec in specs {
+                    self.expressions(spec.children.get(1..2).unwrap_or_default());
+                }
+                self.push_scope(node);
+                for spec in specs {
+                    self.bind_formals_of(spec, BindingKind::Variable);
+                }
+                for spec in specs {
+                    self.expressions(spec.children.get(2..).unwrap_or_default());
+                }
+                self.expressions(rest.get(1..).unwrap_or_default());
+                self.pop_scope();
+            }
+            Some("guard") => {
+                self.push_scope(node);
+                self.body(rest.get(1..).unwrap_or_default());
+                self.pop_scope();
+                if let Some(spec) = rest.first() {
+                    self.push_scope(spec);
+                    if let Some(variable) = spec.children.first() {
+                        self.bind(variable, BindingKind::Variable);
+                    }
+                    self.expressions(spec.children.get(1..).unwrap_or_default());
+                    self.pop_scope();
+                }
+            }
+            Some("case") => {
+                self.expressions(rest.get(..1).unwrap_or_default());
+                // The data of each clause are quoted
+                for clause in rest.iter().skip(1) {
+                    self.expressions(clause.children.get(1..).unwrap_or_default());
+                }
+            }
+            _ => self.expressions(elements),
+        }
+    }
+
+    /// Binds the variable of a `(variable init ...)` binding
+    fn bind_formals_of(&mut self, binding: &Node, kind: BindingKind) {
+        if let Some(variable) = binding.children.first() {
+            self.bind(variable, kind);
+        }
+    }
+
+    /// Resolves a `let` or named `let` form, given the elements following the keyword
+    fn let_form(&mut self, form: &Node, rest: &[Node]) {
+        let name = rest
+            .first()
+            .filter(|first| self.tree.identifier(first).is_some());
+        let rest = if name.is_some() { &rest[1..] } else { rest };
+        let bindings = rest
+            .first()
+            .map_or(&[][..], |bindings| &bindings.children[..]);
+        for binding in bindings {
+            self.expressions(binding.children.get(1..).unwrap_or_default());
+        }
+        self.push_scope(form);
+        if let Some(name) = name {
+            self.bind(name, BindingKind::Variable);
+            self.push_scope(form);
+        }
+        for binding in bindings {
+            self.bind_formals_of(binding, BindingKind::Variable);
+        }
+        self.body(rest.get(1..).unwrap_or_default());
+        self.pop_scope();
+        if name.is_some() {
+            self.pop_scope();
+        }
+    }
+
+    /// Resolves what is unquoted in quasiquote templates nested `depth` levels deep
+    fn quasiquote(&mut self, nodes: &[Node], depth: usize) {
+        for node in nodes {
+            match (node.kind, self.tree.prefix(node)) {
+                (NodeKind::Abbreviation, Some(",")) | (NodeKind::Abbreviation, Some(",@")) => {
+                    if depth == 1 {
+                        self.expressions(&node.children);
+                    } else {
+                        self.quasiquote(&node.children, depth - 1);
+                    }
+                }
+                (NodeKind::Abbreviation, Some("`")) => self.quasiquote(&node.children, depth + 1),
+                (NodeKind::List { .. }, _) => {
+                    let head = node
+                        .children
+                        .first()
+                        .and_then(|head| self.tree.identifier(head));
+                    let rest = node.children.get(1..).unwrap_or_default();
+                    match head {
+                        Some("unquote") | Some("unquote-splicing") if depth == 1 => {
+                            self.expressions(rest)
+                        }
+                        Some("unquote") | Some("unquote-splicing") => {
+                            self.quasiquote(rest, depth - 1)
+                        }
+                        Some("quasiquote") => self.quasiquote(rest, depth + 1),
+                        _ => self.quasiquote(&node.children, depth),
+                    }
+                }
+                _ => self.quasiquote(&node.children, depth),
+            }
+        }
+    }
+}
+
+/// The identifier a `define` or `define-syntax` form binds, given the elements of the form
+pub(crate) fn definition_name(elements: &[Node]) -> Option<&Node> {
+    let target = elements.get(1)?;
+    match target.kind {
+        NodeKind::Atom => Some(target),
+        NodeKind::List { .. } => target
+            .children
+            .first()
+            .filter(|name| name.kind == NodeKind::Atom),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BindingKind, Resolution, Target};
+    use crate::lexer::Token;
+    use crate::syntax_tree::SyntaxTree;
+
+    /// The targets of the identifiers of `text`, as `(name, binding or top-level name)`
+    fn resolve(text: &str) -> Vec<(String, String, bool)> {
+        let tree = SyntaxTree::parse(text);
+        let resolution = Resolution::new(&tree);
+        resolution
+            .occurrences
+            .iter()
+            .map(|occurrence| {
+                let name = match &tree.token(occurrence.token).token {
+                    Token::Identifier(name) => name.clone(),
+                    _ => unreachable!(),
+                };
+                let target = match &occurrence.target {
+                    Target::Local(index) => {
+                        let binding = &resolution.bindings[*index];
+                        format!("{}@{}", binding.name, tree.token(binding.token).span.column)
+                    }
+                    Target::TopLevel(name) => format!("top:{}", name),
+                };
+                (name, target, occurrence.definition)
+            })
+            .collect()
+    }
+
+    fn entry(name: &str, target: &str, definition: bool) -> (String, String, bool) {
+        (name.to_string(), target.to_string(), definition)
+    }
+
+    #[test]
+    fn local_bindings_test() {
+        assert_eq!(
+            resolve("(define (f x) (let ((x (g x))) x))"),
+            vec![
+                entry("f", "top:f", true),
+                entry("x", "x@11", true),
+                entry("x", "x@21", true),
+                entry("g", "top:g", false),
+                entry("x", "x@11", false),
+                entry("x", "x@21", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting_and_shadowing_test() {
+        assert_eq!(
+            resolve("(lambda (quote) (quote x) '(y) `(z ,w))"),
+            vec![
+                entry("quote", "quote@9", true),
+                entry("quote", "quote@9", false),
+                entry("x", "top:x", false),
+                entry("w", "top:w", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn internal_definitions_test() {
+        assert_eq!(
+            resolve("(define (f) (define (g) h) (define h 1) (let loop ((i 0)) (loop h)))"),
+            vec![
+                entry("f", "top:f", true),
+                entry("g", "g@21", true),
+                entry("h", "h@35", false),
+                entry("h", "h@35", true),
+                entry("loop", "loop@45", true),
+                entry("i", "i@52", true),
+                entry("loop", "loop@45", false),
+                entry("h", "h@35", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn bindings_at_test() {
+        let text = "(define (f x) (let ((y 1)) (g y)) (let ((x 2)) x)) (h";
+        let tree = SyntaxTree::parse(text);
+        let resolution = Resolution::new(&tree);
+        let in_scope = |column: usize| {
+            let index = tree
+                .tokens()
+                .iter()
+                .rposition(|token| token.span.column < column)
+                .unwrap();
+            let bindings = resolution.bindings_at(index);
+            let names: Vec<_> = bindings
+                .iter()
+                .map(|binding| (binding.name.as_str(), tree.token(binding.token).span.column))
+                .collect();
+            names
+        };
+        // Inside `(g y)`
+        assert_eq!(in_scope(30), vec![("y", 21), ("x", 11)]);
+        // The inner `x` shadows the parameter
+        assert_eq!(in_scope(48), vec![("x", 41)]);
+        assert_eq!(in_scope(53), vec![]);
+        let kinds: Vec<_> = resolution
+            .bindings
+            .iter()
+            .map(|binding| binding.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                BindingKind::Parameter,
+                BindingKind::Variable,
+                BindingKind::Variable
+            ]
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Registry of the source code read by the lexer, parser and interpreter
+//!
+//! A `SourceMap` owns the text of every registered source: files read from disk, and snippets
+//! such as the expressions typed at a prompt. Each source is identified by a `FileId`, which the
+//! lexer attaches to the tokens it reads from it, and parts of a source are designated by
+//! `Span`s, which the lexer attaches to tokens, the parser and the lexer to their errors, and
+//! which the map converts back to names and text when reporting diagnostics.
+
+use crate::lexer::Token;
+use crate::parser::Position;
+use crate::reader::StringLexer;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Identifies a source registered in a `SourceMap`
+///
+/// Tokens, and the data parsed from them, carry the id of the source they were read from, so
+/// that positions in different files can be told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(pub usize);
+
+/// A range of text in a source: the line and byte column it starts at, and its length in bytes
+///
+/// The lexer attaches a span to every token, and errors and diagnostics point at spans. Tokens
+/// never extend over several lines, but spans covering several tokens, such as a whole list, may;
+/// their length then includes the line terminators in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The source the range is in, if it was read from a registered one
+    pub file: Option<FileId>,
+    /// The line the range starts on, starting from 1
+    pub line: usize,
+    /// The byte offset into the line the range starts at, starting from 0
+    pub column: usize,
+    /// The length of the range in bytes
+    pub len: usize,
+}
+
+impl Span {
+    /// The position the range starts at
+    pub fn start(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+            file: self.file,
+        }
+    }
+
+    /// Whether the range is empty, as it is for positions which do not cover any text
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.start().fmt(f)
+    }
+}
+
+/// A registered source
+struct SourceFile {
+    name: String,
+    path: Option<PathBuf>,
+    text: String,
+    /// Offset of the first byte of each line
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, path: Option<PathBuf>, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        SourceFile {
+            name,
+            path,
+            text,
+            line_starts,
+        }
+    }
+
+    /// The text of a line, starting from 1, without its line terminator
+    fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.text.len());
+        let text = &self.text[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+}
+
+/// The sources read so far, and the conversions between their spans and positions
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Creates an empty source map
+    pub fn new() -> Self {
+        SourceMap { files: Vec::new() }
+    }
+
+    /// Reads and registers a file, named after its path
+    ///
+    /// Registering the same path again reads the file again. If it changed, the new text gets
+    /// a new id, and the text registered before is kept for the spans which refer to it;
+    /// otherwise the id of the last registration is returned.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let last = self
+            .files
+            .iter()
+            .rposition(|f| f.path.as_deref() == Some(path));
+        match last {
+            Some(index) if self.files[index].text == text => Ok(FileId(index)),
+            _ => {
+                let name = path.display().to_string();
+                Ok(self.push(SourceFile::new(name, Some(path.to_path_buf()), text)))
+            }
+        }
+    }
+
+    /// Registers source code which was not read from a file, such as an expression typed at a
+    /// prompt
+    pub fn add_snippet<S: Into<String>, T: Into<String>>(&mut self, name: S, text: T) -> FileId {
+        self.push(SourceFile::new(name.into(), None, text.into()))
+    }
+
+    fn push(&mut self, file: SourceFile) -> FileId {
+        self.files.push(file);
+        FileId(self.files.len() - 1)
+    }
+
+    /// The name of a source: the path of a file, or the name given to a snippet
+    pub fn name(&self, file: FileId) -> Option<&str> {
+        self.files.get(file.0).map(|f| f.name.as_str())
+    }
+
+    /// The path of a source read from a file
+    pub fn path(&self, file: FileId) -> Option<&Path> {
+        self.files.get(file.0)?.path.as_deref()
+    }
+
+    /// The whole text of a source
+    ///
+    /// Registered files are lexed from this text rather than read again, so that the spans of
+    /// their tokens always match the text diagnostics quote.
+    pub fn text(&self, file: FileId) -> Option<&str> {
+        self.files.get(file.0).map(|f| f.text.as_str())
+    }
+
+    /// The text of a line of a source, starting from 1, without its line terminator
+    pub fn line(&self, file: FileId, line: usize) -> Option<&str> {
+        self.files.get(file.0)?.line(line)
+    }
+
+    /// The byte offset of a line and byte column of a source, if it is within the text
+    fn offset(&self, file: FileId, line: usize, column: usize) -> Option<usize> {
+        let source = self.files.get(file.0)?;
+        let offset = source.line_starts.get(line.checked_sub(1)?)? + column;
+        Some(offset).filter(|&offset| offset <= source.text.len())
+    }
+
+    /// The byte range of a span in the text of its source
+    fn range(&self, span: Span) -> Option<Range<usize>> {
+        let file = span.file?;
+        let start = self.offset(file, span.line, span.column)?;
+        let end = start + span.len;
+        Some(start..end).filter(|_| end <= self.files[file.0].text.len())
+    }
+
+    /// The span of `len` bytes starting at a line and byte column of a source
+    pub fn span(&self, file: FileId, line: usize, column: usize, len: usize) -> Option<Span> {
+        let span = Span {
+            file: Some(file),
+            line,
+            column,
+            len,
+        };
+        self.range(span).map(|_| span)
+    }
+
+    /// The empty span at a position in a registered source
+    pub fn position_span(&self, position: Position) -> Option<Span> {
+        self.span(position.file?, position.line, position.column, 0)
+    }
+
+    /// The span of the datum starting at a position in a registered source, such as the whole
+    /// form a runtime error is about, which is only known by where it starts
+    pub fn datum_span(&self, position: Position) -> Option<Span> {
+        let file = position.file?;
+        let start = self.offset(file, position.line, position.column)?;
+        let rest = &self.files[file.0].text[start..];
+        let mut depth = 0usize;
+        let mut last = None;
+        for token in StringLexer::new(rest) {
+            let token = token.ok()?;
+            match &token.token {
+                Token::Whitespace | Token::Comment => continue,
+                Token::Punctuator(p) if p.ends_with('(') => depth += 1,
+                Token::Punctuator(p) if p == ")" => depth = depth.checked_sub(1)?,
+                // Abbreviations such as `'` are part of the datum they precede
+                Token::Punctuator(p) if p != "." => continue,
+                _ => {}
+            }
+            if depth == 0 {
+                last = Some(token.span);
+                break;
+            }
+        }
+        // The lexer counts lines and columns from the start of the datum
+        let last = last?;
+        let (line, column) = match last.line {
+            1 => (position.line, position.column + last.column),
+            line => (position.line + line - 1, last.column),
+        };
+        let end = self.offset(file, line, column)? + last.len;
+        self.span(file, position.line, position.column, end - start)
+    }
+
+    /// The span from the start of `first` to the end of `last`, both in the same source
+    pub fn join(&self, first: Span, last: Span) -> Option<Span> {
+        let start = self.range(first)?.start;
+        let end = self.range(last)?.end;
+        match first.file == last.file && start <= end {
+            true => Some(Span {
+                len: end - start,
+                ..first
+            }),
+            false => None,
+        }
+    }
+
+    /// The position right after the end of a span
+    pub fn end(&self, span: Span) -> Option<Position> {
+        let file = span.file?;
+        let source = &self.files[file.0];
+        let end = self.range(span)?.end;
+        let line = source
+            .line_starts
+            .partition_point(|&line_start| line_start <= end);
+        Some(Position {
+            line,
+            column: end - source.line_starts[line - 1],
+            file: Some(file),
+        })
+    }
+
+    /// The text covered by a span
+    pub fn source_text(&self, span: Span) -> Option<&str> {
+        let range = self.range(span)?;
+        self.files[span.file?.0].text.get(range)
+    }
+
+    /// Formats a message about a span, followed by the line it starts on with the span
+    /// underlined
+    ///
+    /// ```text
+    /// main.scm, line 2, column 14: car: expected pair, got 2
+    ///     2 | (define (f x) (car x))
+    ///       |               ^^^^^^^
+    /// ```
+    pub fn format_diagnostic(&self, span: Span, message: &str) -> String {
+        let (file, name) = match span.file.and_then(|file| Some((file, self.name(file)?))) {
+            Some((file, name)) if self.range(span).is_some() => (file, name),
+            _ => return message.to_string(),
+        };
+        let mut diagnostic = format!("{}, {}: {}", name, span, message);
+        if let Some(text) = self.line(file, span.line) {
+            let width = span.len.min(text.len().saturating_sub(span.column)).max(1);
+            let indent: String = text[..span.column]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            diagnostic.push_str(&format!(
+                "\n{:>5} | {}\n      | {}{}",
+                span.line,
+                text,
+                indent,
+                "^".repeat(width)
+            ));
+        }
+        diagnostic
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileId, SourceMap, Span};
+    use crate::parser::Position;
+    use crate::reader::StringLexer;
+    use std::fs;
+
+    const SOURCE: &str = "(define (f x)\r\n  (car x))\n\n(f 2)";
+
+    #[test]
+    fn span_test() {
+        let mut map = SourceMap::new();
+        let file = map.add_snippet("<input>", SOURCE);
+        assert_eq!(file, FileId(0));
+        assert_eq!(map.name(file), Some("<input>"));
+        assert_eq!(map.path(file), None);
+        assert_eq!(map.line(file, 1), Some("(define (f x)"));
+        assert_eq!(map.line(file, 3), Some(""));
+        assert_eq!(map.line(file, 4), Some("(f 2)"));
+        assert_eq!(map.line(file, 5), None);
+
+        let span = map.span(file, 2, 2, 7).unwrap();
+        assert_eq!(span.len, 7);
+        assert_eq!(map.source_text(span), Some("(car x)"));
+        let end = map.end(span).unwrap();
+        assert_eq!((end.line, end.column), (2, 9));
+        assert_eq!(map.span(file, 4, 3, 10), None);
+
+        let define = map.span(file, 1, 0, 1).unwrap();
+        let close = map.span(file, 2, 9, 1).unwrap();
+        let form = map.join(define, close).unwrap();
+        assert_eq!(form.start(), define.start());
+        assert_eq!(map.source_text(form), Some("(define (f x)\r\n  (car x))"));
+        assert_eq!(map.end(form), map.end(close));
+        assert_eq!(map.join(close, define), None);
+
+        let position = Position {
+            line: 4,
+            column: 3,
+            file: Some(file),
+        };
+        let datum = map
+            .datum_span(Position {
+                column: 0,
+                ..position
+            })
+            .unwrap();
+        assert_eq!(map.source_text(datum), Some("(f 2)"));
+        let datum = map
+            .datum_span(Position {
+                line: 1,
+                column: 8,
+                ..position
+            })
+            .unwrap();
+        assert_eq!(map.source_text(datum), Some("(f x)"));
+        let datum = map
+            .datum_span(Position {
+                line: 1,
+                column: 0,
+                ..position
+            })
+            .unwrap();
+        assert_eq!(map.source_text(datum), Some("(define (f x)\r\n  (car x))"));
+
+        let span = map.position_span(position).unwrap();
+        assert!(span.is_empty());
+        assert_eq!(span.start(), position);
+        assert_eq!(span.to_string(), "line 4, column 3");
+    }
+
+    #[test]
+    fn file_registration_test() {
+        let dir = std::env::temp_dir().join(format!("scheme-source-map-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.scm");
+        let mut map = SourceMap::new();
+        fs::write(&path, "(old)\n").unwrap();
+        let old = map.add_file(&path).unwrap();
+        assert_eq!(map.add_file(&path).unwrap(), old);
+        fs::write(&path, "(new)\n").unwrap();
+        let new = map.add_file(&path).unwrap();
+        assert_ne!(new, old);
+        assert_eq!(map.text(old), Some("(old)\n"));
+        assert_eq!(map.text(new), Some("(new)\n"));
+        assert_eq!(map.path(old), map.path(new));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn token_span_test() {
+        let mut map = SourceMap::new();
+        let file = map.add_snippet("<input>", SOURCE);
+        let texts: Vec<_> = StringLexer::new(SOURCE)
+            .into_iter()
+            .with_file(file)
+            .map(|token| map.source_text(token.unwrap().span))
+            .collect::<Option<_>>()
+            .unwrap();
+        assert_eq!(
+            texts,
+            vec![
+                "(", "define", " ", "(", "f", " ", "x", ")", "  ", "(", "car", " ", "x", ")", ")",
+                "(", "f", " ", "2", ")"
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostic_test() {
+        let mut map = SourceMap::new();
+        let file = map.add_snippet("main.scm", SOURCE);
+        let span = Span {
+            file: Some(file),
+            line: 2,
+            column: 2,
+            len: 7,
+        };
+        assert_eq!(
+            map.format_diagnostic(span, "car: expected pair, got 2"),
+            "main.scm, line 2, column 2: car: expected pair, got 2\n    2 |   (car x))\n      |   ^^^^^^^"
+        );
+        let position = map.position_span(Position {
+            line: 4,
+            column: 0,
+            file: Some(file),
+        });
+        assert_eq!(
+            map.format_diagnostic(position.unwrap(), "oops"),
+            "main.scm, line 4, column 0: oops\n    4 | (f 2)\n      | ^"
+        );
+        let unregistered = Span { file: None, ..span };
+        assert_eq!(map.format_diagnostic(unregistered, "oops"), "oops");
+    }
+}
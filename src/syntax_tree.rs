@@ -0,0 +1,793 @@
+//! Concrete syntax trees, which keep every token of the source text
+//!
+//! Unlike `Datum`s, syntax trees can be built from text that cannot be read: a list that is never
+//! closed ends with the last token, and a stray closing parenthesis becomes a node of its own.
+//! Nodes refer to the tokens they span by index, so that tools such as the language server can
+//! map them back to the text, comments and whitespace included.
+//!
+//! A tree can also be updated after an edit to its text. Tokens never span lines, so only the
+//! edited lines are lexed again, and only the top-level forms from the first one the edit may
+//! affect up to the first one that starts after the edit at the same token are parsed again: the
+//! parse of the rest is the same as before, but for the shifted token indices.
+
+use crate::lexer::{Token, TokenWithPosition};
+use crate::reader::StringLexer;
+use crate::CompilerError;
+use std::ops::Range;
+
+/// The tokens of a text, and the data they make up
+#[derive(Debug)]
+pub struct SyntaxTree {
+    tokens: Vec<TokenWithPosition>,
+    forms: Vec<Node>,
+    lex_error: Option<CompilerError>,
+}
+
+/// A datum in a `SyntaxTree`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// What kind of datum the node is
+    pub kind: NodeKind,
+    /// The index of the first token of the node
+    pub first: usize,
+    /// The index of the last token of the node, which is the closing parenthesis of a closed list
+    pub last: usize,
+    /// The elements of a list or vector, including the dot of a dotted list, or the datum an
+    /// abbreviation applies to
+    pub children: Vec<Node>,
+}
+
+/// A replacement of part of a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The 1-based line and byte column at which the replaced part starts
+    pub start: (usize, usize),
+    /// The 1-based line and byte column at which the replaced part ends
+    pub end: (usize, usize),
+    /// The text replacing it
+    pub text: String,
+}
+
+impl TextEdit {
+    /// Applies the edit to a text, returning the range of lines replaced, and the range of lines
+    /// replacing them; positions past the end of a line or of the text stand for the end
+    pub fn apply(&self, text: &mut String) -> (Range<usize>, Range<usize>) {
+        let start = byte_offset(text, self.start);
+        let end = byte_offset(text, self.end).max(start);
+        let first_line = 1 + text[..start].matches('\n').count();
+        let last_line = first_line + text[start..end].matches('\n').count();
+        text.replace_range(start..end, &self.text);
+        let new_last_line = first_line + self.text.matches('\n').count();
+        (first_line..last_line + 1, first_line..new_last_line + 1)
+    }
+}
+
+/// The byte offset of a 1-based line and byte column
+fn byte_offset(text: &str, (line, column): (usize, usize)) -> usize {
+    let start = match line {
+        0 | 1 => 0,
+        _ => match text.match_indices('\n').nth(line - 2) {
+            Some((index, _)) => index + 1,
+            None => return text.len(),
+        },
+    };
+    let end = text[start..]
+        .find('\n')
+        .map_or(text.len(), |end| start + end);
+    let mut offset = (start + column).min(end);
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The kinds of `Node`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A datum made of a single token, such as an identifier or a string
+    Atom,
+    /// A list, which is not closed if the text ends first
+    List {
+        /// Whether the list ends with a closing parenthesis
+        closed: bool,
+    },
+    /// A vector, which is not closed if the text ends first
+    Vector {
+        /// Whether the vector ends with a closing parenthesis
+        closed: bool,
+    },
+    /// A quote, quasiquote, unquote or unquote-splicing prefix, and the datum following it
+    Abbreviation,
+    /// The dot of a dotted list
+    Dot,
+    /// A closing parenthesis without a matching opening one
+    StrayClose,
+}
+
+impl SyntaxTree {
+    /// Lexes and parses a text; if lexing fails, the tree is built from the tokens before the
+    /// error
+    pub fn parse(text: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut lex_error = None;
+        for token in StringLexer::new(text) {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(error) => {
+                    lex_error = Some(error);
+                    break;
+                }
+            }
+        }
+        let mut tree = SyntaxTree::from_tokens(tokens);
+        tree.lex_error = lex_error;
+        tree
+    }
+
+    /// Builds the tree of a sequence of tokens
+    pub fn from_tokens(tokens: Vec<TokenWithPosition>) -> Self {
+        let mut forms = Vec::new();
+        let mut builder = Builder {
+            tokens: &tokens,
+            next: 0,
+        };
+        while let Some(node) = builder.node() {
+            forms.push(node);
+        }
+        SyntaxTree {
+            tokens,
+            forms,
+            lex_error: None,
+        }
+    }
+
+    /// Applies `edit` to `text`, the text of the tree, and updates the tree to match
+    ///
+    /// Returns the range of the top-level forms of the updated tree that were parsed again. The
+    /// tree is built again from scratch if the text of the tree could not be lexed, or if the
+    /// edited lines cannot be.
+    pub fn edit(&mut self, text: &mut String, edit: &TextEdit) -> Range<usize> {
+        let (old_lines, new_lines) = edit.apply(text);
+        if self.lex_error.is_some() {
+            *self = SyntaxTree::parse(text);
+            return 0..self.forms.len();
+        }
+        let (first_line, last_line) = (old_lines.start, old_lines.end - 1);
+        let new_last_line = new_lines.end - 1;
+        let lines: Vec<&str> = text
+            .lines()
+            .skip(first_line - 1)
+            .take(new_lines.len())
+            .collect();
+        let mut relexed = Vec::new();
+        for token in StringLexer::new(lines.join("\n")) {
+            match token {
+                Ok(mut token) => {
+                    token.span.line += first_line - 1;
+                    relexed.push(token);
+                }
+                Err(_) => {
+                    *self = SyntaxTree::parse(text);
+                    return 0..self.forms.len();
+                }
+            }
+        }
+
+        // The tokens of the edited lines are replaced
+        let removed = self
+            .tokens
+            .partition_point(|token| token.span.line < first_line)
+            ..self
+                .tokens
+                .partition_point(|token| token.span.line <= last_line);
+        let index_shift = relexed.len() as isize - removed.len() as isize;
+        let line_shift = new_last_line as isize - last_line as isize;
+        let tail = self.tokens.split_off(removed.end);
+        self.tokens.truncate(removed.start);
+        self.tokens.extend(relexed);
+        self.tokens.extend(tail.into_iter().map(|mut token| {
+            token.span.line = shift(token.span.line, line_shift);
+            token
+        }));
+
+        // Forms ending before the edited lines are kept, unless tokens after them could still
+        // become part of them
+        let mut forms = std::mem::take(&mut self.forms);
+        let kept = forms
+            .iter()
+            .take_while(|form| form.last < removed.start && form.is_complete())
+            .count();
+        let mut after: Vec<Node> = forms.split_off(kept);
+        after.retain(|form| form.first >= removed.end);
+        let mut after = after.into_iter().peekable();
+        let mut builder = Builder {
+            tokens: &self.tokens,
+            next: forms.last().map_or(0, |form| form.last + 1),
+        };
+        let resynchronized = loop {
+            let next = match builder.skip_atmosphere() {
+                Some(next) => next,
+                None => break false,
+            };
+            while after
+                .peek()
+                .is_some_and(|form| shift(form.first, index_shift) < next)
+            {
+                after.next();
+            }
+            if after
+                .peek()
+                .is_some_and(|form| shift(form.first, index_shift) == next)
+            {
+                break true;
+            }
+            forms.extend(builder.node());
+        };
+        let reparsed = kept..forms.len();
+        if resynchronized {
+            forms.extend(after.map(|mut form| {
+                form.shift(index_shift);
+                form
+            }));
+        }
+        self.forms = forms;
+        reparsed
+    }
+
+    /// All the tokens of the text, in order
+    pub fn tokens(&self) -> &[TokenWithPosition] {
+        &self.tokens
+    }
+
+    /// The token at `index`
+    pub fn token(&self, index: usize) -> &TokenWithPosition {
+        &self.tokens[index]
+    }
+
+    /// The data at the top level of the text
+    pub fn forms(&self) -> &[Node] {
+        &self.forms
+    }
+
+    /// The error that stopped lexing before the end of the text, if any
+    pub fn lex_error(&self) -> Option<&CompilerError> {
+        self.lex_error.as_ref()
+    }
+
+    /// The name of an identifier node
+    pub fn identifier(&self, node: &Node) -> Option<&str> {
+        match (node.kind, &self.tokens[node.first].token) {
+            (NodeKind::Atom, Token::Identifier(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The punctuator of an abbreviation node: `'`, `` ` ``, `,` or `,@`
+    pub fn prefix(&self, node: &Node) -> Option<&str> {
+        match (node.kind, &self.tokens[node.first].token) {
+            (NodeKind::Abbreviation, Token::Punctuator(prefix)) => Some(prefix),
+            _ => None,
+        }
+    }
+
+    /// The edits matching every parenthesis of the text: removing each closing parenthesis
+    /// without an opening one, and closing the lists and vectors left open
+    ///
+    /// Where the indentation suggests a parenthesis is superfluous or missing, as found by
+    /// `superfluous_close` and `missing_close`, it is removed or added there, otherwise the
+    /// unmatched closing parentheses are removed and the lists closed after their last token.
+    /// If the text cannot be lexed, the lists cut short by the error are left open.
+    pub fn balancing_edits(&self) -> Vec<TextEdit> {
+        let mut edits: Vec<TextEdit> = self
+            .forms
+            .iter()
+            .filter(|form| form.kind == NodeKind::StrayClose)
+            .map(|form| {
+                let close = self
+                    .superfluous_close(form.first)
+                    .map_or(form.first, |superfluous| superfluous.close);
+                let token = self.token(close);
+                TextEdit {
+                    start: (token.span.line, token.span.column),
+                    end: (token.span.line, token.span.column + token.span.len),
+                    text: String::new(),
+                }
+            })
+            .collect();
+        let (last, mut unclosed) = match self.forms.last() {
+            Some(form) if self.lex_error.is_none() => (form.last, form.unclosed()),
+            _ => return edits,
+        };
+        if let Some(missing) = self.missing_close() {
+            edits.push(self.insertion_after(missing.after, missing.count));
+            unclosed -= missing.count;
+        }
+        if unclosed > 0 {
+            edits.push(self.insertion_after(last, unclosed));
+        }
+        edits
+    }
+
+    /// Where the indentation suggests closing parentheses are missing: before the first line
+    /// inside a list left open that is indented no further than the line the list starts on
+    ///
+    /// ```text
+    /// (define (f x)
+    ///   (display x)
+    /// (f 1)
+    /// ```
+    pub fn missing_close(&self) -> Option<MissingClose> {
+        if self.lex_error.is_some() {
+            return None;
+        }
+        let mut open: Vec<&Node> = Vec::new();
+        let mut node = self.forms.last()?;
+        loop {
+            match node.kind {
+                NodeKind::List { closed: false } | NodeKind::Vector { closed: false } => {
+                    open.push(node);
+                    let indentation = self.indentation(node.first);
+                    let dedented = node.children.iter().find(|child| {
+                        self.starts_line(child.first)
+                            && self.token(child.first).span.column <= indentation
+                    });
+                    if let Some(dedented) = dedented {
+                        let column = self.token(dedented.first).span.column;
+                        let closed: Vec<&Node> = open
+                            .iter()
+                            .rev()
+                            .take_while(|list| self.indentation(list.first) >= column)
+                            .copied()
+                            .collect();
+                        return Some(MissingClose {
+                            after: self.previous_significant(dedented.first)?,
+                            count: closed.len(),
+                            open: node.first,
+                            dedented: dedented.first,
+                        });
+                    }
+                }
+                NodeKind::Abbreviation => {}
+                _ => return None,
+            }
+            node = node.children.last()?;
+        }
+    }
+
+    /// Where the indentation suggests the closing parenthesis left unmatched at the token
+    /// `stray` is superfluous: at the end of a top-level form followed by a line indented
+    /// further than its first one, as if the line were still inside it
+    ///
+    /// ```text
+    /// (define (f x)
+    ///   (display x))
+    ///   (newline))
+    /// ```
+    pub fn superfluous_close(&self, stray: usize) -> Option<SuperfluousClose> {
+        let is_stray = |form: &Node| form.kind == NodeKind::StrayClose;
+        let position = self
+            .forms
+            .iter()
+            .position(|form| is_stray(form) && form.first == stray)?;
+        let start = self.forms[..position]
+            .iter()
+            .rposition(is_stray)
+            .map_or(0, |index| index + 1);
+        self.forms[start..position]
+            .windows(2)
+            .find(|pair| {
+                matches!(&self.token(pair[0].last).token, Token::Punctuator(p) if p == ")")
+                    && self.starts_line(pair[1].first)
+                    && self.token(pair[1].first).span.column > self.indentation(pair[0].first)
+            })
+            .map(|pair| SuperfluousClose {
+                close: pair[0].last,
+                indented: pair[1].first,
+            })
+    }
+
+    /// An insertion of `count` closing parentheses right after the token `index`
+    fn insertion_after(&self, index: usize, count: usize) -> TextEdit {
+        let token = self.token(index);
+        let end = (token.span.line, token.span.column + token.span.len);
+        TextEdit {
+            start: end,
+            end,
+            text: ")".repeat(count),
+        }
+    }
+
+    /// The index of the last significant token before the token `index`
+    fn previous_significant(&self, index: usize) -> Option<usize> {
+        (0..index).rev().find(|&index| self.is_significant(index))
+    }
+
+    /// Whether the token `index` is the first significant one on its line
+    fn starts_line(&self, index: usize) -> bool {
+        self.previous_significant(index)
+            .is_none_or(|previous| self.token(previous).span.line < self.token(index).span.line)
+    }
+
+    /// The column of the first significant token on the line of the token `index`
+    fn indentation(&self, index: usize) -> usize {
+        let line = self.token(index).span.line;
+        let mut first = index;
+        while let Some(previous) = self.previous_significant(first) {
+            if self.token(previous).span.line < line {
+                break;
+            }
+            first = previous;
+        }
+        self.token(first).span.column
+    }
+
+    fn is_significant(&self, index: usize) -> bool {
+        !matches!(self.tokens[index].token, Token::Whitespace | Token::Comment)
+    }
+}
+
+/// Closing parentheses the indentation of a text suggests are missing, as found by
+/// `SyntaxTree::missing_close`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingClose {
+    /// The index of the token the parentheses are missing after
+    pub after: usize,
+    /// How many parentheses are missing
+    pub count: usize,
+    /// The index of the opening parenthesis of the innermost list they close
+    pub open: usize,
+    /// The index of the first token of the line that is not indented inside that list
+    pub dedented: usize,
+}
+
+/// A closing parenthesis the indentation of a text suggests is superfluous, as found by
+/// `SyntaxTree::superfluous_close`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperfluousClose {
+    /// The index of the superfluous parenthesis
+    pub close: usize,
+    /// The index of the first token of the line indented as if it were inside the form the
+    /// parenthesis closes
+    pub indented: usize,
+}
+
+impl Node {
+    /// Whether the tokens following the node cannot become part of it
+    fn is_complete(&self) -> bool {
+        match self.kind {
+            NodeKind::List { closed } | NodeKind::Vector { closed } => closed,
+            NodeKind::Abbreviation => self.children.first().is_some_and(Node::is_complete),
+            NodeKind::Atom | NodeKind::Dot | NodeKind::StrayClose => true,
+        }
+    }
+
+    /// The number of lists and vectors the text ends inside of, the node being the last one
+    fn unclosed(&self) -> usize {
+        let own = match self.kind {
+            NodeKind::List { closed: false } | NodeKind::Vector { closed: false } => 1,
+            NodeKind::Abbreviation => 0,
+            _ => return 0,
+        };
+        own + self.children.last().map_or(0, Node::unclosed)
+    }
+
+    /// Moves the token indices of the node and its children by `by`
+    fn shift(&mut self, by: isize) {
+        self.first = shift(self.first, by);
+        self.last = shift(self.last, by);
+        for child in &mut self.children {
+            child.shift(by);
+        }
+    }
+}
+
+fn shift(value: usize, by: isize) -> usize {
+    (value as isize + by) as usize
+}
+
+/// Builds nodes from the tokens following `next`
+struct Builder<'a> {
+    tokens: &'a [TokenWithPosition],
+    next: usize,
+}
+
+impl Builder<'_> {
+    /// Skips whitespace and comments, returning the index of the next significant token
+    fn skip_atmosphere(&mut self) -> Option<usize> {
+        while let Some(token) = self.tokens.get(self.next) {
+            if !matches!(token.token, Token::Whitespace | Token::Comment) {
+                return Some(self.next);
+            }
+            self.next += 1;
+        }
+        None
+    }
+
+    /// Builds the next node, if any token is left
+    fn node(&mut self) -> Option<Node> {
+        let first = self.skip_atmosphere()?;
+        self.next += 1;
+        let leaf = |kind| Node {
+            kind,
+            first,
+            last: first,
+            children: Vec::new(),
+        };
+        let punctuator = match &self.tokens[first].token {
+            Token::Punctuator(p) => p.as_str(),
+            _ => return Some(leaf(NodeKind::Atom)),
+        };
+        Some(match punctuator {
+            "(" | "#(" => {
+                let mut children = Vec::new();
+                let closed = loop {
+                    match self.skip_atmosphere() {
+                        Some(index) if is_close(&self.tokens[index]) => {
+                            self.next += 1;
+                            break true;
+                        }
+                        Some(_) => children.extend(self.node()),
+                        None => break false,
+                    }
+                };
+                let kind = if punctuator == "(" {
+                    NodeKind::List { closed }
+                } else {
+                    NodeKind::Vector { closed }
+                };
+                Node {
+                    kind,
+                    first,
+                    last: self.last_significant(),
+                    children,
+                }
+            }
+            ")" => leaf(NodeKind::StrayClose),
+            "." => leaf(NodeKind::Dot),
+            _ => {
+                // A quote or similar prefix applies to the next datum, unless a list ends first
+                let children: Vec<Node> = match self.skip_atmosphere() {
+                    Some(index) if !is_close(&self.tokens[index]) => {
+                        self.node().into_iter().collect()
+                    }
+                    _ => Vec::new(),
+                };
+                Node {
+                    kind: NodeKind::Abbreviation,
+                    first,
+                    last: children.last().map_or(first, |child| child.last),
+                    children,
+                }
+            }
+        })
+    }
+
+    /// The index of the last significant token consumed
+    fn last_significant(&self) -> usize {
+        (0..self.next)
+            .rev()
+            .find(|&index| !matches!(self.tokens[index].token, Token::Whitespace | Token::Comment))
+            .unwrap_or(0)
+    }
+}
+
+fn is_close(token: &TokenWithPosition) -> bool {
+    matches!(&token.token, Token::Punctuator(p) if p == ")")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NodeKind, SyntaxTree, TextEdit};
+
+    #[test]
+    fn tree_test() {
+        let tree = SyntaxTree::parse("(define x ; the x\n  '(1 . 2)) #(a)");
+        assert_eq!(tree.forms().len(), 2);
+        let define = &tree.forms()[0];
+        assert_eq!(define.kind, NodeKind::List { closed: true });
+        assert_eq!(define.children.len(), 3);
+        assert_eq!(tree.identifier(&define.children[1]), Some("x"));
+        let quoted = &define.children[2];
+        assert_eq!(tree.prefix(quoted), Some("'"));
+        let kinds: Vec<_> = quoted.children[0]
+            .children
+            .iter()
+            .map(|child| child.kind)
+            .collect();
+        assert_eq!(kinds, vec![NodeKind::Atom, NodeKind::Dot, NodeKind::Atom]);
+        assert_eq!(tree.token(define.last).span.line, 2);
+        assert_eq!(tree.forms()[1].kind, NodeKind::Vector { closed: true });
+    }
+
+    #[test]
+    fn recovery_test() {
+        let tree = SyntaxTree::parse("(a)) (b '\n");
+        let kinds: Vec<_> = tree.forms().iter().map(|form| form.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NodeKind::List { closed: true },
+                NodeKind::StrayClose,
+                NodeKind::List { closed: false },
+            ]
+        );
+        let unclosed = &tree.forms()[2];
+        assert_eq!(unclosed.children[1].kind, NodeKind::Abbreviation);
+        assert!(unclosed.children[1].children.is_empty());
+        assert_eq!(tree.token(unclosed.last).span.column, 8);
+        let tree = SyntaxTree::parse("(a #\\nope)");
+        assert!(tree.lex_error().is_some());
+        assert_eq!(tree.forms()[0].kind, NodeKind::List { closed: false });
+    }
+
+    #[test]
+    fn balancing_edits_test() {
+        let mut text = String::from("(a)) )\n(b '(c #(d ; e\n");
+        let edits = SyntaxTree::parse(&text).balancing_edits();
+        assert_eq!(edits.len(), 3);
+        crate::paredit::apply_edits(&mut text, &edits);
+        assert_eq!(text, "(a) \n(b '(c #(d))) ; e\n");
+        assert!(SyntaxTree::parse(&text).balancing_edits().is_empty());
+        assert!(SyntaxTree::parse("(a \"b").balancing_edits().is_empty());
+    }
+
+    #[test]
+    fn indentation_test() {
+        let fixed = |text: &str| {
+            let edits = SyntaxTree::parse(text).balancing_edits();
+            let mut text = text.to_string();
+            crate::paredit::apply_edits(&mut text, &edits);
+            text
+        };
+        let text = "(define (f x)\n  (let ((y 1))\n    (display y)\n(define (g) 1)\n";
+        let tree = SyntaxTree::parse(text);
+        let missing = tree.missing_close().unwrap();
+        assert_eq!(missing.count, 2);
+        assert_eq!(tree.token(missing.after).span.line, 3);
+        assert_eq!(tree.token(missing.open).span.line, 2);
+        assert_eq!(tree.token(missing.dedented).span.line, 4);
+        assert_eq!(
+            fixed(text),
+            "(define (f x)\n  (let ((y 1))\n    (display y)))\n(define (g) 1)\n"
+        );
+        // Lists opened after the first token of their line end where their line's do
+        let text = "(define (f x) (let ((y 1))\n  (display y)\n(f 1) (g 2";
+        assert_eq!(
+            fixed(text),
+            "(define (f x) (let ((y 1))\n  (display y)))\n(f 1) (g 2)"
+        );
+        let text = "(begin\n  (display 1)\n  (newline)";
+        assert_eq!(SyntaxTree::parse(text).missing_close(), None);
+
+        let text = "(define (f x)\n  (display x))\n  (newline))\n(f 1)\n";
+        let tree = SyntaxTree::parse(text);
+        let superfluous = tree.superfluous_close(tree.forms()[2].first).unwrap();
+        assert_eq!(tree.token(superfluous.close).span.line, 2);
+        assert_eq!(tree.token(superfluous.indented).span.line, 3);
+        assert_eq!(
+            fixed(text),
+            "(define (f x)\n  (display x)\n  (newline))\n(f 1)\n"
+        );
+        let text = "(begin\n  (display 1)\n  (newline)))\n";
+        let tree = SyntaxTree::parse(text);
+        assert_eq!(tree.superfluous_close(tree.forms()[1].first), None);
+        assert_eq!(fixed(text), "(begin\n  (display 1)\n  (newline))\n");
+    }
+
+    /// Applies `edit` to `text` both incrementally and from scratch, and checks that the trees
+    /// are the same
+    fn check_edit(text: &str, edit: &TextEdit) -> std::ops::Range<usize> {
+        let mut tree = SyntaxTree::parse(text);
+        let mut edited = String::from(text);
+        let reparsed = tree.edit(&mut edited, edit);
+        let expected = SyntaxTree::parse(&edited);
+        let context = format!("{:?} after {:?}", edited, edit);
+        assert_eq!(tree.tokens(), expected.tokens(), "{}", context);
+        assert_eq!(tree.forms(), expected.forms(), "{}", context);
+        assert_eq!(
+            tree.lex_error().map(ToString::to_string),
+            expected.lex_error().map(ToString::to_string),
+            "{}",
+            context
+        );
+        reparsed
+    }
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: String::from(text),
+        }
+    }
+
+    #[test]
+    fn apply_edit_test() {
+        let apply = |edit: TextEdit| {
+            let mut text = String::from("(a b)\n(c λ d)\n");
+            let lines = edit.apply(&mut text);
+            (text, lines)
+        };
+        assert_eq!(
+            apply(edit((2, 1), (2, 5), "x")),
+            (String::from("(a b)\n(x d)\n"), (2..3, 2..3))
+        );
+        assert_eq!(
+            apply(edit((1, 5), (2, 0), ")\n\n")),
+            (String::from("(a b))\n\n(c λ d)\n"), (1..3, 1..4))
+        );
+        // Columns past the end of a line stand for its end
+        assert_eq!(
+            apply(edit((1, 9), (1, 9), " e")),
+            (String::from("(a b) e\n(c λ d)\n"), (1..2, 1..2))
+        );
+        assert_eq!(
+            apply(edit((9, 0), (9, 0), "f")),
+            (String::from("(a b)\n(c λ d)\nf"), (3..4, 3..4))
+        );
+    }
+
+    #[test]
+    fn reparsed_forms_test() {
+        let text = "(define a 1)\n(define (f x)\n  (* x x))\n\n(display (f a))\n";
+        // Only the form around the edit is parsed again
+        assert_eq!(check_edit(text, &edit((3, 5), (3, 6), "y")), 1..2);
+        // Opening a list swallows the forms after it
+        assert_eq!(check_edit(text, &edit((2, 0), (2, 0), "(")), 1..2);
+        // Closing it early makes a stray parenthesis
+        assert_eq!(check_edit(text, &edit((3, 10), (3, 10), ")")), 1..3);
+        assert_eq!(check_edit(text, &edit((1, 0), (5, 0), "")), 0..1);
+        check_edit(text, &edit((4, 0), (4, 0), "\"unterminated\n"));
+        check_edit("(a\n", &edit((2, 0), (2, 0), "b)"));
+        check_edit("(a #\\nope)\n(b)\n", &edit((1, 3), (1, 9), "c"));
+        check_edit("'\n", &edit((2, 0), (2, 0), "x"));
+    }
+
+    #[test]
+    fn random_edits_test() {
+        let texts = [
+            "(define (f x)\n  ; comment\n  (if (> x 0)\n      `(x ,x)\n      #(1 2)))\n(f 2)\n",
+            "(a . b) 'c\n\n(d \"e f\" #\\g)\n)\n(h\n",
+            "",
+        ];
+        let pieces = [
+            "(", ")", "\n", " ", "'", "x", "\"s\"", ";c\n", "#(", ".", "\"", "#\\", "12",
+        ];
+        // A linear congruential generator keeps the test deterministic
+        let mut seed: u64 = 1;
+        let mut random = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (seed >> 33) as usize % bound.max(1)
+        };
+        for text in texts.iter() {
+            let mut text = text.to_string();
+            let mut tree = SyntaxTree::parse(&text);
+            for _ in 0..300 {
+                let lines = text.lines().count() + 1;
+                let start = (random(lines) + 1, random(12));
+                let end = if random(3) == 0 {
+                    (start.0 + random(2), random(12))
+                } else {
+                    start
+                };
+                let end = if end < start { start } else { end };
+                let mut replacement = String::new();
+                for _ in 0..random(3) {
+                    replacement.push_str(pieces[random(pieces.len())]);
+                }
+                tree.edit(&mut text, &edit(start, end, &replacement));
+                let expected = SyntaxTree::parse(&text);
+                assert_eq!(tree.tokens(), expected.tokens(), "{:?}", text);
+                assert_eq!(tree.forms(), expected.forms(), "{:?}", text);
+                assert_eq!(
+                    tree.lex_error().is_some(),
+                    expected.lex_error().is_some(),
+                    "{:?}",
+                    text
+                );
+            }
+        }
+    }
+}
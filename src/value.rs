@@ -0,0 +1,770 @@
+//! Runtime representation of Scheme values
+//!
+//! Unlike `Datum`, which is a tree produced by the parser, a `Value` is built out of shared,
+//! mutable cells. Pairs, strings and vectors live behind reference-counted pointers, which means
+//! two values can refer to the same object, `set-car!` and friends are visible through every
+//! reference, and lists can be made circular.
+use crate::interpreter::{Environment, Procedure, Promise, RuntimeError};
+use crate::lexer::LispNum;
+use crate::parser::{Datum, Position, PositionTree};
+use crate::port::Port;
+use crate::CompilerError;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    rc::Rc,
+};
+
+/// A Scheme value as seen by the runtime
+///
+/// Cloning a `Value` is cheap: heap-allocated objects are reference counted, so a clone refers
+/// to the same underlying object as the original.
+#[derive(Clone)]
+pub enum Value {
+    /// The empty list
+    Null,
+    /// Wraps a boolean
+    Boolean(bool),
+    /// Wraps a number
+    Number(LispNum),
+    /// Wraps a character
+    Character(char),
+    /// A mutable string
+    String(Rc<RefCell<String>>),
+    /// A symbol, compared by name
+    Symbol(Rc<str>),
+    /// A mutable pair
+    Pair(Rc<Pair>),
+    /// A mutable vector
+    Vector(Rc<RefCell<Vec<Value>>>),
+    /// A builtin procedure, closure or continuation
+    Procedure(Rc<Procedure>),
+    /// A promise created by `delay`
+    Promise(Rc<Promise>),
+    /// A condition object describing an error, as raised by `error` and by failing builtins
+    Condition(Rc<RuntimeError>),
+    /// An environment specifier, as accepted by `eval`
+    Environment(Rc<Environment>),
+    /// An input or output port
+    Port(Rc<Port>),
+    /// The object returned by input procedures at end of file
+    EofObject,
+    /// Zero or several values returned by `values`
+    MultipleValues(Rc<Vec<Value>>),
+    /// The value returned by expressions whose value R5RS leaves unspecified
+    Unspecified,
+}
+
+/// A `cons` cell whose `car` and `cdr` can be mutated in place
+///
+/// Pairs built from source code remember where they were read from, which is how the interpreter
+/// knows the position of a failing call. Pairs allocated at runtime have no position.
+pub struct Pair {
+    car: RefCell<Value>,
+    cdr: RefCell<Value>,
+    position: Option<Position>,
+}
+
+impl Pair {
+    /// Where the pair was read from, if it was built from source code
+    ///
+    /// The first pair of a list is positioned at the opening parenthesis, and every following
+    /// pair at the element it holds.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Returns a copy of the `car` of the pair
+    pub fn car(&self) -> Value {
+        self.car.borrow().clone()
+    }
+
+    /// Returns a copy of the `cdr` of the pair
+    pub fn cdr(&self) -> Value {
+        self.cdr.borrow().clone()
+    }
+
+    /// Replaces the `car` of the pair
+    pub fn set_car(&self, value: Value) {
+        *self.car.borrow_mut() = value;
+    }
+
+    /// Replaces the `cdr` of the pair
+    pub fn set_cdr(&self, value: Value) {
+        *self.cdr.borrow_mut() = value;
+    }
+}
+
+impl Drop for Pair {
+    /// Frees the rest of a list iteratively: dropping it recursively would overflow the stack
+    /// on long lists
+    fn drop(&mut self) {
+        let mut rest = std::mem::replace(self.cdr.get_mut(), Value::Null);
+        while let Value::Pair(pair) = rest {
+            rest = match Rc::try_unwrap(pair) {
+                Ok(mut pair) => std::mem::replace(pair.cdr.get_mut(), Value::Null),
+                // Another reference keeps the rest of the list alive
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+impl Value {
+    /// Allocates a fresh pair
+    pub fn cons(car: Value, cdr: Value) -> Self {
+        Value::cons_at(car, cdr, None)
+    }
+
+    /// Allocates a fresh pair remembering the position it was read from
+    pub(crate) fn cons_at(car: Value, cdr: Value, position: Option<Position>) -> Self {
+        Value::Pair(Rc::new(Pair {
+            car: RefCell::new(car),
+            cdr: RefCell::new(cdr),
+            position,
+        }))
+    }
+
+    /// Converts a datum read by the parser, recording in each pair the position it was read from
+    pub fn from_positioned(datum: &Datum, positions: &PositionTree) -> Self {
+        datum_to_value(datum, Some(positions))
+    }
+
+    /// Builds a proper list out of the given values
+    pub fn list<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        Value::list_with_tail(values, Value::Null)
+    }
+
+    /// Builds a list out of the given values, terminated by `tail` instead of the empty list
+    pub fn list_with_tail<I>(values: I, tail: Value) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        values
+            .into_iter()
+            .rev()
+            .fold(tail, |acc, value| Value::cons(value, acc))
+    }
+
+    /// Allocates a fresh mutable string
+    pub fn string<S: Into<String>>(s: S) -> Self {
+        Value::String(Rc::new(RefCell::new(s.into())))
+    }
+
+    /// Creates a symbol with the given name
+    pub fn symbol(name: &str) -> Self {
+        Value::Symbol(Rc::from(name))
+    }
+
+    /// Allocates a fresh mutable vector
+    pub fn vector(values: Vec<Value>) -> Self {
+        Value::Vector(Rc::new(RefCell::new(values)))
+    }
+
+    /// Everything except `#f` counts as true in a conditional
+    pub fn is_true(&self) -> bool {
+        !matches!(self, Value::Boolean(false))
+    }
+
+    /// Returns `true` if the value is the empty list
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns `true` if the value is a symbol with the given name
+    pub fn is_symbol(&self, name: &str) -> bool {
+        matches!(self, Value::Symbol(s) if &**s == name)
+    }
+
+    /// Returns `true` if the value is a finite, proper list
+    ///
+    /// Circular lists are detected and reported as not being lists, as required by R5RS.
+    pub fn is_list(&self) -> bool {
+        let mut slow = self.clone();
+        let mut fast = self.clone();
+        loop {
+            for _ in 0..2 {
+                fast = match fast {
+                    Value::Null => return true,
+                    Value::Pair(p) => p.cdr(),
+                    _ => return false,
+                };
+            }
+            slow = match slow {
+                Value::Pair(p) => p.cdr(),
+                _ => unreachable!(),
+            };
+            if let (Value::Pair(s), Value::Pair(f)) = (&slow, &fast) {
+                if Rc::ptr_eq(s, f) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Collects the elements of a proper list, returning `None` for improper or circular lists
+    pub fn list_to_vec(&self) -> Option<Vec<Value>> {
+        if !self.is_list() {
+            return None;
+        }
+        let mut values = Vec::new();
+        let mut current = self.clone();
+        while let Value::Pair(p) = current {
+            values.push(p.car());
+            current = p.cdr();
+        }
+        Some(values)
+    }
+
+    /// Identity comparison, as performed by `eq?`
+    pub fn is_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Unspecified, Value::Unspecified) => true,
+            (Value::EofObject, Value::EofObject) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Character(a), Value::Character(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Number(LispNum::Integer(a)), Value::Number(LispNum::Integer(b))) => a == b,
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b),
+            (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+            (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+            (Value::Procedure(a), Value::Procedure(b)) => Rc::ptr_eq(a, b),
+            (Value::Promise(a), Value::Promise(b)) => Rc::ptr_eq(a, b),
+            (Value::Condition(a), Value::Condition(b)) => Rc::ptr_eq(a, b),
+            (Value::Environment(a), Value::Environment(b)) => Rc::ptr_eq(a, b),
+            (Value::Port(a), Value::Port(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Operational equivalence, as performed by `eqv?`
+    pub fn is_eqv(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(LispNum::Float(a)), Value::Number(LispNum::Float(b))) => {
+                a.to_bits() == b.to_bits()
+            }
+            _ => self.is_eq(other),
+        }
+    }
+
+    /// Structural equality, as performed by `equal?`
+    ///
+    /// Like the R5RS procedure, this may fail to terminate on circular structures.
+    pub fn is_equal(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => *a.borrow() == *b.borrow(),
+            (Value::Vector(a), Value::Vector(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.is_equal(y))
+            }
+            (Value::Pair(_), Value::Pair(_)) => {
+                let (mut a, mut b) = (self.clone(), other.clone());
+                loop {
+                    match (a, b) {
+                        (Value::Pair(x), Value::Pair(y)) => {
+                            if Rc::ptr_eq(&x, &y) {
+                                return true;
+                            }
+                            if !x.car().is_equal(&y.car()) {
+                                return false;
+                            }
+                            a = x.cdr();
+                            b = y.cdr();
+                        }
+                        (x, y) => return x.is_equal(&y),
+                    }
+                }
+            }
+            _ => self.is_eqv(other),
+        }
+    }
+
+    /// Returns a wrapper whose `Display` implementation prints the value the way `write` does
+    pub fn written(&self) -> Written<'_> {
+        Written(self)
+    }
+}
+
+impl From<&Datum> for Value {
+    fn from(datum: &Datum) -> Self {
+        datum_to_value(datum, None)
+    }
+}
+
+fn datum_to_value(datum: &Datum, positions: Option<&PositionTree>) -> Value {
+    let position = positions.map(|tree| tree.position);
+    let child = |i: usize| positions.and_then(|tree| tree.children.get(i));
+    // Builds a list whose first pair is positioned at the datum, and the others at their elements
+    let list = |items: &[Datum], tail: Value| {
+        let mut list = tail;
+        for (i, item) in items.iter().enumerate().rev() {
+            let item_position = child(i);
+            let pair_position = if i == 0 {
+                position
+            } else {
+                item_position.map(|tree| tree.position)
+            };
+            list = Value::cons_at(datum_to_value(item, item_position), list, pair_position);
+        }
+        list
+    };
+    let abbreviation = |name: &str, datum: &Datum| {
+        let quoted = Value::cons(datum_to_value(datum, child(0)), Value::Null);
+        Value::cons_at(Value::symbol(name), quoted, position)
+    };
+    match datum {
+        Datum::Boolean(b) => Value::Boolean(*b),
+        Datum::Number(n) => Value::Number(*n),
+        Datum::Character(c) => Value::Character(*c),
+        Datum::String(s) => Value::string(s.as_str()),
+        Datum::Identifier(i) => Value::symbol(i),
+        Datum::List(l) => list(l, Value::Null),
+        Datum::DottedPair(car, cdr) => list(car, datum_to_value(cdr, child(car.len()))),
+        Datum::Quote(d) => abbreviation("quote", d),
+        Datum::Backquote(d) => abbreviation("quasiquote", d),
+        Datum::Unquote(d) => abbreviation("unquote", d),
+        Datum::UnquoteSplice(d) => abbreviation("unquote-splicing", d),
+        Datum::Vector(v) => Value::vector(
+            v.iter()
+                .enumerate()
+                .map(|(i, item)| datum_to_value(item, child(i)))
+                .collect(),
+        ),
+    }
+}
+
+impl From<Datum> for Value {
+    fn from(datum: Datum) -> Self {
+        Value::from(&datum)
+    }
+}
+
+impl TryFrom<&Value> for Datum {
+    type Error = CompilerError;
+
+    /// Converts a value back into a tree, failing on circular structure
+    ///
+    /// Shared substructure is copied, so the resulting `Datum` no longer records which parts were
+    /// shared. Two-element lists headed by `quote`, `quasiquote`, `unquote` and `unquote-splicing`
+    /// are turned back into the corresponding abbreviations.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        to_datum(value, &mut HashSet::new())
+    }
+}
+
+impl TryFrom<Value> for Datum {
+    type Error = CompilerError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Datum::try_from(&value)
+    }
+}
+
+fn to_datum(value: &Value, path: &mut HashSet<*const ()>) -> Result<Datum, CompilerError> {
+    match value {
+        Value::Null => Ok(Datum::List(Vec::new())),
+        Value::Boolean(b) => Ok(Datum::Boolean(*b)),
+        Value::Number(n) => Ok(Datum::Number(*n)),
+        Value::Character(c) => Ok(Datum::Character(*c)),
+        Value::String(s) => Ok(Datum::String(s.borrow().clone())),
+        Value::Symbol(s) => Ok(Datum::Identifier(s.to_string())),
+        Value::Vector(v) => {
+            let key = Rc::as_ptr(v) as *const ();
+            if !path.insert(key) {
+                return Err(CompilerError::UnrepresentableValue(String::from(
+                    "circular vector",
+                )));
+            }
+            let items = v
+                .borrow()
+                .iter()
+                .map(|item| to_datum(item, path))
+                .collect::<Result<Vec<_>, _>>()?;
+            path.remove(&key);
+            Ok(Datum::Vector(items))
+        }
+        Value::Pair(_) => {
+            let mut chain = Vec::new();
+            let mut items = Vec::new();
+            let mut current = value.clone();
+            let result = loop {
+                match current {
+                    Value::Pair(p) => {
+                        let key = Rc::as_ptr(&p) as *const ();
+                        if !path.insert(key) {
+                            break Err(CompilerError::UnrepresentableValue(String::from(
+                                "circular list",
+                            )));
+                        }
+                        chain.push(key);
+                        match to_datum(&p.car(), path) {
+                            Ok(datum) => items.push(datum),
+                            Err(e) => break Err(e),
+                        }
+                        current = p.cdr();
+                    }
+                    Value::Null => break Ok(abbreviate(items)),
+                    tail => {
+                        break to_datum(&tail, path)
+                            .map(|cdr| Datum::DottedPair(items, Box::new(cdr)))
+                    }
+                }
+            };
+            for key in chain {
+                path.remove(&key);
+            }
+            result
+        }
+        Value::Procedure(_) => Err(CompilerError::UnrepresentableValue(String::from(
+            "procedure",
+        ))),
+        Value::Promise(_) => Err(CompilerError::UnrepresentableValue(String::from("promise"))),
+        Value::Condition(_) => Err(CompilerError::UnrepresentableValue(String::from(
+            "condition",
+        ))),
+        Value::Environment(_) => Err(CompilerError::UnrepresentableValue(String::from(
+            "environment",
+        ))),
+        Value::Port(_) => Err(CompilerError::UnrepresentableValue(String::from("port"))),
+        Value::EofObject => Err(CompilerError::UnrepresentableValue(String::from(
+            "end of file object",
+        ))),
+        Value::MultipleValues(_) => Err(CompilerError::UnrepresentableValue(String::from(
+            "multiple values",
+        ))),
+        Value::Unspecified => Err(CompilerError::UnrepresentableValue(String::from(
+            "unspecified value",
+        ))),
+    }
+}
+
+fn abbreviate(mut items: Vec<Datum>) -> Datum {
+    if items.len() != 2 {
+        return Datum::List(items);
+    }
+    let wrap: fn(Box<Datum>) -> Datum = match &items[0] {
+        Datum::Identifier(i) if i == "quote" => Datum::Quote,
+        Datum::Identifier(i) if i == "quasiquote" => Datum::Backquote,
+        Datum::Identifier(i) if i == "unquote" => Datum::Unquote,
+        Datum::Identifier(i) if i == "unquote-splicing" => Datum::UnquoteSplice,
+        _ => return Datum::List(items),
+    };
+    wrap(Box::new(items.pop().unwrap()))
+}
+
+/// Wrapper returned by `Value::written`, printing strings and characters in their external
+/// representation
+pub struct Written<'a>(&'a Value);
+
+impl fmt::Display for Written<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(self.0, true).print(self.0, f)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(self, false).print(self, f)
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.written())
+    }
+}
+
+/// Prints values, using datum labels (`#0=` and `#0#`) for objects that are part of a cycle
+struct Printer {
+    write: bool,
+    labels: HashMap<*const (), Option<usize>>,
+    next_label: usize,
+}
+
+fn object_key(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Pair(p) => Some(Rc::as_ptr(p) as *const ()),
+        Value::Vector(v) => Some(Rc::as_ptr(v) as *const ()),
+        _ => None,
+    }
+}
+
+impl Printer {
+    fn new(value: &Value, write: bool) -> Self {
+        let mut cyclic = HashSet::new();
+        find_cycles(value, &mut HashSet::new(), &mut HashSet::new(), &mut cyclic);
+        Printer {
+            write,
+            labels: cyclic.into_iter().map(|key| (key, None)).collect(),
+            next_label: 0,
+        }
+    }
+
+    /// Prints a label definition or reference if `value` needs one. Returns `true` if the value
+    /// was printed as a reference and nothing more needs to be printed.
+    fn print_label(
+        &mut self,
+        value: &Value,
+        f: &mut fmt::Formatter<'_>,
+    ) -> Result<bool, fmt::Error> {
+        let key = match object_key(value) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        match self.labels.get(&key) {
+            Some(Some(n)) => {
+                write!(f, "#{}#", n)?;
+                Ok(true)
+            }
+            Some(None) => {
+                let n = self.next_label;
+                self.next_label += 1;
+                self.labels.insert(key, Some(n));
+                write!(f, "#{}=", n)?;
+                Ok(false)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn print(&mut self, value: &Value, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.print_label(value, f)? {
+            return Ok(());
+        }
+        match value {
+            Value::Null => write!(f, "()"),
+            Value::Boolean(true) => write!(f, "#t"),
+            Value::Boolean(false) => write!(f, "#f"),
+            Value::Number(n) => write_number(n, f),
+            Value::Character(c) if self.write => match c {
+                ' ' => write!(f, "#\\space"),
+                '\n' => write!(f, "#\\newline"),
+                c => write!(f, "#\\{}", c),
+            },
+            Value::Character(c) => write!(f, "{}", c),
+            Value::String(s) if self.write => {
+                write!(f, "\"")?;
+                for c in s.borrow().chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Value::String(s) => write!(f, "{}", s.borrow()),
+            Value::Symbol(s) => write!(f, "{}", s),
+            Value::Vector(v) => {
+                write!(f, "#(")?;
+                for (i, item) in v.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    self.print(item, f)?;
+                }
+                write!(f, ")")
+            }
+            Value::Pair(p) => {
+                write!(f, "(")?;
+                self.print(&p.car(), f)?;
+                let mut rest = p.cdr();
+                loop {
+                    match rest {
+                        Value::Null => break,
+                        Value::Pair(ref next)
+                            if !self.labels.contains_key(&object_key(&rest).unwrap()) =>
+                        {
+                            write!(f, " ")?;
+                            self.print(&next.car(), f)?;
+                            rest = next.cdr();
+                        }
+                        tail => {
+                            write!(f, " . ")?;
+                            self.print(&tail, f)?;
+                            break;
+                        }
+                    }
+                }
+                write!(f, ")")
+            }
+            Value::Procedure(p) => match p.name() {
+                Some(name) => write!(f, "#<procedure {}>", name),
+                None => write!(f, "#<procedure>"),
+            },
+            Value::Promise(_) => write!(f, "#<promise>"),
+            Value::Condition(condition) => write!(f, "#<condition {}>", condition),
+            Value::Environment(_) => write!(f, "#<environment>"),
+            Value::Port(port) => match **port {
+                Port::Input(_) => write!(f, "#<input-port>"),
+                Port::Output(_) => write!(f, "#<output-port>"),
+            },
+            Value::EofObject => write!(f, "#<eof>"),
+            Value::MultipleValues(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    self.print(value, f)?;
+                }
+                Ok(())
+            }
+            Value::Unspecified => write!(f, "#<unspecified>"),
+        }
+    }
+}
+
+fn write_number(n: &LispNum, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match n {
+        LispNum::Integer(i) => write!(f, "{}", i),
+        LispNum::Float(x) if x.is_nan() => write!(f, "+nan.0"),
+        LispNum::Float(x) if x.is_infinite() && *x > 0.0 => write!(f, "+inf.0"),
+        LispNum::Float(x) if x.is_infinite() => write!(f, "-inf.0"),
+        LispNum::Float(x) if x.fract() == 0.0 => write!(f, "{:.1}", x),
+        LispNum::Float(x) => write!(f, "{}", x),
+    }
+}
+
+/// Records in `cyclic` every pair or vector that can reach itself
+fn find_cycles(
+    value: &Value,
+    path: &mut HashSet<*const ()>,
+    done: &mut HashSet<*const ()>,
+    cyclic: &mut HashSet<*const ()>,
+) {
+    match value {
+        Value::Vector(v) => {
+            let key = Rc::as_ptr(v) as *const ();
+            if path.contains(&key) {
+                cyclic.insert(key);
+                return;
+            }
+            if !done.insert(key) {
+                return;
+            }
+            path.insert(key);
+            for item in v.borrow().iter() {
+                find_cycles(item, path, done, cyclic);
+            }
+            path.remove(&key);
+        }
+        Value::Pair(_) => {
+            // Walks the cdr chain iteratively so that long lists don't exhaust the stack
+            let mut chain = Vec::new();
+            let mut current = value.clone();
+            while let Value::Pair(p) = &current {
+                let key = Rc::as_ptr(p) as *const ();
+                if path.contains(&key) {
+                    cyclic.insert(key);
+                    break;
+                }
+                if !done.insert(key) {
+                    break;
+                }
+                path.insert(key);
+                chain.push(key);
+                find_cycles(&p.car(), path, done, cyclic);
+                current = p.cdr();
+            }
+            if let Value::Vector(_) = current {
+                find_cycles(&current, path, done, cyclic);
+            }
+            for key in chain {
+                path.remove(&key);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn symbol_list(names: &[&str]) -> Value {
+        Value::list(names.iter().map(|n| Value::symbol(n)).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn datum_round_trip_test() {
+        let datum = Datum::List(vec![
+            Datum::Identifier(String::from("a")),
+            Datum::Quote(Box::new(Datum::Boolean(true))),
+            Datum::DottedPair(
+                vec![Datum::Number(LispNum::Integer(1))],
+                Box::new(Datum::String(String::from("b"))),
+            ),
+            Datum::Vector(vec![Datum::Character('c')]),
+        ]);
+        let value = Value::from(&datum);
+        assert_eq!(
+            value.written().to_string(),
+            "(a (quote #t) (1 . \"b\") #(#\\c))"
+        );
+        assert_eq!(Datum::try_from(&value).unwrap(), datum);
+    }
+
+    #[test]
+    fn mutation_is_shared_test() {
+        let list = symbol_list(&["a", "b"]);
+        let alias = list.clone();
+        if let Value::Pair(p) = &list {
+            p.set_car(Value::symbol("z"));
+        }
+        assert_eq!(alias.to_string(), "(z b)");
+        assert!(list.is_eq(&alias));
+        assert!(!list.is_eq(&symbol_list(&["z", "b"])));
+        assert!(list.is_equal(&symbol_list(&["z", "b"])));
+    }
+
+    #[test]
+    fn circular_list_test() {
+        let list = symbol_list(&["a", "b"]);
+        if let Value::Pair(first) = &list {
+            if let Value::Pair(second) = first.cdr() {
+                second.set_cdr(list.clone());
+            }
+        }
+        assert!(!list.is_list());
+        assert!(list.list_to_vec().is_none());
+        assert_eq!(list.to_string(), "#0=(a b . #0#)");
+        assert!(Datum::try_from(&list).is_err());
+    }
+
+    #[test]
+    fn long_list_drop_test() {
+        let tail = Value::list(vec![Value::Null; 1_000_000]);
+        let list = Value::list_with_tail(vec![Value::Null; 1_000_000], tail.clone());
+        drop(list);
+        assert_eq!(
+            tail.list_to_vec().map(|values| values.len()),
+            Some(1_000_000)
+        );
+        drop(tail);
+    }
+
+    #[test]
+    fn shared_structure_is_not_labelled_test() {
+        let shared = symbol_list(&["x"]);
+        let outer = Value::list(vec![shared.clone(), shared]);
+        assert_eq!(outer.to_string(), "((x) (x))");
+    }
+
+    #[test]
+    fn eqv_test() {
+        assert!(Value::Number(LispNum::Integer(2)).is_eqv(&Value::Number(LispNum::Integer(2))));
+        assert!(!Value::Number(LispNum::Integer(2)).is_eqv(&Value::Number(LispNum::Float(2.0))));
+        assert!(!Value::string("a").is_eqv(&Value::string("a")));
+        assert!(Value::string("a").is_equal(&Value::string("a")));
+        assert!(Value::symbol("a").is_eq(&Value::symbol("a")));
+    }
+}
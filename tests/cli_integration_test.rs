@@ -0,0 +1,128 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+
+fn oxyscheme(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_oxyscheme"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn lex_matches_golden_tokens() {
+    let golden_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs/golden/");
+
+    for file_res in fs::read_dir(&golden_directory).unwrap() {
+        let golden = file_res.unwrap().path();
+        let name = golden.file_stem().unwrap().to_str().unwrap();
+        let source = format!("inputs/good-inputs/{}.scm", name);
+        assert_eq!(
+            oxyscheme(&["lex", &source]),
+            fs::read_to_string(&golden).unwrap(),
+            "{}",
+            golden.display()
+        );
+    }
+}
+
+#[test]
+fn lex_prints_json_tokens() {
+    let output = oxyscheme(&["lex", "--json", "inputs/good-inputs/hello-world.scm"]);
+    let first = output.lines().next().unwrap();
+    assert_eq!(
+        first,
+        r#"{"column":0,"file":"inputs/good-inputs/hello-world.scm","kind":"comment","line":1,"text":"; Hello world program"}"#
+    );
+    assert_eq!(output.lines().count(), 17);
+}
+
+#[test]
+fn lint_reports_redefinitions() {
+    let lint = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oxyscheme"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("lint")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = lint(&["--error-format=json", "inputs/good-inputs/ex_01.11.scm"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 1);
+    assert!(stderr.contains(r#""code":"W0007""#), "{}", stderr);
+
+    let output = lint(&[
+        "--disable",
+        "redefinition",
+        "inputs/good-inputs/ex_01.11.scm",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn check_fix_removes_stray_parens() {
+    let check = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oxyscheme"))
+            .arg("check")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let source =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs/bad-parser-inputs/bad-hello-world2.scm");
+    let copy = env::temp_dir().join(format!("oxyscheme-fix-{}.scm", process::id()));
+    fs::copy(&source, &copy).unwrap();
+    let copy_name = copy.to_str().unwrap();
+
+    let output = check(&["--error-format=json", copy_name]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(r#""message":"remove the unmatched `)`","replacement":"""#),
+        "{}",
+        stderr
+    );
+
+    let output = check(&["--fix", copy_name]);
+    assert!(output.status.success(), "{:?}", output);
+    let fixed = fs::read_to_string(&copy).unwrap();
+    fs::remove_file(&copy).unwrap();
+    assert_eq!(
+        fixed,
+        fs::read_to_string(&source)
+            .unwrap()
+            .replace("(newline)))", "(newline))")
+    );
+}
+
+#[test]
+fn repl_writes_results_to_output_file() {
+    let results = env::temp_dir().join(format!("oxyscheme-repl-{}.txt", process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oxyscheme"))
+        .args(["repl", "-o", results.to_str().unwrap()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"(+ 1 2)\n(display \"hi\")\n\"s\"\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let written = fs::read_to_string(&results).unwrap();
+    fs::remove_file(&results).unwrap();
+    assert_eq!(written, "3\nhi\"s\"\n");
+    // Only the prompts are left on standard output
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "> > > > \n");
+}
@@ -0,0 +1,48 @@
+use oxyscheme::interpreter::Interpreter;
+use oxyscheme::port::{OutputPort, Port};
+use oxyscheme::reader;
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+#[test]
+fn interpreter_runs_valid_input() {
+    let good_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs/good-inputs/");
+
+    for file_res in fs::read_dir(&good_directory).unwrap() {
+        let file = file_res.unwrap().path();
+        let file_lexer = reader::FileLexer::new(file.to_str().unwrap()).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .set_current_output_port(Rc::new(Port::Output(RefCell::new(OutputPort::string()))));
+        for datum in reader::DatumIterator::new(file_lexer.into_iter()) {
+            let result = interpreter.eval_datum(&datum.unwrap());
+            assert!(
+                result.is_ok(),
+                "{}: {}",
+                file.display(),
+                result.unwrap_err()
+            );
+        }
+    }
+}
+
+#[test]
+fn interpreter_prints_hello_world() {
+    let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs/good-inputs/hello-world.scm");
+    let file_lexer = reader::FileLexer::new(file.to_str().unwrap()).unwrap();
+    let mut interpreter = Interpreter::new();
+    let output = Rc::new(Port::Output(RefCell::new(OutputPort::string())));
+    interpreter.set_current_output_port(output.clone());
+    for datum in reader::DatumIterator::new(file_lexer.into_iter()) {
+        interpreter.eval_datum(&datum.unwrap()).unwrap();
+    }
+    match &*output {
+        Port::Output(port) => assert_eq!(
+            port.borrow().contents(),
+            Some("Hello, World!; Not a real comment\n")
+        ),
+        Port::Input(_) => unreachable!(),
+    }
+}
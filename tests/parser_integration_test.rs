@@ -55,3 +55,11 @@ fn parser_rejects_invalid_input() {
         assert!(vec_of_datums_res.is_err());
     }
 }
+
+#[test]
+fn parser_ignores_trailing_comments() {
+    let string_lexer = reader::StringLexer::new("(display 1) ; done\n\n; really\n");
+    let datum_stream = reader::DatumIterator::new(string_lexer.into_iter());
+    let vec_of_datums_res: Result<Vec<parser::Datum>, CompilerError> = datum_stream.collect();
+    assert_eq!(vec_of_datums_res.unwrap().len(), 1);
+}